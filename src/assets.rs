@@ -13,6 +13,9 @@ pub const ROBOTO_MONO_REGULAR: &[u8] =
 
 pub const ROBOTO_REGULAR: &[u8] = include_bytes!("../assets/roboto/Roboto-Regular.ttf");
 
+// Fallback for accidentals and arrows, which the Roboto fonts don't have
+pub const DEJAVU_SANS: &[u8] = include_bytes!("../assets/dejavu_sans/DejaVuSans.ttf");
+
 pub fn register_quicksand(cx: &mut Context) {
     cx.add_font_mem(QUICKSAND_LIGHT);
     cx.add_font_mem(QUICKSAND_REGULAR);
@@ -0,0 +1,447 @@
+use nih_plug::midi::NoteEvent;
+use nih_plug::nih_log;
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use crate::midi::{
+    release_latched_voices, update_midi_voices, ChannelStates, VoiceOptions, ALL_CHANNELS,
+};
+use crate::Voices;
+
+/// Maximum number of commands that can be queued between two `process()` calls
+pub const COMMAND_QUEUE_CAPACITY: usize = 64;
+
+/// Actions the editor asks the audio thread to perform. Kept small and `Copy` so that sending
+/// and receiving them never allocates.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AudioCommand {
+    /// Removes every voice, and sends a note off out of the plugin for each one still held
+    ClearVoices,
+
+    /// Removes voices kept by latching, leaving held ones
+    ClearLatchedVoices,
+
+    /// Sends a note on out of the plugin, followed by a tuning offset in semitones
+    SendNoteOn {
+        channel: u8,
+        note: u8,
+        velocity: f32,
+        tuning: f32,
+    },
+
+    /// Sends a note off out of the plugin
+    SendNoteOff { channel: u8, note: u8 },
+}
+
+/// Editor side of the command queue.
+/// If the queue is full, commands are dropped, and the number of dropped commands is counted.
+pub struct CommandSender {
+    producer: Producer<AudioCommand>,
+    dropped_count: u32,
+}
+
+impl CommandSender {
+    pub fn send(&mut self, command: AudioCommand) {
+        if self.producer.push(command).is_err() {
+            self.dropped_count += 1;
+            nih_log!(
+                "!!! Command queue full, dropped {:?} ({} dropped in total)",
+                command,
+                self.dropped_count
+            );
+        }
+    }
+}
+
+/// Creates a command queue. The sender goes to the editor, and the consumer to the audio thread.
+pub fn command_queue() -> (CommandSender, Consumer<AudioCommand>) {
+    let (producer, consumer) = RingBuffer::new(COMMAND_QUEUE_CAPACITY);
+    (
+        CommandSender {
+            producer,
+            dropped_count: 0,
+        },
+        consumer,
+    )
+}
+
+/// Applies a command on the audio thread.
+/// Notes sent out of the plugin are also applied to `voices`, so they display on the lattice.
+/// They're placed with the same `options` as notes from the host, so pitch bend and the input
+/// EDO move them the same way.
+pub fn handle_command(
+    voices: &mut Voices,
+    channels: &mut ChannelStates,
+    options: VoiceOptions,
+    command: AudioCommand,
+    mut send_event: impl FnMut(NoteEvent<()>),
+) {
+    let events: [Option<NoteEvent<()>>; 2] = match command {
+        AudioCommand::ClearVoices => {
+            // Held voices were forwarded or auditioned, so they're still sounding downstream.
+            // Latched and sustained ones already had their note offs forwarded.
+            for (key, voice) in voices.iter() {
+                if !voice.is_latched() && !voice.is_sustained() {
+                    send_event(NoteEvent::NoteOff {
+                        timing: 0,
+                        voice_id: key.voice_id,
+                        channel: key.channel,
+                        note: key.note,
+                        velocity: 0.0,
+                    });
+                }
+            }
+            voices.clear();
+            [None, None]
+        }
+        AudioCommand::ClearLatchedVoices => {
+            release_latched_voices(voices);
+            [None, None]
+        }
+        AudioCommand::SendNoteOn {
+            channel,
+            note,
+            velocity,
+            tuning,
+        } => [
+            Some(NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note,
+                velocity,
+            }),
+            Some(NoteEvent::PolyTuning {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note,
+                tuning,
+            }),
+        ],
+        AudioCommand::SendNoteOff { channel, note } => [
+            Some(NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel,
+                note,
+                velocity: 0.0,
+            }),
+            None,
+        ],
+    };
+
+    // Notes played from the editor are always shown, even on filtered channels, and never latch
+    let options = VoiceOptions {
+        enabled_channels: ALL_CHANNELS,
+        latch: false,
+        ..options
+    };
+    for event in events.into_iter().flatten() {
+        update_midi_voices(voices, channels, options, event);
+        send_event(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::{MidiVoice, VoiceKey};
+
+    fn voices_with_notes(notes: &[u8]) -> Voices {
+        let mut voices = Voices::new();
+        for note in notes {
+            let _ = voices.insert(
+                VoiceKey {
+                    voice_id: None,
+                    channel: 0,
+                    note: *note,
+                },
+                MidiVoice::from_midi_data(None, 0, *note),
+            );
+        }
+        voices
+    }
+
+    #[test]
+    fn clear_voices_removes_all_voices() {
+        let mut voices = voices_with_notes(&[60, 64, 67]);
+        let mut sent: Vec<NoteEvent<()>> = Vec::new();
+        handle_command(
+            &mut voices,
+            &mut ChannelStates::default(),
+            VoiceOptions::default(),
+            AudioCommand::ClearVoices,
+            |e| sent.push(e),
+        );
+        assert!(voices.is_empty());
+        let mut released: Vec<u8> = sent
+            .iter()
+            .map(|e| match e {
+                NoteEvent::NoteOff { note, .. } => *note,
+                _ => panic!("expected only note offs, got {:?}", e),
+            })
+            .collect();
+        released.sort();
+        assert_eq!(released, vec![60, 64, 67]);
+    }
+
+    #[test]
+    fn clear_voices_releases_auditioned_note() {
+        let mut voices = voices_with_notes(&[]);
+        let mut channels = ChannelStates::default();
+        handle_command(
+            &mut voices,
+            &mut channels,
+            VoiceOptions::default(),
+            AudioCommand::SendNoteOn {
+                channel: 3,
+                note: 62,
+                velocity: 1.0,
+                tuning: 0.0,
+            },
+            |_| {},
+        );
+        let mut sent: Vec<NoteEvent<()>> = Vec::new();
+        handle_command(
+            &mut voices,
+            &mut channels,
+            VoiceOptions::default(),
+            AudioCommand::ClearVoices,
+            |e| sent.push(e),
+        );
+        assert!(matches!(
+            sent[..],
+            [NoteEvent::NoteOff {
+                channel: 3,
+                note: 62,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn clear_voices_skips_latched_voices() {
+        let mut voices = voices_with_notes(&[]);
+        let mut channels = ChannelStates::default();
+        let latch = VoiceOptions {
+            latch: true,
+            ..VoiceOptions::default()
+        };
+        for event in [
+            NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 1.0,
+            },
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            },
+        ] {
+            update_midi_voices(&mut voices, &mut channels, latch, event);
+        }
+        let mut sent: Vec<NoteEvent<()>> = Vec::new();
+        handle_command(
+            &mut voices,
+            &mut channels,
+            VoiceOptions::default(),
+            AudioCommand::ClearVoices,
+            |e| sent.push(e),
+        );
+        assert!(voices.is_empty());
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    fn clear_latched_voices_keeps_held_voices() {
+        let mut voices = voices_with_notes(&[64]);
+        let mut channels = ChannelStates::default();
+        let latch = VoiceOptions {
+            latch: true,
+            ..VoiceOptions::default()
+        };
+        for event in [
+            NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 1.0,
+            },
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            },
+        ] {
+            update_midi_voices(&mut voices, &mut channels, latch, event);
+        }
+        assert_eq!(voices.len(), 2);
+
+        let mut sent: Vec<NoteEvent<()>> = Vec::new();
+        handle_command(
+            &mut voices,
+            &mut channels,
+            VoiceOptions::default(),
+            AudioCommand::ClearLatchedVoices,
+            |e| sent.push(e),
+        );
+        assert_eq!(
+            voices.keys().collect::<Vec<_>>(),
+            vec![&VoiceKey {
+                voice_id: None,
+                channel: 0,
+                note: 64
+            }]
+        );
+        assert!(sent.is_empty());
+    }
+
+    #[test]
+    fn send_note_on_sends_tuned_note() {
+        let mut voices = voices_with_notes(&[]);
+        let mut sent: Vec<NoteEvent<()>> = Vec::new();
+        handle_command(
+            &mut voices,
+            &mut ChannelStates::default(),
+            VoiceOptions::default(),
+            AudioCommand::SendNoteOn {
+                channel: 2,
+                note: 64,
+                velocity: 0.8,
+                tuning: -0.14,
+            },
+            |e| sent.push(e),
+        );
+        assert_eq!(sent.len(), 2);
+        assert!(matches!(
+            sent[0],
+            NoteEvent::NoteOn {
+                channel: 2,
+                note: 64,
+                ..
+            }
+        ));
+        assert!(matches!(
+            sent[1],
+            NoteEvent::PolyTuning {
+                channel: 2,
+                note: 64,
+                ..
+            }
+        ));
+        let voice = voices
+            .get(&VoiceKey {
+                voice_id: None,
+                channel: 2,
+                note: 64,
+            })
+            .unwrap();
+        assert!((voice.get_pitch() - 63.86).abs() < 0.0001);
+    }
+
+    #[test]
+    fn send_note_on_follows_voice_options() {
+        let mut voices = voices_with_notes(&[]);
+        let mut channels = ChannelStates::default();
+        let options = VoiceOptions {
+            enabled_channels: 0,
+            latch: true,
+            pitch_bend_range: 2.0,
+            ..VoiceOptions::default()
+        };
+        update_midi_voices(
+            &mut voices,
+            &mut channels,
+            options,
+            NoteEvent::MidiPitchBend {
+                timing: 0,
+                channel: 2,
+                value: 0.75,
+            },
+        );
+        handle_command(
+            &mut voices,
+            &mut channels,
+            options,
+            AudioCommand::SendNoteOn {
+                channel: 2,
+                note: 64,
+                velocity: 1.0,
+                tuning: 0.0,
+            },
+            |_| {},
+        );
+        let key = VoiceKey {
+            voice_id: None,
+            channel: 2,
+            note: 64,
+        };
+        // Bent like a note from the host, though its channel is filtered out
+        assert_eq!(voices.get(&key).unwrap().get_pitch(), 65.0);
+
+        // Released without latching
+        handle_command(
+            &mut voices,
+            &mut channels,
+            options,
+            AudioCommand::SendNoteOff {
+                channel: 2,
+                note: 64,
+            },
+            |_| {},
+        );
+        assert!(voices.is_empty());
+    }
+
+    #[test]
+    fn send_note_off_removes_sent_note() {
+        let mut voices = voices_with_notes(&[]);
+        let mut sent: Vec<NoteEvent<()>> = Vec::new();
+        handle_command(
+            &mut voices,
+            &mut ChannelStates::default(),
+            VoiceOptions::default(),
+            AudioCommand::SendNoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 1.0,
+                tuning: 0.0,
+            },
+            |e| sent.push(e),
+        );
+        handle_command(
+            &mut voices,
+            &mut ChannelStates::default(),
+            VoiceOptions::default(),
+            AudioCommand::SendNoteOff {
+                channel: 0,
+                note: 60,
+            },
+            |e| sent.push(e),
+        );
+        assert!(voices.is_empty());
+        assert!(matches!(sent[2], NoteEvent::NoteOff { note: 60, .. }));
+    }
+
+    #[test]
+    fn full_queue_drops_and_counts() {
+        let (mut sender, mut consumer) = command_queue();
+        for _ in 0..COMMAND_QUEUE_CAPACITY + 3 {
+            sender.send(AudioCommand::ClearVoices);
+        }
+        assert_eq!(sender.dropped_count, 3);
+        let mut received = 0;
+        while consumer.pop().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, COMMAND_QUEUE_CAPACITY);
+    }
+}
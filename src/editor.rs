@@ -1,15 +1,33 @@
 use crate::assets;
+use crate::command::CommandSender;
 use crate::GridParams;
 
+use crate::editor::channel_filter::ChannelFilter;
+use crate::editor::clear_latched_button::ClearLatchedButton;
+use crate::editor::edo_preset_button::EdoPresetButton;
+use crate::editor::edo_snap_button::EdoSnapButton;
 use crate::editor::lattice::grid;
 use crate::editor::lattice::Lattice;
+use crate::editor::learn_lock_toggles::LearnLockToggles;
 use crate::editor::note_spectrum::NoteSpectrum;
+use crate::editor::octave_histogram::OctaveHistogram;
+use crate::editor::panic_button::PanicButton;
+use crate::editor::pitch_wheel::PitchWheel;
 use crate::editor::resizer::Resizer;
+use crate::editor::scala_export_button::ScalaExportButton;
+use crate::editor::scala_import_button::ScalaImportButton;
+use crate::editor::scale_buttons::ScaleButtons;
+use crate::editor::settings::{SettingsEvent, SettingsOverlay};
+use crate::editor::settings_button::SettingsButton;
+use crate::editor::status_message::{StatusEvent, StatusMessage};
+use crate::editor::temperament_preset_button::TemperamentPresetButton;
 use crate::editor::tuning_learn_button::TuningLearnButton;
+use crate::mts_esp::MtsClient;
 use crate::MidiLatticeParams;
+use crate::Theme;
 use crate::Voices;
 use nih_plug_vizia::vizia::vg;
-use nih_plug_vizia::vizia::vg::Paint;
+use nih_plug_vizia::vizia::vg::{FontId, Paint};
 use std::cmp::{max, min};
 use std::sync::atomic::Ordering;
 
@@ -18,18 +36,38 @@ use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::ViziaState;
 use nih_plug_vizia::{create_vizia_editor, ViziaTheming};
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
 use triple_buffer::Output;
 
+mod channel_filter;
+mod clear_latched_button;
 mod color;
-mod lattice;
+mod edo_preset_button;
+mod edo_snap_button;
+pub mod lattice;
+mod learn_lock_toggles;
 mod note_spectrum;
+mod octave_histogram;
+mod panic_button;
+mod pitch_wheel;
 mod resizer;
+mod scala_export_button;
+mod scala_import_button;
+mod scale_buttons;
+mod settings;
+mod settings_button;
+mod status_message;
+mod temperament_preset_button;
 mod tuning_learn_button;
 
 pub const BOTTOM_REGION_HEIGHT: f32 = grid::NODE_SIZE * 0.618 + PADDING;
 pub const RIGHT_REGION_WIDTH: f32 = grid::NODE_SIZE * 0.618 + PADDING;
 
+// Height of the octave histogram, at the bottom of the right region
+const OCTAVE_HISTOGRAM_HEIGHT: f32 = grid::NODE_SIZE * 1.2;
+
 pub const PADDING: f32 = grid::NODE_SIZE * 0.08;
 pub const CORNER_RADIUS: f32 = PADDING * 0.55;
 
@@ -37,20 +75,56 @@ pub const CORNER_RADIUS: f32 = PADDING * 0.55;
 pub struct Data {
     params: Arc<MidiLatticeParams>,
     voices_output: Arc<Mutex<Output<Voices>>>,
+    command_sender: Arc<Mutex<CommandSender>>,
+    mts_client: Arc<MtsClient>,
+    settings_open: bool,
+    /// Message shown next to the buttons, such as why a scale couldn't be imported
+    status_message: Option<String>,
 }
 
 impl Data {
-    pub fn new(params: Arc<MidiLatticeParams>, voices_output: Arc<Mutex<Output<Voices>>>) -> Self {
+    pub fn new(
+        params: Arc<MidiLatticeParams>,
+        voices_output: Arc<Mutex<Output<Voices>>>,
+        command_sender: Arc<Mutex<CommandSender>>,
+        mts_client: Arc<MtsClient>,
+    ) -> Self {
         Self {
             params,
             voices_output,
+            command_sender,
+            mts_client,
+            settings_open: false,
+            status_message: None,
         }
     }
 }
 
-impl Model for Data {}
+impl Model for Data {
+    fn event(&mut self, _cx: &mut EventContext, event: &mut Event) {
+        event.map(|settings_event, _meta| match *settings_event {
+            SettingsEvent::Toggle => self.settings_open = !self.settings_open,
+        });
+        event.map(|status_event, _meta| match status_event {
+            StatusEvent::Show(message) => self.status_message = Some(message.clone()),
+            StatusEvent::Clear => self.status_message = None,
+        });
+    }
+}
+
+// Widths of the bottom bar's controls, in buttons: ten buttons, the learn lock toggles and the
+// channel filter. Each control after the first has padding on its left.
+const BOTTOM_BAR_BUTTON_WIDTHS: f32 = 10.0 + 1.5 + 2.0;
+const BOTTOM_BAR_CONTROLS: f32 = 12.0;
+
+/// Narrowest window that fits the bottom bar's controls, and the resizer to their right
+pub const MIN_WINDOW_WIDTH: f32 = BOTTOM_BAR_BUTTON_WIDTHS * (BOTTOM_REGION_HEIGHT - PADDING)
+    + (BOTTOM_BAR_CONTROLS + 1.0) * PADDING
+    + RIGHT_REGION_WIDTH;
 
-pub const MIN_GRID_WIDTH: u8 = 4;
+// The narrowest grid is the narrowest that fits `MIN_WINDOW_WIDTH`, at the default node size. See
+// `min_grid_width()` for other sizes.
+pub const MIN_GRID_WIDTH: u8 = 9;
 pub const MIN_GRID_HEIGHT: u8 = 4;
 pub const MAX_GRID_WIDTH: u8 = 30;
 pub const MAX_GRID_HEIGHT: u8 = 30;
@@ -58,6 +132,45 @@ pub const MAX_GRID_HEIGHT: u8 = 30;
 pub const NON_GRID_HEIGHT: f32 = BOTTOM_REGION_HEIGHT;
 pub const NON_GRID_WIDTH: f32 = RIGHT_REGION_WIDTH;
 
+/// Stores info about fonts for femtovg's canvas.
+pub struct FontInfo {
+    loaded: bool,
+    font_id: Option<FontId>,
+    mono_font_id: Option<FontId>,
+    symbol_font_id: Option<FontId>,
+}
+
+impl Default for FontInfo {
+    fn default() -> FontInfo {
+        FontInfo {
+            loaded: false,
+            font_id: None,
+            mono_font_id: None,
+            symbol_font_id: None,
+        }
+    }
+}
+
+impl FontInfo {
+    /// Loads fonts into the canvas the first time this is called.
+    /// Returns the regular and monospace font IDs.
+    pub fn load_and_get(&mut self, canvas: &mut Canvas) -> (Option<FontId>, Option<FontId>) {
+        if !self.loaded {
+            self.loaded = true;
+            self.font_id = canvas.add_font_mem(assets::ROBOTO_REGULAR).ok();
+            self.mono_font_id = canvas.add_font_mem(assets::ROBOTO_MONO_REGULAR).ok();
+            self.symbol_font_id = canvas.add_font_mem(assets::DEJAVU_SANS).ok();
+        }
+        (self.font_id, self.mono_font_id)
+    }
+
+    /// Returns the font for symbols that the regular and monospace fonts don't have, after
+    /// [`FontInfo::load_and_get`] has loaded it
+    pub fn symbol_font_id(&self) -> Option<FontId> {
+        self.symbol_font_id
+    }
+}
+
 pub fn make_icon_paint(color: vg::Color, width: f32) -> Paint {
     let mut icon_paint = vg::Paint::color(color);
     icon_paint.set_line_width(width);
@@ -72,37 +185,128 @@ pub fn make_icon_stroke_paint(color: vg::Color, scale: f32) -> Paint {
     make_icon_paint(color, PADDING * scale)
 }
 
-pub fn width_to_grid_width(width: f32) -> u8 {
+/// Whether shapes should be snapped to device pixels. This is only done at integer scale factors,
+/// since snapping at fractional ones makes nodes of the same size differ by a pixel.
+fn snaps_to_pixels(scale: f32) -> bool {
+    scale.fract() == 0.0
+}
+
+/// Rounds a coordinate or length in device pixels to a whole pixel, if snapping
+pub fn snap(value: f32, scale: f32) -> f32 {
+    if snaps_to_pixels(scale) {
+        value.round()
+    } else {
+        value
+    }
+}
+
+/// Snaps the edges of a box to device pixels
+pub fn snap_bounds(bounds: BoundingBox, scale: f32) -> BoundingBox {
+    let (x, y) = (snap(bounds.x, scale), snap(bounds.y, scale));
+    BoundingBox {
+        x,
+        y,
+        w: snap(bounds.x + bounds.w, scale) - x,
+        h: snap(bounds.y + bounds.h, scale) - y,
+    }
+}
+
+/// Converts a line width in logical pixels to device pixels. When snapping, the width is rounded
+/// down to a whole number of logical pixels, but is never thinner than one.
+pub fn snap_line_width(width: f32, scale: f32) -> f32 {
+    if snaps_to_pixels(scale) {
+        width.floor().max(1.0) * scale
+    } else {
+        width * scale
+    }
+}
+
+/// Snaps the center of a straight line so that a line of the given width in device pixels covers
+/// whole pixels. Lines with an odd width are centered on a pixel, and others between pixels.
+pub fn snap_line_position(position: f32, line_width: f32, scale: f32) -> f32 {
+    if !snaps_to_pixels(scale) {
+        position
+    } else if line_width.round() as i32 % 2 == 1 {
+        position.floor() + 0.5
+    } else {
+        position.round()
+    }
+}
+
+/// Narrowest grid of nodes this size that fits `MIN_WINDOW_WIDTH`
+pub fn min_grid_width(node_size: f32) -> u8 {
+    ((MIN_WINDOW_WIDTH - NON_GRID_WIDTH - PADDING) / (node_size + PADDING)).ceil() as u8
+}
+
+pub fn width_to_grid_width(width: f32, node_size: f32) -> u8 {
     min(
         MAX_GRID_WIDTH,
         max(
-            MIN_GRID_WIDTH,
-            ((width - NON_GRID_WIDTH) / (grid::NODE_SIZE + PADDING)) as u8,
+            min_grid_width(node_size),
+            ((width - NON_GRID_WIDTH) / (node_size + PADDING)) as u8,
         ),
     )
 }
 
-pub fn height_to_grid_height(height: f32) -> u8 {
+pub fn height_to_grid_height(height: f32, node_size: f32) -> u8 {
     min(
         MAX_GRID_HEIGHT,
         max(
             MIN_GRID_HEIGHT,
-            ((height - NON_GRID_HEIGHT) / (grid::NODE_SIZE + PADDING)) as u8,
+            ((height - NON_GRID_HEIGHT) / (node_size + PADDING)) as u8,
+        ),
+    )
+}
+
+/// Width of the window for a grid this many nodes wide
+pub fn grid_width_to_width(grid_width: u8, node_size: f32) -> f32 {
+    (node_size + PADDING) * grid_width as f32 + NON_GRID_WIDTH + PADDING
+}
+
+/// Height of the window for a grid this many nodes high
+pub fn grid_height_to_height(grid_height: u8, node_size: f32) -> f32 {
+    (node_size + PADDING) * grid_height as f32 + NON_GRID_HEIGHT + PADDING
+}
+
+/// Grid size that keeps the window closest to its size before nodes changed size, rounding to the
+/// nearest whole node
+pub fn grid_size_for_node_size(
+    (grid_width, grid_height): (u8, u8),
+    old_node_size: f32,
+    new_node_size: f32,
+) -> (u8, u8) {
+    let half_node = (new_node_size + PADDING) * 0.5;
+    (
+        width_to_grid_width(
+            grid_width_to_width(grid_width, old_node_size) + half_node,
+            new_node_size,
+        ),
+        height_to_grid_height(
+            grid_height_to_height(grid_height, old_node_size) + half_node,
+            new_node_size,
         ),
     )
 }
 
 pub fn vizia_state(grid_params: Arc<GridParams>) -> Arc<ViziaState> {
     ViziaState::new(move || {
-        let width: u32 = ((grid::NODE_SIZE + PADDING)
-            * (grid_params.width.load(Ordering::Relaxed) as f32)
-            + NON_GRID_WIDTH
-            + PADDING) as u32;
-        let height: u32 = ((grid::NODE_SIZE + PADDING)
-            * (grid_params.height.load(Ordering::Relaxed) as f32)
-            + NON_GRID_HEIGHT
-            + PADDING) as u32;
-        (width, height)
+        // Sizes saved by older versions may be smaller than the window's controls now need
+        let node_size = grid_params.node_size();
+        let grid_width = grid_params
+            .width
+            .load(Ordering::Relaxed)
+            .clamp(min_grid_width(node_size), MAX_GRID_WIDTH);
+        let grid_height = grid_params
+            .height
+            .load(Ordering::Relaxed)
+            .clamp(MIN_GRID_HEIGHT, MAX_GRID_HEIGHT);
+        grid_params.width.store(grid_width, Ordering::Relaxed);
+        grid_params.height.store(grid_height, Ordering::Relaxed);
+
+        (
+            grid_width_to_width(grid_width, node_size) as u32,
+            grid_height_to_height(grid_height, node_size) as u32,
+        )
     })
 }
 
@@ -110,8 +314,13 @@ pub fn create(data: Data) -> Option<Box<dyn Editor>> {
     create_vizia_editor(
         data.params.editor_state.clone(),
         ViziaTheming::None,
-        move |cx, _gui_cx| {
-            let _ = cx.add_stylesheet(include_str!("../assets/theme.css"));
+        move |cx, gui_cx| {
+            // The stylesheet only colors the window behind the views, which draw themselves in
+            // the theme's colors
+            let _ = cx.add_stylesheet(match data.params.grid_params.theme.value() {
+                Theme::Dark => include_str!("../assets/theme.css"),
+                Theme::Light => include_str!("../assets/theme_light.css"),
+            });
             //ParamSetter::new(_gui_ctx.as_ref());
             assets::register_quicksand(cx);
             cx.set_default_font(&[assets::QUICKSAND]);
@@ -124,12 +333,127 @@ pub fn create(data: Data) -> Option<Box<dyn Editor>> {
                 TuningLearnButton::new(
                     cx,
                     Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
                     Data::voices_output,
+                    Data::mts_client,
+                    gui_cx.clone(),
                 )
                 .position_type(PositionType::ParentDirected)
                 .left(Units::Pixels(0.0))
                 .height(Units::Pixels(button_dimensions))
                 .width(Units::Pixels(button_dimensions));
+
+                LearnLockToggles::new(
+                    cx,
+                    Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions * 1.5));
+
+                EdoPresetButton::new(
+                    cx,
+                    Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                EdoSnapButton::new(
+                    cx,
+                    Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                TemperamentPresetButton::new(
+                    cx,
+                    Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                ScalaImportButton::new(
+                    cx,
+                    Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                ScalaExportButton::new(
+                    cx,
+                    Data::params.map(|p| p.tuning_params.clone()),
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                ClearLatchedButton::new(
+                    cx,
+                    Data::command_sender,
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                PanicButton::new(
+                    cx,
+                    Data::command_sender,
+                    Data::params.map(|p| p.grid_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .left(Units::Pixels(PADDING))
+                .height(Units::Pixels(button_dimensions))
+                .width(Units::Pixels(button_dimensions));
+
+                SettingsButton::new(cx, Data::params.map(|p| p.grid_params.clone()))
+                    .position_type(PositionType::ParentDirected)
+                    .left(Units::Pixels(PADDING))
+                    .height(Units::Pixels(button_dimensions))
+                    .width(Units::Pixels(button_dimensions));
+
+                ChannelFilter::new(cx, Data::params)
+                    .position_type(PositionType::ParentDirected)
+                    .left(Units::Pixels(PADDING))
+                    .height(Units::Pixels(button_dimensions))
+                    .width(Units::Pixels(button_dimensions * 2.0));
+
+                ScaleButtons::new(cx, Data::params.map(|p| p.grid_params.clone()))
+                    .position_type(PositionType::ParentDirected)
+                    .left(Units::Pixels(PADDING))
+                    .height(Units::Pixels(button_dimensions))
+                    .width(Units::Pixels(button_dimensions));
+
+                Binding::new(cx, Data::status_message, move |cx, status_message| {
+                    if let Some(message) = status_message.get(cx) {
+                        StatusMessage::new(
+                            cx,
+                            message,
+                            Data::params.map(|p| p.grid_params.clone()),
+                        )
+                        .position_type(PositionType::ParentDirected)
+                        .left(Units::Pixels(PADDING))
+                        .height(Units::Pixels(button_dimensions))
+                        .width(Units::Stretch(1.0));
+                    }
+                });
             })
             .position_type(PositionType::SelfDirected)
             .top(Units::Stretch(1.0))
@@ -138,26 +462,54 @@ pub fn create(data: Data) -> Option<Box<dyn Editor>> {
             .right(Units::Pixels(PADDING))
             .height(Units::Pixels(BOTTOM_REGION_HEIGHT - PADDING));
 
-            Lattice::new(cx, Data::params, Data::voices_output)
+            Lattice::new(cx, Data::params, Data::voices_output, Data::command_sender)
                 .position_type(PositionType::SelfDirected)
                 .bottom(Units::Pixels(BOTTOM_REGION_HEIGHT))
                 .left(Units::Pixels(0.0))
                 .top(Units::Pixels(0.0))
                 .right(Units::Pixels(RIGHT_REGION_WIDTH));
 
-            NoteSpectrum::new(
-                cx,
-                Data::params.map(|p| p.grid_params.clone()),
-                Data::voices_output,
-            )
-            .position_type(PositionType::SelfDirected)
-            .top(Units::Pixels(PADDING))
-            .right(Units::Pixels(PADDING))
-            .left(Units::Stretch(1.0))
-            .bottom(Units::Pixels(BOTTOM_REGION_HEIGHT + PADDING))
-            .width(Units::Pixels(RIGHT_REGION_WIDTH - PADDING));
+            // The pitch wheel is square, stacked on top of the spectrum
+            let right_column_width = RIGHT_REGION_WIDTH - PADDING;
 
-            Resizer::new(cx)
+            PitchWheel::new(cx, Data::params, Data::voices_output)
+                .position_type(PositionType::SelfDirected)
+                .top(Units::Pixels(PADDING))
+                .right(Units::Pixels(PADDING))
+                .left(Units::Stretch(1.0))
+                .width(Units::Pixels(right_column_width))
+                .height(Units::Pixels(right_column_width));
+
+            NoteSpectrum::new(cx, Data::params, Data::voices_output)
+                .position_type(PositionType::SelfDirected)
+                .top(Units::Pixels(right_column_width + PADDING * 2.0))
+                .right(Units::Pixels(PADDING))
+                .left(Units::Stretch(1.0))
+                .bottom(Units::Pixels(
+                    BOTTOM_REGION_HEIGHT + OCTAVE_HISTOGRAM_HEIGHT + PADDING * 2.0,
+                ))
+                .width(Units::Pixels(right_column_width));
+
+            OctaveHistogram::new(cx, Data::params, Data::voices_output)
+                .position_type(PositionType::SelfDirected)
+                .top(Units::Stretch(1.0))
+                .right(Units::Pixels(PADDING))
+                .left(Units::Stretch(1.0))
+                .bottom(Units::Pixels(BOTTOM_REGION_HEIGHT + PADDING))
+                .width(Units::Pixels(right_column_width))
+                .height(Units::Pixels(OCTAVE_HISTOGRAM_HEIGHT));
+
+            SettingsOverlay::new(cx, Data::params)
+                .position_type(PositionType::SelfDirected)
+                .top(Units::Pixels(PADDING))
+                .left(Units::Pixels(PADDING))
+                .bottom(Units::Pixels(BOTTOM_REGION_HEIGHT + PADDING))
+                .right(Units::Pixels(RIGHT_REGION_WIDTH + PADDING))
+                .child_space(Units::Pixels(PADDING))
+                .row_between(Units::Pixels(PADDING))
+                .display(Data::settings_open);
+
+            Resizer::new(cx, Data::params.map(|p| p.grid_params.clone()))
                 .position_type(PositionType::SelfDirected)
                 .right(Units::Pixels(PADDING))
                 .bottom(Units::Pixels(PADDING))
@@ -172,3 +524,182 @@ pub fn create(data: Data) -> Option<Box<dyn Editor>> {
 fn intersects_box(bounds: BoundingBox, (x, y): (f32, f32)) -> bool {
     x >= bounds.x && y >= bounds.y && x <= bounds.x + bounds.w && y <= bounds.y + bounds.h
 }
+
+/// How often views that need to update without input are ticked, about 60 times per second.
+/// This version of vizia has no timers, so ticks come from threads.
+pub const TICK_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Calls `tick` every `interval` until `alive` is dropped, or `tick` returns false, e.g. because
+/// the editor closed and its events can't be emitted anymore. Run on a thread spawned by a view
+/// that owns `alive`, so the thread ends along with the view.
+pub fn run_ticks(alive: Weak<()>, interval: Duration, mut tick: impl FnMut() -> bool) {
+    while alive.strong_count() > 0 && tick() {
+        thread::sleep(interval);
+    }
+}
+
+/// Shows a file dialog on a thread of its own, so the editor keeps drawing and the host isn't
+/// blocked while it's open. `dialog` runs on that thread, and the event it returns, if any, is
+/// emitted from the view that called this.
+pub fn spawn_file_dialog<E>(
+    cx: &mut EventContext,
+    dialog: impl FnOnce() -> Option<E> + Send + 'static,
+) where
+    E: Send + 'static,
+{
+    cx.spawn(move |cx_proxy| {
+        if let Some(event) = dialog() {
+            // Fails if the editor was closed while the dialog was open
+            let _ = cx_proxy.emit(event);
+        }
+    });
+}
+
+#[cfg(test)]
+mod run_ticks_tests {
+    use crate::editor::run_ticks;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn stops_when_tick_fails() {
+        let alive = Arc::new(());
+        let mut ticks = 0;
+        run_ticks(Arc::downgrade(&alive), Duration::ZERO, || {
+            ticks += 1;
+            ticks < 3
+        });
+        assert_eq!(ticks, 3);
+    }
+
+    #[test]
+    fn stops_when_owner_is_dropped() {
+        let alive = Arc::new(());
+        let weak = Arc::downgrade(&alive);
+        let thread = std::thread::spawn(move || {
+            let mut ticks = 0;
+            run_ticks(weak, Duration::from_millis(1), || {
+                ticks += 1;
+                true
+            });
+            ticks
+        });
+        std::thread::sleep(Duration::from_millis(10));
+        drop(alive);
+        assert!(thread.join().unwrap() > 0);
+
+        let mut ticks = 0;
+        run_ticks(std::sync::Weak::new(), Duration::ZERO, || {
+            ticks += 1;
+            true
+        });
+        assert_eq!(ticks, 0);
+    }
+}
+
+#[cfg(test)]
+mod grid_size_tests {
+    use crate::editor::lattice::grid::{MAX_NODE_SIZE, MIN_NODE_SIZE, NODE_SIZE};
+    use crate::editor::{
+        grid_height_to_height, grid_size_for_node_size, grid_width_to_width, height_to_grid_height,
+        min_grid_width, width_to_grid_width, MAX_GRID_HEIGHT, MAX_GRID_WIDTH, MIN_GRID_HEIGHT,
+        MIN_GRID_WIDTH, MIN_WINDOW_WIDTH, PADDING,
+    };
+
+    const NODE_SIZES: [f32; 3] = [MIN_NODE_SIZE, NODE_SIZE, MAX_NODE_SIZE];
+
+    #[test]
+    fn narrowest_grid_fits_controls() {
+        assert_eq!(min_grid_width(NODE_SIZE), MIN_GRID_WIDTH);
+        for node_size in NODE_SIZES {
+            let grid_width = min_grid_width(node_size);
+            assert!(grid_width_to_width(grid_width, node_size) >= MIN_WINDOW_WIDTH);
+            assert!(grid_width_to_width(grid_width - 1, node_size) < MIN_WINDOW_WIDTH);
+        }
+    }
+
+    #[test]
+    fn sizes_round_trip() {
+        for node_size in NODE_SIZES {
+            for grid_width in min_grid_width(node_size)..=MAX_GRID_WIDTH {
+                assert_eq!(
+                    width_to_grid_width(grid_width_to_width(grid_width, node_size), node_size),
+                    grid_width
+                );
+            }
+            for grid_height in MIN_GRID_HEIGHT..=MAX_GRID_HEIGHT {
+                assert_eq!(
+                    height_to_grid_height(grid_height_to_height(grid_height, node_size), node_size),
+                    grid_height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_edge_inputs() {
+        for size in [f32::NEG_INFINITY, -1000.0, 0.0, 1.0, f32::NAN] {
+            assert_eq!(width_to_grid_width(size, NODE_SIZE), MIN_GRID_WIDTH);
+            assert_eq!(height_to_grid_height(size, NODE_SIZE), MIN_GRID_HEIGHT);
+        }
+        for size in [100_000.0, f32::INFINITY] {
+            assert_eq!(width_to_grid_width(size, NODE_SIZE), MAX_GRID_WIDTH);
+            assert_eq!(height_to_grid_height(size, NODE_SIZE), MAX_GRID_HEIGHT);
+        }
+        // Partial nodes round down
+        assert_eq!(
+            width_to_grid_width(grid_width_to_width(10, NODE_SIZE) + 40.0, NODE_SIZE),
+            10
+        );
+    }
+
+    #[test]
+    fn zooming_keeps_window_size() {
+        let zoomed = grid_size_for_node_size((12, 7), NODE_SIZE, MAX_NODE_SIZE);
+        let stride = MAX_NODE_SIZE + PADDING;
+        assert!(
+            (grid_width_to_width(zoomed.0, MAX_NODE_SIZE) - grid_width_to_width(12, NODE_SIZE))
+                .abs()
+                <= stride * 0.5
+        );
+        assert!(
+            (grid_height_to_height(zoomed.1, MAX_NODE_SIZE) - grid_height_to_height(7, NODE_SIZE))
+                .abs()
+                <= stride * 0.5
+        );
+        // Rounding to the nearest node, rather than down, doesn't shrink the window as it zooms
+        assert_eq!(
+            grid_size_for_node_size(zoomed, MAX_NODE_SIZE, NODE_SIZE),
+            (12, 7)
+        );
+    }
+}
+
+#[cfg(test)]
+mod snap_tests {
+    use crate::editor::{snap, snap_line_position, snap_line_width};
+
+    #[test]
+    fn snaps_only_at_integer_scales() {
+        assert_eq!(snap(10.4, 1.0), 10.0);
+        assert_eq!(snap(10.6, 2.0), 11.0);
+        assert_eq!(snap(10.4, 1.5), 10.4);
+    }
+
+    #[test]
+    fn line_widths() {
+        assert_eq!(snap_line_width(1.5, 1.0), 1.0);
+        assert_eq!(snap_line_width(1.5, 2.0), 2.0);
+        assert_eq!(snap_line_width(0.5, 2.0), 2.0);
+        assert_eq!(snap_line_width(1.5, 1.5), 2.25);
+    }
+
+    #[test]
+    fn line_positions() {
+        // Odd widths are centered on pixels, even widths between them
+        assert_eq!(snap_line_position(10.2, 1.0, 1.0), 10.5);
+        assert_eq!(snap_line_position(10.7, 1.0, 1.0), 10.5);
+        assert_eq!(snap_line_position(10.7, 2.0, 2.0), 11.0);
+        assert_eq!(snap_line_position(10.7, 2.0, 1.25), 10.7);
+    }
+}
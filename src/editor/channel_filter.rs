@@ -0,0 +1,170 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{
+    intersects_box, snap_bounds, snap_line_width, FontInfo, CORNER_RADIUS, PADDING,
+};
+use crate::midi::{channel_enabled, ChannelRole};
+use crate::MidiLatticeParams;
+
+// Channel toggles are laid out in two rows
+const COLUMNS: u8 = 8;
+const ROWS: u8 = 2;
+
+/// A toggle for each MIDI channel, deciding whether its notes are shown. Right clicking a toggle
+/// cycles how the channel's notes are drawn: colored, outlined or hidden.
+pub struct ChannelFilter {
+    params: Arc<MidiLatticeParams>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl ChannelFilter {
+    pub fn new<LParams>(cx: &mut Context, params: LParams) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<MidiLatticeParams>>,
+    {
+        Self {
+            params: params.get(cx),
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// Bounds of a channel's toggle, with padding between toggles
+fn toggle_bounds(bounds: BoundingBox, channel: u8, padding: f32) -> BoundingBox {
+    let (column, row) = (channel % COLUMNS, channel / COLUMNS);
+    let w = (bounds.w - padding * (COLUMNS - 1) as f32) / COLUMNS as f32;
+    let h = (bounds.h - padding * (ROWS - 1) as f32) / ROWS as f32;
+    BoundingBox {
+        x: bounds.x + (w + padding) * column as f32,
+        y: bounds.y + (h + padding) * row as f32,
+        w,
+        h,
+    }
+}
+
+impl View for ChannelFilter {
+    fn element(&self) -> Option<&'static str> {
+        Some("channel-filter")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        let padding = PADDING * 0.5 * cx.scale_factor();
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+        let clicked_channel = (0..16)
+            .find(|channel| intersects_box(toggle_bounds(cx.bounds(), *channel, padding), mouse));
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                if let Some(channel) = clicked_channel {
+                    self.params
+                        .enabled_channels
+                        .fetch_xor(1 << channel, Ordering::Relaxed);
+                }
+            }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                if let Some(channel) = clicked_channel {
+                    self.params
+                        .channel_roles
+                        .write()
+                        .unwrap()
+                        .cycle_role(channel);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.params.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = cx.bounds();
+        let padding = PADDING * 0.5 * scale;
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let enabled_channels = self.params.enabled_channels.load(Ordering::Relaxed);
+        let channel_roles = *self.params.channel_roles.read().unwrap();
+        let custom_colors = *self.params.custom_colors.read().unwrap();
+        let grid_params = &self.params.grid_params;
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        for channel in 0..16 {
+            let toggle = snap_bounds(toggle_bounds(bounds, channel, padding), scale);
+            let enabled = channel_enabled(enabled_channels, channel);
+
+            let mut toggle_path = vg::Path::new();
+            toggle_path.rounded_rect(
+                toggle.x,
+                toggle.y,
+                toggle.w,
+                toggle.h,
+                CORNER_RADIUS * scale,
+            );
+            canvas.fill_path(
+                &mut toggle_path,
+                &vg::Paint::color(if intersects_box(toggle, mouse) {
+                    colors.highlight
+                } else if enabled {
+                    colors.base
+                } else {
+                    colors.background
+                }),
+            );
+
+            // Colored channels get a stripe in their color, at middle C for pitch gradients.
+            // Outlined channels get an outline.
+            match channel_roles.role(channel) {
+                ChannelRole::Color => {
+                    let mut stripe_path = vg::Path::new();
+                    stripe_path.rect(
+                        toggle.x,
+                        toggle.y + toggle.h * 0.85,
+                        toggle.w,
+                        toggle.h * 0.15,
+                    );
+                    canvas.fill_path(
+                        &mut stripe_path,
+                        &vg::Paint::color(note_color(
+                            grid_params.color_palette.value(),
+                            &custom_colors,
+                            channel,
+                            60.0,
+                            grid_params.darkest_pitch.value(),
+                            grid_params.brightest_pitch.value(),
+                        )),
+                    );
+                }
+                ChannelRole::Outline => {
+                    let mut outline_paint = vg::Paint::color(colors.text);
+                    outline_paint.set_line_width(snap_line_width(scale, scale));
+                    canvas.stroke_path(&mut toggle_path, &outline_paint);
+                }
+                ChannelRole::Hide => {}
+            }
+
+            // Channel numbers start at 1, as in most DAWs
+            // Hidden channels' numbers are dimmed
+            let mut text_paint = vg::Paint::color(if !enabled {
+                colors.base
+            } else if channel_roles.role(channel) == ChannelRole::Hide {
+                colors.background
+            } else {
+                colors.text
+            });
+            text_paint.set_font_size(toggle.h * 0.6);
+            text_paint.set_text_baseline(vg::Baseline::Middle);
+            text_paint.set_text_align(vg::Align::Center);
+            font_id.map(|f| text_paint.set_font(&[f]));
+            let _ = canvas.fill_text(
+                toggle.x + toggle.w * 0.5,
+                toggle.y + toggle.h * 0.5,
+                &(channel + 1).to_string(),
+                &text_paint,
+            );
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use crate::command::{AudioCommand, CommandSender};
+use crate::GridParams;
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, make_icon_stroke_paint, snap_bounds};
+
+use super::PADDING;
+
+/// Removes notes kept on the lattice by the "Latch Notes" parameter
+pub struct ClearLatchedButton {
+    pressed: bool,
+    command_sender: Arc<Mutex<CommandSender>>,
+    grid_params: Arc<GridParams>,
+}
+
+impl ClearLatchedButton {
+    pub fn new<LCommands, LGridParams>(
+        cx: &mut Context,
+        command_sender: LCommands,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LCommands: Lens<Target = Arc<Mutex<CommandSender>>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            pressed: false,
+            command_sender: command_sender.get(cx),
+            grid_params: grid_params.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for ClearLatchedButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("clear-latched-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.capture();
+                self.pressed = true;
+                self.command_sender
+                    .lock()
+                    .unwrap()
+                    .send(AudioCommand::ClearLatchedVoices);
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.pressed {
+                    cx.release();
+                    self.pressed = false;
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let highlighted: bool =
+            self.pressed || intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            crate::editor::CORNER_RADIUS * scale,
+        );
+        container_path.close();
+
+        let paint = vg::Paint::color(if self.pressed {
+            colors.text
+        } else if highlighted {
+            colors.highlight
+        } else {
+            colors.base
+        });
+        canvas.fill_path(&mut container_path, &paint);
+
+        let icon_line_width: f32 = PADDING * scale;
+        let icon_padding: f32 = PADDING * 1.5 * scale + icon_line_width * 0.5;
+
+        // Draw an open padlock - a body on the bottom half, and a shackle lifted out of it
+        let (left, right) = (bounds.x + icon_padding, bounds.x + bounds.w - icon_padding);
+        let (top, bottom) = (bounds.y + icon_padding, bounds.y + bounds.h - icon_padding);
+        let body_top = top + (bottom - top) * 0.5;
+        let shackle_radius = (right - left) * 0.3;
+        let shackle_x = left + (right - left) * 0.5 + shackle_radius;
+
+        let mut icon_path = vg::Path::new();
+        icon_path.rect(left, body_top, right - left, bottom - body_top);
+        icon_path.move_to(shackle_x - shackle_radius, body_top);
+        icon_path.line_to(shackle_x - shackle_radius, top + shackle_radius);
+        icon_path.arc(
+            shackle_x,
+            top + shackle_radius,
+            shackle_radius,
+            std::f32::consts::PI,
+            0.0,
+            vg::Solidity::Hole,
+        );
+
+        let icon_paint = make_icon_stroke_paint(colors.background, scale);
+
+        canvas.stroke_path(&mut icon_path, &icon_paint);
+    }
+}
@@ -4,28 +4,63 @@ use color_space::{Lch, Rgb};
 use nih_plug_vizia::vizia::vg::{self, Color};
 use once_cell::sync::Lazy;
 
+use crate::{ColorPalette, CustomColors, Theme};
+
 const fn grey(rgb_value: f32) -> vg::Color {
     vg::Color::rgbf(rgb_value, rgb_value, rgb_value)
 }
 
 const MAX_COLOR_VALUE: f32 = 255.0;
 
-// Darkest color - for background
-pub static BACKGROUND_COLOR: vg::Color = grey(0x38 as f32 / MAX_COLOR_VALUE);
-
-// For buttons and nodes in their default state.
-pub static BASE_COLOR: vg::Color = grey(0x60 as f32 / MAX_COLOR_VALUE);
+/// Colors of everything but notes, which depend on the theme
+pub struct ThemeColors {
+    /// Furthest from the text color - for background
+    pub background: vg::Color,
+    /// For buttons and nodes in their default state
+    pub base: vg::Color,
+    /// For highlighted nodes, and moused over buttons
+    pub highlight: vg::Color,
+    /// For text, or focused buttons
+    pub text: vg::Color,
+    /// For lines between sounding nodes, which need to stand out from the nodes' colors
+    pub interval: vg::Color,
+    /// For overlay buttons on lattice, which are only shown on mouse over
+    pub overlay_base: vg::Color,
+    pub overlay_hover: vg::Color,
+    pub overlay_press: vg::Color,
+}
 
-// For highlighted nodes, and moused over buttons.
-pub static HIGHLIGHT_COLOR: vg::Color = grey(0x80 as f32 / MAX_COLOR_VALUE);
+pub static DARK_THEME_COLORS: ThemeColors = ThemeColors {
+    background: grey(0x38 as f32 / MAX_COLOR_VALUE),
+    base: grey(0x60 as f32 / MAX_COLOR_VALUE),
+    highlight: grey(0x80 as f32 / MAX_COLOR_VALUE),
+    text: grey(0xff as f32 / MAX_COLOR_VALUE),
+    interval: vg::Color::rgbf(0.36, 0.88, 0.9),
+    overlay_base: vg::Color::rgbaf(1.0, 1.0, 1.0, 0.4),
+    overlay_hover: vg::Color::rgbaf(1.0, 1.0, 1.0, 0.8),
+    overlay_press: vg::Color::rgbaf(1.0, 1.0, 1.0, 1.0),
+};
 
-// For text, or focused buttons
-pub static TEXT_COLOR: vg::Color = grey(0xff as f32 / MAX_COLOR_VALUE);
+// The dark theme's greys inverted, with a darker interval color to stand out from light nodes.
+// Must match the background in theme_light.css.
+pub static LIGHT_THEME_COLORS: ThemeColors = ThemeColors {
+    background: grey(0xf0 as f32 / MAX_COLOR_VALUE),
+    base: grey(0xc8 as f32 / MAX_COLOR_VALUE),
+    highlight: grey(0xa8 as f32 / MAX_COLOR_VALUE),
+    text: grey(0x20 as f32 / MAX_COLOR_VALUE),
+    interval: vg::Color::rgbf(0.0, 0.42, 0.5),
+    overlay_base: vg::Color::rgbaf(0.0, 0.0, 0.0, 0.4),
+    overlay_hover: vg::Color::rgbaf(0.0, 0.0, 0.0, 0.7),
+    overlay_press: vg::Color::rgbaf(0.0, 0.0, 0.0, 1.0),
+};
 
-// Colors for overlay buttons on lattice, which are only shown on mouse over.
-pub static OVERLAY_COLOR_BASE: vg::Color = vg::Color::rgbaf(1.0, 1.0, 1.0, 0.4);
-pub static OVERLAY_COLOR_HOVER: vg::Color = vg::Color::rgbaf(1.0, 1.0, 1.0, 0.8);
-pub static OVERLAY_COLOR_PRESS: vg::Color = vg::Color::rgbaf(1.0, 1.0, 1.0, 1.0);
+/// Colors of a theme. Views look these up from the theme parameter each time they draw.
+pub fn theme_colors(theme: Theme) -> &'static ThemeColors {
+    match theme {
+        Theme::Dark => &DARK_THEME_COLORS,
+        Theme::Light => &LIGHT_THEME_COLORS,
+    }
+}
 
 // Maps channels to static colors
 // Note: channel numbers here are 1 lower than the MIDI convention they're zero-indexed
@@ -40,13 +75,156 @@ pub static CHANNEL_COLORS: Lazy<[vg::Color; 9]> = Lazy::new(|| {
         Lch::new(70.0, 30.0, 340.0), // 6 pink
         Lch::new(80.0, 0.0, 0.0),    // 7 white
         Lch::new(0.0, 0.0, 0.0),     // 8 black
-                                     // 9-13 are colored based on pitch (see `note_color()`)
-                                     // 14 is just an outline
-                                     // 15 is ignored
+                                     // 9-15 are colored based on pitch (see `note_color()`)
     ]
     .map(|x| lch_to_vg_color(x))
 });
 
+// Replaces `CHANNEL_COLORS` for color vision deficiencies. Hues are from the Okabe-Ito palette,
+// and neighboring channels alternate between dark and light, so they differ in lightness even
+// where their hues look alike.
+pub static COLORBLIND_CHANNEL_COLORS: Lazy<[vg::Color; 9]> = Lazy::new(|| {
+    [
+        Lch::new(52.0, 75.0, 45.0),  // 0 vermillion
+        Lch::new(72.0, 35.0, 240.0), // 1 sky blue
+        Lch::new(45.0, 50.0, 265.0), // 2 blue
+        Lch::new(90.0, 70.0, 100.0), // 3 yellow
+        Lch::new(57.0, 45.0, 165.0), // 4 bluish green
+        Lch::new(75.0, 75.0, 75.0),  // 5 orange
+        Lch::new(62.0, 40.0, 345.0), // 6 reddish purple
+        Lch::new(80.0, 0.0, 0.0),    // 7 white
+        Lch::new(0.0, 0.0, 0.0),     // 8 black
+    ]
+    .map(|x| lch_to_vg_color(x))
+});
+
+// Hues, in Lch degrees, that the palettes for each kind of color vision deficiency are built
+// from. Each palette pairs them with lightnesses and chromas that keep its channels apart under a
+// simulation of that deficiency (Machado et al., 2009).
+const RED_HUE: f64 = 30.0;
+const ORANGE_HUE: f64 = 60.0;
+const YELLOW_HUE: f64 = 95.0;
+const GREEN_HUE: f64 = 135.0;
+const TEAL_HUE: f64 = 190.0;
+const SKY_BLUE_HUE: f64 = 235.0;
+const BLUE_HUE: f64 = 275.0;
+const PURPLE_HUE: f64 = 305.0;
+const MAGENTA_HUE: f64 = 340.0;
+
+// Replaces `CHANNEL_COLORS` for deuteranopia, where reds and greens look alike
+pub static DEUTERANOPIA_CHANNEL_COLORS: Lazy<[vg::Color; 9]> = Lazy::new(|| {
+    [
+        Lch::new(35.0, 55.0, MAGENTA_HUE), // 0 dark magenta
+        Lch::new(35.0, 35.0, GREEN_HUE),   // 1 dark green
+        Lch::new(90.0, 75.0, YELLOW_HUE),  // 2 yellow
+        Lch::new(75.0, 35.0, BLUE_HUE),    // 3 light blue
+        Lch::new(55.0, 55.0, ORANGE_HUE),  // 4 orange
+        Lch::new(45.0, 75.0, PURPLE_HUE),  // 5 purple
+        Lch::new(90.0, 35.0, YELLOW_HUE),  // 6 cream
+        Lch::new(80.0, 0.0, 0.0),          // 7 white
+        Lch::new(0.0, 0.0, 0.0),           // 8 black
+    ]
+    .map(|x| lch_to_vg_color(x))
+});
+
+// Replaces `CHANNEL_COLORS` for protanopia, where reds also look darker
+pub static PROTANOPIA_CHANNEL_COLORS: Lazy<[vg::Color; 9]> = Lazy::new(|| {
+    [
+        Lch::new(90.0, 35.0, YELLOW_HUE),  // 0 cream
+        Lch::new(35.0, 55.0, MAGENTA_HUE), // 1 dark magenta
+        Lch::new(45.0, 55.0, RED_HUE),     // 2 red
+        Lch::new(65.0, 35.0, BLUE_HUE),    // 3 blue
+        Lch::new(90.0, 75.0, YELLOW_HUE),  // 4 yellow
+        Lch::new(55.0, 75.0, ORANGE_HUE),  // 5 orange
+        Lch::new(45.0, 75.0, PURPLE_HUE),  // 6 purple
+        Lch::new(80.0, 0.0, 0.0),          // 7 white
+        Lch::new(0.0, 0.0, 0.0),           // 8 black
+    ]
+    .map(|x| lch_to_vg_color(x))
+});
+
+// Replaces `CHANNEL_COLORS` for tritanopia, where blues and greens look alike, as do yellows and
+// pinks
+pub static TRITANOPIA_CHANNEL_COLORS: Lazy<[vg::Color; 9]> = Lazy::new(|| {
+    [
+        Lch::new(90.0, 75.0, GREEN_HUE),   // 0 light green
+        Lch::new(55.0, 75.0, GREEN_HUE),   // 1 green
+        Lch::new(35.0, 55.0, MAGENTA_HUE), // 2 dark magenta
+        Lch::new(55.0, 75.0, RED_HUE),     // 3 red
+        Lch::new(35.0, 75.0, PURPLE_HUE),  // 4 dark purple
+        Lch::new(75.0, 35.0, RED_HUE),     // 5 pink
+        Lch::new(45.0, 55.0, YELLOW_HUE),  // 6 olive
+        Lch::new(80.0, 0.0, 0.0),          // 7 white
+        Lch::new(0.0, 0.0, 0.0),           // 8 black
+    ]
+    .map(|x| lch_to_vg_color(x))
+});
+
+// Replaces `CHANNEL_COLORS` with colors no darker than the nodes, which stay apart for any kind of
+// color vision deficiency too
+pub static HIGH_CONTRAST_CHANNEL_COLORS: Lazy<[vg::Color; 9]> = Lazy::new(|| {
+    [
+        Lch::new(55.0, 35.0, MAGENTA_HUE),  // 0 mauve
+        Lch::new(55.0, 55.0, ORANGE_HUE),   // 1 orange
+        Lch::new(75.0, 75.0, YELLOW_HUE),   // 2 yellow
+        Lch::new(75.0, 35.0, SKY_BLUE_HUE), // 3 sky blue
+        Lch::new(65.0, 35.0, GREEN_HUE),    // 4 green
+        Lch::new(55.0, 75.0, PURPLE_HUE),   // 5 purple
+        Lch::new(90.0, 55.0, GREEN_HUE),    // 6 light green
+        Lch::new(80.0, 0.0, 0.0),           // 7 white
+        Lch::new(0.0, 0.0, 0.0),            // 8 black
+    ]
+    .map(|x| lch_to_vg_color(x))
+});
+
+// Colors that color swatches cycle through, packed as 0xRRGGBB
+pub const SWATCH_COLORS: [u32; 14] = [
+    0xd0413a, // red
+    0xee8a2c, // orange
+    0xe8cf4a, // yellow
+    0x9bcf3e, // lime
+    0x3fa45b, // green
+    0x36b1a4, // teal
+    0x4cb6e6, // sky blue
+    0x4a6fd6, // blue
+    0x8a55c9, // purple
+    0xc94fb2, // magenta
+    0xef93b8, // pink
+    0xc6c6c6, // white
+    0x8a8a8a, // grey
+    0x000000, // black
+];
+
+/// The swatch color after `current`, or the first if `current` isn't a swatch color
+pub fn next_swatch_color(current: Option<u32>) -> u32 {
+    let next_idx = current
+        .and_then(|rgb| SWATCH_COLORS.iter().position(|swatch| *swatch == rgb))
+        .map_or(0, |idx| (idx + 1) % SWATCH_COLORS.len());
+    SWATCH_COLORS[next_idx]
+}
+
+pub fn unpack_color(rgb: u32) -> Color {
+    Color::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+/// Packs a color's RGB components as 0xRRGGBB, dropping its alpha
+pub fn pack_color(color: Color) -> u32 {
+    let component = |value: f32| (value.clamp(0.0, 1.0) * MAX_COLOR_VALUE).round() as u32;
+    component(color.r) << 16 | component(color.g) << 8 | component(color.b)
+}
+
+/// Turns a packed color's hue by `degrees`, keeping its lightness and chroma. Greys stay grey.
+pub fn rotate_hue(rgb: u32, degrees: f64) -> u32 {
+    let unpacked = unpack_color(rgb);
+    let mut lch = Lch::from(Rgb::new(
+        unpacked.r as f64 * 255.0,
+        unpacked.g as f64 * 255.0,
+        unpacked.b as f64 * 255.0,
+    ));
+    lch.h = (lch.h + degrees).rem_euclid(360.0);
+    pack_color(lch_to_vg_color(lch))
+}
+
 fn lch_to_vg_color(lch_color: Lch) -> vg::Color {
     let rgbcolor = Rgb::from(lch_color);
 
@@ -57,23 +235,242 @@ fn lch_to_vg_color(lch_color: Lch) -> vg::Color {
     )
 }
 
-pub fn note_color(channel: u8, pitch: f32, darkest_pitch: f32, brightest_pitch: f32) -> Color {
+// Intensity of notes played at zero velocity, when velocity affects their appearance
+const MIN_VELOCITY_INTENSITY: f32 = 0.35;
+
+/// Scales opacity or thickness of a note by its velocity, from 0 to 1
+pub fn velocity_intensity(velocity: f32) -> f32 {
+    MIN_VELOCITY_INTENSITY + (1.0 - MIN_VELOCITY_INTENSITY) * velocity.clamp(0.0, 1.0)
+}
+
+/// Moves a color's RGB components toward the theme's highlight color, by `amount` from 0 to 1.
+/// Keeps the color's alpha.
+pub fn blend_toward_highlight(colors: &ThemeColors, color: Color, amount: f32) -> Color {
+    let amount = amount.clamp(0.0, 1.0);
+    let blend = |from: f32, to: f32| from + (to - from) * amount;
+    Color::rgbaf(
+        blend(color.r, colors.highlight.r),
+        blend(color.g, colors.highlight.g),
+        blend(color.b, colors.highlight.b),
+        color.a,
+    )
+}
+
+/// Color of a note on a channel with the [`crate::midi::ChannelRole::Color`] role
+pub fn note_color(
+    palette: ColorPalette,
+    custom: &CustomColors,
+    channel: u8,
+    pitch: f32,
+    darkest_pitch: f32,
+    brightest_pitch: f32,
+) -> Color {
     if channel <= 8 {
         // These channels have a fixed color
-        return CHANNEL_COLORS[usize::from(channel)];
-    } else if channel <= 13 {
+        if let Some(rgb) = custom.channels[usize::from(channel)] {
+            return unpack_color(rgb);
+        }
+        let colors = match palette {
+            ColorPalette::Standard => &CHANNEL_COLORS,
+            ColorPalette::ColorblindFriendly => &COLORBLIND_CHANNEL_COLORS,
+            ColorPalette::Deuteranopia => &DEUTERANOPIA_CHANNEL_COLORS,
+            ColorPalette::Protanopia => &PROTANOPIA_CHANNEL_COLORS,
+            ColorPalette::Tritanopia => &TRITANOPIA_CHANNEL_COLORS,
+            ColorPalette::HighContrast => &HIGH_CONTRAST_CHANNEL_COLORS,
+        };
+        return colors[usize::from(channel)];
+    } else {
         // These channels are colored by pitch, on a gradient
         let pitch_color_index: f64 =
             ((pitch.min(brightest_pitch).max(darkest_pitch) - darkest_pitch)
                 / (brightest_pitch - darkest_pitch).max(0.01)) as f64;
-        return lch_to_vg_color(Lch::new(
-            25.0 + pitch_color_index * 55.0,
-            65.0 - pitch_color_index * 35.0,
-            (-20.0 + pitch_color_index * 110.0).rem_euclid(360.0),
-        ));
-    } else if channel == 14 {
-        return HIGHLIGHT_COLOR;
-    } else {
-        panic!("Invalid midi channel");
+        if custom.darkest.is_none() && custom.brightest.is_none() {
+            return pitch_gradient_color(palette, pitch_color_index);
+        }
+        // Custom ends are blended in RGB, since they can be any two colors
+        let darkest = custom
+            .darkest
+            .map_or_else(|| pitch_gradient_color(palette, 0.0), unpack_color);
+        let brightest = custom
+            .brightest
+            .map_or_else(|| pitch_gradient_color(palette, 1.0), unpack_color);
+        let blend = |from: f32, to: f32| from + (to - from) * pitch_color_index as f32;
+        return Color::rgbf(
+            blend(darkest.r, brightest.r),
+            blend(darkest.g, brightest.g),
+            blend(darkest.b, brightest.b),
+        );
+    }
+}
+
+/// Lightness, chroma and hue at one end of a pitch gradient
+struct GradientEnd {
+    lightness: f64,
+    chroma: f64,
+    hue: f64,
+}
+
+// Each palette's pitch gradient, from the darkest pitch to the brightest. Hues move linearly
+// between the ends, so a hue below 0 or past 360 picks which way round the gradient turns.
+// From dark red to yellow green
+const STANDARD_GRADIENT: [GradientEnd; 2] = [
+    GradientEnd {
+        lightness: 25.0,
+        chroma: 65.0,
+        hue: -20.0,
+    },
+    GradientEnd {
+        lightness: 80.0,
+        chroma: 30.0,
+        hue: 90.0,
+    },
+];
+// From dark blue to yellow through teal, which deuteranopia and protanopia keep apart
+const BLUE_YELLOW_GRADIENT: [GradientEnd; 2] = [
+    GradientEnd {
+        lightness: 25.0,
+        chroma: 35.0,
+        hue: BLUE_HUE,
+    },
+    GradientEnd {
+        lightness: 90.0,
+        chroma: 70.0,
+        hue: YELLOW_HUE,
+    },
+];
+// From dark red to light teal, which tritanopia keeps apart
+const RED_TEAL_GRADIENT: [GradientEnd; 2] = [
+    GradientEnd {
+        lightness: 25.0,
+        chroma: 60.0,
+        hue: RED_HUE,
+    },
+    GradientEnd {
+        lightness: 88.0,
+        chroma: 40.0,
+        hue: TEAL_HUE,
+    },
+];
+// From near black purple to yellow, over the widest range of lightness
+const HIGH_CONTRAST_GRADIENT: [GradientEnd; 2] = [
+    GradientEnd {
+        lightness: 15.0,
+        chroma: 60.0,
+        hue: PURPLE_HUE,
+    },
+    GradientEnd {
+        lightness: 90.0,
+        chroma: 70.0,
+        hue: YELLOW_HUE,
+    },
+];
+
+/// Color of a palette's pitch gradient, from 0 at the darkest pitch to 1 at the brightest
+fn pitch_gradient_color(palette: ColorPalette, pitch_color_index: f64) -> Color {
+    let [darkest, brightest] = match palette {
+        ColorPalette::Standard | ColorPalette::ColorblindFriendly => &STANDARD_GRADIENT,
+        ColorPalette::Deuteranopia | ColorPalette::Protanopia => &BLUE_YELLOW_GRADIENT,
+        ColorPalette::Tritanopia => &RED_TEAL_GRADIENT,
+        ColorPalette::HighContrast => &HIGH_CONTRAST_GRADIENT,
+    };
+    let blend = |from: f64, to: f64| from + (to - from) * pitch_color_index;
+    lch_to_vg_color(Lch::new(
+        blend(darkest.lightness, brightest.lightness),
+        blend(darkest.chroma, brightest.chroma),
+        blend(darkest.hue, brightest.hue).rem_euclid(360.0),
+    ))
+}
+
+#[cfg(test)]
+mod note_color_tests {
+    use crate::editor::color::{
+        next_swatch_color, note_color, pack_color, rotate_hue, unpack_color, SWATCH_COLORS,
+    };
+    use crate::{ColorPalette, CustomColors};
+    use nih_plug::prelude::Enum;
+
+    #[test]
+    fn custom_colors_replace_palette() {
+        let mut custom = CustomColors::default();
+        custom.channels[2] = Some(0x336699);
+        let color = note_color(ColorPalette::Standard, &custom, 2, 60.0, 36.0, 84.0);
+        assert_eq!(color, unpack_color(0x336699));
+        assert_ne!(
+            note_color(ColorPalette::Standard, &custom, 3, 60.0, 36.0, 84.0),
+            color
+        );
+    }
+
+    #[test]
+    fn palettes_keep_channels_apart() {
+        for palette in (0..ColorPalette::variants().len()).map(ColorPalette::from_index) {
+            let colors: Vec<_> = (0..=8)
+                .map(|channel| {
+                    note_color(palette, &CustomColors::default(), channel, 60.0, 36.0, 84.0)
+                })
+                .collect();
+            for (idx, color) in colors.iter().enumerate() {
+                assert!(!colors[idx + 1..].contains(color));
+            }
+        }
+    }
+
+    #[test]
+    fn saved_palettes_keep_their_index() {
+        assert_eq!(ColorPalette::Standard.to_index(), 0);
+        assert_eq!(ColorPalette::ColorblindFriendly.to_index(), 1);
+    }
+
+    #[test]
+    fn custom_gradient_blends_between_ends() {
+        let custom = CustomColors {
+            darkest: Some(0x000000),
+            brightest: Some(0xffffff),
+            ..Default::default()
+        };
+        let color = |pitch| note_color(ColorPalette::Standard, &custom, 9, pitch, 36.0, 84.0);
+        assert_eq!(color(36.0), unpack_color(0x000000));
+        assert_eq!(color(100.0), unpack_color(0xffffff));
+        assert!((color(60.0).r - 0.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn cycles_swatch_colors() {
+        assert_eq!(next_swatch_color(None), SWATCH_COLORS[0]);
+        assert_eq!(next_swatch_color(Some(SWATCH_COLORS[0])), SWATCH_COLORS[1]);
+        assert_eq!(
+            next_swatch_color(Some(SWATCH_COLORS[SWATCH_COLORS.len() - 1])),
+            SWATCH_COLORS[0]
+        );
+        assert_eq!(next_swatch_color(Some(0x123456)), SWATCH_COLORS[0]);
+    }
+
+    #[test]
+    fn packs_colors() {
+        for rgb in [0x000000, 0x336699, 0xd0413a, 0xffffff] {
+            assert_eq!(pack_color(unpack_color(rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn rotates_hue() {
+        // Conversions can round each component by one step
+        let assert_close = |a: u32, b: u32| {
+            let (a, b) = (unpack_color(a), unpack_color(b));
+            for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+                assert!((x - y).abs() <= 1.5 / 255.0, "{:?} != {:?}", a, b);
+            }
+        };
+        let red = SWATCH_COLORS[0];
+        assert_close(rotate_hue(red, 0.0), red);
+        assert_close(rotate_hue(red, 360.0), red);
+        // Muted colors stay in gamut, so turning them back gives the same color
+        let muted = 0x8a7060;
+        assert_close(rotate_hue(rotate_hue(muted, 30.0), -30.0), muted);
+
+        let turned = unpack_color(rotate_hue(red, 180.0));
+        assert!(turned.b > turned.r);
+
+        assert_close(rotate_hue(0x808080, 90.0), 0x808080);
     }
 }
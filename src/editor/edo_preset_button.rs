@@ -0,0 +1,183 @@
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use nih_plug_vizia::widgets::ParamEvent;
+
+use crate::tuning::*;
+use crate::{GridParams, TuningParams, MAX_TOLERANCE};
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::status_message::StatusEvent;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS};
+
+// Equal divisions of the octave that the button cycles through
+pub const EDOS: [u32; 5] = [12, 19, 22, 31, 53];
+
+/// Tunes primes 3, 5, 7 and 11 to an equal division of the octave, with a tuning tolerance of half
+/// a step. Each click moves on to the next division in [`EDOS`].
+pub struct EdoPresetButton {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+
+    /// Index into [`EDOS`] of the last applied division, if any
+    edo_idx: Option<usize>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl EdoPresetButton {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
+            edo_idx: None,
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+
+    /// Tunes to a division, unless a parameter's range can't hold its step. Clamping it would
+    /// leave a tuning that isn't in the division, so that's reported instead.
+    fn apply_edo(&self, cx: &mut EventContext, edo: u32) {
+        let tunings = edo_tunings(&self.tuning_params, edo);
+        if let Some((param, tuning)) = tunings.iter().find(|(param, tuning)| !fits(param, *tuning))
+        {
+            cx.emit(StatusEvent::Show(format!(
+                "Couldn't tune to {}-EDO: {:.1} cents is out of range for {}",
+                edo,
+                tuning,
+                param.name()
+            )));
+            return;
+        }
+        cx.emit(StatusEvent::Clear);
+
+        for (param, tuning) in tunings {
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, tuning).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+
+        let tolerance = &self.tuning_params.tolerance;
+        cx.emit(ParamEvent::BeginSetParameter(tolerance).upcast());
+        cx.emit(
+            ParamEvent::SetParameter(tolerance, edo_tolerance(edo).min(MAX_TOLERANCE)).upcast(),
+        );
+        cx.emit(ParamEvent::EndSetParameter(tolerance).upcast());
+    }
+}
+
+/// Returns the parameters for primes 3, 5, 7 and 11, each with the step of an equal division of the
+/// octave closest to its just interval
+fn edo_tunings(tuning_params: &TuningParams, edo: u32) -> [(&FloatParam, f32); 4] {
+    [
+        (&tuning_params.three, THREE_JUST_F32),
+        (&tuning_params.five, FIVE_JUST_F32),
+        (&tuning_params.seven, SEVEN_JUST_F32),
+        (&tuning_params.eleven, ELEVEN_JUST_F32),
+    ]
+    .map(|(param, just)| (param, edo_approximation(edo, just)))
+}
+
+/// Returns whether a parameter can be set to a value without clamping it
+fn fits(param: &FloatParam, value: f32) -> bool {
+    (param.preview_plain(param.preview_normalized(value)) - value).abs() < 0.001
+}
+
+#[cfg(test)]
+mod edo_tunings_tests {
+    use crate::editor::edo_preset_button::{edo_tunings, fits, EDOS};
+    use crate::TuningParams;
+    use nih_plug::prelude::Param;
+
+    #[test]
+    fn every_edo_fits_parameter_ranges() {
+        let tuning_params = TuningParams::default();
+        for edo in EDOS {
+            for (param, tuning) in edo_tunings(&tuning_params, edo) {
+                assert!(
+                    fits(param, tuning),
+                    "{}-EDO tuning of {} cents doesn't fit {}",
+                    edo,
+                    tuning,
+                    param.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn detects_out_of_range_tunings() {
+        let tuning_params = TuningParams::default();
+        // 5-EDO's fifth is 720 cents, and its major third is a whole tone
+        let [three, five, ..] = edo_tunings(&tuning_params, 5);
+        assert!(fits(three.0, three.1));
+        assert!(!fits(five.0, five.1));
+    }
+}
+
+impl View for EdoPresetButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("edo-preset-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => {
+                let edo_idx = self.edo_idx.map_or(0, |idx| (idx + 1) % EDOS.len());
+                self.edo_idx = Some(edo_idx);
+                self.apply_edo(cx, EDOS[edo_idx]);
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let highlighted: bool = intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(
+            &container_path,
+            &vg::Paint::color(if highlighted {
+                colors.highlight
+            } else {
+                colors.base
+            }),
+        );
+
+        let mut text_paint = vg::Paint::color(colors.text);
+        text_paint.set_font_size(bounds.h * 0.4);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + bounds.w * 0.5,
+            bounds.y + bounds.h * 0.5,
+            &self
+                .edo_idx
+                .map_or(String::from("EDO"), |idx| EDOS[idx].to_string()),
+            &text_paint,
+        );
+    }
+}
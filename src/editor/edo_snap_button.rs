@@ -0,0 +1,107 @@
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use nih_plug_vizia::widgets::ParamEvent;
+
+use crate::editor::edo_preset_button::EDOS;
+use crate::{GridParams, TuningParams};
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS};
+
+/// Rounds the current tunings of primes 3, 5 and 7 to the nearest steps of an equal division of
+/// the octave, e.g. to clean up learned tunings. Right clicks choose the next division in
+/// [`EDOS`].
+pub struct EdoSnapButton {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+
+    /// Index into [`EDOS`] of the division to snap to
+    edo_idx: usize,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl EdoSnapButton {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
+            edo_idx: 0,
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+
+    fn snap(&self, cx: &mut EventContext) {
+        for (param, tuning) in self.tuning_params.edo_snapped_tunings(EDOS[self.edo_idx]) {
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, tuning).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+    }
+}
+
+impl View for EdoSnapButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("edo-snap-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => self.snap(cx),
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                self.edo_idx = (self.edo_idx + 1) % EDOS.len();
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let highlighted: bool = intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(
+            &container_path,
+            &vg::Paint::color(if highlighted {
+                colors.highlight
+            } else {
+                colors.base
+            }),
+        );
+
+        // The division to snap to, with a tilde for "approximately"
+        let mut text_paint = vg::Paint::color(colors.text);
+        text_paint.set_font_size(bounds.h * 0.36);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + bounds.w * 0.5,
+            bounds.y + bounds.h * 0.5,
+            format!("~{}", EDOS[self.edo_idx]),
+            &text_paint,
+        );
+    }
+}
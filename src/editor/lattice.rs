@@ -1,51 +1,118 @@
+use crate::command::CommandSender;
+use crate::GridParams;
 use crate::MidiLatticeParams;
 use crate::Voices;
 
+use nih_plug::prelude::*;
 use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::widgets::{GuiContextEvent, ParamEvent};
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use triple_buffer::Output;
 
-use crate::editor::PADDING;
+use crate::editor::{run_ticks, PADDING, TICK_INTERVAL};
+use crate::MAX_GRID_OFFSET;
 
-use self::drag_region::DragRegion;
+use self::drag_region::{panned_offset, DragRegion};
 use self::grid::Grid;
+use self::grid::GridEvent;
 use self::grid::NODE_SIZE;
+use self::grid_history::{GridHistory, GridSnapshot};
 use self::grid_resizer::GridResizer;
+use self::grid_steppers::{stepped_offset, GridSteppers};
 
 use super::intersects_box;
+mod chord;
 mod drag_region;
 pub mod grid;
+mod grid_history;
 pub mod grid_resizer;
+mod grid_steppers;
+mod png_export;
+mod recent_notes;
+mod svg_export;
+mod tooltip;
+mod voice_deviations;
+
+// How long centering the grid on a double clicked node takes
+const CENTERING_DURATION: Duration = Duration::from_millis(150);
+
+// Nodes that each shift+arrow key press pans the grid by
+const FINE_KEY_STEP: f32 = 0.25;
 
 pub struct Lattice {
     mouse_over: bool,
+    grid_params: Arc<GridParams>,
+    history: GridHistory,
+
+    /// Centering of the grid on a double clicked node, while it's animating
+    centering: Option<Centering>,
+}
+
+/// Animation of the grid's X and Y offsets towards a double clicked node
+struct Centering {
+    from: (f32, f32),
+    to: (f32, f32),
+    start: Instant,
+
+    /// The tick thread stops once this is dropped, when centering finishes or is stopped
+    _ticking: Arc<()>,
+}
+
+/// Grid offset after an arrow key press. Plain presses move to the next whole node, like the
+/// steppers, and shift+presses move by a fraction of a node without snapping.
+fn key_panned_offset(offset: f32, step: i32, fine: bool) -> f32 {
+    if fine {
+        panned_offset(offset, step as f32 * FINE_KEY_STEP)
+    } else {
+        stepped_offset(offset, step)
+    }
+}
+
+/// Offset part way through centering, easing out so the grid slows down as it arrives
+fn centering_offset(from: f32, to: f32, progress: f32) -> f32 {
+    let eased = 1.0 - (1.0 - progress.clamp(0.0, 1.0)).powi(3);
+    from + (to - from) * eased
 }
 
 impl Lattice {
-    pub fn new<LParams, LVoices>(
+    pub fn new<LParams, LVoices, LCommands>(
         cx: &mut Context,
         params: LParams,
         voices_output: LVoices,
+        command_sender: LCommands,
     ) -> Handle<Self>
     where
         LParams: Lens<Target = Arc<MidiLatticeParams>> + Copy,
         LVoices: Lens<Target = Arc<Mutex<Output<Voices>>>>,
+        LCommands: Lens<Target = Arc<Mutex<CommandSender>>>,
     {
-        Self { mouse_over: false }.build(
+        Self {
+            mouse_over: false,
+            grid_params: params.map(|p| p.grid_params.clone()).get(cx),
+            history: GridHistory::new(),
+            centering: None,
+        }
+        .build(
             cx,
             // This is an otherwise empty element only used for custom drawing
-            |cx| {
-                Grid::new(cx, params, voices_output)
+            move |cx| {
+                Grid::new(cx, params, voices_output, command_sender)
                     .position_type(PositionType::SelfDirected)
                     .bottom(Units::Pixels(0.0))
                     .left(Units::Pixels(0.0))
                     .top(Units::Pixels(0.0))
                     .right(Units::Pixels(0.0));
 
-                DragRegion::new(cx, params.map(|p| p.grid_params.clone()))
-                    .position_type(PositionType::ParentDirected)
-                    .width(Units::Stretch(1.0))
-                    .height(Units::Stretch(1.0));
+                DragRegion::new(
+                    cx,
+                    params.map(|p| p.grid_params.clone()),
+                    params.map(|p| p.tuning_params.clone()),
+                )
+                .position_type(PositionType::ParentDirected)
+                .width(Units::Stretch(1.0))
+                .height(Units::Stretch(1.0));
 
                 GridResizer::new(cx, params.map(|p| p.grid_params.clone()))
                     .position_type(PositionType::SelfDirected)
@@ -56,11 +123,139 @@ impl Lattice {
                     .width(Units::Pixels(NODE_SIZE * 1.5))
                     .height(Units::Pixels(NODE_SIZE * 1.5))
                     .visibility(Visibility::Hidden);
+
+                GridSteppers::new(cx, params.map(|p| p.grid_params.clone()))
+                    .position_type(PositionType::SelfDirected)
+                    .top(Units::Pixels(PADDING * 2.0))
+                    .right(Units::Pixels(PADDING * 2.0))
+                    .left(Units::Stretch(1.0))
+                    .bottom(Units::Stretch(1.0))
+                    .width(Units::Pixels(NODE_SIZE * 1.2))
+                    .height(Units::Pixels(NODE_SIZE * 1.2))
+                    .visibility(Visibility::Hidden);
             },
         )
     }
 }
 
+impl Lattice {
+    fn grid_snapshot(&self) -> GridSnapshot {
+        GridSnapshot {
+            x: self.grid_params.x.value(),
+            y: self.grid_params.y.value(),
+            z: self.grid_params.z.value(),
+            w: self.grid_params.w.value(),
+            width: self.grid_params.width.load(Ordering::Relaxed),
+            height: self.grid_params.height.load(Ordering::Relaxed),
+        }
+    }
+
+    fn restore_grid_snapshot(&self, cx: &mut EventContext, snapshot: GridSnapshot) {
+        let grid_params = &self.grid_params;
+        for (param, value) in [(&grid_params.x, snapshot.x), (&grid_params.y, snapshot.y)] {
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, value).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+        for (param, value) in [(&grid_params.z, snapshot.z), (&grid_params.w, snapshot.w)] {
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, value).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+
+        grid_params.width.store(snapshot.width, Ordering::Relaxed);
+        grid_params.height.store(snapshot.height, Ordering::Relaxed);
+        cx.emit(GuiContextEvent::Resize);
+    }
+
+    /// Moves the grid along Z and W at once, and starts animating X and Y, ticking only until
+    /// centering ends
+    fn start_centering(&mut self, cx: &mut EventContext, (x, y, z, w): (f32, f32, i32, i32)) {
+        let grid_params = &self.grid_params;
+        for (param, value) in [(&grid_params.z, z), (&grid_params.w, w)] {
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, value).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+        let ticking = Arc::new(());
+        let thread_ticking = Arc::downgrade(&ticking);
+        cx.spawn(move |cx_proxy| {
+            run_ticks(thread_ticking, TICK_INTERVAL, || {
+                cx_proxy.emit(LatticeEvent::Tick).is_ok()
+            })
+        });
+        // Replacing centering that's still going stops its tick thread
+        self.centering = Some(Centering {
+            from: (grid_params.x.value(), grid_params.y.value()),
+            to: (x, y),
+            start: Instant::now(),
+            _ticking: ticking,
+        });
+    }
+
+    /// Stops centering where it is, so other ways of moving the grid don't fight it
+    fn stop_centering(&mut self) {
+        if self.centering.take().is_some() {
+            self.history.end_gesture(self.grid_snapshot());
+        }
+    }
+
+    /// Pans the grid with the arrow keys, or moves it along Z with Page Up and Page Down, as one
+    /// gesture that can be undone
+    fn move_with_key(&mut self, cx: &mut EventContext, code: Code) {
+        self.stop_centering();
+        cx.emit(LatticeEvent::GestureStart);
+        let grid_params = &self.grid_params;
+        let fine = cx.modifiers().shift();
+        let xy_step = match code {
+            Code::ArrowLeft => Some((&grid_params.x, -1)),
+            Code::ArrowRight => Some((&grid_params.x, 1)),
+            Code::ArrowDown => Some((&grid_params.y, -1)),
+            Code::ArrowUp => Some((&grid_params.y, 1)),
+            _ => None,
+        };
+        if let Some((param, step)) = xy_step {
+            let offset = key_panned_offset(param.value(), step, fine);
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, offset).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        } else {
+            let step = if code == Code::PageUp { 1 } else { -1 };
+            let grid_z = (grid_params.z.value() + step)
+                .clamp(-MAX_GRID_OFFSET as i32, MAX_GRID_OFFSET as i32);
+            cx.emit(ParamEvent::BeginSetParameter(&grid_params.z).upcast());
+            cx.emit(ParamEvent::SetParameter(&grid_params.z, grid_z).upcast());
+            cx.emit(ParamEvent::EndSetParameter(&grid_params.z).upcast());
+        }
+        cx.emit(LatticeEvent::GestureEnd);
+    }
+
+    /// Moves X and Y along the centering animation. Ends the double click's gesture once it's
+    /// done, so undo goes back to where the grid was before centering.
+    fn step_centering(&mut self, cx: &mut EventContext) {
+        let centering = match &self.centering {
+            Some(centering) => centering,
+            None => return,
+        };
+        let progress = centering.start.elapsed().as_secs_f32() / CENTERING_DURATION.as_secs_f32();
+        let grid_params = &self.grid_params;
+        for (param, from, to) in [
+            (&grid_params.x, centering.from.0, centering.to.0),
+            (&grid_params.y, centering.from.1, centering.to.1),
+        ] {
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, centering_offset(from, to, progress)).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+
+        if progress >= 1.0 {
+            self.centering = None;
+            // Handled after the parameter events, so the history sees where the grid ended up
+            cx.emit(LatticeEvent::GestureEnd);
+        }
+    }
+}
+
 /// Represents a mouse event over the lattice.
 /// Necessary because children of the lattice have shared state:
 /// - Children are visible when the mouse is over any part of the lattice
@@ -71,6 +266,21 @@ enum LatticeEvent {
     MouseDown,
     MouseUpFromChild,
     MouseUpToChild,
+    /// A left click on the lattice should audition the node under the mouse. Only sent once
+    /// it's clear the click isn't a pan.
+    ClickPress,
+    /// A left click's audition ended
+    ClickRelease,
+    /// A child started dragging or resizing the grid
+    GestureStart,
+    /// A child finished dragging or resizing the grid, which can be undone
+    GestureEnd,
+    /// The lattice was double clicked, to center the grid on the node under the mouse
+    DoubleClick,
+    /// The grid found the offsets to center it on the double clicked node
+    CenterOn((f32, f32, i32, i32)),
+    /// Time to animate centering
+    Tick,
 }
 
 impl View for Lattice {
@@ -99,8 +309,51 @@ impl View for Lattice {
                 }
             }
             WindowEvent::MouseDown(MouseButton::Left) => {
+                // The new press starts a gesture of its own
+                self.stop_centering();
+                // Take keyboard focus for undo and redo
+                cx.focus();
                 cx.emit_custom(Event::new(LatticeEvent::MouseDown).propagate(Propagation::Subtree));
             }
+            // Ctrl+Z undoes the last drag or resize, and Ctrl+Shift+Z redoes it. Cmd works too.
+            WindowEvent::KeyDown(Code::KeyZ, _)
+                if cx.modifiers().ctrl() || cx.modifiers().meta() =>
+            {
+                let current = self.grid_snapshot();
+                let snapshot = if cx.modifiers().shift() {
+                    self.history.redo(current)
+                } else {
+                    self.history.undo(current)
+                };
+                if let Some(snapshot) = snapshot {
+                    self.restore_grid_snapshot(cx, snapshot);
+                }
+            }
+            // Arrow keys pan by a node, or a quarter node with shift, and Page Up and Page Down
+            // move along the Z axis. Ctrl and Cmd are left for the host's shortcuts.
+            WindowEvent::KeyDown(
+                code @ (Code::ArrowLeft
+                | Code::ArrowRight
+                | Code::ArrowUp
+                | Code::ArrowDown
+                | Code::PageUp
+                | Code::PageDown),
+                _,
+            ) if !cx.modifiers().ctrl() && !cx.modifiers().meta() => self.move_with_key(cx, code),
+            // Right click auditions the node under the cursor, until the button is released.
+            // Left clicks do the same, through `LatticeEvent::ClickPress`, once they can't be pans.
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                cx.capture();
+                cx.emit_custom(
+                    Event::new(GridEvent::AuditionPress).propagate(Propagation::Subtree),
+                );
+            }
+            WindowEvent::MouseUp(MouseButton::Right) => {
+                cx.release();
+                cx.emit_custom(
+                    Event::new(GridEvent::AuditionRelease).propagate(Propagation::Subtree),
+                );
+            }
             _ => {}
         });
         event.map(|lattice_event, _meta| match *lattice_event {
@@ -123,7 +376,78 @@ impl View for Lattice {
                     Event::new(LatticeEvent::MouseUpToChild).propagate(Propagation::Subtree),
                 );
             }
+            LatticeEvent::ClickPress => {
+                cx.emit_custom(
+                    Event::new(GridEvent::AuditionPress).propagate(Propagation::Subtree),
+                );
+            }
+            LatticeEvent::ClickRelease => {
+                cx.emit_custom(
+                    Event::new(GridEvent::AuditionRelease).propagate(Propagation::Subtree),
+                );
+            }
+            LatticeEvent::GestureStart => self.history.begin_gesture(self.grid_snapshot()),
+            // Releasing a double click while centering leaves the gesture for centering to end
+            LatticeEvent::GestureEnd if self.centering.is_none() => {
+                self.history.end_gesture(self.grid_snapshot())
+            }
+            LatticeEvent::DoubleClick => {
+                cx.emit_custom(
+                    Event::new(GridEvent::CenterOnCursor).propagate(Propagation::Subtree),
+                );
+            }
+            LatticeEvent::CenterOn(target) => self.start_centering(cx, target),
+            LatticeEvent::Tick => self.step_centering(cx),
             _ => {}
         });
     }
 }
+
+#[cfg(test)]
+mod centering_offset_tests {
+    use crate::editor::lattice::centering_offset;
+
+    #[test]
+    fn test_goes_from_start_to_end() {
+        assert_eq!(centering_offset(-2.0, 4.0, 0.0), -2.0);
+        assert_eq!(centering_offset(-2.0, 4.0, 1.0), 4.0);
+        assert_eq!(centering_offset(-2.0, 4.0, 1.5), 4.0);
+    }
+
+    #[test]
+    fn test_eases_out() {
+        let halfway = centering_offset(0.0, 1.0, 0.5);
+        assert!(halfway > 0.5 && halfway < 1.0);
+        assert!(centering_offset(0.0, 1.0, 0.25) < halfway);
+    }
+}
+
+#[cfg(test)]
+mod key_panned_offset_tests {
+    use crate::editor::lattice::{key_panned_offset, FINE_KEY_STEP};
+    use crate::MAX_GRID_OFFSET;
+
+    #[test]
+    fn test_steps_to_whole_nodes() {
+        assert_eq!(key_panned_offset(2.0, 1, false), 3.0);
+        assert_eq!(key_panned_offset(2.3, -1, false), 1.0);
+    }
+
+    #[test]
+    fn test_fine_steps_keep_fractions() {
+        assert_eq!(key_panned_offset(2.0, 1, true), 2.0 + FINE_KEY_STEP);
+        assert_eq!(key_panned_offset(2.3, -1, true), 2.3 - FINE_KEY_STEP);
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_eq!(
+            key_panned_offset(MAX_GRID_OFFSET, 1, false),
+            MAX_GRID_OFFSET
+        );
+        assert_eq!(
+            key_panned_offset(-MAX_GRID_OFFSET, -1, true),
+            -MAX_GRID_OFFSET
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use crate::editor::lattice::grid::{
+    dedup_pitch_classes, draw_top_left_text_box, overlay_text_paint, DrawGridArgs,
+};
+use crate::tuning::detect_chord;
+use crate::Accidentals;
+
+use nih_plug_vizia::vizia::prelude::*;
+
+/// Names the sounding notes as a harmonic series chord in the top left corner of the grid, if
+/// they make one
+pub fn draw_chord(canvas: &mut Canvas, args: &DrawGridArgs) {
+    let sounding_pitch_classes = dedup_pitch_classes(
+        args.sorted_voices
+            .iter()
+            .map(|voice| voice.get_pitch_class())
+            .collect(),
+        args.tuning_tolerance,
+    );
+    let heji = args.accidentals == Accidentals::Heji;
+    let chord = match detect_chord(&sounding_pitch_classes, &args.tuning, args.tuning_tolerance) {
+        Some(chord) if heji => chord.to_heji_string(),
+        Some(chord) => chord.to_string(),
+        None => return,
+    };
+
+    draw_top_left_text_box(canvas, args, 0, &chord, &overlay_text_paint(args, heji));
+}
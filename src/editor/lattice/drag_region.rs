@@ -1,16 +1,80 @@
 use crate::editor::color::*;
-use crate::editor::lattice::grid::NODE_SIZE;
+use crate::editor::lattice::grid::{MAX_NODE_SIZE, MIN_NODE_SIZE, NODE_SIZE};
 use crate::editor::lattice::LatticeEvent;
 use crate::editor::*;
 use crate::GridParams;
+use crate::TuningParams;
+use crate::MAX_GRID_OFFSET;
 
 use nih_plug_vizia::vizia::vg;
-use nih_plug_vizia::widgets::ParamEvent;
+use nih_plug_vizia::widgets::{GuiContextEvent, ParamEvent};
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
+
+// How far the mouse needs to move, in logical pixels, for a press to start panning instead of
+// auditioning the node under it
+const PAN_THRESHOLD: f32 = NODE_SIZE * 0.15;
+
+// How long a press needs to be held without panning to audition the node under it, until it's
+// released. Quicker clicks audition the node for `CLICK_AUDITION_DURATION` once released. Either
+// way, presses that turn into pans never make a sound.
+const AUDITION_HOLD_DELAY: Duration = Duration::from_millis(150);
+const CLICK_AUDITION_DURATION: Duration = Duration::from_millis(250);
+
+/// Delayed events that start and end click auditions
+enum DragRegionEvent {
+    /// A press was held for `AUDITION_HOLD_DELAY`
+    HoldElapsed(u64),
+    /// A quick click's audition has lasted `CLICK_AUDITION_DURATION`
+    ClickAuditionEnded(u64),
+}
+
+// How far the grid pans for each line scrolled with the mouse wheel, in logical pixels. This is a
+// quarter of a node, as if dragged that far.
+const SCROLL_LINE_DISTANCE: f32 = (NODE_SIZE + PADDING) * 0.25;
+
+// How much each line ctrl+scrolled grows or shrinks the nodes, in logical pixels
+const NODE_SIZE_SCROLL_STEP: f32 = 5.0;
+
+// How far the tuning tolerance moves along its normalized range for each line alt+scrolled
+const TOLERANCE_SCROLL_STEP: f32 = 0.02;
+
+/// What scrolling over the lattice does, depending on the modifiers held. This is the one place
+/// scroll modifiers are mapped, checked in this order:
+///
+/// | Modifiers  | Action                              |
+/// |------------|-------------------------------------|
+/// | Ctrl+Shift | Move along the Z axis, one node     |
+/// | Ctrl       | Zoom, growing or shrinking nodes    |
+/// | Alt        | Widen or narrow the tuning tolerance |
+/// | Shift      | Pan sideways                        |
+/// | None       | Pan up and down, or sideways        |
+///
+/// Ctrl+scroll used to move along Z, and Shift+scroll used to change the tuning tolerance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScrollAction {
+    MoveZ,
+    Zoom,
+    Tolerance,
+    PanSideways,
+    Pan,
+}
+
+fn scroll_action(ctrl: bool, shift: bool, alt: bool) -> ScrollAction {
+    match (ctrl, shift, alt) {
+        (true, true, _) => ScrollAction::MoveZ,
+        (true, false, _) => ScrollAction::Zoom,
+        (false, _, true) => ScrollAction::Tolerance,
+        (false, true, false) => ScrollAction::PanSideways,
+        (false, false, false) => ScrollAction::Pan,
+    }
+}
 
 /// Draggable region on the lattice. When moused over, shows a visual indicator that it's draggable.
 pub struct DragRegion {
     grid_params: Arc<GridParams>,
+    tuning_params: Arc<TuningParams>,
 
     // Whether something else is being dragged on the lattice.
     lattice_mouse_down: bool,
@@ -22,27 +86,171 @@ pub struct DragRegion {
     // whether mouse motion drags the grid.
     drag_active: bool,
 
+    // Whether the mouse has moved far enough during the drag to pan the grid. Until then, the
+    // press counts as a click, and auditions the node under the mouse.
+    panning: bool,
+
+    // Counts presses, so delayed events from earlier presses can be told apart
+    press_count: u64,
+
+    // Whether a click is auditioning a node
+    auditioning: bool,
+
     // State used to calculate grid position during drag
     start_physical_coordinates: (f32, f32),
     start_grid_coordinates: (f32, f32),
 }
 
 impl DragRegion {
-    pub fn new<L>(cx: &mut Context, grid_params: L) -> Handle<Self>
+    pub fn new<LGridParams, LTuningParams>(
+        cx: &mut Context,
+        grid_params: LGridParams,
+        tuning_params: LTuningParams,
+    ) -> Handle<Self>
     where
-        L: Lens<Target = Arc<GridParams>> + Clone,
+        LGridParams: Lens<Target = Arc<GridParams>> + Clone,
+        LTuningParams: Lens<Target = Arc<TuningParams>>,
     {
         // Styling is done in the style sheet
         DragRegion {
             grid_params: grid_params.get(cx),
+            tuning_params: tuning_params.get(cx),
             lattice_mouse_down: false,
             mouse_over: false,
             drag_active: false,
+            panning: false,
+            press_count: 0,
+            auditioning: false,
             start_physical_coordinates: (0.0, 0.0),
             start_grid_coordinates: (0.0, 0.0),
         }
         .build(cx, |_| {})
     }
+
+    /// Emits an event for the press in progress to this region after a delay, from a thread
+    fn emit_after(
+        &self,
+        cx: &mut EventContext,
+        delay: Duration,
+        event: fn(u64) -> DragRegionEvent,
+    ) {
+        let press = self.press_count;
+        cx.spawn(move |cx_proxy| {
+            std::thread::sleep(delay);
+            let _ = cx_proxy.emit(event(press));
+        });
+    }
+
+    fn start_audition(&mut self, cx: &mut EventContext) {
+        self.auditioning = true;
+        cx.emit(LatticeEvent::ClickPress);
+    }
+
+    fn stop_audition(&mut self, cx: &mut EventContext) {
+        if self.auditioning {
+            self.auditioning = false;
+            cx.emit(LatticeEvent::ClickRelease);
+        }
+    }
+
+    fn scroll(&self, cx: &mut EventContext, action: ScrollAction, scroll_x: f32, scroll_y: f32) {
+        let grid_params = &self.grid_params;
+        match action {
+            // Moves one node per scroll. Some platforms turn shift+scrolling horizontal.
+            ScrollAction::MoveZ => {
+                let scroll_y = if scroll_y != 0.0 { scroll_y } else { scroll_x };
+                if scroll_y == 0.0 {
+                    return;
+                }
+                let grid_z = (grid_params.z.value() + scroll_y.signum() as i32)
+                    .clamp(-MAX_GRID_OFFSET as i32, MAX_GRID_OFFSET as i32);
+
+                cx.emit(ParamEvent::BeginSetParameter(&grid_params.z).upcast());
+                cx.emit(ParamEvent::SetParameter(&grid_params.z, grid_z).upcast());
+                cx.emit(ParamEvent::EndSetParameter(&grid_params.z).upcast());
+            }
+            // Grows or shrinks the window to keep it close to its size
+            ScrollAction::Zoom => {
+                let node_size = grid_params.node_size();
+                let new_node_size = zoomed_node_size(node_size, scroll_y);
+                if new_node_size == node_size {
+                    return;
+                }
+                let (width, height) = grid_size_for_node_size(
+                    (
+                        grid_params.width.load(Ordering::Relaxed),
+                        grid_params.height.load(Ordering::Relaxed),
+                    ),
+                    node_size,
+                    new_node_size,
+                );
+
+                grid_params.set_node_size(new_node_size);
+                grid_params.width.store(width, Ordering::Relaxed);
+                grid_params.height.store(height, Ordering::Relaxed);
+                cx.emit(GuiContextEvent::Resize);
+            }
+            ScrollAction::Tolerance => {
+                let lines = if scroll_y != 0.0 { scroll_y } else { scroll_x };
+                let tolerance = &self.tuning_params.tolerance;
+                let normalized =
+                    scrolled_tolerance(tolerance.unmodulated_normalized_value(), lines);
+
+                cx.emit(ParamEvent::BeginSetParameter(tolerance).upcast());
+                cx.emit(ParamEvent::SetParameterNormalized(tolerance, normalized).upcast());
+                cx.emit(ParamEvent::EndSetParameter(tolerance).upcast());
+            }
+            // Pans by fractions of a node, and doesn't snap to whole nodes like dragging. Pans
+            // sideways with shift, unless the platform already made the scroll horizontal.
+            ScrollAction::PanSideways | ScrollAction::Pan => {
+                let (scroll_x, scroll_y) = if action == ScrollAction::PanSideways && scroll_x == 0.0
+                {
+                    (scroll_y, 0.0)
+                } else {
+                    (scroll_x, scroll_y)
+                };
+                let node_size = grid_params.node_size();
+                let grid_x = panned_offset(
+                    grid_params.x.value(),
+                    -distance_in_nodes(scroll_x * SCROLL_LINE_DISTANCE, node_size),
+                );
+                let grid_y = panned_offset(
+                    grid_params.y.value(),
+                    distance_in_nodes(scroll_y * SCROLL_LINE_DISTANCE, node_size),
+                );
+
+                cx.emit(ParamEvent::BeginSetParameter(&grid_params.x).upcast());
+                cx.emit(ParamEvent::SetParameter(&grid_params.x, grid_x).upcast());
+                cx.emit(ParamEvent::EndSetParameter(&grid_params.x).upcast());
+
+                cx.emit(ParamEvent::BeginSetParameter(&grid_params.y).upcast());
+                cx.emit(ParamEvent::SetParameter(&grid_params.y, grid_y).upcast());
+                cx.emit(ParamEvent::EndSetParameter(&grid_params.y).upcast());
+            }
+        }
+    }
+}
+
+/// Number of nodes that dragging or scrolling a distance in logical pixels pans the grid by
+fn distance_in_nodes(distance: f32, node_size: f32) -> f32 {
+    distance / (node_size + PADDING)
+}
+
+/// Node size after zooming some lines, in logical pixels
+fn zoomed_node_size(node_size: f32, lines: f32) -> f32 {
+    (node_size + lines * NODE_SIZE_SCROLL_STEP).clamp(MIN_NODE_SIZE, MAX_NODE_SIZE)
+}
+
+/// Grid offset after panning some nodes. Panning doesn't snap to whole nodes, so many small
+/// trackpad scrolls add up smoothly.
+pub fn panned_offset(offset: f32, nodes: f32) -> f32 {
+    (offset + nodes).clamp(-MAX_GRID_OFFSET, MAX_GRID_OFFSET)
+}
+
+/// Normalized tuning tolerance after scrolling some lines. Stepping the normalized value follows
+/// the parameter's skew, so small tolerances change by smaller amounts.
+fn scrolled_tolerance(normalized: f32, lines: f32) -> f32 {
+    (normalized + lines * TOLERANCE_SCROLL_STEP).clamp(0.0, 1.0)
 }
 
 impl View for DragRegion {
@@ -63,26 +271,43 @@ impl View for DragRegion {
             }
             _ => {}
         });
+        event.map(|drag_event, _meta| match *drag_event {
+            DragRegionEvent::HoldElapsed(press) => {
+                if press == self.press_count && self.drag_active && !self.panning {
+                    self.start_audition(cx);
+                }
+            }
+            DragRegionEvent::ClickAuditionEnded(press) => {
+                if press == self.press_count && !self.drag_active {
+                    self.stop_audition(cx);
+                }
+            }
+        });
         event.map(|window_event, _meta| match *window_event {
             WindowEvent::MouseDoubleClick(MouseButton::Left) => {
-                // Set coordinates to (0,0)
-                cx.emit(ParamEvent::BeginSetParameter(&self.grid_params.x).upcast());
-                cx.emit(ParamEvent::SetParameter(&self.grid_params.x, 0.0).upcast());
-                cx.emit(ParamEvent::EndSetParameter(&self.grid_params.x).upcast());
-
-                cx.emit(ParamEvent::BeginSetParameter(&self.grid_params.y).upcast());
-                cx.emit(ParamEvent::SetParameter(&self.grid_params.y, 0.0).upcast());
-                cx.emit(ParamEvent::EndSetParameter(&self.grid_params.y).upcast());
-
-                cx.emit(ParamEvent::BeginSetParameter(&self.grid_params.z).upcast());
-                cx.emit(ParamEvent::SetParameter(&self.grid_params.z, 0).upcast());
-                cx.emit(ParamEvent::EndSetParameter(&self.grid_params.z).upcast());
+                // The second press of a double click already started a drag. Cancel it, so moving
+                // the mouse before releasing doesn't pan away from the origin, and releasing
+                // doesn't start another gesture to snap the position.
+                self.drag_active = false;
+                self.panning = false;
+                self.stop_audition(cx);
+                cx.emit(LatticeEvent::GestureStart);
+
+                // Center the grid on the node under the mouse, or go back to the origin if it's
+                // already centered
+                cx.emit(LatticeEvent::DoubleClick);
             }
             WindowEvent::MouseDown(MouseButton::Left) => {
                 cx.capture();
                 // cx.set_active(true);
 
                 self.drag_active = true;
+                self.panning = false;
+                // A quick click's audition may still be going
+                self.stop_audition(cx);
+                self.press_count += 1;
+                self.emit_after(cx, AUDITION_HOLD_DELAY, DragRegionEvent::HoldElapsed);
+                cx.emit(LatticeEvent::GestureStart);
                 self.start_physical_coordinates = (
                     cx.mouse().cursorx, // * cx.scale_factor(),
                     cx.mouse().cursory, // * cx.scale_factor(),
@@ -92,9 +317,21 @@ impl View for DragRegion {
             }
             WindowEvent::MouseUp(MouseButton::Left) => {
                 cx.emit(LatticeEvent::MouseUpFromChild);
+                cx.release();
+
+                if self.auditioning {
+                    self.stop_audition(cx);
+                } else if self.drag_active && !self.panning {
+                    // Released before the hold delay, so this was a click rather than a pan
+                    self.start_audition(cx);
+                    self.emit_after(
+                        cx,
+                        CLICK_AUDITION_DURATION,
+                        DragRegionEvent::ClickAuditionEnded,
+                    );
+                }
 
                 if self.drag_active {
-                    cx.release();
                     self.drag_active = false;
 
                     cx.emit(ParamEvent::BeginSetParameter(&self.grid_params.x).upcast());
@@ -117,6 +354,13 @@ impl View for DragRegion {
                     );
                     cx.emit(ParamEvent::EndSetParameter(&self.grid_params.y).upcast());
                 }
+                // Also ends double clicks, which don't leave a drag active
+                cx.emit(LatticeEvent::GestureEnd);
+            }
+            WindowEvent::MouseScroll(scroll_x, scroll_y) => {
+                let modifiers = cx.modifiers();
+                let action = scroll_action(modifiers.ctrl(), modifiers.shift(), modifiers.alt());
+                self.scroll(cx, action, scroll_x, scroll_y);
             }
             WindowEvent::MouseOver => {
                 self.mouse_over = true;
@@ -129,13 +373,27 @@ impl View for DragRegion {
                     self.start_physical_coordinates;
                 let (start_grid_x, start_grid_y) = self.start_grid_coordinates;
 
-                if self.drag_active {
+                if self.drag_active && !self.panning {
+                    let distance = (mouse_x - start_physical_coordinates_x)
+                        .hypot(mouse_y - start_physical_coordinates_y);
+                    if distance > PAN_THRESHOLD * cx.scale_factor() {
+                        self.panning = true;
+                        self.stop_audition(cx);
+                    }
+                }
+
+                if self.panning {
                     // Move the grid according to how far the mouse moved from the start drag location
-                    let grid_x_offset = (mouse_x - start_physical_coordinates_x)
-                        / (cx.scale_factor() * (NODE_SIZE + PADDING));
+                    let node_size = self.grid_params.node_size();
+                    let grid_x_offset = distance_in_nodes(
+                        (mouse_x - start_physical_coordinates_x) / cx.scale_factor(),
+                        node_size,
+                    );
 
-                    let grid_y_offset = (mouse_y - start_physical_coordinates_y)
-                        / (cx.scale_factor() * (NODE_SIZE + PADDING));
+                    let grid_y_offset = distance_in_nodes(
+                        (mouse_y - start_physical_coordinates_y) / cx.scale_factor(),
+                        node_size,
+                    );
 
                     cx.emit(ParamEvent::BeginSetParameter(&self.grid_params.x).upcast());
                     cx.emit(
@@ -157,6 +415,7 @@ impl View for DragRegion {
     }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
         let bounds = cx.bounds();
 
         if cx.visibility() == Some(Visibility::Visible) {
@@ -205,11 +464,11 @@ impl View for DragRegion {
             icon_path.close();
 
             let color = if self.drag_active {
-                OVERLAY_COLOR_PRESS
+                colors.overlay_press
             } else if self.mouse_over && !self.lattice_mouse_down {
-                OVERLAY_COLOR_HOVER
+                colors.overlay_hover
             } else {
-                OVERLAY_COLOR_BASE
+                colors.overlay_base
             };
 
             canvas.stroke_path(
@@ -219,3 +478,89 @@ impl View for DragRegion {
         }
     }
 }
+
+#[cfg(test)]
+mod panned_offset_tests {
+    use crate::editor::lattice::drag_region::{distance_in_nodes, panned_offset};
+    use crate::editor::lattice::grid::NODE_SIZE;
+    use crate::editor::PADDING;
+    use crate::MAX_GRID_OFFSET;
+
+    #[test]
+    fn test_keeps_fractions() {
+        let nodes = distance_in_nodes((NODE_SIZE + PADDING) * 0.1, NODE_SIZE);
+        let offset = (0..25).fold(0.0, |offset, _| panned_offset(offset, nodes));
+        assert!((offset - 2.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_eq!(panned_offset(MAX_GRID_OFFSET - 0.5, 1.0), MAX_GRID_OFFSET);
+        assert_eq!(panned_offset(-MAX_GRID_OFFSET, -0.25), -MAX_GRID_OFFSET);
+    }
+}
+
+#[cfg(test)]
+mod zoomed_node_size_tests {
+    use crate::editor::lattice::drag_region::{zoomed_node_size, NODE_SIZE_SCROLL_STEP};
+    use crate::editor::lattice::grid::{MAX_NODE_SIZE, MIN_NODE_SIZE, NODE_SIZE};
+
+    #[test]
+    fn test_steps_by_lines() {
+        assert_eq!(
+            zoomed_node_size(NODE_SIZE, 2.0),
+            NODE_SIZE + 2.0 * NODE_SIZE_SCROLL_STEP
+        );
+        assert_eq!(
+            zoomed_node_size(NODE_SIZE, -1.0),
+            NODE_SIZE - NODE_SIZE_SCROLL_STEP
+        );
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_eq!(zoomed_node_size(MAX_NODE_SIZE, 1.0), MAX_NODE_SIZE);
+        assert_eq!(zoomed_node_size(MIN_NODE_SIZE + 1.0, -3.0), MIN_NODE_SIZE);
+    }
+}
+
+#[cfg(test)]
+mod scrolled_tolerance_tests {
+    use crate::editor::lattice::drag_region::{scrolled_tolerance, TOLERANCE_SCROLL_STEP};
+
+    #[test]
+    fn test_steps_by_lines() {
+        assert_eq!(scrolled_tolerance(0.5, 1.0), 0.5 + TOLERANCE_SCROLL_STEP);
+        assert_eq!(
+            scrolled_tolerance(0.5, -2.0),
+            0.5 - 2.0 * TOLERANCE_SCROLL_STEP
+        );
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_eq!(scrolled_tolerance(1.0, 1.0), 1.0);
+        assert_eq!(scrolled_tolerance(0.01, -1.0), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod scroll_action_tests {
+    use crate::editor::lattice::drag_region::{scroll_action, ScrollAction};
+
+    #[test]
+    fn test_modifier_map() {
+        assert_eq!(scroll_action(false, false, false), ScrollAction::Pan);
+        assert_eq!(scroll_action(false, true, false), ScrollAction::PanSideways);
+        assert_eq!(scroll_action(true, false, false), ScrollAction::Zoom);
+        assert_eq!(scroll_action(true, true, false), ScrollAction::MoveZ);
+        assert_eq!(scroll_action(false, false, true), ScrollAction::Tolerance);
+    }
+
+    #[test]
+    fn test_ctrl_wins_over_alt() {
+        assert_eq!(scroll_action(true, false, true), ScrollAction::Zoom);
+        assert_eq!(scroll_action(true, true, true), ScrollAction::MoveZ);
+        assert_eq!(scroll_action(false, true, true), ScrollAction::Tolerance);
+    }
+}
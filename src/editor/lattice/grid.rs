@@ -1,22 +1,41 @@
+use crate::command::{AudioCommand, CommandSender};
+use crate::Accidentals;
+use crate::ColorPalette;
+use crate::CustomColors;
 use crate::MidiLatticeParams;
+use crate::NodeLabel;
+use crate::NoteNaming;
+use crate::ShowWAxis;
 use crate::ShowZAxis;
-use crate::Voices;
+use crate::TuningDetail;
+use crate::VoiceDeviations;
+use crate::{Voices, MAX_GRID_OFFSET, MAX_VOICES};
 
-use crate::assets;
 use crate::editor::color::*;
-use crate::editor::make_icon_paint;
-use crate::midi::MidiVoice;
+use crate::editor::lattice::chord::draw_chord;
+use crate::editor::lattice::png_export::{save_rendered_pngs, RenderedPng};
+use crate::editor::lattice::recent_notes::{draw_last_interval, RecentNotes};
+use crate::editor::lattice::tooltip::draw_tooltip;
+use crate::editor::lattice::voice_deviations::draw_voice_deviations;
+use crate::editor::lattice::LatticeEvent;
+use crate::editor::{intersects_box, make_icon_paint, snap, snap_line_width, FontInfo};
+use crate::midi::{ChannelConfig, ChannelRole, MidiVoice};
 use crate::tuning::NoteNameInfo;
 use crate::tuning::PitchClass;
 use crate::tuning::PitchClassDistance;
 use crate::tuning::PrimeCountVector;
+use crate::tuning::Tolerances;
+use crate::tuning::TuningSnapshot;
 
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::vizia::vg;
 use nih_plug_vizia::vizia::vg::FontId;
 use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::hash::Hash;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, Sender};
 use std::sync::MutexGuard;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
@@ -26,17 +45,49 @@ use crate::editor::{CORNER_RADIUS, PADDING};
 
 pub const NODE_SIZE: f32 = 50.0;
 
+// Range of node sizes that zooming allows. The window's controls stay at `NODE_SIZE`'s scale.
+pub const MIN_NODE_SIZE: f32 = 30.0;
+pub const MAX_NODE_SIZE: f32 = 90.0;
+
+// Auditioned nodes are sent out on the first free channel, or this one if none are free, in the
+// octave starting at this note
+const AUDITION_CHANNEL: u8 = 0;
+const AUDITION_OCTAVE_START_NOTE: u8 = 60;
+const AUDITION_VELOCITY: f32 = 0.8;
+
 pub struct Grid {
-    params: Arc<MidiLatticeParams>,
+    pub params: Arc<MidiLatticeParams>,
 
     // Reads voices from the audio thread
     voices_output: Arc<Mutex<Output<Voices>>>,
 
+    // Sends auditioned notes to the audio thread
+    command_sender: Arc<Mutex<CommandSender>>,
+
+    // MIDI channel and note currently being auditioned, if any
+    auditioned_note: Option<(u8, u8)>,
+
     // Need interior mutability to allow mutation from draw()
     font_info: Mutex<FontInfo>,
 
     // Need interior mutability to allow mutation from draw()
     animation_info: Mutex<AnimationInfo>,
+
+    // Need interior mutability to allow mutation from draw()
+    visible_pitch_classes: Mutex<VisiblePitchClasses>,
+
+    // Need interior mutability to allow mutation from draw()
+    visible_nodes: Mutex<VisibleNodes>,
+
+    // Need interior mutability to allow mutation from draw()
+    recent_notes: Mutex<RecentNotes>,
+
+    // Where to save a PNG image of the grid, and its scale, once it's drawn. Needs interior
+    // mutability to allow mutation from draw(), which has the canvas to render it with.
+    pub png_export: Mutex<Option<(PathBuf, f32)>>,
+
+    // Sends rendered PNG images to be encoded and saved off the draw thread
+    pub png_sender: Mutex<Sender<RenderedPng>>,
 }
 
 /// All the information relevant to displaying voices on a grid. A simplified version of
@@ -45,23 +96,35 @@ pub struct Grid {
 pub struct Voice {
     pitch_class: PitchClass,
     pitch: f32,
-    channel: u8,
+    pub channel: u8,
+    /// MIDI key number
+    pub note: u8,
+    /// Higher for voices struck later
+    pub note_on_order: u64,
+    velocity: f32,
+    pressure: f32,
+    sustained: bool,
 }
 
 impl Voice {
-    const fn new(channel: u8, pitch: f32, pitch_class: PitchClass) -> Self {
+    pub const fn new(channel: u8, pitch: f32, pitch_class: PitchClass) -> Self {
         Voice {
             pitch_class,
             pitch,
             channel,
+            note: 0,
+            note_on_order: 0,
+            velocity: 1.0,
+            pressure: 0.0,
+            sustained: false,
         }
     }
 
-    const fn get_pitch_class(&self) -> PitchClass {
+    pub const fn get_pitch_class(&self) -> PitchClass {
         self.pitch_class
     }
 
-    const fn get_pitch(&self) -> f32 {
+    pub const fn get_pitch(&self) -> f32 {
         self.pitch
     }
 
@@ -89,322 +152,1403 @@ impl Ord for Voice {
     }
 }
 
+// Most released highlights that are kept at once, counting held ones. Held highlights are never
+// evicted.
+const MAX_HIGHLIGHTS: usize = MAX_VOICES * 2;
+
+// How long a node flashes after one of its pitch classes starts sounding
+const ATTACK_FLASH_DURATION: Duration = Duration::from_millis(150);
+
 /// Additional state for displaying things that aren't captured by the current voices
 pub struct AnimationInfo {
-    /// Recent pitch classes are highlighted for a short duration.
-    /// This stores the set of recent voices, with the amount of time left for each.
-    recent_pitch_classes: HashMap<PitchClass, Duration>,
+    /// Nodes and pitch classes that are sounding, or were released recently enough to still be
+    /// highlighted
+    highlights: HashMap<HighlightKey, HighlightState>,
+
+    /// Tuning that the highlighted pitch classes correspond to
+    highlight_tuning: TuningSnapshot,
 
     /// Timestamp of the last draw() call
     last_tick: Instant,
 }
 
-/// Stores info about fonts for femtovg's canvas.
-struct FontInfo {
-    loaded: bool,
-    font_id: Option<FontId>,
-    mono_font_id: Option<FontId>,
+/// What a highlight is attached to
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum HighlightKey {
+    /// A node that voices matched. Stays on the same node when the tuning changes.
+    Node(PrimeCountVector),
+    /// The pitch class of voices that didn't match any visible node
+    PitchClass(PitchClass),
 }
 
-impl Default for FontInfo {
-    fn default() -> FontInfo {
-        FontInfo {
-            loaded: false,
-            font_id: None,
-            mono_font_id: None,
+/// Animation state of one highlight
+#[derive(Clone, PartialEq, Debug)]
+struct HighlightState {
+    /// Time since the highlight started sounding. Not reset while it's held.
+    since_attack: Duration,
+    /// Whether any voice attached to this highlight is sounding
+    held: bool,
+    /// Time left before the highlight disappears. Only counts down after release.
+    time_left: Duration,
+    /// Colors of the voices last attached to this highlight, which fade out after release
+    colors: Vec<vg::Color>,
+}
+
+/// Advances highlight states by `elapsed`, given the keys sounding now. Keys that weren't held
+/// before are new attacks, and held keys that stopped sounding start counting down from the
+/// highlight duration.
+fn update_highlight_states<K: Copy + Eq + Hash>(
+    states: &mut HashMap<K, HighlightState>,
+    sounding_keys: &[K],
+    elapsed: Duration,
+    highlight_duration: Duration,
+) {
+    for (key, state) in states.iter_mut() {
+        let sounding = sounding_keys.contains(key);
+        state.since_attack += elapsed;
+        if sounding && !state.held {
+            // Struck again while the highlight from a previous release was fading
+            state.since_attack = Duration::ZERO;
+        } else if state.held && !sounding {
+            state.time_left = highlight_duration;
+        } else if !state.held {
+            // Limit to current highlight duration. Prevents long-lived higlights if duration
+            // parameter is reduced significantly
+            state.time_left = state
+                .time_left
+                .saturating_sub(elapsed)
+                .min(highlight_duration);
+        }
+        state.held = sounding;
+    }
+
+    for key in sounding_keys {
+        states.entry(*key).or_insert(HighlightState {
+            since_attack: Duration::ZERO,
+            held: true,
+            time_left: Duration::ZERO,
+            colors: Vec::new(),
+        });
+    }
+
+    states.retain(|_, state| state.held || state.time_left > Duration::ZERO);
+
+    // With a long highlight duration, fast runs through many tunings could otherwise keep adding
+    // released highlights. Evict the ones that would expire soonest.
+    if states.len() > MAX_HIGHLIGHTS {
+        let mut released: Vec<(Duration, K)> = states
+            .iter()
+            .filter(|(_, state)| !state.held)
+            .map(|(key, state)| (state.time_left, *key))
+            .collect();
+        released.sort_unstable_by_key(|(time_left, _)| *time_left);
+        let excess = states.len() - MAX_HIGHLIGHTS;
+        for (_, key) in released.into_iter().take(excess) {
+            states.remove(&key);
+        }
+    }
+    debug_assert!(states.len() <= MAX_HIGHLIGHTS.max(sounding_keys.len()));
+}
+
+#[cfg(test)]
+mod update_highlight_states_tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::{
+        editor::lattice::grid::{update_highlight_states, HighlightState, MAX_HIGHLIGHTS},
+        tuning::{PitchClass, OCTAVE_MICROCENTS},
+    };
+
+    const FRAME: Duration = Duration::from_millis(10);
+    const HIGHLIGHT_DURATION: Duration = Duration::from_millis(100);
+
+    fn c() -> PitchClass {
+        PitchClass::from_cents_f32(0.0)
+    }
+
+    fn tick(states: &mut HashMap<PitchClass, HighlightState>, sounding: &[PitchClass]) {
+        update_highlight_states(states, sounding, FRAME, HIGHLIGHT_DURATION);
+    }
+
+    #[test]
+    fn attack_time_isnt_reset_while_held() {
+        let mut states = HashMap::new();
+        tick(&mut states, &[c()]);
+        assert_eq!(states[&c()].since_attack, Duration::ZERO);
+        assert!(states[&c()].held);
+
+        tick(&mut states, &[c()]);
+        tick(&mut states, &[c()]);
+        assert_eq!(states[&c()].since_attack, FRAME * 2);
+        assert!(states[&c()].held);
+    }
+
+    #[test]
+    fn release_counts_down_then_expires() {
+        let mut states = HashMap::new();
+        tick(&mut states, &[c()]);
+        tick(&mut states, &[]);
+        assert!(!states[&c()].held);
+        assert_eq!(states[&c()].time_left, HIGHLIGHT_DURATION);
+
+        tick(&mut states, &[]);
+        assert_eq!(states[&c()].time_left, HIGHLIGHT_DURATION - FRAME);
+
+        for _ in 0..9 {
+            tick(&mut states, &[]);
+        }
+        assert!(states.is_empty());
+    }
+
+    #[test]
+    fn strike_during_release_is_new_attack() {
+        let mut states = HashMap::new();
+        tick(&mut states, &[c()]);
+        tick(&mut states, &[c()]);
+        tick(&mut states, &[]);
+        tick(&mut states, &[]);
+        assert_eq!(states[&c()].since_attack, FRAME * 3);
+
+        tick(&mut states, &[c()]);
+        assert_eq!(states[&c()].since_attack, Duration::ZERO);
+        assert!(states[&c()].held);
+    }
+
+    #[test]
+    fn size_stays_bounded_over_long_sessions() {
+        let mut states = HashMap::new();
+        // Simple linear congruential generator, to avoid depending on a random number crate
+        let mut seed: u64 = 12345;
+        let mut next_random = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) as u32
+        };
+        // An hour of 100 ms frames, with notes in random tunings and a long highlight duration
+        for _ in 0..36_000 {
+            let sounding: Vec<PitchClass> = (0..next_random() % 8)
+                .map(|_| PitchClass::from_microcents(next_random() % OCTAVE_MICROCENTS))
+                .collect();
+            update_highlight_states(
+                &mut states,
+                &sounding,
+                Duration::from_millis(100),
+                Duration::from_secs(100),
+            );
+            assert!(states.len() <= MAX_HIGHLIGHTS);
+            for pitch_class in &sounding {
+                assert!(states[pitch_class].held);
+            }
+        }
+    }
+
+    #[test]
+    fn evicts_soonest_to_expire() {
+        let mut states = HashMap::new();
+        let long_duration = Duration::from_secs(100);
+        // Each pitch class is released on the tick after it's struck
+        for microcents in 0..MAX_HIGHLIGHTS as u32 + 1 {
+            let pitch_class = PitchClass::from_microcents(microcents);
+            update_highlight_states(&mut states, &[pitch_class], FRAME, long_duration);
+        }
+        update_highlight_states(&mut states, &[], FRAME, long_duration);
+        assert_eq!(states.len(), MAX_HIGHLIGHTS);
+        assert!(!states.contains_key(&PitchClass::from_microcents(0)));
+        assert!(states.contains_key(&PitchClass::from_microcents(1)));
+    }
+
+    #[test]
+    fn shorter_duration_limits_time_left() {
+        let mut states = HashMap::new();
+        tick(&mut states, &[c()]);
+        tick(&mut states, &[]);
+        update_highlight_states(&mut states, &[], FRAME, FRAME * 3);
+        assert_eq!(states[&c()].time_left, FRAME * 3);
+    }
+}
+
+/// How to keep highlighted pitch classes attached to their nodes after a tuning change
+#[derive(PartialEq, Debug)]
+enum HighlightUpdate {
+    /// The tuning hasn't changed by more than the tolerance
+    Keep,
+    /// Only C moved, so every node moved by the same amount
+    Translate(PitchClass),
+    /// Intervals changed, so nodes moved by different amounts
+    Clear,
+}
+
+fn highlight_update(
+    old_tuning: &TuningSnapshot,
+    new_tuning: &TuningSnapshot,
+    tuning_tolerance: PitchClassDistance,
+) -> HighlightUpdate {
+    let moved = |old: PitchClass, new: PitchClass| old.distance_to(new) > tuning_tolerance;
+    if moved(old_tuning.three, new_tuning.three)
+        || moved(old_tuning.five, new_tuning.five)
+        || moved(old_tuning.seven, new_tuning.seven)
+        || moved(old_tuning.eleven, new_tuning.eleven)
+    {
+        HighlightUpdate::Clear
+    } else if moved(old_tuning.c_offset, new_tuning.c_offset) {
+        HighlightUpdate::Translate(new_tuning.c_offset - old_tuning.c_offset)
+    } else {
+        HighlightUpdate::Keep
+    }
+}
+
+/// Moves highlights attached to pitch classes along with a tuning change. Highlights attached to
+/// nodes stay where they are, since the nodes move with the tuning.
+fn retune_highlights(states: &mut HashMap<HighlightKey, HighlightState>, update: HighlightUpdate) {
+    match update {
+        HighlightUpdate::Keep => {}
+        HighlightUpdate::Translate(offset) => {
+            let retuned: HashMap<HighlightKey, HighlightState> = states
+                .drain()
+                .map(|(key, state)| match key {
+                    HighlightKey::PitchClass(pitch_class) => {
+                        (HighlightKey::PitchClass(pitch_class + offset), state)
+                    }
+                    HighlightKey::Node(_) => (key, state),
+                })
+                .collect();
+            *states = retuned;
+        }
+        HighlightUpdate::Clear => states.retain(|key, _| matches!(key, HighlightKey::Node(_))),
+    }
+}
+
+#[cfg(test)]
+mod retune_highlights_tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use crate::{
+        editor::lattice::grid::{retune_highlights, HighlightKey, HighlightState, HighlightUpdate},
+        tuning::{PitchClass, PrimeCountVector},
+    };
+
+    fn g() -> HighlightKey {
+        HighlightKey::Node(PrimeCountVector::new(1, 0, 0, 0))
+    }
+
+    fn off_grid(cents: f32) -> HighlightKey {
+        HighlightKey::PitchClass(PitchClass::from_cents_f32(cents))
+    }
+
+    fn highlights() -> HashMap<HighlightKey, HighlightState> {
+        [g(), off_grid(50.0)]
+            .into_iter()
+            .map(|key| {
+                (
+                    key,
+                    HighlightState {
+                        since_attack: Duration::ZERO,
+                        held: false,
+                        time_left: Duration::from_secs(1),
+                        colors: Vec::new(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nodes_keep_highlights_through_interval_changes() {
+        let mut states = highlights();
+        retune_highlights(&mut states, HighlightUpdate::Clear);
+        assert_eq!(states.keys().cloned().collect::<Vec<_>>(), vec![g()]);
+    }
+
+    #[test]
+    fn pitch_classes_follow_c_offset_changes() {
+        let mut states = highlights();
+        retune_highlights(
+            &mut states,
+            HighlightUpdate::Translate(PitchClass::from_cents_f32(10.0)),
+        );
+        assert_eq!(states.len(), 2);
+        assert!(states.contains_key(&g()));
+        assert!(states.contains_key(&off_grid(60.0)));
+    }
+}
+
+/// Attaches each voice to every node that it matches, or to its own pitch class if it matches
+/// none of them. Returns each highlight key with the voices attached to it.
+fn sounding_highlights(
+    sorted_voices: &Vec<Voice>,
+    nodes: &[PrimeCountVector],
+    tuning: &TuningSnapshot,
+    tolerances: &Tolerances,
+) -> Vec<(HighlightKey, Vec<Voice>)> {
+    let mut sounding: Vec<(HighlightKey, Vec<Voice>)> = Vec::new();
+    let mut matched_pitch_classes: Vec<PitchClass> = Vec::new();
+    for primes in nodes {
+        let matching_voices = get_matching_voices(
+            tuning.pitch_class(primes),
+            sorted_voices,
+            tolerances.for_primes(primes),
+        );
+        if !matching_voices.is_empty() {
+            matched_pitch_classes
+                .extend(matching_voices.iter().map(|voice| voice.get_pitch_class()));
+            sounding.push((HighlightKey::Node(*primes), matching_voices));
+        }
+    }
+
+    // Voices are sorted, so voices with the same pitch class are next to each other
+    for voice in sorted_voices {
+        if matched_pitch_classes.contains(&voice.get_pitch_class()) {
+            continue;
+        }
+        let key = HighlightKey::PitchClass(voice.get_pitch_class());
+        match sounding.last_mut() {
+            Some((last_key, key_voices)) if *last_key == key => key_voices.push(*voice),
+            _ => sounding.push((key, vec![*voice])),
+        }
+    }
+    sounding
+}
+
+#[cfg(test)]
+mod sounding_highlights_tests {
+    use crate::{
+        editor::lattice::grid::{sounding_highlights, HighlightKey, Voice},
+        tuning::{PitchClass, PitchClassDistance, PrimeCountVector, Tolerances, TuningSnapshot},
+    };
+
+    fn twelve_tet() -> TuningSnapshot {
+        TuningSnapshot {
+            c_offset: PitchClass::from_cents_f32(0.0),
+            three: PitchClass::from_cents_f32(700.0),
+            five: PitchClass::from_cents_f32(400.0),
+            seven: PitchClass::from_cents_f32(1000.0),
+            eleven: PitchClass::from_cents_f32(500.0),
+        }
+    }
+
+    fn voice(cents: f32) -> Voice {
+        Voice::new(0, 60.0, PitchClass::from_cents_f32(cents))
+    }
+
+    /// Keys of the sounding highlights, with the number of voices attached to each
+    fn sounding_keys(
+        voices: &Vec<Voice>,
+        nodes: &[PrimeCountVector],
+    ) -> Vec<(HighlightKey, usize)> {
+        sounding_highlights(
+            voices,
+            nodes,
+            &twelve_tet(),
+            &Tolerances::uniform(PitchClassDistance::from_cents_f32(1.0)),
+        )
+        .into_iter()
+        .map(|(key, key_voices)| (key, key_voices.len()))
+        .collect()
+    }
+
+    #[test]
+    fn voices_attach_to_every_matching_node() {
+        // In 12-TET, three major thirds up from C is C again
+        let c = PrimeCountVector::new(0, 0, 0, 0);
+        let g = PrimeCountVector::new(1, 0, 0, 0);
+        let b_sharp = PrimeCountVector::new(0, 3, 0, 0);
+        assert_eq!(
+            sounding_keys(&vec![voice(0.0)], &[c, g, b_sharp]),
+            vec![(HighlightKey::Node(c), 1), (HighlightKey::Node(b_sharp), 1)]
+        );
+    }
+
+    #[test]
+    fn unmatched_voices_attach_to_their_pitch_class() {
+        let c = PrimeCountVector::new(0, 0, 0, 0);
+        assert_eq!(
+            sounding_keys(&vec![voice(0.0), voice(50.0), voice(50.0)], &[c]),
+            vec![
+                (HighlightKey::Node(c), 1),
+                (
+                    HighlightKey::PitchClass(PitchClass::from_cents_f32(50.0)),
+                    2
+                )
+            ]
+        );
+    }
+}
+
+/// Moves sounding highlights attached to pitch classes onto highlighted pitch classes within the
+/// tuning tolerance of them, combining the voices of highlights that end up with the same key.
+fn merge_sounding_highlights(
+    sounding: Vec<(HighlightKey, Vec<Voice>)>,
+    sorted_highlighted_pitch_classes: &Vec<PitchClass>,
+    tuning_tolerance: PitchClassDistance,
+) -> Vec<(HighlightKey, Vec<Voice>)> {
+    let mut merged: Vec<(HighlightKey, Vec<Voice>)> = Vec::with_capacity(sounding.len());
+    for (key, key_voices) in sounding {
+        let key = match key {
+            HighlightKey::PitchClass(pitch_class) => {
+                nearest_pitch_class(pitch_class, sorted_highlighted_pitch_classes)
+                    .filter(|(nearest, _)| nearest.distance_to(pitch_class) <= tuning_tolerance)
+                    .map_or(key, |(nearest, _)| HighlightKey::PitchClass(nearest))
+            }
+            HighlightKey::Node(_) => key,
+        };
+        match merged.iter_mut().find(|(merged_key, _)| *merged_key == key) {
+            Some((_, merged_voices)) => merged_voices.extend(key_voices),
+            None => merged.push((key, key_voices)),
         }
     }
+    merged
+}
+
+#[cfg(test)]
+mod merge_sounding_highlights_tests {
+    use crate::{
+        editor::lattice::grid::{merge_sounding_highlights, HighlightKey, Voice},
+        tuning::{PitchClass, PitchClassDistance, PrimeCountVector},
+    };
+
+    fn off_grid(cents: f32) -> HighlightKey {
+        HighlightKey::PitchClass(PitchClass::from_cents_f32(cents))
+    }
+
+    fn voice(cents: f32) -> Voice {
+        Voice::new(0, 60.0, PitchClass::from_cents_f32(cents))
+    }
+
+    /// Keys of the merged highlights, with the number of voices attached to each
+    fn merged_keys(
+        sounding: Vec<(HighlightKey, Vec<Voice>)>,
+        highlighted_cents: &[f32],
+    ) -> Vec<(HighlightKey, usize)> {
+        let highlighted: Vec<PitchClass> = highlighted_cents
+            .iter()
+            .map(|cents| PitchClass::from_cents_f32(*cents))
+            .collect();
+        merge_sounding_highlights(
+            sounding,
+            &highlighted,
+            PitchClassDistance::from_cents_f32(1.0),
+        )
+        .into_iter()
+        .map(|(key, key_voices)| (key, key_voices.len()))
+        .collect()
+    }
+
+    #[test]
+    fn merges_pitch_classes_within_tolerance() {
+        assert_eq!(
+            merged_keys(
+                vec![
+                    (off_grid(10.5), vec![voice(10.5)]),
+                    (off_grid(30.0), vec![voice(30.0)]),
+                    (off_grid(9.2), vec![voice(9.2)]),
+                ],
+                &[10.0, 50.0]
+            ),
+            vec![(off_grid(10.0), 2), (off_grid(30.0), 1)]
+        );
+    }
+
+    #[test]
+    fn merges_across_zero() {
+        assert_eq!(
+            merged_keys(vec![(off_grid(0.5), vec![voice(0.5)])], &[1199.8]),
+            vec![(off_grid(1199.8), 1)]
+        );
+    }
+
+    #[test]
+    fn keeps_nodes() {
+        let c = HighlightKey::Node(PrimeCountVector::new(0, 0, 0, 0));
+        assert_eq!(
+            merged_keys(vec![(c, vec![voice(0.0)])], &[0.0]),
+            vec![(c, 1)]
+        );
+    }
+}
+
+/// How a highlighted node is drawn in one frame
+struct Highlight {
+    /// Whether it started sounding recently enough to flash
+    struck: bool,
+    /// Colors of the voices that were attached to it, fading out since they were released
+    fading_colors: Option<Vec<vg::Color>>,
+}
+
+/// Highlights to draw in one frame
+#[derive(Default)]
+struct Highlights {
+    nodes: HashMap<PrimeCountVector, Highlight>,
+    /// Highlights of voices that didn't match any visible node, which apply to any node that
+    /// matches them later, e.g. after the grid is moved
+    pitch_classes: HashMap<PitchClass, Highlight>,
+    sorted_pitch_classes: Vec<PitchClass>,
+}
+
+impl Highlights {
+    /// The highlight of a node, if it has one
+    fn get(
+        &self,
+        primes: &PrimeCountVector,
+        pitch_class: PitchClass,
+        tuning_tolerance: PitchClassDistance,
+    ) -> Option<&Highlight> {
+        self.nodes.get(primes).or_else(|| {
+            nearest_pitch_class(pitch_class, &self.sorted_pitch_classes)
+                .filter(|(nearest, _)| nearest.distance_to(pitch_class) <= tuning_tolerance)
+                .and_then(|(nearest, _)| self.pitch_classes.get(&nearest))
+        })
+    }
+}
+
+/// Events sent to the grid, mostly by its parent [`Lattice`](super::Lattice)
+pub enum GridEvent {
+    /// Start auditioning the node under the mouse cursor
+    AuditionPress,
+    /// Stop auditioning
+    AuditionRelease,
+    /// Remove highlights left by released notes
+    ClearHighlights,
+    /// Center the grid on the node under the mouse cursor, including mini nodes
+    CenterOnCursor,
+    /// Save the grid as an SVG image
+    ExportSvg,
+    /// Save the grid as a PNG image, at its scale in the grid params
+    ExportPng,
+    /// Where to save a PNG image was chosen, and at what scale
+    PngPathChosen(PathBuf, f32),
 }
 
 impl Grid {
-    pub fn new<LParams, LVoices>(
+    pub fn new<LParams, LVoices, LCommands>(
         cx: &mut Context,
         params: LParams,
         voices_output: LVoices,
+        command_sender: LCommands,
     ) -> Handle<Self>
     where
         LParams: Lens<Target = Arc<MidiLatticeParams>>,
         LVoices: Lens<Target = Arc<Mutex<Output<Voices>>>>,
+        LCommands: Lens<Target = Arc<Mutex<CommandSender>>>,
     {
+        let (png_sender, png_receiver) = channel::<RenderedPng>();
         Self {
             params: params.get(cx),
             voices_output: voices_output.get(cx),
+            command_sender: command_sender.get(cx),
+            auditioned_note: None,
             animation_info: Mutex::new(AnimationInfo {
-                recent_pitch_classes: HashMap::new(),
+                highlights: HashMap::new(),
+                highlight_tuning: lattice_tuning(&params.get(cx)),
                 last_tick: Instant::now(),
             }),
             font_info: Mutex::new(FontInfo::default()),
+            visible_pitch_classes: Mutex::new(VisiblePitchClasses::default()),
+            visible_nodes: Mutex::new(VisibleNodes::default()),
+            recent_notes: Mutex::new(RecentNotes::default()),
+            png_export: Mutex::new(None),
+            png_sender: Mutex::new(png_sender),
         }
-        .build(cx, |_cx| {})
+        .build(cx, |cx| save_rendered_pngs(cx, png_receiver))
     }
 
     fn load_and_get_fonts(&self, canvas: &mut Canvas) -> (Option<FontId>, Option<FontId>) {
-        let mut font_info = self.font_info.lock().unwrap();
-        if !font_info.loaded {
-            font_info.loaded = true;
-            font_info.font_id = canvas.add_font_mem(assets::ROBOTO_REGULAR).ok();
-            font_info.mono_font_id = canvas.add_font_mem(assets::ROBOTO_MONO_REGULAR).ok();
-        }
-        (font_info.font_id, font_info.mono_font_id)
+        self.font_info.lock().unwrap().load_and_get(canvas)
     }
 
-    fn update_and_get_highlighted_pitch_classes(
+    /// Updates highlights from the voices sounding now, attaching them to the given visible nodes,
+    /// and returns the highlights to draw
+    fn update_and_get_highlights(
         &self,
         voices: &Vec<Voice>,
         highlight_duration: Duration,
-    ) -> Vec<PitchClass> {
+        tuning: &TuningSnapshot,
+        tolerances: &Tolerances,
+        nodes: &[PrimeCountVector],
+        coloring: &VoiceColoring,
+    ) -> Highlights {
         let mut animation_info: MutexGuard<'_, AnimationInfo> = self.animation_info.lock().unwrap();
         let time_since_last_draw: Duration = Instant::now() - animation_info.last_tick;
 
-        // Tick timer on all pitch classes
-        for time_left in animation_info.recent_pitch_classes.values_mut() {
-            if time_since_last_draw > *time_left {
-                *time_left = Duration::ZERO;
-            } else {
-                *time_left -= time_since_last_draw;
-                // Limit to current highlight duration. Prevents long-lived higlights if duration
-                // parameter is reduced significantly
-                *time_left = highlight_duration.min(*time_left);
-            }
+        // Highlights attached to nodes stay on them, but those attached to pitch classes need to
+        // follow tuning changes
+        let update = highlight_update(&animation_info.highlight_tuning, tuning, tolerances.global);
+        if update != HighlightUpdate::Keep {
+            retune_highlights(&mut animation_info.highlights, update);
+            animation_info.highlight_tuning = *tuning;
         }
 
+        // Don't count hidden or outline-only channels
+        let sounding_voices: Vec<Voice> = voices
+            .iter()
+            .filter(|voice| coloring.channel_roles.role(voice.get_channel()) == ChannelRole::Color)
+            .cloned()
+            .collect();
+        // Voices sliding off the grid would otherwise add a highlight for every pitch class they
+        // pass through
+        let mut highlighted_pitch_classes: Vec<PitchClass> = animation_info
+            .highlights
+            .keys()
+            .filter_map(|key| match key {
+                HighlightKey::PitchClass(pitch_class) => Some(*pitch_class),
+                HighlightKey::Node(_) => None,
+            })
+            .collect();
+        highlighted_pitch_classes.sort();
+        let sounding = merge_sounding_highlights(
+            sounding_highlights(&sounding_voices, nodes, tuning, tolerances),
+            &highlighted_pitch_classes,
+            tolerances.global,
+        );
+        let sounding_keys: Vec<HighlightKey> = sounding.iter().map(|(key, _)| *key).collect();
+        update_highlight_states(
+            &mut animation_info.highlights,
+            &sounding_keys,
+            time_since_last_draw,
+            highlight_duration,
+        );
         animation_info.last_tick = Instant::now();
 
-        // Refresh currently playing pitch classes
-        for voice in voices.iter() {
-            // Don't count ignored or outline-only channels
-            if voice.get_channel() <= 13 {
-                animation_info
-                    .recent_pitch_classes
-                    .insert(voice.get_pitch_class(), highlight_duration);
+        // Remember the colors of held highlights, to fade out when they're released
+        for (key, key_voices) in &sounding {
+            if let Some(state) = animation_info.highlights.get_mut(key) {
+                state.colors = key_voices
+                    .iter()
+                    .filter_map(|voice| coloring.color(voice))
+                    .collect();
             }
         }
 
-        // Drop expired pitch classes
-        animation_info
-            .recent_pitch_classes
-            .retain(|_, v: &mut Duration| *v > Duration::ZERO);
-
-        // Collect, sort and return set of surviving pitch classes
-        let mut result: Vec<PitchClass> = animation_info
-            .recent_pitch_classes
-            .keys()
-            .cloned()
-            .collect();
-        result.sort();
-
-        result
+        let mut highlights = Highlights::default();
+        for (key, state) in animation_info.highlights.iter() {
+            let highlight = Highlight {
+                struck: state.held && state.since_attack < ATTACK_FLASH_DURATION,
+                fading_colors: (!state.held && !state.colors.is_empty()).then(|| {
+                    let remaining =
+                        state.time_left.as_secs_f32() / highlight_duration.as_secs_f32().max(0.001);
+                    fade_colors(&state.colors, remaining)
+                }),
+            };
+            match key {
+                HighlightKey::Node(primes) => {
+                    highlights.nodes.insert(*primes, highlight);
+                }
+                HighlightKey::PitchClass(pitch_class) => {
+                    highlights.sorted_pitch_classes.push(*pitch_class);
+                    highlights.pitch_classes.insert(*pitch_class, highlight);
+                }
+            }
+        }
+        highlights.sorted_pitch_classes.sort();
+        highlights
     }
 }
 
 /// Arguments used to draw the grid. Passed into sub-methods of [`Grid::draw()`].
-struct DrawGridArgs {
-    scaled_node_size: f32,
-    scaled_padding: f32,
-    scaled_corner_radius: f32,
-    bounds: BoundingBox,
+pub struct DrawGridArgs {
+    scale_factor: f32,
+    pub scaled_node_size: f32,
+    pub scaled_padding: f32,
+    pub scaled_corner_radius: f32,
+    pub bounds: BoundingBox,
     grid_width: i32,
     grid_height: i32,
     grid_x: f32,
     grid_y: f32,
     grid_z: i32,
-    show_z_axis: ShowZAxis,
-    darkest_pitch: f32,
-    brightest_pitch: f32,
-    sorted_voices: Vec<Voice>,
-    c_offset: PitchClass,
-    three_tuning: PitchClass,
-    five_tuning: PitchClass,
-    seven_tuning: PitchClass,
-    tuning_tolerance: PitchClassDistance,
+    grid_w: i32,
+    node_label: NodeLabel,
+    show_edges: bool,
+    show_intervals: bool,
+    pub show_chord: bool,
+    pub voice_deviations: VoiceDeviations,
+    show_last_interval: bool,
+    /// Fifths from C to the note at the lattice's origin
+    pub root_fifths: i32,
+    note_naming: NoteNaming,
+    pub accidentals: Accidentals,
+    /// Frequency of middle C in Hz
+    pub c_frequency: f32,
+    /// Whether nodes show frequencies instead of fractional cents
+    show_frequencies: bool,
+    /// Whether nodes show their deviation from 12-TET instead of fractional cents
+    show_deviations: bool,
+    /// Octave that frequencies are shown in, where octave 4 starts at middle C
+    pub frequency_octave: i32,
+    pub octave: f32,
+    coloring: VoiceColoring,
+    pub sorted_voices: Vec<Voice>,
+    pub tuning: TuningSnapshot,
+    /// Global tuning tolerance
+    pub tuning_tolerance: PitchClassDistance,
+    /// Tuning tolerances for matching voices to nodes, which can differ by the nodes' primes
+    tolerances: Tolerances,
     font_id: Option<FontId>,
     mono_font_id: Option<FontId>,
-    highlighted_pitch_classes: Vec<PitchClass>,
+    /// Fallback for HEJI accidentals
+    symbol_font_id: Option<FontId>,
+    highlights: Highlights,
+    /// Every node that can be drawn on the grid, which is cached across frames
+    nodes: Arc<GridNodes>,
+    pub theme_colors: &'static ThemeColors,
 }
 
 impl DrawGridArgs {
-    fn new(grid: &Grid, cx: &mut DrawContext, canvas: &mut Canvas) -> DrawGridArgs {
+    pub fn new(
+        grid: &Grid,
+        canvas: &mut Canvas,
+        bounds: BoundingBox,
+        scale_factor: f32,
+    ) -> DrawGridArgs {
         let (font_id, mono_font_id): (Option<FontId>, Option<FontId>) =
             grid.load_and_get_fonts(canvas);
+        let symbol_font_id = grid.font_info.lock().unwrap().symbol_font_id();
+        DrawGridArgs {
+            font_id,
+            mono_font_id,
+            symbol_font_id,
+            ..DrawGridArgs::for_bounds(grid, bounds, scale_factor)
+        }
+    }
+
+    /// Arguments for drawing the grid in the given bounds, without any fonts loaded
+    pub fn for_bounds(grid: &Grid, bounds: BoundingBox, scale_factor: f32) -> DrawGridArgs {
+        let theme_colors = theme_colors(grid.params.grid_params.theme.value());
+        let coloring = VoiceColoring {
+            theme_colors,
+            palette: grid.params.grid_params.color_palette.value(),
+            custom_colors: *grid.params.custom_colors.read().unwrap(),
+            darkest_pitch: grid.params.grid_params.darkest_pitch.value(),
+            brightest_pitch: grid.params.grid_params.brightest_pitch.value(),
+            dim_sustained: grid.params.grid_params.dim_sustained.value(),
+            velocity_affects_color: grid.params.grid_params.velocity_affects_color.value(),
+            channel_roles: *grid.params.channel_roles.read().unwrap(),
+        };
 
-        let sorted_voices = grid.get_sorted_voices();
+        // Hidden channels' voices don't match any nodes
+        let mut sorted_voices = grid.get_sorted_voices();
+        sorted_voices
+            .retain(|voice| coloring.channel_roles.role(voice.get_channel()) != ChannelRole::Hide);
 
         let highlight_duration =
             Duration::from_secs_f32(grid.params.grid_params.highlight_time.value());
 
-        let highlighted_pitch_classes =
-            grid.update_and_get_highlighted_pitch_classes(&sorted_voices, highlight_duration);
+        let tuning = lattice_tuning(&grid.params);
+        let tolerances = grid.params.tuning_params.tolerances();
+        let tuning_tolerance = tolerances.global;
 
-        let scaled_padding = PADDING * cx.scale_factor();
+        let scaled_padding = PADDING * scale_factor;
         let grid_width = grid.params.grid_params.width.load(Ordering::Relaxed) as i32;
         let grid_height = grid.params.grid_params.height.load(Ordering::Relaxed) as i32;
+        let (grid_x, grid_y, grid_z, grid_w) = (
+            grid.params.grid_params.x.value(),
+            grid.params.grid_params.y.value(),
+            grid.params.grid_params.z.value(),
+            grid.params.grid_params.w.value(),
+        );
+
+        let nodes = grid.visible_nodes.lock().unwrap().update(&grid.params);
+        let highlights = grid.update_and_get_highlights(
+            &sorted_voices,
+            highlight_duration,
+            &tuning,
+            &tolerances,
+            &nodes.primes,
+            &coloring,
+        );
 
         // We can't just use `NODE_SIZE` here because that turns out to be slightly too big in
         // practice. Not sure why. Calculating it off the actual width/height works better.
+        // This isn't snapped to pixels, since rounding it would add up across the grid. Each
+        // node's edges are snapped instead.
         let scaled_node_size =
-            (cx.bounds().width() - scaled_padding * (grid_width as f32 + 1.0)) / grid_width as f32;
+            (bounds.width() - scaled_padding * (grid_width as f32 + 1.0)) / grid_width as f32;
 
         DrawGridArgs {
+            scale_factor,
             scaled_node_size,
             scaled_padding,
-            scaled_corner_radius: CORNER_RADIUS * cx.scale_factor(),
-            bounds: cx.bounds(),
+            scaled_corner_radius: CORNER_RADIUS * scale_factor,
+            bounds,
             grid_width,
             grid_height,
-            grid_x: grid.params.grid_params.x.value(),
-            grid_y: grid.params.grid_params.y.value(),
-            grid_z: grid.params.grid_params.z.value(),
-            show_z_axis: grid.params.grid_params.show_z_axis.value(),
-            darkest_pitch: grid.params.grid_params.darkest_pitch.value(),
-            brightest_pitch: grid.params.grid_params.brightest_pitch.value(),
+            grid_x,
+            grid_y,
+            grid_z,
+            grid_w,
+            node_label: grid.params.grid_params.node_label.value(),
+            show_edges: grid.params.grid_params.show_edges.value(),
+            show_intervals: grid.params.grid_params.show_intervals.value(),
+            show_chord: grid.params.grid_params.show_chord.value(),
+            voice_deviations: grid.params.grid_params.voice_deviations.value(),
+            show_last_interval: grid.params.grid_params.show_last_interval.value(),
+            root_fifths: grid.params.grid_params.root_note.value().fifths(),
+            note_naming: grid.params.grid_params.note_naming.value(),
+            accidentals: grid.params.grid_params.accidentals.value(),
+            c_frequency: grid.params.tuning_params.c_frequency.value(),
+            show_frequencies: grid.params.grid_params.tuning_detail.value() == TuningDetail::Hertz,
+            show_deviations: grid.params.grid_params.tuning_detail.value()
+                == TuningDetail::Deviation,
+            frequency_octave: grid.params.grid_params.frequency_octave.value(),
+            octave: grid.params.tuning_params.octave.value(),
+            coloring,
             sorted_voices,
-            c_offset: PitchClass::from_cents_f32(grid.params.tuning_params.c_offset.value()),
-            three_tuning: PitchClass::from_cents_f32(grid.params.tuning_params.three.value()),
-            five_tuning: PitchClass::from_cents_f32(grid.params.tuning_params.five.value()),
-            seven_tuning: PitchClass::from_cents_f32(grid.params.tuning_params.seven.value()),
-            tuning_tolerance: PitchClassDistance::from_cents_f32(
-                grid.params.tuning_params.tolerance.value(),
-            ),
-            font_id,
-            mono_font_id,
-            highlighted_pitch_classes,
+            tuning,
+            tuning_tolerance,
+            tolerances,
+            font_id: None,
+            mono_font_id: None,
+            symbol_font_id: None,
+            highlights,
+            nodes,
+            theme_colors,
         }
     }
 }
 
-struct DrawNodeArgs {
-    draw: bool,
-    draw_node_x: f32,
-    draw_node_y: f32,
+pub struct DrawNodeArgs {
+    pub draw: bool,
+    pub draw_node_x: f32,
+    pub draw_node_y: f32,
+    /// Width and height of the node's square, which differ by up to a pixel between nodes since
+    /// each node's edges are snapped to pixels separately
+    pub draw_node_size: (f32, f32),
     base_z: i32,
+    base_w: i32,
+    /// Position of the node in the lattice
+    primes: PrimeCountVector,
     pitch_class: PitchClass,
+    /// Cents above C of the pitch class
+    cents: f64,
     note_name_info: NoteNameInfo,
-    colors: Vec<vg::Color>,
-    draw_outline: bool,
-    outline_width: f32,
-    highlighted: bool,
+    /// Letter name to show, which is in Helmholtz notation if that's chosen and the node has
+    /// sounding notes
+    letter_name: String,
+    /// Ratio to show instead of cents, if ratios are shown, the node is tuned justly and the
+    /// ratio is small enough
+    ratio: Option<(u64, u64)>,
+    /// Prime counts to show instead of cents, if monzos are shown and this isn't a mini node
+    monzo: Option<String>,
+    /// Frequency in Hz, if frequencies are shown
+    frequency: Option<f32>,
+    /// Cents above the closest 12-TET semitone, if deviations are shown
+    deviation: Option<f32>,
+    pub colors: Vec<vg::Color>,
+    /// Whether the colors are fading out from released voices, over the highlight color
+    pub fading: bool,
+    pub draw_outline: bool,
+    pub outline_width: f32,
+    pub highlighted: bool,
+    pub struck: bool,
+    /// Whether any voices match this node
+    sounding: bool,
 }
 
 impl DrawNodeArgs {
-    fn new(
-        args: &DrawGridArgs,
-        base_x: i32,
-        base_y: i32,
-        base_z: i32,
-        primes: PrimeCountVector,
-    ) -> Self {
-        let (draw_node_x, draw_node_y): (f32, f32) = (
-            args.bounds.x
-                + (args.scaled_padding
-                    + (base_x as f32 - args.grid_x.rem_euclid(1.0))
-                        * (args.scaled_node_size + args.scaled_padding)),
-            args.bounds.y
-                + (args.scaled_padding
-                    + ((base_y as f32 + args.grid_y.rem_euclid(1.0))
-                        * (args.scaled_node_size + args.scaled_padding))),
-        );
-
-        // Pitch class represented by this node
-        let pitch_class: PitchClass =
-            primes.pitch_class(args.three_tuning, args.five_tuning, args.seven_tuning)
-                + args.c_offset;
+    fn new(args: &DrawGridArgs, node: &GridNode) -> Self {
+        let GridNode {
+            base_x,
+            base_y,
+            base_z,
+            base_w,
+            primes,
+            pitch_class,
+            shown,
+        } = *node;
+        let (draw_node_x, draw_node_y): (f32, f32) = node_position(args, base_x, base_y);
+        let draw_node_size = node_draw_size(args, base_x, base_y);
+
+        let tuning_tolerance = args.tolerances.for_primes(&primes);
 
         let matching_voices =
-            get_matching_voices(pitch_class, &args.sorted_voices, args.tuning_tolerance);
+            get_matching_voices(pitch_class, &args.sorted_voices, tuning_tolerance);
+
+        let highlight = args.highlights.get(&primes, pitch_class, tuning_tolerance);
+        let highlighted = highlight.is_some();
+        let struck = highlight.map_or(false, |highlight| highlight.struck);
+
+        let note_name_info = primes.note_name_info(args.root_fifths);
+
+        // Helmholtz notation takes its octave from the lowest sounding note
+        let lowest_pitch = matching_voices
+            .iter()
+            .map(|voice| voice.get_pitch())
+            .min_by(|a, b| a.total_cmp(b));
+        let letter_name = match (&args.note_naming, lowest_pitch) {
+            (NoteNaming::Helmholtz, Some(pitch)) => note_name_info.helmholtz_letter_name(pitch),
+            _ => note_name_info.letter_name.to_string(),
+        };
 
-        let highlighted = has_matching_pitch_class(
-            pitch_class,
-            &args.highlighted_pitch_classes,
-            args.tuning_tolerance,
-        );
+        let ratio = if args.node_label == NodeLabel::Ratio
+            && args.tuning.is_just(&primes, tuning_tolerance)
+        {
+            primes.ratio().filter(|(numerator, denominator)| {
+                *numerator <= MAX_RATIO_TERM && *denominator <= MAX_RATIO_TERM
+            })
+        } else {
+            None
+        };
+        // Mini nodes are too small for monzos, so they keep showing cents
+        let monzo = (args.node_label == NodeLabel::Monzo && base_z == 0 && base_w == 0)
+            .then(|| primes.monzo_str());
 
-        let note_name_info = primes.note_name_info();
+        let frequency = args.show_frequencies.then(|| {
+            pitch_class.to_frequency_in_octave(args.c_frequency, args.octave, args.frequency_octave)
+        });
+        let deviation = args
+            .show_deviations
+            .then(|| pitch_class.deviation_from_12tet());
 
         // Determine colors and outline
         let mut colors: Vec<vg::Color> = Vec::with_capacity(15);
         let mut draw_outline = false;
         for v in &matching_voices {
-            if v.get_channel() <= 13 {
-                colors.push(note_color(
-                    v.get_channel(),
-                    v.get_pitch(),
-                    args.darkest_pitch,
-                    args.brightest_pitch,
-                ));
-            } else if v.get_channel() == 14 {
+            colors.extend(args.coloring.color(v));
+            if args.coloring.channel_roles.role(v.get_channel()) == ChannelRole::Outline {
                 draw_outline = true;
             }
         }
 
-        // I think this sorts primarily by hue, which is what we want
-        colors.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        colors.dedup();
+        // Released voices fade out instead of disappearing
+        let fading = colors.is_empty() && highlighted;
+        if fading {
+            if let Some(fading_colors) =
+                highlight.and_then(|highlight| highlight.fading_colors.as_ref())
+            {
+                colors = fading_colors.clone();
+            }
+        }
 
-        let draw = match base_z {
+        let colors = merge_node_colors(colors);
+
+        let playing = matching_voices.len() != 0 || highlighted;
+        let draw = match (base_z, base_w) {
             // Always draw main nodes
-            0 => true,
-            // Nodes that aren't at zero on the Z axis have additional logic
-            -1 | 1 => {
-                if matching_voices.len() != 0 || highlighted {
-                    match args.show_z_axis {
-                        ShowZAxis::Yes => true,
-                        ShowZAxis::No => false,
-                        ShowZAxis::Auto => {
-                            // Whether the seventh harmonic is equal to the meantone minor seventh
-                            // i.e. whether it's equal to two perfect fourths
-                            let dependent_seven = (args.three_tuning.multiply(-2))
-                                .distance_to(args.seven_tuning)
-                                <= args.tuning_tolerance;
-                            !dependent_seven
-                        }
-                    }
-                } else {
-                    false
-                }
-            }
-            _ => false,
+            (0, 0) => true,
+            // Mini nodes on shown axes are drawn while they're playing
+            _ => shown && playing,
         };
 
         DrawNodeArgs {
             draw,
             draw_node_x,
             draw_node_y,
+            draw_node_size,
             base_z,
+            base_w,
+            primes,
             pitch_class,
+            cents: pitch_class.to_cents_f64(),
             note_name_info,
+            letter_name,
+            ratio,
+            monzo,
+            frequency,
+            deviation,
             colors,
+            fading,
             draw_outline,
-            outline_width: args.scaled_padding * OUTLINE_PADDING_RATIO,
+            outline_width: snap_line_width(PADDING * OUTLINE_PADDING_RATIO, args.scale_factor),
             highlighted,
+            struck,
+            sounding: !matching_voices.is_empty(),
+        }
+    }
+
+    /// The corner of the main node that this node is drawn on, if it's a mini node
+    pub fn mini_node_corner(&self) -> Option<MiniNodeCorner> {
+        match (self.base_z, self.base_w) {
+            (1, 0) => Some(MiniNodeCorner::TopRight),
+            (-1, 0) => Some(MiniNodeCorner::BottomLeft),
+            (0, 1) => Some(MiniNodeCorner::BottomRight),
+            (0, -1) => Some(MiniNodeCorner::TopLeft),
+            _ => None,
         }
     }
 }
 
-fn prepare_canvas(_cx: &mut DrawContext, canvas: &mut Canvas, args: &DrawGridArgs) {
-    // Hides everything out of args.bounds - for nodes that stick out when scrolling
-    canvas.intersect_scissor(
-        args.bounds.x + args.scaled_padding * OUTLINE_PADDING_RATIO,
-        args.bounds.y + args.scaled_padding * OUTLINE_PADDING_RATIO,
-        args.bounds.w - args.scaled_padding * OUTLINE_PADDING_RATIO * 2.0,
-        args.bounds.h - args.scaled_padding * OUTLINE_PADDING_RATIO * 2.0,
-    );
+/// Settings for coloring voices on the grid
+struct VoiceColoring {
+    theme_colors: &'static ThemeColors,
+    palette: ColorPalette,
+    custom_colors: CustomColors,
+    darkest_pitch: f32,
+    brightest_pitch: f32,
+    dim_sustained: bool,
+    velocity_affects_color: bool,
+    channel_roles: ChannelConfig,
+}
 
-    // Carve out entire background, with half padding around.
-    // This is necessary to use clipping when drawing with femtovg's composite operations.
-    // We'll put the background back afterwards in `finish_canvas`.
-    canvas.global_composite_operation(vg::CompositeOperation::DestinationOut);
-    let mut background_path = vg::Path::new();
-    background_path.rect(
-        args.bounds.x + args.scaled_padding * 0.75, // Add buffer above 0.5 to avoid dark lines
-        args.bounds.y + args.scaled_padding * 0.75,
+impl VoiceColoring {
+    /// Returns a voice's color, or `None` if its channel isn't colored
+    fn color(&self, v: &Voice) -> Option<vg::Color> {
+        if self.channel_roles.role(v.get_channel()) != ChannelRole::Color {
+            return None;
+        }
+        // Pressing harder on a key makes its node glow
+        let mut color = blend_toward_highlight(
+            self.theme_colors,
+            note_color(
+                self.palette,
+                &self.custom_colors,
+                v.get_channel(),
+                v.get_pitch(),
+                self.darkest_pitch,
+                self.brightest_pitch,
+            ),
+            v.pressure,
+        );
+        let mut alpha = 1.0;
+        if self.velocity_affects_color {
+            alpha *= velocity_intensity(v.velocity);
+        }
+        if v.sustained && self.dim_sustained {
+            alpha *= SUSTAINED_ALPHA;
+        }
+        color.set_alphaf(alpha);
+        Some(color)
+    }
+}
+
+/// Scales colors' alpha by `remaining`, from 1 right after release to 0 when the highlight ends
+fn fade_colors(colors: &[vg::Color], remaining: f32) -> Vec<vg::Color> {
+    colors
+        .iter()
+        .map(|color| {
+            let mut faded = *color;
+            faded.set_alphaf(color.a * remaining.clamp(0.0, 1.0));
+            faded
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod fade_colors_tests {
+    use crate::editor::lattice::grid::fade_colors;
+    use nih_plug_vizia::vizia::vg::Color;
+
+    #[test]
+    fn scales_alpha() {
+        let colors = [
+            Color::rgbaf(0.5, 0.2, 0.1, 0.5),
+            Color::rgbaf(0.1, 0.2, 0.5, 1.0),
+        ];
+        assert_eq!(
+            fade_colors(&colors, 0.5),
+            vec![
+                Color::rgbaf(0.5, 0.2, 0.1, 0.25),
+                Color::rgbaf(0.1, 0.2, 0.5, 0.5)
+            ]
+        );
+        assert_eq!(fade_colors(&colors, 0.0)[1].a, 0.0);
+    }
+}
+
+/// Sorts a node's colors and merges duplicates, e.g. from octaves played on the same channel.
+/// Merged colors keep the highest alpha, so a node is as bright as its loudest voice.
+fn merge_node_colors(mut colors: Vec<vg::Color>) -> Vec<vg::Color> {
+    // I think this sorts primarily by hue, which is what we want
+    colors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    colors.dedup_by(|color, kept| {
+        let same = color.r == kept.r && color.g == kept.g && color.b == kept.b;
+        if same {
+            kept.a = kept.a.max(color.a);
+        }
+        same
+    });
+    colors
+}
+
+#[cfg(test)]
+mod merge_node_colors_tests {
+    use crate::editor::lattice::grid::merge_node_colors;
+    use nih_plug_vizia::vizia::vg::Color;
+
+    #[test]
+    fn keeps_loudest_of_same_color() {
+        let colors = vec![
+            Color::rgbaf(0.5, 0.2, 0.1, 0.4),
+            Color::rgbaf(0.1, 0.2, 0.5, 1.0),
+            Color::rgbaf(0.5, 0.2, 0.1, 0.9),
+        ];
+        assert_eq!(
+            merge_node_colors(colors),
+            vec![
+                Color::rgbaf(0.1, 0.2, 0.5, 1.0),
+                Color::rgbaf(0.5, 0.2, 0.1, 0.9)
+            ]
+        );
+    }
+}
+
+/// Top left corner of the node at a physical position on the grid, before snapping to pixels
+fn exact_node_position(args: &DrawGridArgs, base_x: i32, base_y: i32) -> (f32, f32) {
+    let node_stride = args.scaled_node_size + args.scaled_padding;
+    (
+        args.bounds.x
+            + args.scaled_padding
+            + (base_x as f32 - args.grid_x.rem_euclid(1.0)) * node_stride,
+        args.bounds.y
+            + args.scaled_padding
+            + (base_y as f32 + args.grid_y.rem_euclid(1.0)) * node_stride,
+    )
+}
+
+/// Top left corner of the node at a physical position on the grid
+fn node_position(args: &DrawGridArgs, base_x: i32, base_y: i32) -> (f32, f32) {
+    let (x, y) = exact_node_position(args, base_x, base_y);
+    (snap(x, args.scale_factor), snap(y, args.scale_factor))
+}
+
+/// Width and height of the node at a physical position on the grid, once both of its edges are
+/// snapped to pixels
+fn node_draw_size(args: &DrawGridArgs, base_x: i32, base_y: i32) -> (f32, f32) {
+    let (x, y) = exact_node_position(args, base_x, base_y);
+    let (snapped_x, snapped_y) = node_position(args, base_x, base_y);
+    (
+        snap(x + args.scaled_node_size, args.scale_factor) - snapped_x,
+        snap(y + args.scaled_node_size, args.scale_factor) - snapped_y,
+    )
+}
+
+/// Draws lines between the centers of neighboring nodes, by fifths vertically and thirds
+/// horizontally. Drawn before the nodes, so only the parts between nodes show.
+fn draw_edges(canvas: &mut Canvas, args: &DrawGridArgs, (extra_right, extra_top): (i32, i32)) {
+    let half_node_size = args.scaled_node_size * 0.5;
+    let mut edges_path = vg::Path::new();
+    for base_x in 0..args.grid_width + extra_right {
+        for base_y in -extra_top..args.grid_height {
+            let (x, y) = node_position(args, base_x, base_y);
+            let (center_x, center_y) = (x + half_node_size, y + half_node_size);
+            if base_x + 1 < args.grid_width + extra_right {
+                let (right_x, _) = node_position(args, base_x + 1, base_y);
+                edges_path.move_to(center_x, center_y);
+                edges_path.line_to(right_x + half_node_size, center_y);
+            }
+            if base_y + 1 < args.grid_height {
+                let (_, below_y) = node_position(args, base_x, base_y + 1);
+                edges_path.move_to(center_x, center_y);
+                edges_path.line_to(center_x, below_y + half_node_size);
+            }
+        }
+    }
+    let mut edges_paint = vg::Paint::color(args.theme_colors.base);
+    edges_paint.set_line_width(snap_line_width(PADDING * 0.5, args.scale_factor));
+    canvas.stroke_path(&edges_path, &edges_paint);
+}
+
+/// How two sounding nodes next to each other on the lattice are joined
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum IntervalStep {
+    /// A step along the 3 axis, drawn wide
+    Fifth,
+    /// A step along the 5 axis, or diagonally along the 3 and 5 axes, drawn narrow
+    Third,
+    /// A step along the 7 axis, drawn dashed
+    Seventh,
+}
+
+/// How two nodes are joined, if they're one step apart along the 3, 5 or 7 axis, or diagonally
+/// along the 3 and 5 axes, such as the fifth, major third and minor third of a major triad. Nodes
+/// that differ along the 11 axis aren't joined, so the lines only show the Tonnetz of 3, 5 and 7.
+fn interval_step(a: &PrimeCountVector, b: &PrimeCountVector) -> Option<IntervalStep> {
+    if a.elevens != b.elevens {
+        return None;
+    }
+    match (
+        (a.threes - b.threes).abs(),
+        (a.fives - b.fives).abs(),
+        (a.sevens - b.sevens).abs(),
+    ) {
+        (1, 0, 0) => Some(IntervalStep::Fifth),
+        (0, 1, 0) | (1, 1, 0) => Some(IntervalStep::Third),
+        (0, 0, 1) => Some(IntervalStep::Seventh),
+        _ => None,
+    }
+}
+
+/// Indices of the pairs of sounding nodes that are joined, and how
+fn interval_edges(nodes: &[PrimeCountVector]) -> Vec<(usize, usize, IntervalStep)> {
+    let mut edges = Vec::new();
+    for (i, a) in nodes.iter().enumerate() {
+        for (j, b) in nodes.iter().enumerate().skip(i + 1) {
+            if let Some(step) = interval_step(a, b) {
+                edges.push((i, j, step));
+            }
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod interval_edges_tests {
+    use crate::editor::lattice::grid::{interval_edges, IntervalStep};
+    use crate::tuning::PrimeCountVector;
+
+    #[test]
+    fn joins_neighbors() {
+        // C, E and G of a major triad, a D two fifths away from C, and a harmonic seventh
+        let c = PrimeCountVector::new(0, 0, 0, 0);
+        let e = PrimeCountVector::new(0, 1, 0, 0);
+        let g = PrimeCountVector::new(1, 0, 0, 0);
+        let d = PrimeCountVector::new(2, 0, 0, 0);
+        let b_flat = PrimeCountVector::new(0, 0, 1, 0);
+        assert_eq!(
+            interval_edges(&[c, e, g, d, b_flat]),
+            vec![
+                (0, 1, IntervalStep::Third),
+                (0, 2, IntervalStep::Fifth),
+                (0, 4, IntervalStep::Seventh),
+                (1, 2, IntervalStep::Third),
+                (2, 3, IntervalStep::Fifth),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_other_intervals() {
+        let c = PrimeCountVector::new(0, 0, 0, 0);
+        // Two steps along one axis, a step along 3 and 7, and an undecimal step
+        let d = PrimeCountVector::new(2, 0, 0, 0);
+        let f = PrimeCountVector::new(-1, 0, 1, 0);
+        let f_up = PrimeCountVector::new(0, 0, 0, 1);
+        assert!(interval_edges(&[c]).is_empty());
+        assert!(interval_edges(&[c, d, f, f_up]).is_empty());
+    }
+}
+
+/// A sounding node, main or mini, and where lines to it end
+struct SoundingNode {
+    primes: PrimeCountVector,
+    center: (f32, f32),
+    /// How far short of the center lines stop, to keep the node's text readable
+    inset: f32,
+}
+
+/// The sounding nodes, main and mini. Mini nodes only count where they're drawn.
+fn sounding_nodes(args: &DrawGridArgs, groups: &[NodeGroup]) -> Vec<SoundingNode> {
+    let half_node_size = args.scaled_node_size * 0.5;
+    let mut nodes = Vec::new();
+    for group in groups {
+        if group.node.sounding {
+            nodes.push(SoundingNode {
+                primes: group.node.primes,
+                center: (
+                    group.node.draw_node_x + half_node_size,
+                    group.node.draw_node_y + half_node_size,
+                ),
+                inset: args.scaled_node_size * 0.35,
+            });
+        }
+        for mini_node_args in group
+            .mini_nodes
+            .iter()
+            .filter(|mini_node_args| mini_node_args.sounding)
+        {
+            if let Some(mini_node) = mini_node_bounds(args, mini_node_args) {
+                nodes.push(SoundingNode {
+                    primes: mini_node_args.primes,
+                    center: (
+                        mini_node.x + mini_node.w * 0.5,
+                        mini_node.y + mini_node.h * 0.5,
+                    ),
+                    inset: mini_node.w * 0.5,
+                });
+            }
+        }
+    }
+    nodes
+}
+
+/// Draws lines between neighboring sounding nodes, over the nodes. Fifths are wide, thirds
+/// narrow and sevenths dashed. The lines stop short of the nodes' centers, to keep their text
+/// readable.
+fn draw_interval_edges(canvas: &mut Canvas, args: &DrawGridArgs, nodes: &[SoundingNode]) {
+    let primes: Vec<PrimeCountVector> = nodes.iter().map(|node| node.primes).collect();
+    let (mut fifths_path, mut thirds_path, mut sevenths_path) =
+        (vg::Path::new(), vg::Path::new(), vg::Path::new());
+    for (i, j, step) in interval_edges(&primes) {
+        let (a, b) = (&nodes[i], &nodes[j]);
+        let ((a_x, a_y), (b_x, b_y)) = (a.center, b.center);
+        let length = ((b_x - a_x).powi(2) + (b_y - a_y).powi(2)).sqrt();
+        if length <= a.inset + b.inset {
+            continue;
+        }
+        let at = |distance: f32| {
+            (
+                a_x + (b_x - a_x) * distance / length,
+                a_y + (b_y - a_y) * distance / length,
+            )
+        };
+        let (start, end) = (a.inset, length - b.inset);
+        match step {
+            IntervalStep::Fifth | IntervalStep::Third => {
+                let path = if step == IntervalStep::Fifth {
+                    &mut fifths_path
+                } else {
+                    &mut thirds_path
+                };
+                let ((start_x, start_y), (end_x, end_y)) = (at(start), at(end));
+                path.move_to(start_x, start_y);
+                path.line_to(end_x, end_y);
+            }
+            IntervalStep::Seventh => {
+                let dash_length = args.scaled_padding * 2.0;
+                let num_dashes = ((end - start) / dash_length).ceil().max(1.0) as i32;
+                let dash_step = (end - start) / num_dashes as f32;
+                for dash in 0..num_dashes {
+                    let dash_start = start + dash_step * dash as f32;
+                    let ((start_x, start_y), (end_x, end_y)) =
+                        (at(dash_start), at(dash_start + dash_step * 0.5));
+                    sevenths_path.move_to(start_x, start_y);
+                    sevenths_path.line_to(end_x, end_y);
+                }
+            }
+        }
+    }
+    let color = args.theme_colors.interval;
+    canvas.stroke_path(
+        &fifths_path,
+        &make_icon_paint(color, args.scaled_padding * 1.5),
+    );
+    canvas.stroke_path(
+        &thirds_path,
+        &make_icon_paint(color, args.scaled_padding * 0.75),
+    );
+    canvas.stroke_path(
+        &sevenths_path,
+        &make_icon_paint(color, args.scaled_padding * 0.75),
+    );
+}
+
+fn prepare_canvas(canvas: &mut Canvas, args: &DrawGridArgs) {
+    // Hides everything out of args.bounds - for nodes that stick out when scrolling
+    canvas.intersect_scissor(
+        args.bounds.x + args.scaled_padding * OUTLINE_PADDING_RATIO,
+        args.bounds.y + args.scaled_padding * OUTLINE_PADDING_RATIO,
+        args.bounds.w - args.scaled_padding * OUTLINE_PADDING_RATIO * 2.0,
+        args.bounds.h - args.scaled_padding * OUTLINE_PADDING_RATIO * 2.0,
+    );
+
+    // Carve out entire background, with half padding around.
+    // This is necessary to use clipping when drawing with femtovg's composite operations.
+    // We'll put the background back afterwards in `finish_canvas`.
+    canvas.global_composite_operation(vg::CompositeOperation::DestinationOut);
+    let mut background_path = vg::Path::new();
+    background_path.rect(
+        args.bounds.x + args.scaled_padding * 0.75, // Add buffer above 0.5 to avoid dark lines
+        args.bounds.y + args.scaled_padding * 0.75,
         args.bounds.w - args.scaled_padding * 1.5,
         args.bounds.h - args.scaled_padding * 1.5,
     );
-    canvas.fill_path(&background_path, &vg::Paint::color(BACKGROUND_COLOR));
+    canvas.fill_path(
+        &background_path,
+        &vg::Paint::color(args.theme_colors.background),
+    );
     canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
 }
 
-fn finish_canvas(_cx: &mut DrawContext, canvas: &mut Canvas, args: &DrawGridArgs) {
+fn finish_canvas(canvas: &mut Canvas, args: &DrawGridArgs) {
     // Restore the background rectangle that we removed in prepare_canvas()
     canvas.global_composite_operation(vg::CompositeOperation::DestinationOver);
     let mut background_path_refill = vg::Path::new();
@@ -415,7 +1559,10 @@ fn finish_canvas(_cx: &mut DrawContext, canvas: &mut Canvas, args: &DrawGridArgs
         args.bounds.h + args.scaled_padding * 2.0,
         args.scaled_corner_radius,
     );
-    canvas.fill_path(&background_path_refill, &vg::Paint::color(BACKGROUND_COLOR));
+    canvas.fill_path(
+        &background_path_refill,
+        &vg::Paint::color(args.theme_colors.background),
+    );
 }
 
 fn draw_extra_colors(
@@ -426,74 +1573,162 @@ fn draw_extra_colors(
     size: f32,
     half_num_stripes: u8,
 ) {
-    if node_args.colors.len() > 0 {
-        for stripe_idx in 0..half_num_stripes * 2 {
-            if stripe_idx as usize % node_args.colors.len() == 0 {
-                continue;
-            }
-            let mut color_path = vg::Path::new();
-            if stripe_idx < half_num_stripes {
-                let stripe_offset_a: f32 = stripe_idx as f32 / half_num_stripes as f32 * size;
-                let stripe_offset_b: f32 = (stripe_idx + 1) as f32 / half_num_stripes as f32 * size;
-                color_path.move_to(x + stripe_offset_a, y);
-                color_path.line_to(x + stripe_offset_b, y);
-                color_path.line_to(x, y + stripe_offset_b);
-                color_path.line_to(x, y + stripe_offset_a);
-                color_path.close();
-            } else {
-                let stripe_offset_a: f32 =
-                    (stripe_idx - half_num_stripes) as f32 / half_num_stripes as f32 * size;
-                let stripe_offset_b: f32 =
-                    (stripe_idx - half_num_stripes + 1) as f32 / half_num_stripes as f32 * size;
-                color_path.move_to(x + size, y + stripe_offset_a);
-                color_path.line_to(x + size, y + stripe_offset_b);
-                color_path.line_to(x + stripe_offset_b, y + size);
-                color_path.line_to(x + stripe_offset_a, y + size);
-                color_path.close();
-            }
-            canvas.fill_path(
-                &color_path,
-                &vg::Paint::color(node_args.colors[stripe_idx as usize % node_args.colors.len()]),
-            );
+    for (color_idx, corners) in stripes((x, y, size), half_num_stripes, node_args.colors.len()) {
+        let mut color_path = vg::Path::new();
+        color_path.move_to(corners[0].0, corners[0].1);
+        for (corner_x, corner_y) in &corners[1..] {
+            color_path.line_to(*corner_x, *corner_y);
         }
+        color_path.close();
+        canvas.fill_path(&color_path, &vg::Paint::color(node_args.colors[color_idx]));
     }
 
     canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
 }
 
-const OUTLINE_PADDING_RATIO: f32 = 0.5;
+/// Diagonal stripes across a square at `(x, y)`, for colors after the first. Colors repeat over
+/// `half_num_stripes` stripes from the top left corner, then as many from the bottom right, and
+/// the first color's stripes are left out. Returns each stripe's color index and corners.
+pub fn stripes(
+    (x, y, size): (f32, f32, f32),
+    half_num_stripes: u8,
+    num_colors: usize,
+) -> Vec<(usize, [(f32, f32); 4])> {
+    let offset = |stripe_idx: u8| stripe_idx as f32 / half_num_stripes as f32 * size;
+    (0..half_num_stripes * 2)
+        .filter(|stripe_idx| num_colors > 0 && *stripe_idx as usize % num_colors != 0)
+        .map(|stripe_idx| {
+            let corners = if stripe_idx < half_num_stripes {
+                let (a, b) = (offset(stripe_idx), offset(stripe_idx + 1));
+                [(x + a, y), (x + b, y), (x, y + b), (x, y + a)]
+            } else {
+                let (a, b) = (
+                    offset(stripe_idx - half_num_stripes),
+                    offset(stripe_idx - half_num_stripes + 1),
+                );
+                [
+                    (x + size, y + a),
+                    (x + size, y + b),
+                    (x + b, y + size),
+                    (x + a, y + size),
+                ]
+            };
+            (stripe_idx as usize % num_colors, corners)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod stripes_tests {
+    use crate::editor::lattice::grid::stripes;
+
+    #[test]
+    fn test_one_color_has_no_stripes() {
+        assert!(stripes((0.0, 0.0, 6.0), 3, 1).is_empty());
+        assert!(stripes((0.0, 0.0, 6.0), 3, 0).is_empty());
+    }
+
+    #[test]
+    fn test_skips_first_color() {
+        let color_idxs: Vec<usize> = stripes((0.0, 0.0, 6.0), 3, 2)
+            .iter()
+            .map(|(color_idx, _)| *color_idx)
+            .collect();
+        assert_eq!(color_idxs, vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn test_stripes_meet_in_corners() {
+        let stripes = stripes((10.0, 20.0, 4.0), 2, 2);
+        // Second stripe from the top left, then the second from the bottom right
+        assert_eq!(
+            stripes,
+            vec![
+                (1, [(12.0, 20.0), (14.0, 20.0), (10.0, 24.0), (10.0, 22.0)]),
+                (1, [(14.0, 22.0), (14.0, 24.0), (14.0, 24.0), (12.0, 24.0)]),
+            ]
+        );
+    }
+}
+
+pub const OUTLINE_PADDING_RATIO: f32 = 0.5;
+// Ratios with larger numerators or denominators don't fit on nodes, so cents are shown instead
+const MAX_RATIO_TERM: u64 = 999;
+// Opacity of colors for voices held only by the sustain pedal, if they're dimmed
+const SUSTAINED_ALPHA: f32 = 0.5;
+// Size of tooltip text, relative to nodes
+pub const TOOLTIP_FONT_SIZE_RATIO: f32 = 0.24;
 const TOP: f32 = PI * 1.5;
 const RIGHT: f32 = PI * 2.0;
 
-/// Draw a node where there are no factors of 7 in the pitch class. This is the regular-sized
-/// rounded rectangle that is always displayed, and covers most of the grid area.
-/// If smaller nodes for 7 are displayed, this node changes appearance to make room.
+/// Draw a node where there are no factors of 7 or 11 in the pitch class. This is the
+/// regular-sized rounded rectangle that is always displayed, and covers most of the grid area.
+/// If smaller nodes for 7 or 11 are displayed, this node changes appearance to make room.
+///
+/// The corners for 11 mirror the ones for 7, so the text is laid out the same way, mirrored.
+/// If there are smaller nodes for both 7 and 11, there's no room left for text.
 fn draw_node_zero_z(
     canvas: &mut Canvas,
     args: &DrawGridArgs,
     node_args: &DrawNodeArgs,
-    draw_z_pos: bool,
-    draw_z_neg: bool,
+    (draw_z_pos, draw_z_neg): (bool, bool),
+    (draw_w_pos, draw_w_neg): (bool, bool),
 ) {
     draw_main_node_square(canvas, args, node_args);
-    draw_note_name(canvas, args, node_args, draw_z_pos, draw_z_neg);
-    draw_tuning_cents(canvas, args, node_args, draw_z_neg);
+    fill_node_texts(
+        canvas,
+        args,
+        &main_node_texts(
+            args,
+            node_args,
+            (draw_z_pos, draw_z_neg),
+            (draw_w_pos, draw_w_neg),
+        ),
+    );
+    let draw_w = draw_w_pos || draw_w_neg;
     if draw_z_pos {
         remove_top_right_corner(canvas, args, node_args);
     }
     if draw_z_neg {
         remove_bottom_left_corner(canvas, args, node_args);
     }
+    if draw_w {
+        // Mirror horizontally across the middle of the node
+        canvas.save();
+        canvas.translate(
+            node_args.draw_node_x * 2.0 + node_args.draw_node_size.0,
+            0.0,
+        );
+        canvas.scale(-1.0, 1.0);
+        if draw_w_neg {
+            remove_top_right_corner(canvas, args, node_args);
+        }
+        if draw_w_pos {
+            remove_bottom_left_corner(canvas, args, node_args);
+        }
+        canvas.restore();
+    }
 
     fn draw_main_node_square(canvas: &mut Canvas, args: &DrawGridArgs, node_args: &DrawNodeArgs) {
         let mut node_path = vg::Path::new();
+        let (node_w, node_h) = node_args.draw_node_size;
         node_path.rounded_rect(
             node_args.draw_node_x,
             node_args.draw_node_y,
-            args.scaled_node_size,
-            args.scaled_node_size,
+            node_w,
+            node_h,
             args.scaled_corner_radius,
         );
+        if node_args.colors.is_empty() || node_args.fading {
+            canvas.fill_path(
+                &mut node_path,
+                &vg::Paint::color(if node_args.highlighted {
+                    args.theme_colors.highlight
+                } else {
+                    args.theme_colors.base
+                }),
+            );
+        }
         if node_args.colors.len() > 0 {
             canvas.fill_path(&mut node_path, &vg::Paint::color(node_args.colors[0]));
             if node_args.colors.len() > 1 {
@@ -503,171 +1738,33 @@ fn draw_node_zero_z(
                     node_args,
                     node_args.draw_node_x,
                     node_args.draw_node_y,
-                    args.scaled_node_size,
+                    node_w.max(node_h),
                     (node_args.colors.len() * 3) as u8,
                 );
                 canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
             }
-        } else {
-            canvas.fill_path(
-                &mut node_path,
-                &vg::Paint::color(if node_args.highlighted {
-                    HIGHLIGHT_COLOR
-                } else {
-                    BASE_COLOR
-                }),
-            );
+        }
+
+        // Flash briefly when struck
+        if node_args.struck {
+            let mut flash_color = args.theme_colors.text;
+            flash_color.set_alphaf(0.3);
+            canvas.fill_path(&mut node_path, &vg::Paint::color(flash_color));
         }
 
         // Draw outline for channel 16
         if node_args.draw_outline {
             canvas.stroke_path(
                 &node_path,
-                &make_icon_paint(TEXT_COLOR, node_args.outline_width),
-            );
-        }
-    }
-
-    fn draw_note_name(
-        canvas: &mut Canvas,
-        args: &DrawGridArgs,
-        node_args: &DrawNodeArgs,
-        draw_z_pos: bool,
-        draw_z_neg: bool,
-    ) {
-        let mut text_paint = vg::Paint::color(TEXT_COLOR);
-        text_paint.set_text_align(vg::Align::Right);
-
-        let show_syntonic_commas =
-            args.three_tuning.multiply(4).distance_to(args.five_tuning) > args.tuning_tolerance;
-        let max_accidental_str_len = (if show_syntonic_commas {
-            node_args.note_name_info.syntonic_commas.abs()
-        } else {
-            0
-        })
-        .max(node_args.note_name_info.sharps_or_flats.abs())
-        .min(2);
-
-        let (letter_name_size, align_x, letter_name_y) = if !draw_z_pos && !draw_z_neg {
-            // Standard position
-            (0.60, 0.48, 0.58)
-        } else if !draw_z_pos && draw_z_neg {
-            // Centered horizontally on top half
-            (0.50, 0.48, 0.44)
-        } else if draw_z_pos && !draw_z_neg {
-            // Centered vertically on left half
-            match max_accidental_str_len {
-                0 => (0.60, 0.44, 0.58),
-                1 => (0.45, 0.32, 0.58),
-                _ => (0.37, 0.26, 0.58),
-            }
-        } else {
-            // Squished into top left corner
-            match max_accidental_str_len {
-                0 => (0.45, 0.38, 0.41),
-                1 => (0.45, 0.30, 0.41),
-                _ => (0.36, 0.25, 0.385),
-            }
-        };
-
-        let accidentals_size = letter_name_size * 0.48;
-        let sharps_flats_y = letter_name_y - accidentals_size * 0.88;
-        let syntonic_commas_y = sharps_flats_y + accidentals_size * 0.84;
-
-        text_paint.set_font_size(args.scaled_node_size * letter_name_size);
-
-        // Letter name
-        args.mono_font_id.map(|f| text_paint.set_font(&[f]));
-        let _ = canvas.fill_text(
-            node_args.draw_node_x + args.scaled_node_size * align_x,
-            node_args.draw_node_y + args.scaled_node_size * letter_name_y,
-            format!("{}", node_args.note_name_info.letter_name),
-            &text_paint,
-        );
-
-        // Sharps or flats
-        text_paint.set_font_size(args.scaled_node_size * accidentals_size);
-        text_paint.set_text_align(vg::Align::Left);
-        let _ = canvas.fill_text(
-            node_args.draw_node_x + args.scaled_node_size * align_x,
-            node_args.draw_node_y + args.scaled_node_size * sharps_flats_y,
-            node_args.note_name_info.sharps_or_flats_str(),
-            &text_paint,
-        );
-
-        // Syntonic commas - only displayed if four perfect fifths don't make a third
-        if show_syntonic_commas {
-            let _ = canvas.fill_text(
-                node_args.draw_node_x + args.scaled_node_size * align_x,
-                node_args.draw_node_y + args.scaled_node_size * syntonic_commas_y,
-                node_args.note_name_info.syntonic_comma_str(),
-                &text_paint,
-            );
-        }
-    }
-
-    fn draw_tuning_cents(
-        canvas: &mut Canvas,
-        args: &DrawGridArgs,
-        node_args: &DrawNodeArgs,
-        draw_z_neg: bool,
-    ) {
-        let mut text_paint = vg::Paint::color(TEXT_COLOR);
-        text_paint.set_text_align(vg::Align::Center);
-        args.font_id.map(|f| text_paint.set_font(&[f]));
-        if draw_z_neg {
-            text_paint.set_font_size(args.scaled_node_size * 0.21);
-            let removed_square_size =
-                MINI_NODE_SIZE_RATIO * args.scaled_node_size + args.scaled_padding;
-            let (x, y) = (
-                node_args.draw_node_x + removed_square_size,
-                node_args.draw_node_y + removed_square_size,
-            );
-            let size = args.scaled_node_size - removed_square_size;
-
-            let _ = canvas.fill_text(
-                x + size * 0.5,
-                y + size * 0.48,
-                node_args.pitch_class.trunc_cents().to_string(),
-                &text_paint,
-            );
-
-            text_paint.set_font_size(args.scaled_node_size * 0.18);
-            let rounded_pitch_class = node_args.pitch_class.round(2);
-            let _ = canvas.fill_text(
-                x + size * 0.5,
-                y + size * 0.8,
-                format!(
-                    ".{}{}",
-                    rounded_pitch_class.get_decimal_digit_num(0),
-                    rounded_pitch_class.get_decimal_digit_num(1),
-                ),
-                &text_paint,
-            );
-        } else {
-            text_paint.set_font_size(args.scaled_node_size * 0.25);
-            let rounded_pitch_class = node_args.pitch_class.round(2);
-            let _ = canvas.fill_text(
-                node_args.draw_node_x + args.scaled_node_size * 0.5,
-                node_args.draw_node_y + args.scaled_node_size * 0.88,
-                format!(
-                    "{}.{}{}",
-                    node_args.pitch_class.trunc_cents(),
-                    rounded_pitch_class.get_decimal_digit_num(0),
-                    rounded_pitch_class.get_decimal_digit_num(1),
-                ),
-                &text_paint,
+                &make_icon_paint(args.theme_colors.text, node_args.outline_width),
             );
         }
     }
 
     fn remove_top_right_corner(canvas: &mut Canvas, args: &DrawGridArgs, node_args: &DrawNodeArgs) {
-        let (mini_node_x, mini_node_y) = get_mini_node_pos(true, args, node_args);
-        let mini_node_size: f32 = args.scaled_node_size * MINI_NODE_SIZE_RATIO;
-
-        let (background_square_x, background_square_y) =
-            (mini_node_x - args.scaled_padding, mini_node_y);
-        let background_square_size = mini_node_size + args.scaled_padding;
+        let cutout = corner_cutout(args, node_args, MiniNodeCorner::TopRight);
+        let (background_square_x, background_square_y) = (cutout.x, cutout.y);
+        let background_square_size = cutout.w;
 
         // Carve out top right region to make space and padding for mini-node
         let mut negative_path = vg::Path::new();
@@ -719,7 +1816,10 @@ fn draw_node_zero_z(
         );
 
         canvas.global_composite_operation(vg::CompositeOperation::DestinationOut);
-        canvas.fill_path(&mut negative_path, &vg::Paint::color(BACKGROUND_COLOR));
+        canvas.fill_path(
+            &mut negative_path,
+            &vg::Paint::color(args.theme_colors.background),
+        );
         canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
 
         if node_args.draw_outline {
@@ -755,7 +1855,10 @@ fn draw_node_zero_z(
 
             canvas.stroke_path(
                 &mut outline_path,
-                &make_icon_paint(TEXT_COLOR, args.scaled_padding * OUTLINE_PADDING_RATIO),
+                &make_icon_paint(
+                    args.theme_colors.text,
+                    args.scaled_padding * OUTLINE_PADDING_RATIO,
+                ),
             );
         }
     }
@@ -765,12 +1868,9 @@ fn draw_node_zero_z(
         args: &DrawGridArgs,
         node_args: &DrawNodeArgs,
     ) {
-        let (mini_node_x, mini_node_y) = get_mini_node_pos(false, args, node_args);
-        let mini_node_size: f32 = args.scaled_node_size * MINI_NODE_SIZE_RATIO;
-
-        let (background_square_x, background_square_y) =
-            (mini_node_x, mini_node_y - args.scaled_padding);
-        let background_square_size = mini_node_size + args.scaled_padding;
+        let cutout = corner_cutout(args, node_args, MiniNodeCorner::BottomLeft);
+        let (background_square_x, background_square_y) = (cutout.x, cutout.y);
+        let background_square_size = cutout.w;
 
         // Carve out top right region to make space and padding for mini-node
         let mut negative_path = vg::Path::new();
@@ -822,7 +1922,10 @@ fn draw_node_zero_z(
         );
 
         canvas.global_composite_operation(vg::CompositeOperation::DestinationOut);
-        canvas.fill_path(&mut negative_path, &vg::Paint::color(BACKGROUND_COLOR));
+        canvas.fill_path(
+            &mut negative_path,
+            &vg::Paint::color(args.theme_colors.background),
+        );
         canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
 
         if node_args.draw_outline {
@@ -860,199 +1963,1455 @@ fn draw_node_zero_z(
             );
             canvas.stroke_path(
                 &mut outline_path,
-                &make_icon_paint(TEXT_COLOR, args.scaled_padding * OUTLINE_PADDING_RATIO),
+                &make_icon_paint(
+                    args.theme_colors.text,
+                    args.scaled_padding * OUTLINE_PADDING_RATIO,
+                ),
             );
         }
     }
 }
 
-static MINI_NODE_SIZE_RATIO: f32 = 3.0 / 7.0;
+/// Font that a line of node text is drawn in
+#[derive(Clone, Copy)]
+pub enum NodeFont {
+    Regular,
+    Mono,
+    /// Monospace, falling back to the symbol font for HEJI's accidentals
+    Accidentals,
+}
 
-fn get_mini_node_pos(
-    z_positive: bool,
+/// A line of text on a node, laid out once for both the canvas and SVG export. Text sits on its
+/// baseline at `y`, and is aligned to `x` by `align`.
+pub struct NodeText {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub font_size: f32,
+    pub align: vg::Align,
+    pub font: NodeFont,
+}
+
+fn fill_node_texts(canvas: &mut Canvas, args: &DrawGridArgs, texts: &[NodeText]) {
+    let heji = args.accidentals == Accidentals::Heji;
+    for node_text in texts {
+        let mut text_paint = vg::Paint::color(args.theme_colors.text);
+        text_paint.set_font_size(node_text.font_size);
+        text_paint.set_text_align(node_text.align);
+        match (node_text.font, args.mono_font_id, args.symbol_font_id) {
+            (NodeFont::Regular, _, _) => {
+                args.font_id.map(|f| text_paint.set_font(&[f]));
+            }
+            (NodeFont::Accidentals, Some(mono), Some(symbol)) if heji => {
+                text_paint.set_font(&[mono, symbol]);
+            }
+            (_, Some(mono), _) => text_paint.set_font(&[mono]),
+            _ => {}
+        }
+        let _ = canvas.fill_text(node_text.x, node_text.y, &node_text.text, &text_paint);
+    }
+}
+
+/// Text on a main node, given which of its mini nodes are drawn. There's only room for text if
+/// mini nodes are drawn for at most one of 7 and 11.
+pub fn main_node_texts(
     args: &DrawGridArgs,
     node_args: &DrawNodeArgs,
-) -> (f32, f32) {
-    match z_positive {
-        true => (
-            node_args.draw_node_x
-                + (args.scaled_node_size - args.scaled_node_size * MINI_NODE_SIZE_RATIO),
-            node_args.draw_node_y,
-        ),
-        false => (
-            node_args.draw_node_x,
-            node_args.draw_node_y
-                + (args.scaled_node_size - args.scaled_node_size * MINI_NODE_SIZE_RATIO),
-        ),
+    (draw_z_pos, draw_z_neg): (bool, bool),
+    (draw_w_pos, draw_w_neg): (bool, bool),
+) -> Vec<NodeText> {
+    let mut texts = Vec::new();
+    if args.node_label == NodeLabel::ColorsOnly {
+        return texts;
     }
-}
-/// Draw a node with a factor of 7 in the pitch class.
-/// This is a small rounded rectangle on the top right or bottom left of the "main" nodes
-fn draw_node_nonzero_z(canvas: &mut Canvas, args: &DrawGridArgs, node_args: &DrawNodeArgs) {
-    if !node_args.draw {
-        return;
+    let (draw_z, draw_w) = (draw_z_pos || draw_z_neg, draw_w_pos || draw_w_neg);
+    let draw_name = args.node_label != NodeLabel::CentsOnly;
+    if !draw_w {
+        if draw_name {
+            note_name_texts(&mut texts, args, node_args, draw_z_pos, draw_z_neg, false);
+        }
+        tuning_cents_texts(&mut texts, args, node_args, draw_z_neg, false);
+    } else if !draw_z {
+        if draw_name {
+            note_name_texts(&mut texts, args, node_args, draw_w_neg, draw_w_pos, true);
+        }
+        tuning_cents_texts(&mut texts, args, node_args, draw_w_pos, true);
     }
+    texts
+}
 
-    let mini_node_size: f32 = args.scaled_node_size * MINI_NODE_SIZE_RATIO;
-    let (mini_node_x, mini_node_y) = get_mini_node_pos(node_args.base_z == 1, args, node_args);
+/// Adds a main node's letter name, sharps or flats and commas to its text. The letter name moves
+/// aside for mini nodes in the corners given by `draw_z_pos` and `draw_z_neg`, which are
+/// mirrored horizontally for 11's corners.
+fn note_name_texts(
+    texts: &mut Vec<NodeText>,
+    args: &DrawGridArgs,
+    node_args: &DrawNodeArgs,
+    draw_z_pos: bool,
+    draw_z_neg: bool,
+    mirrored: bool,
+) {
+    let show_syntonic_commas =
+        args.tuning.three.multiply(4).distance_to(args.tuning.five) > args.tuning_tolerance;
+    let show_undecimal_commas = !args.tuning.eleven_collapses(args.tuning_tolerance);
+    let info = &node_args.note_name_info;
+    let heji = args.accidentals == Accidentals::Heji;
+
+    // Syntonic commas, septimal commas and undecimal quarter tones share a row under the sharps
+    // or flats. Septimal commas are only shown if 7 isn't tuned as a minor seventh.
+    let mut commas_str = String::new();
+    if show_syntonic_commas {
+        commas_str.push_str(&if heji {
+            info.heji_syntonic_comma_str()
+        } else {
+            info.syntonic_comma_str()
+        });
+    }
+    if !args.tuning.seven_collapses(args.tuning_tolerance) {
+        commas_str.push_str(&if heji {
+            info.heji_septimal_comma_str()
+        } else {
+            info.septimal_comma_str()
+        });
+    }
+    if show_undecimal_commas {
+        commas_str.push_str(&info.undecimal_comma_str());
+    }
+    let sharps_or_flats_str = if heji {
+        info.heji_sharps_or_flats_str()
+    } else {
+        info.sharps_or_flats_str()
+    };
 
-    // Clear background
-    canvas.global_composite_operation(vg::CompositeOperation::DestinationOut);
-    let mut background_rect_path = vg::Path::new();
-    canvas.fill_path(&mut background_rect_path, &vg::Paint::color(BASE_COLOR));
-    canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
+    let max_accidental_str_len = (commas_str.chars().count() as i32)
+        .max(sharps_or_flats_str.chars().count() as i32)
+        .min(2);
 
-    // Draw background rectangle
-    let mut mini_node_path = vg::Path::new();
-    mini_node_path.rounded_rect(
-        mini_node_x,
-        mini_node_y,
-        mini_node_size,
-        mini_node_size,
-        args.scaled_corner_radius,
-    );
-    if node_args.colors.len() > 0 {
-        canvas.fill_path(&mut mini_node_path, &vg::Paint::color(node_args.colors[0]));
+    let (letter_name_size, align_x, letter_name_y) = if !draw_z_pos && !draw_z_neg {
+        // Standard position
+        (0.60, 0.48, 0.58)
+    } else if !draw_z_pos && draw_z_neg {
+        // Centered horizontally on top half
+        (0.50, 0.48, 0.44)
+    } else if draw_z_pos && !draw_z_neg {
+        // Centered vertically on left half
+        match max_accidental_str_len {
+            0 => (0.60, 0.44, 0.58),
+            1 => (0.45, 0.32, 0.58),
+            _ => (0.37, 0.26, 0.58),
+        }
     } else {
-        canvas.fill_path(
-            &mut mini_node_path,
-            &vg::Paint::color(if node_args.highlighted {
-                HIGHLIGHT_COLOR
-            } else {
-                BASE_COLOR
-            }),
+        // Squished into top left corner
+        match max_accidental_str_len {
+            0 => (0.45, 0.38, 0.41),
+            1 => (0.45, 0.30, 0.41),
+            _ => (0.36, 0.25, 0.385),
+        }
+    };
+    // Letter names are right aligned, so they need some extra space when mirrored
+    let align_x = if mirrored { 1.04 - align_x } else { align_x };
+
+    let accidentals_size = letter_name_size * 0.48;
+    let sharps_flats_y = letter_name_y - accidentals_size * 0.88;
+    let syntonic_commas_y = sharps_flats_y + accidentals_size * 0.84;
+
+    let x = node_args.draw_node_x + args.scaled_node_size * align_x;
+
+    // Letter name
+    texts.push(NodeText {
+        text: node_args.letter_name.clone(),
+        x,
+        y: node_args.draw_node_y + args.scaled_node_size * letter_name_y,
+        font_size: args.scaled_node_size * letter_name_size,
+        align: vg::Align::Right,
+        font: NodeFont::Mono,
+    });
+
+    // Sharps or flats. Roboto Mono doesn't have HEJI's symbols, so they fall back to the
+    // symbol font.
+    texts.push(NodeText {
+        text: sharps_or_flats_str,
+        x,
+        y: node_args.draw_node_y + args.scaled_node_size * sharps_flats_y,
+        font_size: args.scaled_node_size * accidentals_size,
+        align: vg::Align::Left,
+        font: NodeFont::Accidentals,
+    });
+
+    // Syntonic commas are only displayed if four perfect fifths don't make a third, and
+    // undecimal quarter tones only if 11 isn't tuned as a fourth or augmented fourth
+    if !commas_str.is_empty() {
+        texts.push(NodeText {
+            text: commas_str,
+            x,
+            y: node_args.draw_node_y + args.scaled_node_size * syntonic_commas_y,
+            font_size: args.scaled_node_size * accidentals_size,
+            align: vg::Align::Left,
+            font: NodeFont::Accidentals,
+        });
+    }
+}
+
+/// Adds a main node's cents, or its ratio, frequency or deviation, to its text. They move into
+/// the bottom right for a mini node in the bottom left given by `draw_z_neg`, which is mirrored
+/// horizontally for 11's corners.
+fn tuning_cents_texts(
+    texts: &mut Vec<NodeText>,
+    args: &DrawGridArgs,
+    node_args: &DrawNodeArgs,
+    draw_z_neg: bool,
+    mirrored: bool,
+) {
+    let (first_row, second_row) = tuning_text_rows(args, node_args);
+    let mut push_centered = |text: String, x: f32, y: f32, font_size: f32| {
+        texts.push(NodeText {
+            text,
+            x,
+            y,
+            font_size,
+            align: vg::Align::Center,
+            font: NodeFont::Regular,
+        })
+    };
+    let center_x = node_args.draw_node_x + args.scaled_node_size * 0.5;
+    if draw_z_neg {
+        let removed_square_size =
+            MINI_NODE_SIZE_RATIO * args.scaled_node_size + args.scaled_padding;
+        let (x, y) = (
+            node_args.draw_node_x + if mirrored { 0.0 } else { removed_square_size },
+            node_args.draw_node_y + removed_square_size,
+        );
+        let size = args.scaled_node_size - removed_square_size;
+
+        push_centered(
+            first_row,
+            x + size * 0.5,
+            y + size * 0.48,
+            args.scaled_node_size * 0.21,
+        );
+        push_centered(
+            second_row,
+            x + size * 0.5,
+            y + size * 0.8,
+            args.scaled_node_size * 0.18,
+        );
+    } else if node_args.frequency.is_some() || node_args.deviation.is_some() {
+        // Frequencies and deviations don't fit on the same row as cents
+        push_centered(
+            first_row,
+            center_x,
+            node_args.draw_node_y + args.scaled_node_size * 0.76,
+            args.scaled_node_size * 0.2,
+        );
+        push_centered(
+            second_row,
+            center_x,
+            node_args.draw_node_y + args.scaled_node_size * 0.93,
+            args.scaled_node_size * 0.17,
+        );
+    } else if args.node_label == NodeLabel::CentsOnly {
+        // Cents take the note name's place in the middle
+        push_centered(
+            first_row + &second_row,
+            center_x,
+            node_args.draw_node_y + args.scaled_node_size * 0.6,
+            args.scaled_node_size * 0.3,
+        );
+    } else {
+        push_centered(
+            first_row + &second_row,
+            center_x,
+            node_args.draw_node_y + args.scaled_node_size * 0.88,
+            args.scaled_node_size * 0.25,
+        );
+    }
+}
+
+/// Text on a mini node: whole cents or the numerator, over fractional cents, the frequency or
+/// the denominator
+pub fn mini_node_texts(
+    args: &DrawGridArgs,
+    node_args: &DrawNodeArgs,
+    mini_node: BoundingBox,
+) -> Vec<NodeText> {
+    if args.node_label == NodeLabel::ColorsOnly {
+        return Vec::new();
+    }
+    let (first_row, second_row) = tuning_text_rows(args, node_args);
+    vec![
+        NodeText {
+            text: first_row,
+            x: mini_node.x + mini_node.w * 0.5,
+            y: mini_node.y + mini_node.h * 0.5,
+            font_size: args.scaled_node_size * 0.19,
+            align: vg::Align::Center,
+            font: NodeFont::Regular,
+        },
+        NodeText {
+            text: second_row,
+            x: mini_node.x + mini_node.w * 0.5,
+            y: mini_node.y + mini_node.h * 0.83,
+            font_size: args.scaled_node_size * 0.16,
+            align: vg::Align::Center,
+            font: NodeFont::Regular,
+        },
+    ]
+}
+
+static MINI_NODE_SIZE_RATIO: f32 = 3.0 / 7.0;
+
+/// Corners of a main node where mini nodes are drawn. +7 is on the top right and -7 on the
+/// bottom left; +11 is on the bottom right and -11 on the top left.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MiniNodeCorner {
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    TopLeft,
+}
+
+fn get_mini_node_pos(
+    corner: MiniNodeCorner,
+    args: &DrawGridArgs,
+    node_args: &DrawNodeArgs,
+) -> (f32, f32) {
+    let offset = args.scaled_node_size - args.scaled_node_size * MINI_NODE_SIZE_RATIO;
+    let (offset_x, offset_y) = match corner {
+        MiniNodeCorner::TopRight => (offset, 0.0),
+        MiniNodeCorner::BottomLeft => (0.0, offset),
+        MiniNodeCorner::BottomRight => (offset, offset),
+        MiniNodeCorner::TopLeft => (0.0, 0.0),
+    };
+    (
+        node_args.draw_node_x + offset_x,
+        node_args.draw_node_y + offset_y,
+    )
+}
+/// Square carved out of a main node's corner to make room for a mini node, and the padding
+/// around it
+pub fn corner_cutout(
+    args: &DrawGridArgs,
+    node_args: &DrawNodeArgs,
+    corner: MiniNodeCorner,
+) -> BoundingBox {
+    let size = args.scaled_node_size * MINI_NODE_SIZE_RATIO + args.scaled_padding;
+    let (near_x, near_y) = (node_args.draw_node_x, node_args.draw_node_y);
+    let (far_x, far_y) = (
+        near_x + args.scaled_node_size - size,
+        near_y + args.scaled_node_size - size,
+    );
+    let (x, y) = match corner {
+        MiniNodeCorner::TopRight => (far_x, near_y),
+        MiniNodeCorner::BottomLeft => (near_x, far_y),
+        MiniNodeCorner::BottomRight => (far_x, far_y),
+        MiniNodeCorner::TopLeft => (near_x, near_y),
+    };
+    BoundingBox {
+        x,
+        y,
+        w: size,
+        h: size,
+    }
+}
+
+/// Bounds of a mini node, if it's drawn
+pub fn mini_node_bounds(args: &DrawGridArgs, node_args: &DrawNodeArgs) -> Option<BoundingBox> {
+    let corner = match node_args.mini_node_corner() {
+        Some(corner) if node_args.draw => corner,
+        _ => return None,
+    };
+    let (x, y) = get_mini_node_pos(corner, args, node_args);
+    let size = args.scaled_node_size * MINI_NODE_SIZE_RATIO;
+    Some(BoundingBox {
+        x,
+        y,
+        w: size,
+        h: size,
+    })
+}
+
+/// Draw a node with a factor of 7 or 11 in the pitch class.
+/// This is a small rounded rectangle on a corner of the "main" nodes
+fn draw_node_nonzero_z(canvas: &mut Canvas, args: &DrawGridArgs, node_args: &DrawNodeArgs) {
+    let mini_node = match mini_node_bounds(args, node_args) {
+        Some(mini_node) => mini_node,
+        None => return,
+    };
+
+    // Clear background
+    canvas.global_composite_operation(vg::CompositeOperation::DestinationOut);
+    let mut background_rect_path = vg::Path::new();
+    canvas.fill_path(
+        &mut background_rect_path,
+        &vg::Paint::color(args.theme_colors.base),
+    );
+    canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
+
+    // Draw background rectangle
+    let mut mini_node_path = vg::Path::new();
+    mini_node_path.rounded_rect(
+        mini_node.x,
+        mini_node.y,
+        mini_node.w,
+        mini_node.h,
+        args.scaled_corner_radius,
+    );
+    if node_args.colors.is_empty() || node_args.fading {
+        canvas.fill_path(
+            &mut mini_node_path,
+            &vg::Paint::color(if node_args.highlighted {
+                args.theme_colors.highlight
+            } else {
+                args.theme_colors.base
+            }),
+        );
+    }
+    if node_args.colors.len() > 0 {
+        canvas.fill_path(&mut mini_node_path, &vg::Paint::color(node_args.colors[0]));
+    }
+
+    // Draw stripes if needed
+    canvas.global_composite_operation(vg::CompositeOperation::Atop);
+    draw_extra_colors(canvas, node_args, mini_node.x, mini_node.y, mini_node.w, 3);
+    canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
+
+    // Draw outline if needed
+    if node_args.draw_outline {
+        canvas.stroke_path(
+            &mini_node_path,
+            &make_icon_paint(args.theme_colors.text, node_args.outline_width),
+        );
+    }
+
+    fill_node_texts(canvas, args, &mini_node_texts(args, node_args, mini_node));
+}
+
+/// Draws one line of text in a box in the top left corner of the grid. Boxes in higher slots go
+/// under those in lower slots.
+pub fn draw_top_left_text_box(
+    canvas: &mut Canvas,
+    args: &DrawGridArgs,
+    slot: usize,
+    text: &str,
+    text_paint: &vg::Paint,
+) {
+    let text_width = canvas
+        .measure_text(0.0, 0.0, text, text_paint)
+        .map_or(0.0, |metrics| metrics.width());
+    let box_height =
+        args.scaled_node_size * TOOLTIP_FONT_SIZE_RATIO * 1.2 + args.scaled_padding * 2.0;
+    let (x, y) = (
+        args.bounds.x + args.scaled_padding * 2.0,
+        args.bounds.y
+            + args.scaled_padding * 2.0
+            + (box_height + args.scaled_padding) * slot as f32,
+    );
+    let mut box_path = vg::Path::new();
+    box_path.rounded_rect(
+        x,
+        y,
+        text_width + args.scaled_padding * 2.0,
+        box_height,
+        args.scaled_corner_radius,
+    );
+    canvas.fill_path(
+        &mut box_path,
+        &vg::Paint::color(args.theme_colors.background),
+    );
+
+    let _ = canvas.fill_text(
+        x + args.scaled_padding,
+        y + args.scaled_padding,
+        text,
+        text_paint,
+    );
+}
+
+/// Paint for text in boxes over the grid. Roboto Mono doesn't have HEJI's symbols, so they fall
+/// back to the symbol font.
+pub fn overlay_text_paint(args: &DrawGridArgs, heji: bool) -> vg::Paint {
+    let mut text_paint = vg::Paint::color(args.theme_colors.text);
+    text_paint.set_font_size(args.scaled_node_size * TOOLTIP_FONT_SIZE_RATIO);
+    text_paint.set_text_align(vg::Align::Left);
+    text_paint.set_text_baseline(vg::Baseline::Top);
+    match (args.mono_font_id, args.symbol_font_id) {
+        (Some(mono), Some(symbol)) if heji => text_paint.set_font(&[mono, symbol]),
+        (Some(mono), _) => text_paint.set_font(&[mono]),
+        _ => {}
+    }
+    text_paint
+}
+
+/// Returns the tunings of the lattice's nodes, whose origin is the root note
+pub fn lattice_tuning(params: &MidiLatticeParams) -> TuningSnapshot {
+    params
+        .tuning_params
+        .snapshot()
+        .with_root(params.grid_params.root_note.value().fifths())
+}
+
+/// Splits a node's tuning into two rows of text, for nodes without room for one long row.
+/// Ratios are split into the numerator and "/denominator", and cents into whole cents and
+/// two decimal places. If frequencies or deviations from 12-TET are shown, they replace the
+/// decimal places. Monzos take up the first row.
+fn tuning_text_rows(args: &DrawGridArgs, node_args: &DrawNodeArgs) -> (String, String) {
+    let (whole_cents, hundredths) = cents_rows(node_args.cents);
+    if let Some(monzo) = &node_args.monzo {
+        return (monzo.clone(), String::new());
+    }
+    match (node_args.ratio, node_args.frequency) {
+        (Some((numerator, denominator)), _) => (numerator.to_string(), format!("/{}", denominator)),
+        (None, Some(frequency)) => (whole_cents, frequency_str(frequency)),
+        (None, None) => match node_args.deviation {
+            Some(deviation) => (whole_cents, deviation_str(deviation)),
+            None => (whole_cents, hundredths),
+        },
+    }
+}
+
+/// Formats a deviation from 12-TET in whole cents, with its sign, like "+14¢" or "-10¢"
+fn deviation_str(deviation: f32) -> String {
+    format!("{:+}¢", deviation.round() as i32)
+}
+
+/// Rounds cents to a whole number of steps of `1 / steps_per_cent` cents. Cents that round up to
+/// 1200 wrap back to 0, like the pitch classes they're from.
+pub fn round_cents(cents: f64, steps_per_cent: f64) -> u64 {
+    let octave_steps = (1200.0 * steps_per_cent).round() as u64;
+    (cents * steps_per_cent).round() as u64 % octave_steps
+}
+
+/// Splits cents into whole cents and two decimal places, like "701" and ".96"
+fn cents_rows(cents: f64) -> (String, String) {
+    let hundredths = round_cents(cents, 100.0);
+    (
+        (hundredths / 100).to_string(),
+        format!(".{:02}", hundredths % 100),
+    )
+}
+
+/// Formats a frequency in Hz, with one decimal place if it fits in four digits
+fn frequency_str(frequency: f32) -> String {
+    let one_decimal = format!("{:.1}", frequency);
+    if one_decimal.len() <= 5 {
+        one_decimal
+    } else {
+        format!("{:.0}", frequency)
+    }
+}
+
+impl View for Grid {
+    fn element(&self) -> Option<&'static str> {
+        Some("lattice-display")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|grid_event: &GridEvent, _meta| match grid_event {
+            GridEvent::AuditionPress => {
+                self.stop_audition();
+                let node_primes = self.node_at_position(
+                    cx.bounds(),
+                    cx.scale_factor(),
+                    (cx.mouse().cursorx, cx.mouse().cursory),
+                );
+                if let Some(primes) = node_primes {
+                    self.start_audition(primes);
+                }
+            }
+            GridEvent::AuditionRelease => self.stop_audition(),
+            GridEvent::ClearHighlights => {
+                self.animation_info.lock().unwrap().highlights.clear();
+            }
+            GridEvent::CenterOnCursor => {
+                let args = DrawGridArgs::for_bounds(self, cx.bounds(), cx.scale_factor());
+                let cursor = (cx.mouse().cursorx, cx.mouse().cursory);
+                if let Some(primes) = node_under_cursor(&args, cursor) {
+                    cx.emit(LatticeEvent::CenterOn(centering_target(
+                        &primes,
+                        (args.grid_x, args.grid_y, args.grid_z, args.grid_w),
+                    )));
+                }
+            }
+            GridEvent::ExportSvg => self.export_svg(cx),
+            GridEvent::ExportPng => self.choose_png_path(cx),
+            GridEvent::PngPathChosen(path, scale) => {
+                // Rendering needs the canvas, so it waits for the next draw
+                *self.png_export.lock().unwrap() = Some((path.clone(), *scale));
+            }
+        });
+    }
+
+    // TODO: factor this out into methods
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let _start_time = Instant::now();
+
+        let args: DrawGridArgs = DrawGridArgs::new(self, canvas, cx.bounds(), cx.scale_factor());
+
+        self.draw_lattice(canvas, &args);
+
+        // Describe the node under the cursor, unless the lattice is being dragged or auditioned
+        let mouse = cx.mouse();
+        if mouse.left.state == MouseButtonState::Released
+            && mouse.right.state == MouseButtonState::Released
+        {
+            let cursor = (mouse.cursorx, mouse.cursory);
+            if let Some(primes) = node_under_cursor(&args, cursor) {
+                draw_tooltip(canvas, &args, &primes, cursor);
+            }
+        }
+
+        self.export_pending_png(cx, canvas);
+
+        /*
+        nih_log!(
+            "*** draw() finished in {} us",
+            start_time.elapsed().as_micros()
+        );
+        */
+    }
+}
+
+// Helper methods for drawing
+impl Grid {
+    /// Draws the grid and the text boxes over it, everything but the tooltip
+    pub fn draw_lattice(&self, canvas: &mut Canvas, args: &DrawGridArgs) {
+        prepare_canvas(canvas, args);
+
+        let (extra_right, extra_top) = extra_columns_and_rows(args.grid_x, args.grid_y);
+
+        if args.show_edges {
+            draw_edges(canvas, args, (extra_right, extra_top));
+        }
+
+        let groups = node_groups(args);
+
+        // Draw lattice nodes one by one
+        for group in &groups {
+            let [pos_z, neg_z, pos_w, neg_w] = &group.mini_nodes;
+            draw_node_zero_z(
+                canvas,
+                args,
+                &group.node,
+                (pos_z.draw, neg_z.draw),
+                (pos_w.draw, neg_w.draw),
+            );
+            for mini_node_args in &group.mini_nodes {
+                draw_node_nonzero_z(canvas, args, mini_node_args);
+            }
+        }
+
+        if args.show_intervals {
+            draw_interval_edges(canvas, args, &sounding_nodes(args, &groups));
+        }
+
+        finish_canvas(canvas, args);
+
+        if args.show_chord {
+            draw_chord(canvas, args);
+        }
+        if args.voice_deviations != VoiceDeviations::Off {
+            let mut visible_pitch_classes = self.visible_pitch_classes.lock().unwrap();
+            draw_voice_deviations(canvas, args, visible_pitch_classes.get(&self.params));
+        }
+
+        // Keep track of notes even while the interval is hidden, so it's right once it's shown
+        let mut recent_notes = self.recent_notes.lock().unwrap();
+        recent_notes.update(args.sorted_voices);
+        if args.show_last_interval {
+            if let Some(interval) = recent_notes.last_interval() {
+                draw_last_interval(canvas, args, interval);
+            }
+        }
+    }
+}
+
+/// A main node on the grid, with the mini nodes on its corners
+pub struct NodeGroup {
+    pub node: DrawNodeArgs,
+    /// Mini nodes on the +7, -7, +11 and -11 corners, in that order
+    pub mini_nodes: [DrawNodeArgs; 4],
+}
+
+/// Every main node on the grid, including the extra row and column shown when the grid is
+/// partially scrolled, with its mini nodes
+pub fn node_groups(args: &DrawGridArgs) -> Vec<NodeGroup> {
+    args.nodes
+        .groups
+        .iter()
+        .map(|group| NodeGroup {
+            node: DrawNodeArgs::new(args, &group.node),
+            mini_nodes: group
+                .mini_nodes
+                .map(|mini_node| DrawNodeArgs::new(args, &mini_node)),
+        })
+        .collect()
+}
+
+/// Physical position of the main node at a position in the grid's bounds, if any. This is the
+/// inverse of [`node_position()`], without its snapping to pixels.
+fn base_node_at(
+    bounds: BoundingBox,
+    (scaled_node_size, scaled_padding): (f32, f32),
+    (grid_x, grid_y): (f32, f32),
+    (x, y): (f32, f32),
+) -> Option<(i32, i32)> {
+    let node_stride = scaled_node_size + scaled_padding;
+    let pos_x = (x - bounds.x - scaled_padding) / node_stride + grid_x.rem_euclid(1.0);
+    let pos_y = (y - bounds.y - scaled_padding) / node_stride - grid_y.rem_euclid(1.0);
+    let (base_x, base_y) = (pos_x.floor(), pos_y.floor());
+
+    // The padding between nodes doesn't belong to any node
+    if (pos_x - base_x) * node_stride > scaled_node_size
+        || (pos_y - base_y) * node_stride > scaled_node_size
+    {
+        return None;
+    }
+    Some((base_x as i32, base_y as i32))
+}
+
+/// Returns the prime count vector of the node under the cursor. Mini nodes sit on the corners of
+/// main nodes, so they're checked first, and only count while they're drawn.
+fn node_under_cursor(args: &DrawGridArgs, cursor: (f32, f32)) -> Option<PrimeCountVector> {
+    if !intersects_box(args.bounds, cursor) {
+        return None;
+    }
+    let base = base_node_at(
+        args.bounds,
+        (args.scaled_node_size, args.scaled_padding),
+        (args.grid_x, args.grid_y),
+        cursor,
+    )?;
+    let group = args
+        .nodes
+        .groups
+        .iter()
+        .find(|group| (group.node.base_x, group.node.base_y) == base)?;
+
+    for mini_node in &group.mini_nodes {
+        let node_args = DrawNodeArgs::new(args, mini_node);
+        if mini_node_bounds(args, &node_args).map_or(false, |b| intersects_box(b, cursor)) {
+            return Some(mini_node.primes);
+        }
+    }
+    Some(group.node.primes)
+}
+
+#[cfg(test)]
+mod base_node_at_tests {
+    use crate::editor::lattice::grid::base_node_at;
+    use nih_plug_vizia::vizia::prelude::BoundingBox;
+
+    // Nodes 10 wide with 2 padding, so node 0 spans 2 to 12 and node 1 spans 14 to 24
+    const BOUNDS: BoundingBox = BoundingBox {
+        x: 0.0,
+        y: 0.0,
+        w: 100.0,
+        h: 100.0,
+    };
+
+    #[test]
+    fn test_finds_nodes() {
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.0, 0.0), (5.0, 5.0)),
+            Some((0, 0))
+        );
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.0, 0.0), (20.0, 30.0)),
+            Some((1, 2))
+        );
+    }
+
+    #[test]
+    fn test_skips_padding() {
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.0, 0.0), (13.0, 5.0)),
+            None
+        );
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.0, 0.0), (5.0, 13.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_follows_partial_offsets() {
+        // Partial X offsets move nodes left, and partial Y offsets move them down
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.5, 0.0), (10.0, 5.0)),
+            Some((1, 0))
+        );
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.0, 0.5), (5.0, 10.0)),
+            Some((0, 0))
+        );
+        assert_eq!(
+            base_node_at(BOUNDS, (10.0, 2.0), (0.0, 0.5), (5.0, 5.0)),
+            Some((0, -1))
+        );
+    }
+}
+
+/// Returns the prime count vector of the node at a physical position on the grid.
+/// With no grid offset, C is placed as close as possible to the center of the grid.
+fn node_prime_count_vector(
+    (base_x, base_y, base_z, base_w): (i32, i32, i32, i32),
+    (grid_width, grid_height): (i32, i32),
+    (grid_x, grid_y, grid_z, grid_w): (f32, f32, i32, i32),
+) -> PrimeCountVector {
+    // Offsets for the coordinates of C on the grid
+    let (x_offset, y_offset) = ((grid_width - 1) / 2, grid_height / 2);
+    PrimeCountVector::new(
+        y_offset - base_y + grid_y.floor() as i32,
+        base_x - x_offset + grid_x.floor() as i32,
+        base_z + grid_z,
+        base_w + grid_w,
+    )
+}
+
+/// Grid offsets that put a node at the center of the grid, the inverse of
+/// [`node_prime_count_vector()`]. A node that's already centered goes back to the origin instead.
+fn centering_target(
+    primes: &PrimeCountVector,
+    current: (f32, f32, i32, i32),
+) -> (f32, f32, i32, i32) {
+    let max_offset = MAX_GRID_OFFSET as i32;
+    let target = (
+        primes.fives.clamp(-max_offset, max_offset) as f32,
+        primes.threes.clamp(-max_offset, max_offset) as f32,
+        primes.sevens.clamp(-max_offset, max_offset),
+        primes.elevens.clamp(-max_offset, max_offset),
+    );
+    if target == current {
+        (0.0, 0.0, 0, 0)
+    } else {
+        target
+    }
+}
+
+#[cfg(test)]
+mod centering_target_tests {
+    use crate::editor::lattice::grid::{centering_target, node_prime_count_vector};
+    use crate::tuning::PrimeCountVector;
+    use crate::MAX_GRID_OFFSET;
+
+    #[test]
+    fn test_centers_node() {
+        let (grid_width, grid_height) = (9, 7);
+        let current = (2.4, -1.0, 0, 0);
+        let primes = node_prime_count_vector((7, 1, 0, 0), (grid_width, grid_height), current);
+        let target = centering_target(&primes, current);
+        assert_eq!(target, (5.0, 1.0, 0, 0));
+        assert_eq!(
+            node_prime_count_vector(
+                ((grid_width - 1) / 2, grid_height / 2, 0, 0),
+                (grid_width, grid_height),
+                target
+            ),
+            primes
+        );
+    }
+
+    #[test]
+    fn test_moves_along_mini_node_axis() {
+        let primes = PrimeCountVector::new(1, 2, 1, 0);
+        assert_eq!(
+            centering_target(&primes, (0.0, 0.0, 0, 0)),
+            (2.0, 1.0, 1, 0)
+        );
+        let primes = PrimeCountVector::new(0, 0, 0, -1);
+        assert_eq!(
+            centering_target(&primes, (0.0, 0.0, 0, 0)),
+            (0.0, 0.0, 0, -1)
+        );
+    }
+
+    #[test]
+    fn test_centered_node_goes_to_origin() {
+        let primes = PrimeCountVector::new(-2, 3, 1, 0);
+        assert_eq!(
+            centering_target(&primes, (3.0, -2.0, 1, 0)),
+            (0.0, 0.0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        let primes = PrimeCountVector::new(0, 25, -30, 0);
+        let max = MAX_GRID_OFFSET as i32;
+        assert_eq!(
+            centering_target(&primes, (18.0, 0.0, -19, 0)),
+            (MAX_GRID_OFFSET, 0.0, -max, 0)
+        );
+    }
+}
+
+/// When grid x or y is not a round number, we need to add a row or column to avoid blanks.
+/// Returns the number of extra columns on the right and rows on the top.
+fn extra_columns_and_rows(grid_x: f32, grid_y: f32) -> (i32, i32) {
+    (
+        if grid_x == grid_x.round() { 0 } else { 1 },
+        if grid_y == grid_y.round() { 0 } else { 1 },
+    )
+}
+
+/// Everything that decides which pitch classes are visible on the grid
+#[derive(PartialEq, Clone, Copy)]
+struct VisibleGrid {
+    tuning: TuningSnapshot,
+    size: (i32, i32),
+    offset: (f32, f32, i32, i32),
+    tuning_tolerance: PitchClassDistance,
+}
+
+impl VisibleGrid {
+    fn new(params: &MidiLatticeParams) -> Self {
+        let grid_params = &params.grid_params;
+        VisibleGrid {
+            tuning: lattice_tuning(params),
+            size: (
+                grid_params.width.load(Ordering::Relaxed) as i32,
+                grid_params.height.load(Ordering::Relaxed) as i32,
+            ),
+            offset: (
+                grid_params.x.value(),
+                grid_params.y.value(),
+                grid_params.z.value(),
+                grid_params.w.value(),
+            ),
+            tuning_tolerance: PitchClassDistance::from_cents_f32(
+                params.tuning_params.tolerance.value(),
+            ),
+        }
+    }
+
+    /// Returns every pitch class of the main nodes on the grid, sorted
+    fn pitch_classes(&self) -> Vec<PitchClass> {
+        let mut pitch_classes = get_grid_pitch_classes(&self.tuning, self.size, self.offset);
+        pitch_classes.sort_unstable();
+        pitch_classes
+    }
+}
+
+/// Caches the pitch classes visible on the grid, which only change along with the grid's size,
+/// position or tuning, so they aren't recomputed on every frame
+#[derive(Default)]
+pub struct VisiblePitchClasses {
+    grid: Option<VisibleGrid>,
+    /// Every visible pitch class, sorted, for matching
+    pitch_classes: Vec<PitchClass>,
+    /// One pitch class for each cluster, for drawing
+    representatives: Vec<PitchClass>,
+}
+
+impl VisiblePitchClasses {
+    /// Recomputes the pitch classes if the grid has changed since they were last computed
+    pub fn update(&mut self, params: &MidiLatticeParams) -> &Self {
+        self.update_for_grid(VisibleGrid::new(params))
+    }
+
+    fn update_for_grid(&mut self, grid: VisibleGrid) -> &Self {
+        if self.grid != Some(grid) {
+            self.pitch_classes = grid.pitch_classes();
+            self.representatives =
+                dedup_pitch_classes(self.pitch_classes.clone(), grid.tuning_tolerance);
+            self.grid = Some(grid);
+        }
+        self
+    }
+
+    /// Returns the sorted pitch classes of the main nodes visible on the grid, with one
+    /// representative for each cluster of pitch classes within the tuning tolerance of each other.
+    pub fn representatives(&self) -> &Vec<PitchClass> {
+        &self.representatives
+    }
+
+    /// Returns whether a pitch class matches any main node visible on the grid. This checks every
+    /// node rather than the representatives, which can be up to a tolerance below the top of
+    /// their cluster.
+    pub fn matches(&self, pitch_class: PitchClass, tuning_tolerance: PitchClassDistance) -> bool {
+        has_matching_pitch_class(pitch_class, &self.pitch_classes, tuning_tolerance)
+    }
+}
+
+/// A node that can be drawn on the grid, at its physical position
+#[derive(Clone, Copy)]
+pub struct GridNode {
+    // x = fives, y = threes, z = sevens, w = elevens
+    base_x: i32,
+    base_y: i32,
+    base_z: i32,
+    base_w: i32,
+    primes: PrimeCountVector,
+    pitch_class: PitchClass,
+    /// Whether the node's axis is shown, so it's drawn while it's playing. Always true for main
+    /// nodes.
+    shown: bool,
+}
+
+/// A main node that can be drawn on the grid, with the mini nodes on its corners
+pub struct GridNodeGroup {
+    node: GridNode,
+    /// Mini nodes on the +7, -7, +11 and -11 corners, in that order
+    mini_nodes: [GridNode; 4],
+}
+
+/// Every node that can be drawn on the grid
+#[derive(Default)]
+pub struct GridNodes {
+    /// Every main node, including the extra row and column shown when the grid is partially
+    /// scrolled, with its mini nodes
+    groups: Vec<GridNodeGroup>,
+    /// Prime count vectors of every node, main and mini, for tracking highlights
+    primes: Vec<PrimeCountVector>,
+}
+
+impl GridNodes {
+    fn new(grid: &VisibleGrid, show_z_axis: &ShowZAxis, show_w_axis: &ShowWAxis) -> Self {
+        let (grid_width, grid_height) = grid.size;
+        let (grid_x, grid_y, _, _) = grid.offset;
+        let show_z_axis = match show_z_axis {
+            ShowZAxis::Yes => true,
+            ShowZAxis::No => false,
+            ShowZAxis::Auto => !grid.tuning.seven_collapses(grid.tuning_tolerance),
+        };
+        let show_w_axis = match show_w_axis {
+            ShowWAxis::Yes => true,
+            ShowWAxis::No => false,
+            ShowWAxis::Auto => !grid.tuning.eleven_collapses(grid.tuning_tolerance),
+        };
+
+        let (extra_right, extra_top) = extra_columns_and_rows(grid_x, grid_y);
+        let mut nodes = GridNodes::default();
+        for base_x in 0..grid_width + extra_right {
+            for base_y in -extra_top..grid_height {
+                let node = |base_z: i32, base_w: i32, shown: bool| {
+                    let primes = node_prime_count_vector(
+                        (base_x, base_y, base_z, base_w),
+                        grid.size,
+                        grid.offset,
+                    );
+                    GridNode {
+                        base_x,
+                        base_y,
+                        base_z,
+                        base_w,
+                        primes,
+                        pitch_class: grid.tuning.pitch_class(&primes),
+                        shown,
+                    }
+                };
+                let group = GridNodeGroup {
+                    node: node(0, 0, true),
+                    mini_nodes: [
+                        node(1, 0, show_z_axis),
+                        node(-1, 0, show_z_axis),
+                        node(0, 1, show_w_axis),
+                        node(0, -1, show_w_axis),
+                    ],
+                };
+                nodes.primes.push(group.node.primes);
+                nodes
+                    .primes
+                    .extend(group.mini_nodes.iter().map(|mini_node| mini_node.primes));
+                nodes.groups.push(group);
+            }
+        }
+        nodes
+    }
+}
+
+/// Caches the nodes that can be drawn on the grid, with their positions and prime count vectors.
+/// These only change along with the grid's size, position or tuning, or which axes are shown, so
+/// only matching them to voices needs to happen on every frame.
+#[derive(Default)]
+pub struct VisibleNodes {
+    key: Option<(VisibleGrid, ShowZAxis, ShowWAxis)>,
+    nodes: Arc<GridNodes>,
+}
+
+impl VisibleNodes {
+    /// Recomputes the nodes if the grid has changed since they were last computed
+    pub fn update(&mut self, params: &MidiLatticeParams) -> Arc<GridNodes> {
+        self.update_for_grid(
+            VisibleGrid::new(params),
+            params.grid_params.show_z_axis.value(),
+            params.grid_params.show_w_axis.value(),
+        )
+    }
+
+    fn update_for_grid(
+        &mut self,
+        grid: VisibleGrid,
+        show_z_axis: ShowZAxis,
+        show_w_axis: ShowWAxis,
+    ) -> Arc<GridNodes> {
+        let key = (grid, show_z_axis, show_w_axis);
+        if self.key.as_ref() != Some(&key) {
+            self.nodes = Arc::new(GridNodes::new(&key.0, &key.1, &key.2));
+            self.key = Some(key);
+        }
+        self.nodes.clone()
+    }
+}
+
+/// Returns the pitch classes of the main nodes on the grid, including the extra row and column
+/// shown when the grid is partially scrolled. May contain duplicates.
+fn get_grid_pitch_classes(
+    tuning: &TuningSnapshot,
+    (grid_width, grid_height): (i32, i32),
+    (grid_x, grid_y, grid_z, grid_w): (f32, f32, i32, i32),
+) -> Vec<PitchClass> {
+    let (extra_right, extra_top) = extra_columns_and_rows(grid_x, grid_y);
+    let mut pitch_classes: Vec<PitchClass> = Vec::new();
+    for base_x in 0..grid_width + extra_right {
+        for base_y in -extra_top..grid_height {
+            let primes = node_prime_count_vector(
+                (base_x, base_y, 0, 0),
+                (grid_width, grid_height),
+                (grid_x, grid_y, grid_z, grid_w),
+            );
+            pitch_classes.push(tuning.pitch_class(&primes));
+        }
+    }
+    pitch_classes
+}
+
+/// Sorts pitch classes, and keeps only the lowest of each cluster of pitch classes within the
+/// tuning tolerance of it. Clusters can wrap around the octave.
+///
+/// Pitch classes near the top of a cluster can match things its representative doesn't, so match
+/// against the full list instead.
+pub fn dedup_pitch_classes(
+    mut pitch_classes: Vec<PitchClass>,
+    tuning_tolerance: PitchClassDistance,
+) -> Vec<PitchClass> {
+    pitch_classes.sort_unstable();
+    let mut representatives: Vec<PitchClass> = Vec::new();
+    for pitch_class in pitch_classes {
+        match representatives.last() {
+            Some(last) if last.distance_to(pitch_class) <= tuning_tolerance => {}
+            _ => representatives.push(pitch_class),
+        }
+    }
+
+    // The last cluster may be the start of the first, across the end of the octave
+    if representatives.len() > 1
+        && representatives[representatives.len() - 1].distance_to(representatives[0])
+            <= tuning_tolerance
+    {
+        representatives.pop();
+    }
+    representatives
+}
+
+#[cfg(test)]
+mod dedup_pitch_classes_tests {
+    use crate::{
+        editor::lattice::grid::{dedup_pitch_classes, get_grid_pitch_classes},
+        tuning::{
+            PitchClass, PitchClassDistance, TuningSnapshot, ELEVEN_JUST, FIVE_JUST,
+            OCTAVE_MICROCENTS, SEVEN_JUST, THREE_JUST,
+        },
+    };
+
+    fn visible_count(three: PitchClass, five: PitchClass) -> usize {
+        let tuning = TuningSnapshot {
+            c_offset: PitchClass::from_microcents(0),
+            three,
+            five,
+            seven: SEVEN_JUST,
+            eleven: ELEVEN_JUST,
+        };
+        let pitch_classes = get_grid_pitch_classes(&tuning, (7, 7), (0.0, 0.0, 0, 0));
+        assert_eq!(pitch_classes.len(), 49);
+        dedup_pitch_classes(pitch_classes, PitchClassDistance::from_cents_f32(1.0)).len()
+    }
+
+    #[test]
+    fn dedups_12tet() {
+        assert_eq!(
+            visible_count(
+                PitchClass::from_cents_f32(700.0),
+                PitchClass::from_cents_f32(400.0)
+            ),
+            12
         );
     }
 
-    // Draw stripes if needed
-    canvas.global_composite_operation(vg::CompositeOperation::Atop);
-    draw_extra_colors(
-        canvas,
-        node_args,
-        mini_node_x,
-        mini_node_y,
-        mini_node_size,
-        3,
-    );
-    canvas.global_composite_operation(vg::CompositeOperation::SourceOver);
-
-    // Draw outline if needed
-    if node_args.draw_outline {
-        canvas.stroke_path(
-            &mini_node_path,
-            &make_icon_paint(TEXT_COLOR, node_args.outline_width),
+    #[test]
+    fn dedups_31edo() {
+        // Fifths and thirds are 18 and 10 steps, both with some rounding error
+        assert_eq!(
+            visible_count(
+                PitchClass::from_cents_f32(1200.0 * 18.0 / 31.0),
+                PitchClass::from_cents_f32(1200.0 * 10.0 / 31.0)
+            ),
+            31
         );
     }
 
-    // Draw text (first row; whole number cents)
-    let mut text_paint = vg::Paint::color(TEXT_COLOR);
-    text_paint.set_font_size(args.scaled_node_size * 0.19);
-    text_paint.set_text_align(vg::Align::Center);
-    args.font_id.map(|f| text_paint.set_font(&[f]));
-    let _ = canvas.fill_text(
-        mini_node_x + mini_node_size * 0.5,
-        mini_node_y + mini_node_size * 0.5,
-        node_args.pitch_class.trunc_cents().to_string(),
-        &text_paint,
-    );
+    #[test]
+    fn keeps_distinct_just_intonation() {
+        assert_eq!(visible_count(THREE_JUST, FIVE_JUST), 49);
+    }
 
-    // Draw text (second row; fractional cents)
-    text_paint.set_font_size(args.scaled_node_size * 0.16);
-    let rounded_pitch_class = node_args.pitch_class.round(2);
-    let _ = canvas.fill_text(
-        mini_node_x + mini_node_size * 0.5,
-        mini_node_y + mini_node_size * 0.83,
-        format!(
-            ".{}{}",
-            rounded_pitch_class.get_decimal_digit_num(0),
-            rounded_pitch_class.get_decimal_digit_num(1),
-        ),
-        &text_paint,
-    );
+    #[test]
+    fn dedups_across_zero() {
+        assert_eq!(
+            dedup_pitch_classes(
+                vec![
+                    PitchClass::from_microcents(OCTAVE_MICROCENTS - 1),
+                    PitchClass::from_microcents(700_000_000),
+                    PitchClass::from_microcents(1),
+                    PitchClass::from_microcents(700_000_050),
+                ],
+                PitchClassDistance::from_microcents(100)
+            ),
+            vec![
+                PitchClass::from_microcents(1),
+                PitchClass::from_microcents(700_000_000)
+            ]
+        );
+    }
 }
 
-impl View for Grid {
-    fn element(&self) -> Option<&'static str> {
-        Some("lattice-display")
+#[cfg(test)]
+mod visible_pitch_classes_tests {
+    use crate::{
+        editor::lattice::grid::{VisibleGrid, VisiblePitchClasses},
+        tuning::{
+            PitchClass, PitchClassDistance, TuningSnapshot, ELEVEN_JUST, FIVE_JUST, SEVEN_JUST,
+            THREE_JUST,
+        },
+    };
+
+    fn grid(width: i32) -> VisibleGrid {
+        VisibleGrid {
+            tuning: TuningSnapshot {
+                c_offset: PitchClass::from_microcents(0),
+                three: THREE_JUST,
+                five: FIVE_JUST,
+                seven: SEVEN_JUST,
+                eleven: ELEVEN_JUST,
+            },
+            size: (width, 1),
+            offset: (0.0, 0.0, 0, 0),
+            tuning_tolerance: PitchClassDistance::from_cents_f32(1.0),
+        }
     }
 
-    fn event(&mut self, _cx: &mut EventContext, _event: &mut Event) {}
+    #[test]
+    fn recomputes_only_when_grid_changes() {
+        let mut visible = VisiblePitchClasses::default();
+        assert_eq!(visible.update_for_grid(grid(3)).representatives().len(), 3);
 
-    // TODO: factor this out into methods
-    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
-        let _start_time = Instant::now();
+        // Stale pitch classes show that nothing was recomputed
+        visible.representatives.clear();
+        assert_eq!(visible.update_for_grid(grid(3)).representatives().len(), 0);
+
+        assert_eq!(visible.update_for_grid(grid(5)).representatives().len(), 5);
+    }
 
-        let args: DrawGridArgs = DrawGridArgs::new(self, cx, canvas);
+    #[test]
+    fn matches_near_top_of_cluster() {
+        let tolerance = PitchClassDistance::from_cents_f32(1.0);
+        let mut visible = VisiblePitchClasses::default();
+        visible.update_for_grid(grid(1));
+        // One cluster, represented by its lowest pitch class
+        visible.pitch_classes = vec![
+            PitchClass::from_cents_f32(700.0),
+            PitchClass::from_cents_f32(700.9),
+        ];
+        visible.representatives = vec![PitchClass::from_cents_f32(700.0)];
 
-        prepare_canvas(cx, canvas, &args);
+        // Within the tolerance of the top of the cluster, but not of its representative
+        assert!(visible.matches(PitchClass::from_cents_f32(701.8), tolerance));
+        assert!(visible.matches(PitchClass::from_cents_f32(699.1), tolerance));
+        assert!(!visible.matches(PitchClass::from_cents_f32(702.0), tolerance));
+    }
+}
 
-        // When grid x or y is not a round number, we need to add a row or column to avoid blanks
-        let (extra_right, extra_top) = (
-            if args.grid_x == args.grid_x.round() {
-                0
-            } else {
-                1
-            },
-            if args.grid_y == args.grid_y.round() {
-                0
-            } else {
-                1
+#[cfg(test)]
+mod visible_nodes_tests {
+    use crate::{
+        editor::lattice::grid::{VisibleGrid, VisibleNodes},
+        tuning::{
+            PitchClass, PitchClassDistance, PrimeCountVector, TuningSnapshot, ELEVEN_JUST,
+            FIVE_JUST, SEVEN_JUST, THREE_JUST,
+        },
+        ShowWAxis, ShowZAxis,
+    };
+
+    fn grid(width: i32, x: f32) -> VisibleGrid {
+        VisibleGrid {
+            tuning: TuningSnapshot {
+                c_offset: PitchClass::from_microcents(0),
+                three: THREE_JUST,
+                five: FIVE_JUST,
+                seven: SEVEN_JUST,
+                eleven: ELEVEN_JUST,
             },
+            size: (width, 1),
+            offset: (x, 0.0, 0, 0),
+            tuning_tolerance: PitchClassDistance::from_cents_f32(1.0),
+        }
+    }
+
+    #[test]
+    fn recomputes_only_when_grid_changes() {
+        let mut visible = VisibleNodes::default();
+        let nodes = visible.update_for_grid(grid(3, 0.0), ShowZAxis::Auto, ShowWAxis::Auto);
+        assert_eq!(nodes.groups.len(), 3);
+        assert_eq!(nodes.primes.len(), 15);
+
+        let same = visible.update_for_grid(grid(3, 0.0), ShowZAxis::Auto, ShowWAxis::Auto);
+        assert!(std::sync::Arc::ptr_eq(&nodes, &same));
+
+        // Partially scrolled grids show an extra column
+        let scrolled = visible.update_for_grid(grid(3, 0.5), ShowZAxis::Auto, ShowWAxis::Auto);
+        assert_eq!(scrolled.groups.len(), 4);
+
+        let hidden = visible.update_for_grid(grid(3, 0.5), ShowZAxis::No, ShowWAxis::Auto);
+        assert!(!std::sync::Arc::ptr_eq(&scrolled, &hidden));
+    }
+
+    #[test]
+    fn mini_nodes_follow_shown_axes() {
+        let mut visible = VisibleNodes::default();
+        let nodes = visible.update_for_grid(grid(1, 0.0), ShowZAxis::No, ShowWAxis::Auto);
+        let group = &nodes.groups[0];
+        assert_eq!(group.node.primes, PrimeCountVector::new(0, 0, 0, 0));
+        assert_eq!(
+            group.mini_nodes[0].primes,
+            PrimeCountVector::new(0, 0, 1, 0)
         );
+        assert_eq!(
+            group.mini_nodes[3].primes,
+            PrimeCountVector::new(0, 0, 0, -1)
+        );
+        let shown: Vec<bool> = group.mini_nodes.iter().map(|node| node.shown).collect();
+        // Just elevens don't collapse onto fifths, so they're shown automatically
+        assert_eq!(shown, vec![false, false, true, true]);
+    }
+}
+
+// Helper methods for auditioning
+impl Grid {
+    /// Returns the prime count vector of the main node at a position in the grid's bounds, if
+    /// any. This is the inverse of the node position calculation in [`DrawNodeArgs::new()`].
+    fn node_at_position(
+        &self,
+        bounds: BoundingBox,
+        scale_factor: f32,
+        (x, y): (f32, f32),
+    ) -> Option<PrimeCountVector> {
+        if !intersects_box(bounds, (x, y)) {
+            return None;
+        }
+
+        let grid_params = &self.params.grid_params;
+        let grid_width = grid_params.width.load(Ordering::Relaxed) as i32;
+        let grid_height = grid_params.height.load(Ordering::Relaxed) as i32;
+        let (grid_x, grid_y) = (grid_params.x.value(), grid_params.y.value());
 
-        // Offsets for the coordinates of C on the grid (makes it as close as possible to the center)
-        let (x_offset, y_offset) = (
-            ((args.grid_width - 1) / 2) as i32,
-            (args.grid_height / 2) as i32,
+        let scaled_padding = PADDING * scale_factor;
+        let scaled_node_size = snap(
+            (bounds.width() - scaled_padding * (grid_width as f32 + 1.0)) / grid_width as f32,
+            scale_factor,
         );
+        let (base_x, base_y) = base_node_at(
+            bounds,
+            (scaled_node_size, scaled_padding),
+            (grid_x, grid_y),
+            (x, y),
+        )?;
+
+        Some(node_prime_count_vector(
+            (base_x, base_y, 0, 0),
+            (grid_width, grid_height),
+            (grid_x, grid_y, grid_params.z.value(), grid_params.w.value()),
+        ))
+    }
 
-        // x = fives
-        for base_x in 0..args.grid_width + extra_right {
-            // y = threes
-            for base_y in -extra_top..args.grid_height {
-                // Draw lattice nodes one by one
-                // z = sevens
-                let make_draw_node_args = |base_z| {
-                    DrawNodeArgs::new(
-                        &args,
-                        base_x,
-                        base_y,
-                        base_z,
-                        PrimeCountVector::new(
-                            y_offset - i32::from(base_y) + args.grid_y.floor() as i32,
-                            i32::from(base_x - x_offset) + args.grid_x.floor() as i32,
-                            base_z + args.grid_z,
-                        ),
-                    )
-                };
-                let (node_args_zero_z, node_args_pos_z, node_args_neg_z) = (
-                    make_draw_node_args(0),
-                    make_draw_node_args(1),
-                    make_draw_node_args(-1),
-                );
+    /// Sends a note on for the pitch class of the given node
+    fn start_audition(&mut self, primes: PrimeCountVector) {
+        let pitch_class = lattice_tuning(&self.params).pitch_class(&primes);
+        let (note, tuning) = pitch_class.to_midi_note_and_tuning(AUDITION_OCTAVE_START_NOTE);
+        let used_channels: Vec<u8> = {
+            let mut voices_output = self.voices_output.lock().unwrap();
+            voices_output
+                .read()
+                .values()
+                .map(|voice| voice.get_channel())
+                .collect()
+        };
+        let channel =
+            free_audition_channel(&used_channels, &self.params.channel_roles.read().unwrap());
+
+        self.command_sender
+            .lock()
+            .unwrap()
+            .send(AudioCommand::SendNoteOn {
+                channel,
+                note,
+                velocity: AUDITION_VELOCITY,
+                tuning,
+            });
+        self.auditioned_note = Some((channel, note));
+    }
 
-                draw_node_zero_z(
-                    canvas,
-                    &args,
-                    &node_args_zero_z,
-                    node_args_pos_z.draw,
-                    node_args_neg_z.draw,
-                );
-                draw_node_nonzero_z(canvas, &args, &node_args_pos_z);
-                draw_node_nonzero_z(canvas, &args, &node_args_neg_z);
-            }
+    /// Sends a note off for the currently auditioned note, if there is one
+    fn stop_audition(&mut self) {
+        if let Some((channel, note)) = self.auditioned_note.take() {
+            self.command_sender
+                .lock()
+                .unwrap()
+                .send(AudioCommand::SendNoteOff { channel, note });
         }
+    }
+}
 
-        finish_canvas(cx, canvas, &args);
+/// Returns the first colored channel without any voices, so that an auditioned note's tuning
+/// doesn't affect notes already playing. Falls back to [`AUDITION_CHANNEL`].
+fn free_audition_channel(used_channels: &[u8], channel_roles: &ChannelConfig) -> u8 {
+    (0..16)
+        .find(|channel| {
+            channel_roles.role(*channel) == ChannelRole::Color && !used_channels.contains(channel)
+        })
+        .unwrap_or(AUDITION_CHANNEL)
+}
 
-        /*
-        nih_log!(
-            "*** draw() finished in {} us",
-            start_time.elapsed().as_micros()
+#[cfg(test)]
+mod free_audition_channel_tests {
+    use crate::editor::lattice::grid::{free_audition_channel, AUDITION_CHANNEL};
+    use crate::midi::ChannelConfig;
+
+    #[test]
+    fn skips_used_channels() {
+        assert_eq!(free_audition_channel(&[], &ChannelConfig::default()), 0);
+        assert_eq!(
+            free_audition_channel(&[0, 2, 1, 0], &ChannelConfig::default()),
+            3
+        );
+    }
+
+    #[test]
+    fn skips_channels_that_arent_colored() {
+        let mut channel_roles = ChannelConfig::default();
+        channel_roles.cycle_role(0);
+        assert_eq!(free_audition_channel(&[], &channel_roles), 1);
+    }
+
+    #[test]
+    fn falls_back_when_all_are_used() {
+        let used: Vec<u8> = (0..16).collect();
+        assert_eq!(
+            free_audition_channel(&used, &ChannelConfig::default()),
+            AUDITION_CHANNEL
         );
-        */
     }
 }
+
 // Helper methods for drawing
 impl Grid {
     /// Retrieves the list of `MidiVoice` from the triple buffer, and returns a vector of `Voice`
@@ -1063,32 +3422,93 @@ impl Grid {
             .read()
             .values()
             .cloned()
-            .map(|v: MidiVoice| Voice::new(v.get_channel(), v.get_pitch(), v.get_pitch_class()))
+            .map(|v: MidiVoice| Voice {
+                note: v.get_note(),
+                note_on_order: v.get_note_on_order(),
+                velocity: v.get_velocity(),
+                pressure: v.get_pressure(),
+                sustained: v.is_sustained(),
+                ..Voice::new(v.get_channel(), v.get_pitch(), v.get_pitch_class())
+            })
             .collect();
         result.sort_unstable_by(|v1, v2| v1.pitch_class.cmp(&v2.pitch_class));
         result
     }
 }
 
-// Returns whether a pitch class matches any in a list of sorted pitch classes
-fn has_matching_pitch_class(
-    pitch_class: PitchClass,
-    sorted_pitch_classes: &Vec<PitchClass>,
-    tuning_tolerance: PitchClassDistance,
-) -> bool {
-    if sorted_pitch_classes.len() == 0 {
-        return false;
+#[cfg(test)]
+mod highlight_update_tests {
+    use crate::{
+        editor::lattice::grid::{highlight_update, HighlightUpdate},
+        tuning::{PitchClass, PitchClassDistance, TuningSnapshot, ELEVEN_JUST},
+    };
+
+    fn tuning(c_offset: f32, three: f32, five: f32, seven: f32) -> TuningSnapshot {
+        TuningSnapshot {
+            c_offset: PitchClass::from_cents_f32(c_offset),
+            three: PitchClass::from_cents_f32(three),
+            five: PitchClass::from_cents_f32(five),
+            seven: PitchClass::from_cents_f32(seven),
+            eleven: ELEVEN_JUST,
+        }
+    }
+
+    #[test]
+    fn keeps_changes_within_tolerance() {
+        assert_eq!(
+            highlight_update(
+                &tuning(0.0, 700.0, 400.0, 1000.0),
+                &tuning(0.5, 700.5, 399.5, 1000.0),
+                PitchClassDistance::from_cents_f32(1.0)
+            ),
+            HighlightUpdate::Keep
+        );
     }
 
-    // Lowest pitch class that could match
-    let candidate_idx: usize = sorted_pitch_classes
-        .partition_point(|pc: &PitchClass| *pc < pitch_class - PitchClass::from(tuning_tolerance));
+    #[test]
+    fn translates_c_offset_changes() {
+        assert_eq!(
+            highlight_update(
+                &tuning(10.0, 700.0, 400.0, 1000.0),
+                &tuning(-20.0, 700.0, 400.0, 1000.0),
+                PitchClassDistance::from_cents_f32(1.0)
+            ),
+            HighlightUpdate::Translate(PitchClass::from_cents_f32(-30.0))
+        );
+    }
 
-    if candidate_idx == sorted_pitch_classes.len() {
-        return sorted_pitch_classes[0].distance_to(pitch_class) <= tuning_tolerance;
+    #[test]
+    fn clears_on_interval_changes() {
+        assert_eq!(
+            highlight_update(
+                &tuning(0.0, 700.0, 400.0, 1000.0),
+                &tuning(0.0, 701.955, 400.0, 1000.0),
+                PitchClassDistance::from_cents_f32(1.0)
+            ),
+            HighlightUpdate::Clear
+        );
+        // Interval changes take priority over C offset changes
+        assert_eq!(
+            highlight_update(
+                &tuning(0.0, 700.0, 400.0, 1000.0),
+                &tuning(30.0, 700.0, 400.0, 968.826),
+                PitchClassDistance::from_cents_f32(1.0)
+            ),
+            HighlightUpdate::Clear
+        );
     }
+}
 
-    return sorted_pitch_classes[candidate_idx].distance_to(pitch_class) <= tuning_tolerance;
+// Returns whether a pitch class matches any in a list of sorted pitch classes. Only the closest
+// can match, which is one of the neighbors of the pitch class's place in the list.
+pub fn has_matching_pitch_class(
+    pitch_class: PitchClass,
+    sorted_pitch_classes: &Vec<PitchClass>,
+    tuning_tolerance: PitchClassDistance,
+) -> bool {
+    nearest_pitch_class(pitch_class, sorted_pitch_classes).map_or(false, |(nearest, _)| {
+        nearest.distance_to(pitch_class) <= tuning_tolerance
+    })
 }
 
 #[cfg(test)]
@@ -1147,6 +3567,140 @@ mod has_matching_pitch_class_tests {
             PitchClassDistance::from_microcents(100)
         ));
     }
+
+    #[test]
+    fn matches_at_tolerance_boundaries() {
+        let tolerance: u32 = 1_000_000;
+        // Points to search from, around zero and in the middle of the octave
+        let searches = [
+            0,
+            1,
+            tolerance - 1,
+            tolerance,
+            tolerance + 1,
+            600_000_000,
+            OCTAVE_MICROCENTS - tolerance,
+            OCTAVE_MICROCENTS - 1,
+        ];
+        for search in searches {
+            for offset in [tolerance - 1, tolerance, tolerance + 1] {
+                let should_match = offset <= tolerance;
+                for (voice, other) in [
+                    // Below the search point, with another pitch class above it
+                    (search + OCTAVE_MICROCENTS - offset, search + 200_000_000),
+                    // Above the search point, with another pitch class below it
+                    (search + offset, search + OCTAVE_MICROCENTS - 200_000_000),
+                ] {
+                    let mut sorted = vec![
+                        PitchClass::from_microcents(voice),
+                        PitchClass::from_microcents(other),
+                    ];
+                    sorted.sort();
+                    assert_eq!(
+                        has_matching_pitch_class(
+                            PitchClass::from_microcents(search),
+                            &sorted,
+                            PitchClassDistance::from_microcents(tolerance)
+                        ),
+                        should_match,
+                        "search {}, offset {}, sorted {:?}",
+                        search,
+                        offset,
+                        sorted
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the closest of a list of sorted pitch classes to a pitch class, and how many cents the
+/// pitch class is above it - negative if below. Distances wrap around the octave.
+pub fn nearest_pitch_class(
+    pitch_class: PitchClass,
+    sorted_pitch_classes: &Vec<PitchClass>,
+) -> Option<(PitchClass, f32)> {
+    if sorted_pitch_classes.is_empty() {
+        return None;
+    }
+
+    // The closest is either the lowest one above the pitch class, or the one before it. Either
+    // may be across the end of the octave.
+    let len = sorted_pitch_classes.len();
+    let above_idx = sorted_pitch_classes.partition_point(|pc: &PitchClass| *pc < pitch_class);
+    let nearest = [above_idx % len, (above_idx + len - 1) % len]
+        .into_iter()
+        .map(|idx| sorted_pitch_classes[idx])
+        .min_by_key(|pc| pc.distance_to(pitch_class))?;
+
+    let cents_above = (pitch_class - nearest).to_cents_f32();
+    Some((
+        nearest,
+        if cents_above > 600.0 {
+            cents_above - 1200.0
+        } else {
+            cents_above
+        },
+    ))
+}
+
+#[cfg(test)]
+mod nearest_pitch_class_tests {
+    use crate::{
+        editor::lattice::grid::nearest_pitch_class,
+        tuning::{PitchClass, OCTAVE_MICROCENTS},
+    };
+
+    #[test]
+    fn finds_closest_above_or_below() {
+        let pitch_classes = vec![
+            PitchClass::from_microcents(400_000_000),
+            PitchClass::from_microcents(700_000_000),
+        ];
+        assert_eq!(
+            nearest_pitch_class(PitchClass::from_microcents(690_000_000), &pitch_classes),
+            Some((PitchClass::from_microcents(700_000_000), -10.0))
+        );
+        assert_eq!(
+            nearest_pitch_class(PitchClass::from_microcents(420_000_000), &pitch_classes),
+            Some((PitchClass::from_microcents(400_000_000), 20.0))
+        );
+    }
+
+    #[test]
+    fn wraps_around_zero() {
+        assert_eq!(
+            nearest_pitch_class(
+                PitchClass::from_microcents(5_000_000),
+                &vec![
+                    PitchClass::from_microcents(400_000_000),
+                    PitchClass::from_microcents(OCTAVE_MICROCENTS - 5_000_000),
+                ]
+            ),
+            Some((
+                PitchClass::from_microcents(OCTAVE_MICROCENTS - 5_000_000),
+                10.0
+            ))
+        );
+        assert_eq!(
+            nearest_pitch_class(
+                PitchClass::from_microcents(OCTAVE_MICROCENTS - 5_000_000),
+                &vec![
+                    PitchClass::from_microcents(5_000_000),
+                    PitchClass::from_microcents(400_000_000),
+                ]
+            ),
+            Some((PitchClass::from_microcents(5_000_000), -10.0))
+        );
+    }
+
+    #[test]
+    fn none_without_pitch_classes() {
+        assert_eq!(
+            nearest_pitch_class(PitchClass::from_microcents(0), &vec![]),
+            None
+        );
+    }
 }
 
 /// Returns the subset of a vector of voices with a given pitch class.
@@ -1156,60 +3710,40 @@ fn get_matching_voices(
     tuning_tolerance: PitchClassDistance,
 ) -> Vec<Voice> {
     let mut matching_voices: Vec<Voice> = Vec::new();
-
-    if sorted_voices.len() == 0 {
+    let len = sorted_voices.len();
+    if len == 0 {
         return matching_voices;
     }
-
-    // Lowest pitch class that could match
-    let mut start_idx: usize = sorted_voices.partition_point(|v| {
-        v.get_pitch_class() < pitch_class - PitchClass::from(tuning_tolerance)
-    });
-
-    if start_idx == sorted_voices.len() {
-        start_idx = 0;
-    }
-
-    let mut idx = start_idx;
-
-    // Loop forwards from start idx
-    loop {
-        if sorted_voices[idx]
+    let matches = |idx: usize| {
+        sorted_voices[idx]
             .get_pitch_class()
             .distance_to(pitch_class)
-            > tuning_tolerance
-        {
-            break;
-        }
-        matching_voices.push(sorted_voices[idx]);
-        if idx == sorted_voices.len() - 1 {
-            idx = 0;
-        } else {
-            idx += 1;
-        }
-        if idx == start_idx {
-            return matching_voices;
-        }
-    }
+            <= tuning_tolerance
+    };
 
-    // Loop backwards from start idx
-    idx = start_idx;
-    loop {
-        if idx == 0 {
-            idx = sorted_voices.len() - 1;
-        } else {
-            idx -= 1;
-        }
-        if sorted_voices[idx]
-            .get_pitch_class()
-            .distance_to(pitch_class)
-            > tuning_tolerance
-        {
-            break;
-        }
-        matching_voices.push(sorted_voices[idx]);
+    // Lowest pitch class that could match. If there's none above it, the matches may continue
+    // from the start of the octave.
+    let start_idx: usize = sorted_voices.partition_point(|v| {
+        v.get_pitch_class() < pitch_class - PitchClass::from(tuning_tolerance)
+    }) % len;
+
+    // Matches form one run of indices, wrapping around the end of the vector. Walk forwards from
+    // the start index, then backwards from just before it, visiting each index at most once.
+    let forward_count = (0..len)
+        .map(|offset| (start_idx + offset) % len)
+        .take_while(|idx| matches(*idx))
+        .count();
+    let backward_count = (1..len - forward_count + 1)
+        .map(|offset| (start_idx + len - offset) % len)
+        .take_while(|idx| matches(*idx))
+        .count();
+
+    for offset in 0..forward_count {
+        matching_voices.push(sorted_voices[(start_idx + offset) % len]);
+    }
+    for offset in 1..backward_count + 1 {
+        matching_voices.push(sorted_voices[(start_idx + len - offset) % len]);
     }
-
     matching_voices
 }
 
@@ -1217,7 +3751,10 @@ fn get_matching_voices(
 mod get_matching_voices_tests {
     use crate::{
         editor::lattice::grid::{get_matching_voices, Voice},
-        tuning::{PitchClass, PitchClassDistance, OCTAVE_MICROCENTS},
+        tuning::{
+            PitchClass, PitchClassDistance, PrimeCountVector, Tolerances, OCTAVE_MICROCENTS,
+            SEVEN_JUST, THREE_JUST,
+        },
     };
 
     #[test]
@@ -1241,6 +3778,27 @@ mod get_matching_voices_tests {
         assert_eq!(output, target);
     }
 
+    #[test]
+    fn matches_untuned_notes_to_untempered_nodes_within_tolerance() {
+        // E in 12-TET is about 13.7 cents above the just major third
+        let just_third = PitchClass::from_cents_f32(386.3137);
+        let voices = vec![Voice::new(0, 0.0, PitchClass::from_midi_note(64))];
+        assert_eq!(
+            get_matching_voices(
+                just_third,
+                &voices,
+                PitchClassDistance::from_cents_f32(14.0)
+            ),
+            voices
+        );
+        assert!(get_matching_voices(
+            just_third,
+            &voices,
+            PitchClassDistance::from_cents_f32(13.0)
+        )
+        .is_empty());
+    }
+
     #[test]
     fn slightly_positive_matches_slightly_negative() {
         let output = get_matching_voices(
@@ -1271,6 +3829,38 @@ mod get_matching_voices_tests {
         assert_eq!(output, target);
     }
 
+    #[test]
+    fn uses_tolerance_of_node_primes() {
+        let tolerances = Tolerances {
+            global: PitchClassDistance::from_cents(1),
+            three: None,
+            five: None,
+            seven: Some(PitchClassDistance::from_cents(10)),
+        };
+        // 5 cents off from the nodes
+        let voices = vec![
+            Voice::new(0, 0.0, THREE_JUST + PitchClass::from_cents_f32(5.0)),
+            Voice::new(0, 0.0, SEVEN_JUST + PitchClass::from_cents_f32(5.0)),
+        ];
+        let matches = |primes: PrimeCountVector, pitch_class: PitchClass| {
+            get_matching_voices(pitch_class, &voices, tolerances.for_primes(&primes)).len()
+        };
+        assert_eq!(matches(PrimeCountVector::new(1, 0, 0, 0), THREE_JUST), 0);
+        assert_eq!(matches(PrimeCountVector::new(0, 0, 1, 0), SEVEN_JUST), 1);
+
+        // With only the global tolerance, as in older saved states, neither matches
+        let tolerances = Tolerances::uniform(PitchClassDistance::from_cents(1));
+        assert_eq!(
+            get_matching_voices(
+                SEVEN_JUST,
+                &voices,
+                tolerances.for_primes(&PrimeCountVector::new(0, 0, 1, 0))
+            )
+            .len(),
+            0
+        );
+    }
+
     #[test]
     fn slightly_positive_matches_slightly_negative_multiple_voices() {
         let mut output = get_matching_voices(
@@ -1312,4 +3902,147 @@ mod get_matching_voices_tests {
         target.sort();
         assert_eq!(output, target);
     }
+
+    /// Minimal xorshift generator, so tests can use many voice sets without a random crate
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn below(&mut self, bound: u32) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as u32
+        }
+    }
+
+    /// Sorted pitch classes and channels, which tell apart voices with the same pitch class
+    fn voice_ids(voices: &[Voice]) -> Vec<(PitchClass, u8)> {
+        let mut ids: Vec<(PitchClass, u8)> = voices
+            .iter()
+            .map(|voice| (voice.get_pitch_class(), voice.get_channel()))
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    fn assert_matches_brute_force(
+        pitch_class: PitchClass,
+        voices: &mut Vec<Voice>,
+        tuning_tolerance: PitchClassDistance,
+    ) {
+        voices.sort();
+        let expected: Vec<Voice> = voices
+            .iter()
+            .filter(|voice| voice.get_pitch_class().distance_to(pitch_class) <= tuning_tolerance)
+            .cloned()
+            .collect();
+        assert_eq!(
+            voice_ids(&get_matching_voices(pitch_class, voices, tuning_tolerance)),
+            voice_ids(&expected),
+            "pitch class {}, tolerance {}, voices {:?}",
+            pitch_class,
+            tuning_tolerance,
+            voices
+        );
+    }
+
+    #[test]
+    fn matches_each_voice_once_when_all_match() {
+        let mut voices: Vec<Voice> = (0..8)
+            .map(|channel| Voice::new(channel, 0.0, PitchClass::from_midi_note(channel * 3)))
+            .collect();
+        for midi_note in 0..12 {
+            assert_matches_brute_force(
+                PitchClass::from_midi_note(midi_note),
+                &mut voices,
+                PitchClassDistance::from_cents(600),
+            );
+        }
+    }
+
+    #[test]
+    fn matches_brute_force_for_random_voices() {
+        let mut rng = TestRng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..2000 {
+            let voice_count = rng.below(12) as u8;
+            // Clusters near the ends of the octave test wrapping around
+            let mut voices: Vec<Voice> = (0..voice_count)
+                .map(|channel| {
+                    let microcents = match rng.below(3) {
+                        0 => rng.below(20_000_000),
+                        1 => OCTAVE_MICROCENTS - 1 - rng.below(20_000_000),
+                        _ => rng.below(OCTAVE_MICROCENTS),
+                    };
+                    Voice::new(channel, 0.0, PitchClass::from_microcents(microcents))
+                })
+                .collect();
+            let pitch_class = PitchClass::from_microcents(rng.below(OCTAVE_MICROCENTS));
+            let tuning_tolerance = PitchClassDistance::from_microcents(match rng.below(3) {
+                0 => rng.below(30_000_000),
+                1 => rng.below(600_000_000),
+                _ => 600_000_000,
+            });
+            assert_matches_brute_force(pitch_class, &mut voices, tuning_tolerance);
+        }
+    }
+}
+
+#[cfg(test)]
+mod cents_rows_tests {
+    use crate::editor::lattice::grid::cents_rows;
+    use crate::tuning::{PitchClass, THREE_JUST};
+
+    fn rows(whole: &str, fraction: &str) -> (String, String) {
+        (String::from(whole), String::from(fraction))
+    }
+
+    #[test]
+    fn rounds_to_two_decimal_places() {
+        assert_eq!(cents_rows(THREE_JUST.to_cents_f64()), rows("701", ".96"));
+        assert_eq!(cents_rows(0.0), rows("0", ".00"));
+        // Rounding carries into the whole cents
+        assert_eq!(cents_rows(701.996), rows("702", ".00"));
+    }
+
+    #[test]
+    fn wraps_at_top_of_octave() {
+        let almost_c = PitchClass::from_microcents(1_199_996_000);
+        assert_eq!(cents_rows(almost_c.to_cents_f64()), rows("0", ".00"));
+        // Halfway between 1199.99 and 1200 rounds either way, but never shows 1200
+        for microcents in [1_199_994_000, 1_199_995_000, 1_199_999_999] {
+            let (whole_cents, _) =
+                cents_rows(PitchClass::from_microcents(microcents).to_cents_f64());
+            assert_ne!(whole_cents, "1200");
+        }
+    }
+}
+
+#[cfg(test)]
+mod deviation_str_tests {
+    use crate::editor::lattice::grid::deviation_str;
+
+    #[test]
+    fn signed_whole_cents() {
+        assert_eq!(deviation_str(13.7), "+14¢");
+        assert_eq!(deviation_str(-13.7), "-14¢");
+        assert_eq!(deviation_str(-0.2), "+0¢");
+    }
+}
+
+#[cfg(test)]
+mod frequency_str_tests {
+    use crate::editor::lattice::grid::frequency_str;
+
+    #[test]
+    fn one_decimal_below_1000_hz() {
+        assert_eq!(frequency_str(261.6256), "261.6");
+        assert_eq!(frequency_str(440.0), "440.0");
+        assert_eq!(frequency_str(999.9), "999.9");
+    }
+
+    #[test]
+    fn whole_numbers_from_1000_hz() {
+        assert_eq!(frequency_str(999.96), "1000");
+        assert_eq!(frequency_str(1046.502), "1047");
+    }
 }
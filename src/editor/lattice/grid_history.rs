@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+// How many gestures can be undone
+const MAX_HISTORY: usize = 32;
+
+/// Position and size of the grid, as restored by undo and redo
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GridSnapshot {
+    pub x: f32,
+    pub y: f32,
+    pub z: i32,
+    pub w: i32,
+    pub width: u8,
+    pub height: u8,
+}
+
+/// Bounded undo and redo history of the grid's position and size, one entry per gesture
+pub struct GridHistory {
+    undo: VecDeque<GridSnapshot>,
+    redo: Vec<GridSnapshot>,
+    /// The grid before the gesture in progress, if any
+    gesture_start: Option<GridSnapshot>,
+}
+
+impl GridHistory {
+    pub fn new() -> Self {
+        GridHistory {
+            undo: VecDeque::with_capacity(MAX_HISTORY),
+            redo: Vec::with_capacity(MAX_HISTORY),
+            gesture_start: None,
+        }
+    }
+
+    pub fn begin_gesture(&mut self, current: GridSnapshot) {
+        self.gesture_start = Some(current);
+    }
+
+    /// Records the gesture in progress, unless it didn't change anything
+    pub fn end_gesture(&mut self, current: GridSnapshot) {
+        if let Some(start) = self.gesture_start.take() {
+            if start != current {
+                self.push_undo(start);
+                self.redo.clear();
+            }
+        }
+    }
+
+    /// Returns the grid before the last gesture, and remembers the current grid for redo
+    pub fn undo(&mut self, current: GridSnapshot) -> Option<GridSnapshot> {
+        let snapshot = self.undo.pop_back()?;
+        self.redo.push(current);
+        Some(snapshot)
+    }
+
+    /// Returns the grid before the last undo, and remembers the current grid for undo
+    pub fn redo(&mut self, current: GridSnapshot) -> Option<GridSnapshot> {
+        let snapshot = self.redo.pop()?;
+        self.push_undo(current);
+        Some(snapshot)
+    }
+
+    fn push_undo(&mut self, snapshot: GridSnapshot) {
+        if self.undo.len() == MAX_HISTORY {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod grid_history_tests {
+    use crate::editor::lattice::grid_history::{GridHistory, GridSnapshot, MAX_HISTORY};
+
+    fn at_x(x: f32) -> GridSnapshot {
+        GridSnapshot {
+            x,
+            y: 0.0,
+            z: 0,
+            w: 0,
+            width: 7,
+            height: 7,
+        }
+    }
+
+    fn gesture(history: &mut GridHistory, from: f32, to: f32) {
+        history.begin_gesture(at_x(from));
+        history.end_gesture(at_x(to));
+    }
+
+    #[test]
+    fn undoes_and_redoes_gestures() {
+        let mut history = GridHistory::new();
+        gesture(&mut history, 0.0, 1.0);
+        gesture(&mut history, 1.0, 2.0);
+
+        assert_eq!(history.undo(at_x(2.0)), Some(at_x(1.0)));
+        assert_eq!(history.undo(at_x(1.0)), Some(at_x(0.0)));
+        assert_eq!(history.undo(at_x(0.0)), None);
+
+        assert_eq!(history.redo(at_x(0.0)), Some(at_x(1.0)));
+        assert_eq!(history.redo(at_x(1.0)), Some(at_x(2.0)));
+        assert_eq!(history.redo(at_x(2.0)), None);
+        assert_eq!(history.undo(at_x(2.0)), Some(at_x(1.0)));
+    }
+
+    #[test]
+    fn ignores_gestures_without_changes() {
+        let mut history = GridHistory::new();
+        gesture(&mut history, 0.0, 0.0);
+        history.end_gesture(at_x(1.0));
+        assert_eq!(history.undo(at_x(1.0)), None);
+    }
+
+    #[test]
+    fn new_gesture_clears_redo() {
+        let mut history = GridHistory::new();
+        gesture(&mut history, 0.0, 1.0);
+        history.undo(at_x(1.0));
+        gesture(&mut history, 0.0, 3.0);
+        assert_eq!(history.redo(at_x(3.0)), None);
+        assert_eq!(history.undo(at_x(3.0)), Some(at_x(0.0)));
+    }
+
+    #[test]
+    fn forgets_oldest_gestures() {
+        let mut history = GridHistory::new();
+        for i in 0..MAX_HISTORY + 5 {
+            gesture(&mut history, i as f32, i as f32 + 1.0);
+        }
+        let mut undone = Vec::new();
+        let mut current = at_x((MAX_HISTORY + 5) as f32);
+        while let Some(snapshot) = history.undo(current) {
+            undone.push(snapshot.x);
+            current = snapshot;
+        }
+        assert_eq!(undone.len(), MAX_HISTORY);
+        assert_eq!(undone.last(), Some(&5.0));
+    }
+}
@@ -1,5 +1,4 @@
 use crate::editor::color::*;
-use crate::editor::lattice::grid;
 use crate::editor::lattice::LatticeEvent;
 use crate::editor::width_to_grid_width;
 use crate::editor::*;
@@ -54,12 +53,14 @@ impl View for GridResizer {
             WindowEvent::MouseDown(MouseButton::Left) => {
                 cx.capture();
                 self.drag_active = true;
+                cx.emit(LatticeEvent::GestureStart);
             }
             WindowEvent::MouseUp(MouseButton::Left) => {
                 cx.emit(LatticeEvent::MouseUpFromChild);
                 if self.drag_active {
                     cx.release();
                     self.drag_active = false;
+                    cx.emit(LatticeEvent::GestureEnd);
                 }
             }
             WindowEvent::MouseOver => {
@@ -70,14 +71,17 @@ impl View for GridResizer {
             }
             WindowEvent::MouseMove(_x, _y) => {
                 if self.drag_active {
+                    let node_size = self.grid_params.node_size();
                     let (width, height) = (
                         width_to_grid_width(
                             (cx.mouse().cursorx / cx.scale_factor() as f32 + RIGHT_REGION_WIDTH)
-                                + grid::NODE_SIZE,
+                                + node_size,
+                            node_size,
                         ),
                         height_to_grid_height(
                             (cx.mouse().cursory / cx.scale_factor() as f32 + BOTTOM_REGION_HEIGHT)
-                                + grid::NODE_SIZE,
+                                + node_size,
+                            node_size,
                         ),
                     );
 
@@ -92,17 +96,18 @@ impl View for GridResizer {
     }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
         let scale: f32 = cx.scale_factor() as f32;
         let bounds = cx.bounds();
 
         let icon_padding: f32 = PADDING * 1.6 * scale;
 
         let color = if self.drag_active {
-            OVERLAY_COLOR_PRESS
+            colors.overlay_press
         } else if self.mouse_over && !self.lattice_mouse_down {
-            OVERLAY_COLOR_HOVER
+            colors.overlay_hover
         } else {
-            OVERLAY_COLOR_BASE
+            colors.overlay_base
         };
         let icon_paint = &make_icon_paint(color, PADDING * 2.0 * scale);
         let mut icon_path = vg::Path::new();
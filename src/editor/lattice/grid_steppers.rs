@@ -0,0 +1,228 @@
+use crate::editor::color::*;
+use crate::editor::lattice::LatticeEvent;
+use crate::editor::*;
+use crate::GridParams;
+use crate::MAX_GRID_OFFSET;
+
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::ParamEvent;
+use std::sync::{Arc, Mutex};
+
+// Grid axes that have steppers, from top to bottom
+const AXES: [&str; 3] = ["X", "Y", "Z"];
+
+// Steps of the buttons in each row, from left to right, after the axis label
+const STEPS: [i32; 2] = [-1, 1];
+
+/// A row of - and + buttons for each of the grid's X, Y and Z offsets, which move the grid by one
+/// node. Only visible when the mouse is over the lattice.
+pub struct GridSteppers {
+    grid_params: Arc<GridParams>,
+
+    // Whether something else is being dragged on the lattice
+    lattice_mouse_down: bool,
+
+    // Axis and step index of the button being pressed
+    pressed_button: Option<(usize, usize)>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl GridSteppers {
+    pub fn new<LGridParams>(cx: &mut Context, grid_params: LGridParams) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        GridSteppers {
+            grid_params: grid_params.get(cx),
+            lattice_mouse_down: false,
+            pressed_button: None,
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+
+    fn step(&self, cx: &mut EventContext, axis: usize, step: i32) {
+        let grid_params = &self.grid_params;
+        cx.emit(LatticeEvent::GestureStart);
+        match axis {
+            0 | 1 => {
+                let param = if axis == 0 {
+                    &grid_params.x
+                } else {
+                    &grid_params.y
+                };
+                cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+                cx.emit(
+                    ParamEvent::SetParameter(param, stepped_offset(param.value(), step)).upcast(),
+                );
+                cx.emit(ParamEvent::EndSetParameter(param).upcast());
+            }
+            _ => {
+                let grid_z = (grid_params.z.value() + step)
+                    .clamp(-MAX_GRID_OFFSET as i32, MAX_GRID_OFFSET as i32);
+                cx.emit(ParamEvent::BeginSetParameter(&grid_params.z).upcast());
+                cx.emit(ParamEvent::SetParameter(&grid_params.z, grid_z).upcast());
+                cx.emit(ParamEvent::EndSetParameter(&grid_params.z).upcast());
+            }
+        }
+        cx.emit(LatticeEvent::GestureEnd);
+    }
+}
+
+/// Moves a grid offset to the next whole node in the direction of the step. Offsets between
+/// nodes, e.g. after scrolling, snap to the nearest node first.
+pub fn stepped_offset(offset: f32, step: i32) -> f32 {
+    (offset.round() + step as f32).clamp(-MAX_GRID_OFFSET, MAX_GRID_OFFSET)
+}
+
+/// Bounds of a cell in the given row and column. Column 0 holds the axis label.
+fn cell_bounds(bounds: BoundingBox, row: usize, column: usize) -> BoundingBox {
+    let (w, h) = (
+        bounds.w / (STEPS.len() + 1) as f32,
+        bounds.h / AXES.len() as f32,
+    );
+    BoundingBox {
+        x: bounds.x + w * column as f32,
+        y: bounds.y + h * row as f32,
+        w,
+        h,
+    }
+}
+
+/// Axis and step index of the button under the mouse, if any
+fn button_at(bounds: BoundingBox, mouse: (f32, f32)) -> Option<(usize, usize)> {
+    (0..AXES.len())
+        .flat_map(|axis| (0..STEPS.len()).map(move |step_idx| (axis, step_idx)))
+        .find(|(axis, step_idx)| intersects_box(cell_bounds(bounds, *axis, step_idx + 1), mouse))
+}
+
+impl View for GridSteppers {
+    fn element(&self) -> Option<&'static str> {
+        Some("grid-steppers")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|lattice_event, _meta| match *lattice_event {
+            LatticeEvent::MouseOver => cx.set_visibility(Visibility::Visible),
+            LatticeEvent::MouseOut => cx.set_visibility(Visibility::Hidden),
+            LatticeEvent::MouseDown => {
+                self.lattice_mouse_down = true;
+            }
+            LatticeEvent::MouseUpToChild => {
+                self.lattice_mouse_down = false;
+            }
+            _ => {}
+        });
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.capture();
+                self.pressed_button =
+                    button_at(cx.bounds(), (cx.mouse().cursorx, cx.mouse().cursory));
+                if let Some((axis, step_idx)) = self.pressed_button {
+                    self.step(cx, axis, STEPS[step_idx]);
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                cx.emit(LatticeEvent::MouseUpFromChild);
+                cx.release();
+                self.pressed_button = None;
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = cx.bounds();
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let hovered_button = if self.lattice_mouse_down && self.pressed_button.is_none() {
+            None
+        } else {
+            button_at(bounds, (cx.mouse().cursorx, cx.mouse().cursory))
+        };
+
+        let mut text_paint = vg::Paint::color(colors.overlay_base);
+        text_paint.set_font_size(cell_bounds(bounds, 0, 0).h * 0.7);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+
+        for (axis, label) in AXES.iter().enumerate() {
+            let label_box = cell_bounds(bounds, axis, 0);
+            let _ = canvas.fill_text(
+                label_box.x + label_box.w * 0.5,
+                label_box.y + label_box.h * 0.5,
+                label,
+                &text_paint,
+            );
+
+            for (step_idx, step) in STEPS.iter().enumerate() {
+                let button = cell_bounds(bounds, axis, step_idx + 1).shrink_sides(
+                    PADDING * 0.25 * scale,
+                    PADDING * 0.25 * scale,
+                    PADDING * 0.25 * scale,
+                    PADDING * 0.25 * scale,
+                );
+                let color = if self.pressed_button == Some((axis, step_idx)) {
+                    colors.overlay_press
+                } else if hovered_button == Some((axis, step_idx)) {
+                    colors.overlay_hover
+                } else {
+                    colors.overlay_base
+                };
+
+                let mut button_path = vg::Path::new();
+                button_path.rounded_rect(
+                    button.x,
+                    button.y,
+                    button.w,
+                    button.h,
+                    CORNER_RADIUS * scale,
+                );
+                let mut outline_paint = vg::Paint::color(color);
+                outline_paint.set_line_width(scale);
+                canvas.stroke_path(&mut button_path, &outline_paint);
+
+                // A minus sign, with a vertical bar through it for plus
+                let (center_x, center_y) = (button.x + button.w * 0.5, button.y + button.h * 0.5);
+                let arm = button.w.min(button.h) * 0.25;
+                let mut icon_path = vg::Path::new();
+                icon_path.move_to(center_x - arm, center_y);
+                icon_path.line_to(center_x + arm, center_y);
+                if *step > 0 {
+                    icon_path.move_to(center_x, center_y - arm);
+                    icon_path.line_to(center_x, center_y + arm);
+                }
+                canvas.stroke_path(&mut icon_path, &make_icon_paint(color, 1.5 * scale));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod stepped_offset_tests {
+    use crate::editor::lattice::grid_steppers::stepped_offset;
+    use crate::MAX_GRID_OFFSET;
+
+    #[test]
+    fn test_steps_from_whole_nodes() {
+        assert_eq!(stepped_offset(0.0, 1), 1.0);
+        assert_eq!(stepped_offset(3.0, -1), 2.0);
+    }
+
+    #[test]
+    fn test_snaps_to_nearest_node_first() {
+        assert_eq!(stepped_offset(2.3, 1), 3.0);
+        assert_eq!(stepped_offset(2.7, -1), 2.0);
+        assert_eq!(stepped_offset(-1.6, 1), -1.0);
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_eq!(stepped_offset(MAX_GRID_OFFSET, 1), MAX_GRID_OFFSET);
+        assert_eq!(stepped_offset(-MAX_GRID_OFFSET, -1), -MAX_GRID_OFFSET);
+    }
+}
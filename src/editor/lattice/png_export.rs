@@ -0,0 +1,157 @@
+use crate::editor::lattice::grid::{DrawGridArgs, Grid, GridEvent};
+use crate::editor::spawn_file_dialog;
+use crate::editor::status_message::StatusEvent;
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+
+/// Pixels of a PNG image of the grid, or why they couldn't be rendered, and where to save them
+pub struct RenderedPng {
+    path: PathBuf,
+    pixels: Result<(u32, u32, Vec<u8>), String>,
+}
+
+/// Saves images sent from the grid's draws until the grid, and its sender, are dropped. The
+/// status is updated as each one is written, or if writing failed.
+pub fn save_rendered_pngs(cx: &mut Context, receiver: Receiver<RenderedPng>) {
+    cx.spawn(move |cx_proxy| {
+        for rendered in receiver {
+            let status = match rendered
+                .pixels
+                .and_then(|(width, height, rgba)| write_png(&rendered.path, (width, height), &rgba))
+            {
+                Ok(()) => StatusEvent::Clear,
+                Err(message) => StatusEvent::Show(format!("Couldn't export lattice: {}", message)),
+            };
+            if cx_proxy.emit(status).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+// Helper methods for PNG export
+impl Grid {
+    /// Asks where to save the grid as a PNG image, at its scale in the grid params, without
+    /// blocking the editor. The image is rendered on the next draw once a path is chosen.
+    pub fn choose_png_path(&self, cx: &mut EventContext) {
+        let scale = self.params.grid_params.png_scale() as f32;
+        spawn_file_dialog(cx, move || {
+            rfd::FileDialog::new()
+                .add_filter("PNG image", &["png"])
+                .set_file_name("midi_lattice.png")
+                .save_file()
+                .map(|path| GridEvent::PngPathChosen(path, scale))
+        });
+    }
+
+    /// Renders the PNG image waiting to be exported, if any, and sends it to be saved
+    pub fn export_pending_png(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        if let Some((path, png_scale)) = self.png_export.lock().unwrap().take() {
+            let pixels = self.render_png(canvas, cx.bounds(), cx.scale_factor(), png_scale);
+            let _ = self
+                .png_sender
+                .lock()
+                .unwrap()
+                .send(RenderedPng { path, pixels });
+        }
+    }
+
+    /// Renders the grid into an offscreen image, at `png_scale` pixels per logical pixel whatever
+    /// the window's scale, and reads back its width, height and RGBA pixels
+    fn render_png(
+        &self,
+        canvas: &mut Canvas,
+        bounds: BoundingBox,
+        scale_factor: f32,
+        png_scale: f32,
+    ) -> Result<(u32, u32, Vec<u8>), String> {
+        let (width, height) = png_size(bounds, scale_factor, png_scale);
+        let args = DrawGridArgs::new(
+            self,
+            canvas,
+            BoundingBox {
+                x: 0.0,
+                y: 0.0,
+                w: width as f32,
+                h: height as f32,
+            },
+            png_scale,
+        );
+        let image = canvas
+            .create_image_empty(
+                width as usize,
+                height as usize,
+                vg::PixelFormat::Rgba8,
+                vg::ImageFlags::empty(),
+            )
+            .map_err(|e| format!("{:?}", e))?;
+
+        // Drawn without the window's transform and scissor
+        canvas.save();
+        canvas.reset();
+        canvas.set_render_target(vg::RenderTarget::Image(image));
+        canvas.clear_rect(0, 0, width, height, args.theme_colors.background);
+        self.draw_lattice(canvas, &args);
+        let screenshot = canvas.screenshot();
+        canvas.set_render_target(vg::RenderTarget::Screen);
+        canvas.restore();
+        canvas.delete_image(image);
+
+        let screenshot = screenshot.map_err(|e| format!("{:?}", e))?;
+        let rgba = screenshot
+            .as_ref()
+            .pixels()
+            .flat_map(|pixel| [pixel.r, pixel.g, pixel.b, pixel.a])
+            .collect();
+        Ok((width, height, rgba))
+    }
+}
+
+/// Size in pixels of a PNG image of a grid with the given physical bounds
+fn png_size(bounds: BoundingBox, scale_factor: f32, png_scale: f32) -> (u32, u32) {
+    let pixels = |physical: f32| ((physical / scale_factor * png_scale).round() as u32).max(1);
+    (pixels(bounds.w), pixels(bounds.h))
+}
+
+#[cfg(test)]
+mod png_size_tests {
+    use crate::editor::lattice::png_export::png_size;
+    use nih_plug_vizia::vizia::prelude::BoundingBox;
+
+    #[test]
+    fn test_ignores_window_scale() {
+        let logical = BoundingBox {
+            x: 10.0,
+            y: 10.0,
+            w: 300.0,
+            h: 200.0,
+        };
+        let physical = BoundingBox {
+            x: 15.0,
+            y: 15.0,
+            w: 450.0,
+            h: 300.0,
+        };
+        assert_eq!(png_size(logical, 1.0, 2.0), (600, 400));
+        assert_eq!(png_size(physical, 1.5, 2.0), (600, 400));
+        assert_eq!(png_size(physical, 1.5, 1.0), (300, 200));
+    }
+}
+
+/// Encodes RGBA pixels as a PNG image, and saves it
+fn write_png(path: &Path, (width, height): (u32, u32), rgba: &[u8]) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| {
+            writer.write_image_data(rgba)?;
+            writer.finish()
+        })
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,126 @@
+use crate::editor::lattice::grid::{
+    draw_top_left_text_box, overlay_text_paint, DrawGridArgs, Voice,
+};
+use crate::tuning::{PitchClass, PitchClassDistance};
+
+use nih_plug_vizia::vizia::prelude::*;
+
+/// Shows the interval between the last two notes struck in the top left corner of the grid, under
+/// the chord if that's shown
+pub fn draw_last_interval(canvas: &mut Canvas, args: &DrawGridArgs, interval: PitchClassDistance) {
+    draw_top_left_text_box(
+        canvas,
+        args,
+        if args.show_chord { 1 } else { 0 },
+        &last_interval_str(interval),
+        &overlay_text_paint(args, false),
+    );
+}
+
+/// Formats the interval between two notes in cents, like "Last interval 386.3¢"
+fn last_interval_str(interval: PitchClassDistance) -> String {
+    format!("Last interval {:.1}¢", interval.to_cents_f32())
+}
+
+/// Pitch classes of the last two notes struck, which are remembered after they're released
+#[derive(Default)]
+pub struct RecentNotes {
+    /// Channel, key and note on order of each voice sounding at the last update, to tell which
+    /// voices are new
+    sounding: Vec<(u8, u8, u64)>,
+    /// Most recent last
+    last_two: [Option<PitchClass>; 2],
+}
+
+impl RecentNotes {
+    /// Adds the voices that weren't sounding at the last update, in the order they were struck
+    pub fn update(&mut self, voices: &[Voice]) {
+        let note_on_id = |voice: &Voice| (voice.channel, voice.note, voice.note_on_order);
+        let mut struck: Vec<&Voice> = voices
+            .iter()
+            .filter(|voice| !self.sounding.contains(&note_on_id(voice)))
+            .collect();
+        struck.sort_by_key(|voice| voice.note_on_order);
+        for voice in struck {
+            self.last_two = [self.last_two[1], Some(voice.get_pitch_class())];
+        }
+        self.sounding = voices.iter().map(note_on_id).collect();
+    }
+
+    /// Distance between the pitch classes of the last two notes struck
+    pub fn last_interval(&self) -> Option<PitchClassDistance> {
+        match self.last_two {
+            [Some(previous), Some(last)] => Some(previous.distance_to(last)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod recent_notes_tests {
+    use crate::{
+        editor::lattice::grid::Voice,
+        editor::lattice::recent_notes::{last_interval_str, RecentNotes},
+        tuning::{PitchClass, PitchClassDistance},
+    };
+
+    fn voice(note: u8, note_on_order: u64, cents: f32) -> Voice {
+        // Struct update syntax needs every field of `Voice` to be visible here, so set them
+        let mut voice = Voice::new(0, note as f32, PitchClass::from_cents_f32(cents));
+        voice.note = note;
+        voice.note_on_order = note_on_order;
+        voice
+    }
+
+    fn assert_last_interval(recent_notes: &RecentNotes, cents: f32) {
+        assert_eq!(
+            recent_notes.last_interval(),
+            Some(PitchClassDistance::from_cents_f32(cents))
+        );
+    }
+
+    #[test]
+    fn needs_two_notes() {
+        let mut recent_notes = RecentNotes::default();
+        assert_eq!(recent_notes.last_interval(), None);
+        recent_notes.update(&[voice(60, 0, 0.0)]);
+        assert_eq!(recent_notes.last_interval(), None);
+    }
+
+    #[test]
+    fn remembers_released_notes() {
+        let mut recent_notes = RecentNotes::default();
+        recent_notes.update(&[voice(60, 0, 0.0)]);
+        recent_notes.update(&[]);
+        recent_notes.update(&[voice(64, 0, 386.3)]);
+        assert_last_interval(&recent_notes, 386.3);
+        // Held notes aren't struck again
+        recent_notes.update(&[voice(64, 0, 386.3)]);
+        assert_last_interval(&recent_notes, 386.3);
+    }
+
+    #[test]
+    fn orders_notes_struck_together() {
+        let mut recent_notes = RecentNotes::default();
+        recent_notes.update(&[voice(60, 0, 0.0)]);
+        // Voices come sorted by pitch class, not by when they were struck
+        recent_notes.update(&[voice(60, 0, 0.0), voice(62, 2, 203.9), voice(67, 1, 702.0)]);
+        assert_last_interval(&recent_notes, 702.0 - 203.9);
+    }
+
+    #[test]
+    fn counts_restruck_keys() {
+        let mut recent_notes = RecentNotes::default();
+        recent_notes.update(&[voice(60, 0, 0.0), voice(67, 1, 702.0)]);
+        recent_notes.update(&[voice(67, 1, 702.0), voice(60, 2, 0.0)]);
+        assert_last_interval(&recent_notes, 702.0);
+    }
+
+    #[test]
+    fn formats_cents() {
+        assert_eq!(
+            last_interval_str(PitchClassDistance::from_cents_f32(386.31)),
+            "Last interval 386.3¢"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use crate::editor::lattice::grid::{
+    corner_cutout, main_node_texts, mini_node_bounds, mini_node_texts, node_groups, stripes,
+    DrawGridArgs, DrawNodeArgs, Grid, NodeFont, NodeText,
+};
+use crate::editor::spawn_file_dialog;
+use crate::editor::status_message::StatusEvent;
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+// Helper methods for SVG export
+impl Grid {
+    /// Asks where to save the grid as an SVG image, and writes it, without blocking the editor.
+    /// The status is updated once it's written, or if writing failed.
+    pub fn export_svg(&self, cx: &mut EventContext) {
+        // Render before asking, so the image shows the grid as it was when export was clicked
+        let svg = grid_svg(&DrawGridArgs::for_bounds(
+            self,
+            cx.bounds(),
+            cx.scale_factor(),
+        ));
+        spawn_file_dialog(cx, move || {
+            let path = rfd::FileDialog::new()
+                .add_filter("SVG image", &["svg"])
+                .set_file_name("midi_lattice.svg")
+                .save_file()?;
+            Some(match std::fs::write(path, svg) {
+                Ok(()) => StatusEvent::Clear,
+                Err(e) => StatusEvent::Show(format!("Couldn't export lattice: {}", e)),
+            })
+        });
+    }
+}
+
+/// Renders the grid's nodes as an SVG document, with the same shapes, colors and text as the
+/// canvas. Edges and the text boxes over the grid are left out.
+fn grid_svg(args: &DrawGridArgs) -> String {
+    let bounds = args.bounds;
+    let bounds_attributes = format!(
+        "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"",
+        bounds.x, bounds.y, bounds.w, bounds.h
+    );
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         viewBox=\"{} {} {} {}\">\n",
+        bounds.w, bounds.h, bounds.x, bounds.y, bounds.w, bounds.h
+    );
+    svg += &format!(
+        "<rect {} {}/>\n",
+        bounds_attributes,
+        svg_paint("fill", args.theme_colors.background)
+    );
+
+    // Nodes stick out of the grid when it's partially scrolled
+    svg += &format!(
+        "<clipPath id=\"grid\"><rect {}/></clipPath>\n<g clip-path=\"url(#grid)\">\n",
+        bounds_attributes
+    );
+    let mut num_clip_paths = 0;
+    for group in node_groups(args) {
+        let (node_args, mini_node_args) = (&group.node, &group.mini_nodes);
+        let main_node = BoundingBox {
+            x: node_args.draw_node_x,
+            y: node_args.draw_node_y,
+            w: node_args.draw_node_size.0,
+            h: node_args.draw_node_size.1,
+        };
+        svg_node(&mut svg, args, node_args, main_node, &mut num_clip_paths);
+
+        let [pos_z, neg_z, pos_w, neg_w] = mini_node_args;
+        svg_texts(
+            &mut svg,
+            args,
+            &main_node_texts(
+                args,
+                node_args,
+                (pos_z.draw, neg_z.draw),
+                (pos_w.draw, neg_w.draw),
+            ),
+        );
+        for mini_node_args in mini_node_args {
+            if let (Some(corner), Some(mini_node)) = (
+                mini_node_args.mini_node_corner(),
+                mini_node_bounds(args, mini_node_args),
+            ) {
+                let cutout = corner_cutout(args, node_args, corner);
+                svg += &format!(
+                    "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>\n",
+                    cutout.x,
+                    cutout.y,
+                    cutout.w,
+                    cutout.h,
+                    svg_paint("fill", args.theme_colors.background)
+                );
+                svg_node(
+                    &mut svg,
+                    args,
+                    mini_node_args,
+                    mini_node,
+                    &mut num_clip_paths,
+                );
+                svg_texts(
+                    &mut svg,
+                    args,
+                    &mini_node_texts(args, mini_node_args, mini_node),
+                );
+            }
+        }
+    }
+    svg += "</g>\n</svg>\n";
+    svg
+}
+
+/// Adds a node's background, colors and outline to an SVG document. Main nodes have more
+/// stripes than mini nodes, and flash when struck.
+fn svg_node(
+    svg: &mut String,
+    args: &DrawGridArgs,
+    node_args: &DrawNodeArgs,
+    node: BoundingBox,
+    num_clip_paths: &mut usize,
+) {
+    let is_main_node = node_args.mini_node_corner().is_none();
+    let rect = format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"{}\"",
+        node.x, node.y, node.w, node.h, args.scaled_corner_radius
+    );
+    if node_args.colors.is_empty() || node_args.fading {
+        let background = if node_args.highlighted {
+            args.theme_colors.highlight
+        } else {
+            args.theme_colors.base
+        };
+        *svg += &format!("{} {}/>\n", rect, svg_paint("fill", background));
+    }
+    if let Some(color) = node_args.colors.first() {
+        *svg += &format!("{} {}/>\n", rect, svg_paint("fill", *color));
+    }
+
+    let half_num_stripes = if is_main_node {
+        (node_args.colors.len() * 3) as u8
+    } else {
+        3
+    };
+    let stripes = stripes(
+        (node.x, node.y, node.w),
+        half_num_stripes,
+        node_args.colors.len(),
+    );
+    if !stripes.is_empty() {
+        // Stripes are cut off at the node's rounded corners
+        *num_clip_paths += 1;
+        *svg += &format!(
+            "<clipPath id=\"node{}\">{}/></clipPath>\n<g clip-path=\"url(#node{})\">\n",
+            num_clip_paths, rect, num_clip_paths
+        );
+        for (color_idx, corners) in stripes {
+            let points: Vec<String> = corners
+                .iter()
+                .map(|(x, y)| format!("{},{}", x, y))
+                .collect();
+            *svg += &format!(
+                "<polygon points=\"{}\" {}/>\n",
+                points.join(" "),
+                svg_paint("fill", node_args.colors[color_idx])
+            );
+        }
+        *svg += "</g>\n";
+    }
+
+    if is_main_node && node_args.struck {
+        let mut flash_color = args.theme_colors.text;
+        flash_color.set_alphaf(0.3);
+        *svg += &format!("{} {}/>\n", rect, svg_paint("fill", flash_color));
+    }
+    if node_args.draw_outline {
+        *svg += &format!(
+            "{} fill=\"none\" {} stroke-width=\"{}\"/>\n",
+            rect,
+            svg_paint("stroke", args.theme_colors.text),
+            node_args.outline_width
+        );
+    }
+}
+
+/// Adds lines of node text to an SVG document
+fn svg_texts(svg: &mut String, args: &DrawGridArgs, texts: &[NodeText]) {
+    for node_text in texts.iter().filter(|node_text| !node_text.text.is_empty()) {
+        let font_family = match node_text.font {
+            NodeFont::Regular => "Roboto",
+            NodeFont::Mono => "'Roboto Mono'",
+            NodeFont::Accidentals => "'Roboto Mono', 'DejaVu Sans'",
+        };
+        let text_anchor = match node_text.align {
+            vg::Align::Left => "start",
+            vg::Align::Center => "middle",
+            vg::Align::Right => "end",
+        };
+        *svg += &format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"{}\" text-anchor=\"{}\" {}>{}\
+             </text>\n",
+            node_text.x,
+            node_text.y,
+            node_text.font_size,
+            font_family,
+            text_anchor,
+            svg_paint("fill", args.theme_colors.text),
+            escape_xml(&node_text.text)
+        );
+    }
+}
+
+/// SVG attributes painting a fill or stroke in a color, like `fill="#ff8000"`. Translucent
+/// colors get an opacity attribute too.
+fn svg_paint(attribute: &str, color: vg::Color) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let paint = format!(
+        "{}=\"#{:02x}{:02x}{:02x}\"",
+        attribute,
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    );
+    if color.a < 1.0 {
+        format!("{} {}-opacity=\"{}\"", paint, attribute, color.a)
+    } else {
+        paint
+    }
+}
+
+#[cfg(test)]
+mod svg_paint_tests {
+    use crate::editor::lattice::svg_export::svg_paint;
+    use nih_plug_vizia::vizia::vg;
+
+    #[test]
+    fn test_opaque_colors() {
+        assert_eq!(
+            svg_paint("fill", vg::Color::rgb(255, 128, 0)),
+            "fill=\"#ff8000\""
+        );
+    }
+
+    #[test]
+    fn test_translucent_colors() {
+        assert_eq!(
+            svg_paint("stroke", vg::Color::rgbaf(0.0, 0.0, 1.0, 0.5)),
+            "stroke=\"#0000ff\" stroke-opacity=\"0.5\""
+        );
+    }
+}
+
+/// Escapes characters that would be read as markup in SVG text, like the "<" of septimal commas
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod escape_xml_tests {
+    use crate::editor::lattice::svg_export::escape_xml;
+
+    #[test]
+    fn test_escapes_markup() {
+        assert_eq!(escape_xml("Bb<"), "Bb&lt;");
+        assert_eq!(escape_xml("F#>"), "F#&gt;");
+        assert_eq!(escape_xml("a & b"), "a &amp; b");
+    }
+
+    #[test]
+    fn test_leaves_other_text() {
+        assert_eq!(escape_xml("E-"), "E-");
+        assert_eq!(escape_xml("701.96"), "701.96");
+    }
+}
@@ -0,0 +1,92 @@
+use crate::editor::lattice::grid::{
+    overlay_text_paint, round_cents, DrawGridArgs, OUTLINE_PADDING_RATIO, TOOLTIP_FONT_SIZE_RATIO,
+};
+use crate::editor::make_icon_paint;
+use crate::tuning::PrimeCountVector;
+use crate::Accidentals;
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+/// Draws a box next to the cursor with a node's full note name, prime factorization, just
+/// intonation ratio, cents and frequency, kept inside the grid
+pub fn draw_tooltip(
+    canvas: &mut Canvas,
+    args: &DrawGridArgs,
+    primes: &PrimeCountVector,
+    (cursor_x, cursor_y): (f32, f32),
+) {
+    let heji = args.accidentals == Accidentals::Heji;
+    let note_name_info = primes.note_name_info(args.root_fifths);
+    let mut lines = vec![
+        if heji {
+            note_name_info.to_heji_string()
+        } else {
+            note_name_info.to_string()
+        },
+        primes.factorization_str(),
+    ];
+    if let Some((numerator, denominator)) = primes.ratio() {
+        lines.push(format!("{}/{}", numerator, denominator));
+    }
+    let cents = args.tuning.pitch_class(primes).to_cents_f64();
+    let ten_thousandths = round_cents(cents, 10000.0);
+    lines.push(format!(
+        "{}.{:04} cents",
+        ten_thousandths / 10000,
+        ten_thousandths % 10000
+    ));
+    let frequency = args.tuning.pitch_class(primes).to_frequency_in_octave(
+        args.c_frequency,
+        args.octave,
+        args.frequency_octave,
+    );
+    lines.push(format!("{:.2} Hz", frequency));
+
+    let text_paint = overlay_text_paint(args, heji);
+    let line_height = args.scaled_node_size * TOOLTIP_FONT_SIZE_RATIO * 1.2;
+    let text_width = lines
+        .iter()
+        .filter_map(|line| canvas.measure_text(0.0, 0.0, line, &text_paint).ok())
+        .map(|metrics| metrics.width())
+        .fold(0.0, f32::max);
+    let (width, height) = (
+        text_width + args.scaled_padding * 2.0,
+        line_height * lines.len() as f32 + args.scaled_padding * 2.0,
+    );
+
+    // Below and to the right of the cursor, unless that doesn't fit
+    let offset = args.scaled_padding * 3.0;
+    let mut x = cursor_x + offset;
+    if x + width > args.bounds.x + args.bounds.w {
+        x = cursor_x - offset - width;
+    }
+    let mut y = cursor_y + offset;
+    if y + height > args.bounds.y + args.bounds.h {
+        y = cursor_y - offset - height;
+    }
+    let (x, y) = (x.max(args.bounds.x), y.max(args.bounds.y));
+
+    let mut box_path = vg::Path::new();
+    box_path.rounded_rect(x, y, width, height, args.scaled_corner_radius);
+    canvas.fill_path(
+        &mut box_path,
+        &vg::Paint::color(args.theme_colors.background),
+    );
+    canvas.stroke_path(
+        &mut box_path,
+        &make_icon_paint(
+            args.theme_colors.text,
+            args.scaled_padding * OUTLINE_PADDING_RATIO,
+        ),
+    );
+
+    for (line_idx, line) in lines.iter().enumerate() {
+        let _ = canvas.fill_text(
+            x + args.scaled_padding,
+            y + args.scaled_padding + line_height * line_idx as f32,
+            line,
+            &text_paint,
+        );
+    }
+}
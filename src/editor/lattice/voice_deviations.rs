@@ -0,0 +1,185 @@
+use crate::editor::lattice::grid::{
+    nearest_pitch_class, overlay_text_paint, DrawGridArgs, Voice, TOOLTIP_FONT_SIZE_RATIO,
+};
+use crate::tuning::{PitchClass, PitchClassDistance};
+use crate::Accidentals;
+use crate::VoiceDeviations;
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+
+/// Lists sounding notes with their deviation from the closest visible node, worst first, in the
+/// bottom left corner of the grid
+pub fn draw_voice_deviations(
+    canvas: &mut Canvas,
+    args: &DrawGridArgs,
+    visible_pitch_classes: &Vec<PitchClass>,
+) {
+    let heji = args.accidentals == Accidentals::Heji;
+    let lines = voice_deviation_lines(
+        &args.sorted_voices,
+        visible_pitch_classes,
+        args.tuning_tolerance,
+        args.voice_deviations == VoiceDeviations::All,
+        heji,
+    );
+    if lines.is_empty() {
+        return;
+    }
+
+    let text_paint = overlay_text_paint(args, heji);
+
+    let line_height = args.scaled_node_size * TOOLTIP_FONT_SIZE_RATIO * 1.2;
+    let text_width = lines
+        .iter()
+        .filter_map(|line| canvas.measure_text(0.0, 0.0, line, &text_paint).ok())
+        .map(|metrics| metrics.width())
+        .fold(0.0, f32::max);
+    let (width, height) = (
+        text_width + args.scaled_padding * 2.0,
+        line_height * lines.len() as f32 + args.scaled_padding * 2.0,
+    );
+    let (x, y) = (
+        args.bounds.x + args.scaled_padding * 2.0,
+        args.bounds.y + args.bounds.h - args.scaled_padding * 2.0 - height,
+    );
+
+    let mut box_path = vg::Path::new();
+    box_path.rounded_rect(x, y, width, height, args.scaled_corner_radius);
+    canvas.fill_path(
+        &mut box_path,
+        &vg::Paint::color(args.theme_colors.background),
+    );
+
+    for (line_idx, line) in lines.iter().enumerate() {
+        let _ = canvas.fill_text(
+            x + args.scaled_padding,
+            y + args.scaled_padding + line_height * line_idx as f32,
+            line,
+            &text_paint,
+        );
+    }
+}
+
+// Most voices listed with their deviations, so long chords don't cover the grid
+const MAX_DEVIATION_LINES: usize = 4;
+
+/// Describes voices by note name and signed cents from the closest of the sorted pitch classes,
+/// like "E4 -13.7¢", worst first. Voices within the tuning tolerance of a pitch class are only
+/// included if `include_matched` is set. Note names use HEJI's sharp and flat symbols if `heji`
+/// is set.
+fn voice_deviation_lines(
+    voices: &[Voice],
+    sorted_pitch_classes: &Vec<PitchClass>,
+    tuning_tolerance: PitchClassDistance,
+    include_matched: bool,
+    heji: bool,
+) -> Vec<String> {
+    let mut deviations: Vec<(&Voice, f32)> = voices
+        .iter()
+        .filter_map(|voice| {
+            let (nearest, deviation) =
+                nearest_pitch_class(voice.get_pitch_class(), sorted_pitch_classes)?;
+            let matched = nearest.distance_to(voice.get_pitch_class()) <= tuning_tolerance;
+            (include_matched || !matched).then_some((voice, deviation))
+        })
+        .collect();
+    deviations.sort_by(|(_, d1), (_, d2)| d2.abs().total_cmp(&d1.abs()));
+    deviations
+        .into_iter()
+        .take(MAX_DEVIATION_LINES)
+        .map(|(voice, deviation)| {
+            let pitch_class = voice.get_pitch_class();
+            format!(
+                "{}{} {:+.1}¢",
+                if heji {
+                    pitch_class.twelve_tet_heji_name()
+                } else {
+                    pitch_class.twelve_tet_name()
+                },
+                (voice.get_pitch().round() as i32).div_euclid(12) - 1,
+                deviation
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod voice_deviation_lines_tests {
+    use crate::{
+        editor::lattice::grid::Voice,
+        editor::lattice::voice_deviations::voice_deviation_lines,
+        tuning::{PitchClass, PitchClassDistance},
+    };
+
+    fn voice(pitch: f32) -> Voice {
+        Voice::new(0, pitch, PitchClass::from_midi_note_offset_f32(pitch))
+    }
+
+    #[test]
+    fn lists_unmatched_voices_worst_first() {
+        let pitch_classes = vec![
+            PitchClass::from_cents_f32(0.0),
+            PitchClass::from_cents_f32(386.0),
+            PitchClass::from_cents_f32(702.0),
+        ];
+        let voices = [voice(60.0), voice(64.0), voice(67.3)];
+        assert_eq!(
+            voice_deviation_lines(
+                &voices,
+                &pitch_classes,
+                PitchClassDistance::from_cents(5),
+                false,
+                false
+            ),
+            vec!["G4 +28.0¢", "E4 +14.0¢"]
+        );
+        assert_eq!(
+            voice_deviation_lines(
+                &voices,
+                &pitch_classes,
+                PitchClassDistance::from_cents(5),
+                true,
+                false
+            ),
+            vec!["G4 +28.0¢", "E4 +14.0¢", "C4 +0.0¢"]
+        );
+    }
+
+    #[test]
+    fn keeps_only_the_worst_voices() {
+        let pitch_classes = vec![PitchClass::from_cents_f32(0.0)];
+        let voices = [
+            voice(60.1),
+            voice(60.2),
+            voice(60.3),
+            voice(59.6),
+            voice(59.85),
+        ];
+        assert_eq!(
+            voice_deviation_lines(
+                &voices,
+                &pitch_classes,
+                PitchClassDistance::from_cents(5),
+                false,
+                false
+            ),
+            vec!["C4 -40.0¢", "C4 +30.0¢", "C4 +20.0¢", "C4 -15.0¢"]
+        );
+    }
+
+    #[test]
+    fn names_notes_with_heji_symbols() {
+        let pitch_classes = vec![PitchClass::from_cents_f32(0.0)];
+        assert_eq!(
+            voice_deviation_lines(
+                &[voice(70.2)],
+                &pitch_classes,
+                PitchClassDistance::from_cents(5),
+                false,
+                true
+            ),
+            vec!["B♭4 -180.0¢"]
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, make_icon_paint, snap_bounds, FontInfo, CORNER_RADIUS};
+use crate::{GridParams, TuningParams};
+
+use super::PADDING;
+
+// Tunings in the order tuning learn numbers them
+const LABELS: [&str; 5] = ["C", "3", "5", "7", "11"];
+
+/// A toggle for each tuning that tuning learn sets, to lock it against learning. Locked toggles
+/// are filled and show a padlock.
+pub struct LearnLockToggles {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl LearnLockToggles {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// Bounds of a tuning's toggle, with padding between toggles
+fn toggle_bounds(bounds: BoundingBox, tuning_idx: usize, padding: f32) -> BoundingBox {
+    let columns = LABELS.len();
+    let w = (bounds.w - padding * (columns - 1) as f32) / columns as f32;
+    BoundingBox {
+        x: bounds.x + (w + padding) * tuning_idx as f32,
+        y: bounds.y,
+        w,
+        h: bounds.h,
+    }
+}
+
+impl View for LearnLockToggles {
+    fn element(&self) -> Option<&'static str> {
+        Some("learn-lock-toggles")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        let padding = PADDING * 0.5 * cx.scale_factor();
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                if let Some(tuning_idx) = (0..LABELS.len())
+                    .find(|idx| intersects_box(toggle_bounds(cx.bounds(), *idx, padding), mouse))
+                {
+                    self.tuning_params.toggle_learn_lock(tuning_idx);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let padding = PADDING * 0.5 * scale;
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        for (tuning_idx, label) in LABELS.iter().enumerate() {
+            let toggle = snap_bounds(toggle_bounds(cx.bounds(), tuning_idx, padding), scale);
+            let locked = self.tuning_params.learn_locked(tuning_idx);
+
+            let mut toggle_path = vg::Path::new();
+            toggle_path.rounded_rect(
+                toggle.x,
+                toggle.y,
+                toggle.w,
+                toggle.h,
+                CORNER_RADIUS * scale,
+            );
+            canvas.fill_path(
+                &mut toggle_path,
+                &vg::Paint::color(if locked {
+                    colors.text
+                } else if intersects_box(toggle, mouse) {
+                    colors.highlight
+                } else {
+                    colors.base
+                }),
+            );
+
+            let foreground = if locked {
+                colors.background
+            } else {
+                colors.text
+            };
+            let mut text_paint = vg::Paint::color(foreground);
+            text_paint.set_font_size(toggle.w * if label.len() > 1 { 0.6 } else { 0.8 });
+            text_paint.set_text_baseline(vg::Baseline::Middle);
+            text_paint.set_text_align(vg::Align::Center);
+            font_id.map(|f| text_paint.set_font(&[f]));
+            let _ = canvas.fill_text(
+                toggle.x + toggle.w * 0.5,
+                toggle.y + toggle.h * 0.3,
+                label,
+                &text_paint,
+            );
+
+            // A closed padlock under the label - a body, and a shackle that goes into it
+            if locked {
+                let (left, right) = (toggle.x + toggle.w * 0.25, toggle.x + toggle.w * 0.75);
+                let (top, bottom) = (toggle.y + toggle.h * 0.55, toggle.y + toggle.h * 0.85);
+                let body_top = top + (bottom - top) * 0.45;
+                let shackle_radius = (right - left) * 0.3;
+                let shackle_x = left + (right - left) * 0.5;
+
+                let mut icon_path = vg::Path::new();
+                icon_path.rect(left, body_top, right - left, bottom - body_top);
+                icon_path.move_to(shackle_x - shackle_radius, body_top);
+                icon_path.line_to(shackle_x - shackle_radius, top + shackle_radius);
+                icon_path.arc(
+                    shackle_x,
+                    top + shackle_radius,
+                    shackle_radius,
+                    std::f32::consts::PI,
+                    0.0,
+                    vg::Solidity::Hole,
+                );
+                icon_path.line_to(shackle_x + shackle_radius, body_top);
+
+                canvas.stroke_path(&mut icon_path, &make_icon_paint(foreground, scale));
+            }
+        }
+    }
+}
@@ -1,19 +1,37 @@
-use crate::midi::MidiVoice;
-use crate::GridParams;
+use crate::midi::{ChannelRole, MidiVoice};
+use crate::tuning::PrimeCountVector;
+use crate::MidiLatticeParams;
+use crate::MAX_GRID_OFFSET;
 
 use crate::Voices;
 
+use nih_plug::prelude::*;
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::ParamEvent;
 use std::sync::{Arc, Mutex};
 use triple_buffer::Output;
 
 use crate::editor::color::*;
 
-use crate::editor::CORNER_RADIUS;
+use crate::editor::lattice::grid::lattice_tuning;
+use crate::editor::{snap, snap_line_position, snap_line_width, CORNER_RADIUS, PADDING};
 
+const MIN_PITCH: f32 = 60.0 - 12.0 * 3.0;
+const MAX_PITCH: f32 = 60.0 + 12.0 * 3.0;
+
+// Most factors of 3, 5 and 7 in total that a located node can have
+const LOCATE_SEARCH_RADIUS: i32 = 4;
+
+// Width of each note's line in logical pixels. Soft notes get thinner lines when velocity affects
+// their appearance, down to the minimum.
+const LINE_WIDTH: f32 = 1.5;
+const MIN_LINE_WIDTH: f32 = 0.75;
+
+/// Shows sounding notes as lines by pitch. Clicking a line moves the lattice to center the node
+/// closest to its note.
 pub struct NoteSpectrum {
-    params: Arc<GridParams>,
+    params: Arc<MidiLatticeParams>,
     voices_output: Arc<Mutex<Output<Voices>>>,
 }
 
@@ -24,7 +42,7 @@ impl NoteSpectrum {
         voices_output: LVoices,
     ) -> Handle<Self>
     where
-        LParams: Lens<Target = Arc<GridParams>>,
+        LParams: Lens<Target = Arc<MidiLatticeParams>>,
         LVoices: Lens<Target = Arc<Mutex<Output<Voices>>>>,
     {
         Self {
@@ -33,6 +51,54 @@ impl NoteSpectrum {
         }
         .build(cx, |_cx| {})
     }
+
+    /// Moves the grid so that the node closest to a voice's pitch class is in the center
+    fn locate(&self, cx: &mut EventContext, voice: &MidiVoice) {
+        let tuning = lattice_tuning(&self.params);
+        let (primes, _) = PrimeCountVector::nearest(
+            voice.get_pitch_class() - tuning.c_offset,
+            tuning.three,
+            tuning.five,
+            tuning.seven,
+            LOCATE_SEARCH_RADIUS,
+        );
+
+        let grid_params = &self.params.grid_params;
+        for (param, value) in [
+            (&grid_params.x, primes.fives as f32),
+            (&grid_params.y, primes.threes as f32),
+        ] {
+            let value = value.clamp(-MAX_GRID_OFFSET, MAX_GRID_OFFSET);
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, value).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+        // Located nodes have no elevens, so they're on the W = 0 plane
+        for (param, value) in [(&grid_params.z, primes.sevens), (&grid_params.w, 0)] {
+            let value = value.clamp(-MAX_GRID_OFFSET as i32, MAX_GRID_OFFSET as i32);
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, value).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+    }
+
+    /// Returns the visible voices, without hidden channels
+    fn visible_voices(&self) -> Vec<MidiVoice> {
+        let mut voices_output = self.voices_output.lock().unwrap();
+        let voices: Vec<MidiVoice> = voices_output.read().values().cloned().collect();
+        std::mem::drop(voices_output);
+        let channel_roles = *self.params.channel_roles.read().unwrap();
+        voices
+            .into_iter()
+            .filter(|voice| channel_roles.role(voice.get_channel()) != ChannelRole::Hide)
+            .collect()
+    }
+}
+
+/// Returns the height of a pitch's line, from the bottom of the spectrum. Pitches out of range
+/// are at the top or bottom.
+fn pitch_height(pitch: f32, height: f32) -> f32 {
+    ((pitch - MIN_PITCH) / (MAX_PITCH - MIN_PITCH) * height).clamp(0.0, height)
 }
 
 impl View for NoteSpectrum {
@@ -40,9 +106,35 @@ impl View for NoteSpectrum {
         Some("lattice")
     }
 
-    fn event(&mut self, _cx: &mut EventContext, _event: &mut Event) {}
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                let bounds = cx.bounds();
+                let bottom = bounds.y + bounds.height();
+                let cursor_height = bottom - cx.mouse().cursory;
+                let max_distance = PADDING * cx.scale_factor();
+                if let Some(voice) = self
+                    .visible_voices()
+                    .into_iter()
+                    .map(|voice| {
+                        let line_height = pitch_height(voice.get_pitch(), bounds.height());
+                        ((line_height - cursor_height).abs(), voice)
+                    })
+                    .filter(|(distance, _)| *distance <= max_distance)
+                    .min_by(|(d1, _), (d2, _)| d1.total_cmp(d2))
+                    .map(|(_, voice)| voice)
+                {
+                    self.locate(cx, &voice);
+                }
+            }
+            _ => {}
+        });
+    }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.params.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor();
+
         // Background rectangle
         let mut background_path = vg::Path::new();
         background_path.rounded_rect(
@@ -52,75 +144,72 @@ impl View for NoteSpectrum {
             cx.bounds().height(),
             CORNER_RADIUS * cx.scale_factor(),
         );
-        canvas.fill_path(&background_path, &vg::Paint::color(BASE_COLOR));
-
-        let min_pitch: f32 = 60.0 - 12.0 * 3.0;
-        let max_pitch: f32 = 60.0 + 12.0 * 3.0;
+        canvas.fill_path(&background_path, &vg::Paint::color(colors.base));
 
         // Draw notes
-        let mut voices_output = self.voices_output.lock().unwrap();
-        let voices: Vec<MidiVoice> = voices_output.read().values().cloned().collect();
-        std::mem::drop(voices_output);
-        for voice in voices {
-            if voice.get_channel() == 15 {
-                continue;
-            }
+        let custom_colors = *self.params.custom_colors.read().unwrap();
+        for voice in self.visible_voices() {
             let pitch = voice.get_pitch();
             let color = note_color(
+                self.params.grid_params.color_palette.value(),
+                &custom_colors,
                 voice.get_channel(),
                 pitch,
-                self.params.darkest_pitch.value(),
-                self.params.brightest_pitch.value(),
+                self.params.grid_params.darkest_pitch.value(),
+                self.params.grid_params.brightest_pitch.value(),
             );
 
-            let pitch_idx = if pitch < min_pitch {
-                min_pitch
-            } else if pitch > max_pitch {
-                max_pitch
-            } else {
-                (pitch - min_pitch) / (max_pitch - min_pitch)
-            };
-
-            let mut pitch_path = vg::Path::new();
-            pitch_path.move_to(
-                cx.bounds().x,
-                cx.bounds().y + cx.bounds().height() - pitch_idx * cx.bounds().height(),
+            let line_width = snap_line_width(
+                if self.params.grid_params.velocity_affects_color.value() {
+                    (LINE_WIDTH * velocity_intensity(voice.get_velocity())).max(MIN_LINE_WIDTH)
+                } else {
+                    LINE_WIDTH
+                },
+                scale,
             );
-            pitch_path.line_to(
-                cx.bounds().x + cx.bounds().width(),
-                cx.bounds().y + cx.bounds().height() - pitch_idx * cx.bounds().height(),
+            let line_y = snap_line_position(
+                cx.bounds().y + cx.bounds().height() - pitch_height(pitch, cx.bounds().height()),
+                line_width,
+                scale,
             );
+            let mut pitch_path = vg::Path::new();
+            pitch_path.move_to(cx.bounds().x, line_y);
+            pitch_path.line_to(cx.bounds().x + cx.bounds().width(), line_y);
 
             let mut paint = vg::Paint::color(color);
-            paint.set_line_width(1.5 * cx.scale_factor());
+            paint.set_line_width(line_width);
             paint.set_line_cap(vg::LineCap::Butt);
             canvas.stroke_path(&pitch_path, &paint);
         }
 
-        // Notches on side
+        // Notches on side, every half octave from middle C. These follow the octave's stretch.
+        let half_octave_semitones = self.params.tuning_params.octave.value() / 200.0;
         for half_octave in -10..11i32 {
-            let notch_pitch = 60.0 + 6.0 * half_octave as f32;
-            if notch_pitch < min_pitch + 1.0 || notch_pitch > max_pitch - 1.0 {
+            let notch_pitch = 60.0 + half_octave_semitones * half_octave as f32;
+            if notch_pitch < MIN_PITCH + 1.0 || notch_pitch > MAX_PITCH - 1.0 {
                 continue;
             }
-            let pitch_idx = (notch_pitch - min_pitch) / (max_pitch - min_pitch);
+            let pitch_idx = (notch_pitch - MIN_PITCH) / (MAX_PITCH - MIN_PITCH);
             let mut notch_path = vg::Path::new();
             let (length, width): (f32, f32) = if half_octave.rem_euclid(2) == 0 {
                 (0.2, 3.0)
             } else {
                 (0.1, 2.0)
             };
-            notch_path.move_to(
-                cx.bounds().x + cx.bounds().width() * (1.0 - length),
+            let line_width = snap_line_width(width, scale);
+            let line_y = snap_line_position(
                 cx.bounds().y + cx.bounds().height() - pitch_idx * cx.bounds().height(),
+                line_width,
+                scale,
             );
-            notch_path.line_to(
-                cx.bounds().x + cx.bounds().width(),
-                cx.bounds().y + cx.bounds().height() - pitch_idx * cx.bounds().height(),
+            notch_path.move_to(
+                snap(cx.bounds().x + cx.bounds().width() * (1.0 - length), scale),
+                line_y,
             );
+            notch_path.line_to(cx.bounds().x + cx.bounds().width(), line_y);
 
-            let mut notch_paint = vg::Paint::color(BACKGROUND_COLOR);
-            notch_paint.set_line_width(width * cx.scale_factor());
+            let mut notch_paint = vg::Paint::color(colors.background);
+            notch_paint.set_line_width(line_width);
             notch_paint.set_line_cap(vg::LineCap::Round);
 
             canvas.stroke_path(&notch_path, &notch_paint);
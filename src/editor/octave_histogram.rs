@@ -0,0 +1,154 @@
+use crate::midi::{ChannelRole, MidiVoice};
+use crate::MidiLatticeParams;
+use crate::Voices;
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::{Arc, Mutex};
+use triple_buffer::Output;
+
+use crate::editor::color::*;
+use crate::editor::{CORNER_RADIUS, PADDING};
+
+/// Number of octaves shown, covering the MIDI note range from C-1 to G9. Notes bent outside of
+/// them are counted in the lowest or highest octave.
+pub const OCTAVE_COUNT: usize = 11;
+
+// MIDI octave number of the lowest bar. C-1 is MIDI note 0.
+const LOWEST_OCTAVE: i32 = -1;
+
+/// Bars counting the sounding voices in each octave, from the bottom up.
+/// The octave with the most voices is emphasized.
+pub struct OctaveHistogram {
+    params: Arc<MidiLatticeParams>,
+    voices_output: Arc<Mutex<Output<Voices>>>,
+}
+
+impl OctaveHistogram {
+    pub fn new<LParams, LVoices>(
+        cx: &mut Context,
+        params: LParams,
+        voices_output: LVoices,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<MidiLatticeParams>>,
+        LVoices: Lens<Target = Arc<Mutex<Output<Voices>>>>,
+    {
+        Self {
+            params: params.get(cx),
+            voices_output: voices_output.get(cx),
+        }
+        .build(cx, |_cx| {})
+    }
+}
+
+/// Returns the histogram bar for a pitch in MIDI note numbers. Pitches count towards the octave
+/// of their closest note, so a slightly flat C4 is still in the C4 octave.
+fn octave_bucket(pitch: f32) -> usize {
+    let octave = (pitch.round() as i32).div_euclid(12) - 1;
+    (octave - LOWEST_OCTAVE).clamp(0, OCTAVE_COUNT as i32 - 1) as usize
+}
+
+#[cfg(test)]
+mod octave_bucket_tests {
+    use crate::editor::octave_histogram::{octave_bucket, OCTAVE_COUNT};
+
+    #[test]
+    fn splits_octaves_at_c() {
+        // B3 and C4
+        assert_eq!(octave_bucket(59.0), 4);
+        assert_eq!(octave_bucket(60.0), 5);
+        assert_eq!(octave_bucket(71.0), 5);
+        assert_eq!(octave_bucket(72.0), 6);
+    }
+
+    #[test]
+    fn uses_closest_note() {
+        assert_eq!(octave_bucket(59.6), 5);
+        assert_eq!(octave_bucket(59.4), 4);
+    }
+
+    #[test]
+    fn covers_midi_range() {
+        // C-1, B-1 and C0
+        assert_eq!(octave_bucket(0.0), 0);
+        assert_eq!(octave_bucket(11.0), 0);
+        assert_eq!(octave_bucket(12.0), 1);
+        // C9 and G9
+        assert_eq!(octave_bucket(120.0), OCTAVE_COUNT - 1);
+        assert_eq!(octave_bucket(127.0), OCTAVE_COUNT - 1);
+    }
+
+    #[test]
+    fn clamps_bent_notes_to_range() {
+        assert_eq!(octave_bucket(-2.0), 0);
+        assert_eq!(octave_bucket(134.0), OCTAVE_COUNT - 1);
+    }
+}
+
+impl View for OctaveHistogram {
+    fn element(&self) -> Option<&'static str> {
+        Some("octave-histogram")
+    }
+
+    fn event(&mut self, _cx: &mut EventContext, _event: &mut Event) {}
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.params.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = cx.bounds();
+
+        // Background rectangle
+        let mut background_path = vg::Path::new();
+        background_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(&background_path, &vg::Paint::color(colors.base));
+
+        let mut voices_output = self.voices_output.lock().unwrap();
+        let voices: Vec<MidiVoice> = voices_output.read().values().cloned().collect();
+        std::mem::drop(voices_output);
+
+        let channel_roles = *self.params.channel_roles.read().unwrap();
+        let mut counts: [u32; OCTAVE_COUNT] = [0; OCTAVE_COUNT];
+        for voice in voices {
+            if channel_roles.role(voice.get_channel()) == ChannelRole::Hide {
+                continue;
+            }
+            counts[octave_bucket(voice.get_pitch())] += 1;
+        }
+        let max_count = *counts.iter().max().unwrap();
+        if max_count == 0 {
+            return;
+        }
+
+        // Bars grow from the left, lowest octave at the bottom
+        let inner_padding = PADDING * scale * 0.5;
+        let bar_stride = (bounds.h - inner_padding) / OCTAVE_COUNT as f32;
+        let max_bar_width = bounds.w - inner_padding * 2.0;
+        for (octave_idx, count) in counts.iter().enumerate() {
+            if *count == 0 {
+                continue;
+            }
+            let mut bar_path = vg::Path::new();
+            bar_path.rect(
+                bounds.x + inner_padding,
+                bounds.y + bounds.h - (octave_idx as f32 + 1.0) * bar_stride,
+                max_bar_width * *count as f32 / max_count as f32,
+                bar_stride - inner_padding,
+            );
+            canvas.fill_path(
+                &bar_path,
+                &vg::Paint::color(if *count == max_count {
+                    colors.text
+                } else {
+                    colors.highlight
+                }),
+            );
+        }
+    }
+}
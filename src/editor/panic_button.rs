@@ -0,0 +1,107 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use crate::command::{AudioCommand, CommandSender};
+use crate::GridParams;
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, make_icon_stroke_paint, snap_bounds};
+
+use super::PADDING;
+
+/// Clears all voices on the audio thread, for notes that got stuck without a note off
+pub struct PanicButton {
+    pressed: bool,
+    command_sender: Arc<Mutex<CommandSender>>,
+    grid_params: Arc<GridParams>,
+}
+
+impl PanicButton {
+    pub fn new<LCommands, LGridParams>(
+        cx: &mut Context,
+        command_sender: LCommands,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LCommands: Lens<Target = Arc<Mutex<CommandSender>>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            pressed: false,
+            command_sender: command_sender.get(cx),
+            grid_params: grid_params.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for PanicButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("panic-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                cx.capture();
+                self.pressed = true;
+                self.command_sender
+                    .lock()
+                    .unwrap()
+                    .send(AudioCommand::ClearVoices);
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.pressed {
+                    cx.release();
+                    self.pressed = false;
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let highlighted: bool =
+            self.pressed || intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            crate::editor::CORNER_RADIUS * scale,
+        );
+        container_path.close();
+
+        let paint = vg::Paint::color(if self.pressed {
+            colors.text
+        } else if highlighted {
+            colors.highlight
+        } else {
+            colors.base
+        });
+        canvas.fill_path(&mut container_path, &paint);
+
+        let icon_line_width: f32 = PADDING * scale;
+        let icon_padding: f32 = PADDING * 1.5 * scale + icon_line_width * 0.5;
+
+        // Draw a cross
+        let mut icon_path = vg::Path::new();
+        icon_path.move_to(bounds.x + icon_padding, bounds.y + icon_padding);
+        icon_path.line_to(
+            bounds.x + bounds.w - icon_padding,
+            bounds.y + bounds.h - icon_padding,
+        );
+        icon_path.move_to(bounds.x + bounds.w - icon_padding, bounds.y + icon_padding);
+        icon_path.line_to(bounds.x + icon_padding, bounds.y + bounds.h - icon_padding);
+
+        let icon_paint = make_icon_stroke_paint(colors.background, scale);
+
+        canvas.stroke_path(&mut icon_path, &icon_paint);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::midi::{ChannelRole, MidiVoice};
+use crate::tuning::{PitchClass, PitchClassDistance};
+use crate::MidiLatticeParams;
+use crate::Voices;
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use triple_buffer::Output;
+
+use crate::editor::color::*;
+use crate::editor::lattice::grid::VisiblePitchClasses;
+use crate::editor::{CORNER_RADIUS, PADDING};
+
+/// Circle showing voices and visible grid pitch classes by their position in the octave.
+/// C is at the top, and pitch increases clockwise.
+pub struct PitchWheel {
+    params: Arc<MidiLatticeParams>,
+    voices_output: Arc<Mutex<Output<Voices>>>,
+
+    // Need interior mutability to allow mutation from draw()
+    visible_pitch_classes: Mutex<VisiblePitchClasses>,
+}
+
+impl PitchWheel {
+    pub fn new<LParams, LVoices>(
+        cx: &mut Context,
+        params: LParams,
+        voices_output: LVoices,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<MidiLatticeParams>>,
+        LVoices: Lens<Target = Arc<Mutex<Output<Voices>>>>,
+    {
+        Self {
+            params: params.get(cx),
+            voices_output: voices_output.get(cx),
+            visible_pitch_classes: Mutex::new(VisiblePitchClasses::default()),
+        }
+        .build(cx, |_cx| {})
+    }
+}
+
+/// Returns the point on a circle at the angle of a pitch class
+fn wheel_point(center: (f32, f32), radius: f32, pitch_class: PitchClass) -> (f32, f32) {
+    let angle = pitch_class.to_octave_fraction() * 2.0 * PI - PI * 0.5;
+    (
+        center.0 + radius * angle.cos(),
+        center.1 + radius * angle.sin(),
+    )
+}
+
+impl View for PitchWheel {
+    fn element(&self) -> Option<&'static str> {
+        Some("pitch-wheel")
+    }
+
+    fn event(&mut self, _cx: &mut EventContext, _event: &mut Event) {}
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.params.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = cx.bounds();
+
+        // Background rectangle
+        let mut background_path = vg::Path::new();
+        background_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(&background_path, &vg::Paint::color(colors.base));
+
+        let center = (bounds.x + bounds.w * 0.5, bounds.y + bounds.h * 0.5);
+        let dot_radius = PADDING * scale * 0.6;
+        let radius = bounds.w.min(bounds.h) * 0.5 - PADDING * scale * 0.5 - dot_radius;
+
+        let mut circle_path = vg::Path::new();
+        circle_path.circle(center.0, center.1, radius);
+        let mut circle_paint = vg::Paint::color(colors.background);
+        circle_paint.set_line_width(scale);
+        canvas.stroke_path(&circle_path, &circle_paint);
+
+        // Tick marks for pitch classes on the grid
+        let mut visible_pitch_classes = self.visible_pitch_classes.lock().unwrap();
+        let visible_pitch_classes = visible_pitch_classes.update(&self.params);
+        let mut tick_path = vg::Path::new();
+        for pitch_class in visible_pitch_classes.representatives() {
+            let (inner_x, inner_y) = wheel_point(center, radius - dot_radius, *pitch_class);
+            let (outer_x, outer_y) = wheel_point(center, radius, *pitch_class);
+            tick_path.move_to(inner_x, inner_y);
+            tick_path.line_to(outer_x, outer_y);
+        }
+        let mut tick_paint = vg::Paint::color(colors.background);
+        tick_paint.set_line_width(scale);
+        tick_paint.set_line_cap(vg::LineCap::Butt);
+        canvas.stroke_path(&tick_path, &tick_paint);
+
+        // Voices - filled if they match a node on the grid, hollow otherwise
+        let mut voices_output = self.voices_output.lock().unwrap();
+        let voices: Vec<MidiVoice> = voices_output.read().values().cloned().collect();
+        std::mem::drop(voices_output);
+        let tuning_tolerance =
+            PitchClassDistance::from_cents_f32(self.params.tuning_params.tolerance.value());
+        let grid_params = &self.params.grid_params;
+        let channel_roles = *self.params.channel_roles.read().unwrap();
+        let custom_colors = *self.params.custom_colors.read().unwrap();
+        for voice in voices {
+            if channel_roles.role(voice.get_channel()) == ChannelRole::Hide {
+                continue;
+            }
+            let color = note_color(
+                grid_params.color_palette.value(),
+                &custom_colors,
+                voice.get_channel(),
+                voice.get_pitch(),
+                grid_params.darkest_pitch.value(),
+                grid_params.brightest_pitch.value(),
+            );
+            let (dot_x, dot_y) = wheel_point(center, radius, voice.get_pitch_class());
+            let mut dot_path = vg::Path::new();
+            dot_path.circle(dot_x, dot_y, dot_radius);
+
+            if visible_pitch_classes.matches(voice.get_pitch_class(), tuning_tolerance) {
+                canvas.fill_path(&dot_path, &vg::Paint::color(color));
+            } else {
+                let mut hollow_paint = vg::Paint::color(color);
+                hollow_paint.set_line_width(scale * 1.5);
+                canvas.stroke_path(&dot_path, &hollow_paint);
+            }
+        }
+    }
+}
@@ -1,12 +1,18 @@
 //! A resize handle for uniformly scaling a plugin GUI.
 
-use crate::editor::{intersects_box, CORNER_RADIUS, PADDING};
+use crate::editor::{intersects_box, snap_bounds, CORNER_RADIUS, PADDING};
 use nih_plug_vizia::vizia::prelude::*;
 use nih_plug_vizia::vizia::vg;
+use std::sync::Arc;
 
 use super::make_icon_stroke_paint;
 
 use crate::editor::color::*;
+use crate::GridParams;
+
+/// Smallest and largest scale factors the window can be resized to
+pub const MIN_SCALE: f64 = 0.5;
+pub const MAX_SCALE: f64 = 4.0;
 
 /// A resize handle placed at the bottom right of the window that lets you resize the window.
 ///
@@ -25,18 +31,24 @@ pub struct Resizer {
     start_dpi_factor: f32,
     /// The cursor position in physical screen pixels when the drag started.
     start_physical_coordinates: (f32, f32),
+
+    grid_params: Arc<GridParams>,
 }
 
 impl Resizer {
     /// Create a resize handle at the bottom right of the window. This should be created at the top
     /// level. Dragging this handle around will cause the window to be resized.
-    pub fn new(cx: &mut Context) -> Handle<Self> {
+    pub fn new<LGridParams>(cx: &mut Context, grid_params: LGridParams) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
         // Styling is done in the style sheet
         Resizer {
             drag_active: false,
             start_scale_factor: 1.0,
             start_dpi_factor: 1.0,
             start_physical_coordinates: (0.0, 0.0),
+            grid_params: grid_params.get(cx),
         }
         .build(cx, |_| {})
     }
@@ -84,8 +96,8 @@ impl View for Resizer {
                         * (compensated_physical_x / start_physical_x)
                             .max(compensated_physical_y / start_physical_y)
                             as f64)
-                        .max(0.5)
-                        .min(4.0);
+                        .max(MIN_SCALE)
+                        .min(MAX_SCALE);
 
                     cx.set_user_scale_factor(new_scale_factor);
                 }
@@ -95,8 +107,9 @@ impl View for Resizer {
     }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
         let scale: f32 = cx.scale_factor() as f32;
-        let bounds = cx.bounds();
+        let bounds = snap_bounds(cx.bounds(), scale);
         let highlighted: bool =
             self.drag_active || intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
 
@@ -112,17 +125,17 @@ impl View for Resizer {
 
         // Fill with background color
         let paint = vg::Paint::color(if self.drag_active {
-            TEXT_COLOR
+            colors.text
         } else if highlighted {
-            HIGHLIGHT_COLOR
+            colors.highlight
         } else {
-            BASE_COLOR
+            colors.base
         });
         canvas.fill_path(&mut container_path, &paint);
 
         let icon_line_width: f32 = PADDING * scale;
         let icon_padding: f32 = PADDING * scale + icon_line_width * 0.5;
-        let color = BACKGROUND_COLOR;
+        let color = colors.background;
         let icon_paint = make_icon_stroke_paint(color, scale);
         let mut icon_path = vg::Path::new();
         // top right
@@ -0,0 +1,111 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use crate::tuning::scala::to_scl;
+use crate::{GridParams, TuningParams};
+use std::sync::Arc;
+
+use crate::editor::color::*;
+use crate::editor::status_message::StatusEvent;
+use crate::editor::{intersects_box, make_icon_stroke_paint, snap_bounds, spawn_file_dialog};
+
+use super::PADDING;
+
+/// Saves the tuning of primes 3, 5 and 7 to a Scala (.scl) scale file
+pub struct ScalaExportButton {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+}
+
+impl ScalaExportButton {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+
+    /// Asks where to save the file, and writes it, without blocking the editor. The status is
+    /// updated once it's written, or if writing failed.
+    fn export(&self, cx: &mut EventContext) {
+        // Saves the tunings as they were when export was clicked
+        let scl = to_scl(&self.tuning_params.snapshot());
+        spawn_file_dialog(cx, move || {
+            let path = rfd::FileDialog::new()
+                .add_filter("Scala scale", &["scl"])
+                .set_file_name("midi_lattice.scl")
+                .save_file()?;
+            Some(match std::fs::write(path, scl) {
+                Ok(()) => StatusEvent::Clear,
+                Err(e) => StatusEvent::Show(format!("Couldn't export scale: {}", e)),
+            })
+        });
+    }
+}
+
+impl View for ScalaExportButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("scala-export-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => self.export(cx),
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let highlighted: bool = intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            crate::editor::CORNER_RADIUS * scale,
+        );
+        container_path.close();
+
+        let paint = vg::Paint::color(if highlighted {
+            colors.highlight
+        } else {
+            colors.base
+        });
+        canvas.fill_path(&mut container_path, &paint);
+
+        let icon_line_width: f32 = PADDING * scale;
+        let icon_padding: f32 = PADDING * scale + icon_line_width * 0.5;
+
+        // Draw save symbol - an arrow pointing down onto a line
+        let center_x = bounds.x + bounds.w * 0.5;
+        let bottom_y = bounds.y + bounds.h - icon_padding;
+        let arrow_tip_y = bottom_y - icon_line_width * 2.0;
+        let arrow_head_size = bounds.w * 0.2;
+        let mut icon_path = vg::Path::new();
+        icon_path.move_to(center_x, bounds.y + icon_padding);
+        icon_path.line_to(center_x, arrow_tip_y);
+        icon_path.move_to(center_x - arrow_head_size, arrow_tip_y - arrow_head_size);
+        icon_path.line_to(center_x, arrow_tip_y);
+        icon_path.line_to(center_x + arrow_head_size, arrow_tip_y - arrow_head_size);
+        icon_path.move_to(bounds.x + icon_padding, bottom_y);
+        icon_path.line_to(bounds.x + bounds.w - icon_padding, bottom_y);
+
+        let icon_paint = make_icon_stroke_paint(colors.background, scale);
+
+        canvas.stroke_path(&mut icon_path, &icon_paint);
+    }
+}
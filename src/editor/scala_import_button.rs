@@ -0,0 +1,140 @@
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use nih_plug_vizia::widgets::ParamEvent;
+
+use crate::tuning::scala::{parse_scl, scale_tunings};
+use crate::{GridParams, TuningParams};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::editor::color::*;
+use crate::editor::status_message::StatusEvent;
+use crate::editor::{intersects_box, make_icon_stroke_paint, snap_bounds, spawn_file_dialog};
+
+use super::PADDING;
+
+/// Sets the tuning of primes 3, 5, 7 and 11 from the intervals of a Scala (.scl) scale file
+pub struct ScalaImportButton {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+}
+
+pub enum ScalaImportEvent {
+    /// A file was picked in the dialog
+    Picked(PathBuf),
+}
+
+impl ScalaImportButton {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+
+    /// Applies a scale file. Returns an error message if the file can't be used.
+    fn import(&self, cx: &mut EventContext, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let scale = parse_scl(&contents).map_err(|e| e.to_string())?;
+
+        let tunings = scale_tunings(&scale);
+        for (param, tuning) in [
+            (&self.tuning_params.three, tunings.three),
+            (&self.tuning_params.five, tunings.five),
+            (&self.tuning_params.seven, tunings.seven),
+            (&self.tuning_params.eleven, tunings.eleven),
+        ] {
+            if let Some(tuning) = tuning {
+                cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+                cx.emit(ParamEvent::SetParameter(param, tuning.to_cents_f32()).upcast());
+                cx.emit(ParamEvent::EndSetParameter(param).upcast());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl View for ScalaImportButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("scala-import-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(
+            |import_event: &ScalaImportEvent, _meta| match import_event {
+                ScalaImportEvent::Picked(path) => {
+                    cx.emit(match self.import(cx, path) {
+                        Ok(()) => StatusEvent::Clear,
+                        Err(message) => {
+                            StatusEvent::Show(format!("Couldn't import scale: {}", message))
+                        }
+                    });
+                }
+            },
+        );
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => {
+                spawn_file_dialog(cx, || {
+                    rfd::FileDialog::new()
+                        .add_filter("Scala scale", &["scl"])
+                        .pick_file()
+                        .map(ScalaImportEvent::Picked)
+                });
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let highlighted: bool = intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            crate::editor::CORNER_RADIUS * scale,
+        );
+        container_path.close();
+
+        let paint = vg::Paint::color(if highlighted {
+            colors.highlight
+        } else {
+            colors.base
+        });
+        canvas.fill_path(&mut container_path, &paint);
+
+        let icon_line_width: f32 = PADDING * scale;
+        let icon_padding: f32 = PADDING * scale + icon_line_width * 0.5;
+
+        // Draw scale symbol - a staircase
+        let step_count = 3;
+        let step_size = (bounds.w - icon_padding * 2.0) / step_count as f32;
+        let mut icon_path = vg::Path::new();
+        icon_path.move_to(bounds.x + icon_padding, bounds.y + bounds.h - icon_padding);
+        for step_idx in 0..step_count {
+            let step_x = bounds.x + icon_padding + step_size * step_idx as f32;
+            let step_y = bounds.y + bounds.h - icon_padding - step_size * (step_idx + 1) as f32;
+            icon_path.line_to(step_x, step_y);
+            icon_path.line_to(step_x + step_size, step_y);
+        }
+
+        let icon_paint = make_icon_stroke_paint(colors.background, scale);
+
+        canvas.stroke_path(&mut icon_path, &icon_paint);
+    }
+}
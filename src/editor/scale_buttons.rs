@@ -0,0 +1,173 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use crate::editor::color::*;
+use crate::editor::resizer::{MAX_SCALE, MIN_SCALE};
+use crate::editor::{intersects_box, make_icon_paint, snap_bounds, CORNER_RADIUS, PADDING};
+use crate::GridParams;
+use std::sync::Arc;
+
+/// Amount by which each click changes the window's scale factor
+pub const SCALE_CHANGE_AMOUNT: f64 = 0.1;
+
+// Steps of the buttons, from top to bottom
+const STEPS: [i32; 2] = [1, -1];
+
+/// A + button over a - button, which grow or shrink the whole window
+pub struct ScaleButtons {
+    grid_params: Arc<GridParams>,
+
+    // Step index of the button being pressed
+    pressed_button: Option<usize>,
+}
+
+impl ScaleButtons {
+    pub fn new<LGridParams>(cx: &mut Context, grid_params: LGridParams) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            grid_params: grid_params.get(cx),
+            pressed_button: None,
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// Moves a scale factor to the next multiple of [`SCALE_CHANGE_AMOUNT`] in the direction of the
+/// step. Scale factors between multiples, e.g. after dragging the resizer, snap to the nearest one
+/// first.
+fn stepped_scale(scale: f64, step: i32) -> f64 {
+    (((scale / SCALE_CHANGE_AMOUNT).round() + step as f64) * SCALE_CHANGE_AMOUNT)
+        .clamp(MIN_SCALE, MAX_SCALE)
+}
+
+/// Bounds of a step's button, with padding between the buttons
+fn button_bounds(bounds: BoundingBox, step_idx: usize, padding: f32) -> BoundingBox {
+    let h = (bounds.h - padding) * 0.5;
+    BoundingBox {
+        x: bounds.x,
+        y: bounds.y + (h + padding) * step_idx as f32,
+        w: bounds.w,
+        h,
+    }
+}
+
+/// Step index of the button under the mouse, if any
+fn button_at(bounds: BoundingBox, mouse: (f32, f32), padding: f32) -> Option<usize> {
+    (0..STEPS.len())
+        .find(|step_idx| intersects_box(button_bounds(bounds, *step_idx, padding), mouse))
+}
+
+impl View for ScaleButtons {
+    fn element(&self) -> Option<&'static str> {
+        Some("scale-buttons")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        let padding = PADDING * 0.5 * cx.scale_factor();
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                self.pressed_button = button_at(
+                    cx.bounds(),
+                    (cx.mouse().cursorx, cx.mouse().cursory),
+                    padding,
+                );
+                if let Some(step_idx) = self.pressed_button {
+                    cx.capture();
+                    cx.set_user_scale_factor(stepped_scale(
+                        cx.user_scale_factor(),
+                        STEPS[step_idx],
+                    ));
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.pressed_button.is_some() {
+                    cx.release();
+                    self.pressed_button = None;
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let padding = PADDING * 0.5 * scale;
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        for (step_idx, step) in STEPS.iter().enumerate() {
+            let button = snap_bounds(button_bounds(cx.bounds(), step_idx, padding), scale);
+            let pressed = self.pressed_button == Some(step_idx);
+
+            let mut button_path = vg::Path::new();
+            button_path.rounded_rect(
+                button.x,
+                button.y,
+                button.w,
+                button.h,
+                CORNER_RADIUS * scale,
+            );
+            canvas.fill_path(
+                &mut button_path,
+                &vg::Paint::color(if pressed {
+                    colors.text
+                } else if intersects_box(button, mouse) {
+                    colors.highlight
+                } else {
+                    colors.base
+                }),
+            );
+
+            // A minus sign, with a vertical bar through it for plus
+            let (center_x, center_y) = (button.x + button.w * 0.5, button.y + button.h * 0.5);
+            let arm = button.w.min(button.h) * 0.3;
+            let mut icon_path = vg::Path::new();
+            icon_path.move_to(center_x - arm, center_y);
+            icon_path.line_to(center_x + arm, center_y);
+            if *step > 0 {
+                icon_path.move_to(center_x, center_y - arm);
+                icon_path.line_to(center_x, center_y + arm);
+            }
+            let icon_color = if pressed {
+                colors.background
+            } else {
+                colors.text
+            };
+            canvas.stroke_path(&mut icon_path, &make_icon_paint(icon_color, 1.5 * scale));
+        }
+    }
+}
+
+#[cfg(test)]
+mod stepped_scale_tests {
+    use crate::editor::resizer::{MAX_SCALE, MIN_SCALE};
+    use crate::editor::scale_buttons::stepped_scale;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_steps_by_change_amount() {
+        assert_close(stepped_scale(1.0, 1), 1.1);
+        assert_close(stepped_scale(1.0, -1), 0.9);
+        assert_close(stepped_scale(stepped_scale(1.0, 1), 1), 1.2);
+    }
+
+    #[test]
+    fn test_snaps_to_nearest_step_first() {
+        assert_close(stepped_scale(1.23, 1), 1.3);
+        assert_close(stepped_scale(1.27, -1), 1.2);
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_close(stepped_scale(MIN_SCALE, -1), MIN_SCALE);
+        assert_close(stepped_scale(MAX_SCALE, 1), MAX_SCALE);
+    }
+}
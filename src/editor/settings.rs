@@ -0,0 +1,151 @@
+use crate::{GridParams, MidiLatticeParams};
+
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::Arc;
+
+use crate::editor::color::*;
+use crate::editor::{run_ticks, CORNER_RADIUS, PADDING, TICK_INTERVAL};
+use crate::tuning::{ELEVEN_JUST_F32, FIVE_JUST_F32, SEVEN_JUST_F32, THREE_JUST_F32};
+
+use self::color_swatches::ColorSwatches;
+use self::png_export_button::PngExportButton;
+use self::reset_button::{ResetButton, ResetScope};
+use self::svg_export_button::SvgExportButton;
+use self::tuning_param_control::{ControlEvent, TuningParamControl};
+
+mod color_swatches;
+mod png_export_button;
+mod reset_button;
+mod svg_export_button;
+mod tuning_param_control;
+
+/// Height of each row of controls in the overlay
+pub const ROW_HEIGHT: f32 = super::lattice::grid::NODE_SIZE * 0.5;
+
+pub enum SettingsEvent {
+    /// Show or hide the settings overlay
+    Toggle,
+}
+
+enum SettingsTickEvent {
+    Tick,
+}
+
+/// Overlay on top of the lattice, for adjusting parameters without the host's generic UI
+pub struct SettingsOverlay {
+    grid_params: Arc<GridParams>,
+
+    /// The tick thread stops once this is dropped along with the view
+    _alive: Arc<()>,
+}
+
+impl SettingsOverlay {
+    pub fn new<LParams>(cx: &mut Context, params: LParams) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<MidiLatticeParams>> + Copy,
+    {
+        let alive = Arc::new(());
+        let thread_alive = Arc::downgrade(&alive);
+        Self {
+            grid_params: params.map(|p| p.grid_params.clone()).get(cx),
+            _alive: alive,
+        }
+        .build(cx, move |cx| {
+            let tuning_params = || params.map(|p| p.tuning_params.clone());
+            let grid_params = || params.map(|p| p.grid_params.clone());
+
+            TuningParamControl::new(
+                cx,
+                tuning_params(),
+                grid_params(),
+                "C offset",
+                |p| &p.c_offset,
+                Some(0.0),
+            )
+            .height(Units::Pixels(ROW_HEIGHT));
+            TuningParamControl::new(
+                cx,
+                tuning_params(),
+                grid_params(),
+                "Fifth",
+                |p| &p.three,
+                Some(THREE_JUST_F32),
+            )
+            .height(Units::Pixels(ROW_HEIGHT));
+            TuningParamControl::new(
+                cx,
+                tuning_params(),
+                grid_params(),
+                "Third",
+                |p| &p.five,
+                Some(FIVE_JUST_F32),
+            )
+            .height(Units::Pixels(ROW_HEIGHT));
+            TuningParamControl::new(
+                cx,
+                tuning_params(),
+                grid_params(),
+                "Seventh",
+                |p| &p.seven,
+                Some(SEVEN_JUST_F32),
+            )
+            .height(Units::Pixels(ROW_HEIGHT));
+            TuningParamControl::new(
+                cx,
+                tuning_params(),
+                grid_params(),
+                "Eleventh",
+                |p| &p.eleven,
+                Some(ELEVEN_JUST_F32),
+            )
+            .height(Units::Pixels(ROW_HEIGHT));
+
+            ColorSwatches::new(cx, params).height(Units::Pixels(ROW_HEIGHT));
+
+            HStack::new(cx, |cx| {
+                ResetButton::new(cx, params, ResetScope::Display, "Reset display");
+                ResetButton::new(cx, params, ResetScope::All, "Reset all");
+                SvgExportButton::new(cx, grid_params());
+                PngExportButton::new(cx, grid_params());
+            })
+            .col_between(Units::Pixels(PADDING))
+            .height(Units::Pixels(ROW_HEIGHT));
+
+            // Tick for held buttons to repeat, until the editor is closed
+            cx.spawn(move |cx_proxy| {
+                run_ticks(thread_alive, TICK_INTERVAL, || {
+                    cx_proxy.emit(SettingsTickEvent::Tick).is_ok()
+                })
+            });
+        })
+    }
+}
+
+impl View for SettingsOverlay {
+    fn element(&self) -> Option<&'static str> {
+        Some("settings-overlay")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        // Forward ticks to the controls
+        event.map(|tick_event, _meta| match *tick_event {
+            SettingsTickEvent::Tick => {
+                cx.emit_custom(Event::new(ControlEvent::Tick).propagate(Propagation::Subtree));
+            }
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let mut background_path = vg::Path::new();
+        background_path.rounded_rect(
+            cx.bounds().x,
+            cx.bounds().y,
+            cx.bounds().width(),
+            cx.bounds().height(),
+            CORNER_RADIUS * cx.scale_factor(),
+        );
+        canvas.fill_path(&background_path, &vg::Paint::color(colors.background));
+    }
+}
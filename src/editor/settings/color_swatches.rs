@@ -0,0 +1,179 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS, PADDING};
+use crate::{CustomColors, MidiLatticeParams};
+
+// Degrees that each step of scrolling over a swatch turns its hue
+const HUE_SCROLL_STEP: f64 = 10.0;
+
+// Channels 1 to 9, then the darkest and brightest ends of the pitch gradient
+const SWATCHES: usize = 11;
+
+/// A swatch for each fixed channel color and each end of the pitch gradient. Clicking a swatch
+/// moves it on to the next of [`SWATCH_COLORS`], scrolling over it turns its hue, and right
+/// clicking it goes back to the color palette's color.
+pub struct ColorSwatches {
+    params: Arc<MidiLatticeParams>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl ColorSwatches {
+    pub fn new<LParams>(cx: &mut Context, params: LParams) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<MidiLatticeParams>>,
+    {
+        Self {
+            params: params.get(cx),
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// Bounds of a swatch, with padding between swatches
+fn swatch_bounds(bounds: BoundingBox, swatch: usize, padding: f32) -> BoundingBox {
+    let w = (bounds.w - padding * (SWATCHES - 1) as f32) / SWATCHES as f32;
+    BoundingBox {
+        x: bounds.x + (w + padding) * swatch as f32,
+        y: bounds.y,
+        w,
+        h: bounds.h,
+    }
+}
+
+/// Channel and pitch whose color a swatch shows. Gradient ends are shown as channel 10's color
+/// at the darkest and brightest pitches.
+fn swatch_note(params: &MidiLatticeParams, swatch: usize) -> (u8, f32) {
+    match swatch {
+        0..=8 => (swatch as u8, 60.0),
+        9 => (9, params.grid_params.darkest_pitch.value()),
+        _ => (9, params.grid_params.brightest_pitch.value()),
+    }
+}
+
+/// Color a swatch shows, whether or not it's been picked
+fn shown_color(
+    params: &MidiLatticeParams,
+    custom_colors: &CustomColors,
+    swatch: usize,
+) -> vg::Color {
+    let grid_params = &params.grid_params;
+    let (channel, pitch) = swatch_note(params, swatch);
+    note_color(
+        grid_params.color_palette.value(),
+        custom_colors,
+        channel,
+        pitch,
+        grid_params.darkest_pitch.value(),
+        grid_params.brightest_pitch.value(),
+    )
+}
+
+/// The custom color a swatch picks, if it's been picked
+fn swatch_color(custom_colors: &mut CustomColors, swatch: usize) -> &mut Option<u32> {
+    match swatch {
+        0..=8 => &mut custom_colors.channels[swatch],
+        9 => &mut custom_colors.darkest,
+        _ => &mut custom_colors.brightest,
+    }
+}
+
+impl View for ColorSwatches {
+    fn element(&self) -> Option<&'static str> {
+        Some("color-swatches")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        let padding = PADDING * cx.scale_factor();
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+        let clicked_swatch = (0..SWATCHES)
+            .find(|swatch| intersects_box(swatch_bounds(cx.bounds(), *swatch, padding), mouse));
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                if let Some(swatch) = clicked_swatch {
+                    let mut custom_colors = self.params.custom_colors.write().unwrap();
+                    let color = swatch_color(&mut custom_colors, swatch);
+                    *color = Some(next_swatch_color(*color));
+                }
+            }
+            WindowEvent::MouseScroll(_, scroll_y) => {
+                if let Some(swatch) = clicked_swatch {
+                    let mut custom_colors = self.params.custom_colors.write().unwrap();
+                    let shown = pack_color(shown_color(&self.params, &custom_colors, swatch));
+                    *swatch_color(&mut custom_colors, swatch) =
+                        Some(rotate_hue(shown, scroll_y as f64 * HUE_SCROLL_STEP));
+                }
+            }
+            WindowEvent::MouseDown(MouseButton::Right) => {
+                if let Some(swatch) = clicked_swatch {
+                    *swatch_color(&mut self.params.custom_colors.write().unwrap(), swatch) = None;
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.params.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let padding = PADDING * scale;
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let custom_colors = *self.params.custom_colors.read().unwrap();
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        for swatch in 0..SWATCHES {
+            let swatch_box = snap_bounds(swatch_bounds(cx.bounds(), swatch, padding), scale);
+
+            let label = match swatch {
+                0..=8 => (swatch + 1).to_string(),
+                9 => String::from("Lo"),
+                _ => String::from("Hi"),
+            };
+            let mut swatch_path = vg::Path::new();
+            swatch_path.rounded_rect(
+                swatch_box.x,
+                swatch_box.y,
+                swatch_box.w,
+                swatch_box.h,
+                CORNER_RADIUS * scale,
+            );
+            canvas.fill_path(
+                &mut swatch_path,
+                &vg::Paint::color(shown_color(&self.params, &custom_colors, swatch)),
+            );
+            if intersects_box(swatch_box, mouse) {
+                let mut outline_paint = vg::Paint::color(colors.text);
+                outline_paint.set_line_width(scale);
+                canvas.stroke_path(&mut swatch_path, &outline_paint);
+            }
+
+            // Labels have the background color behind them, to stay readable on any color
+            let mut label_path = vg::Path::new();
+            label_path.rounded_rect(
+                swatch_box.x + swatch_box.w * 0.2,
+                swatch_box.y + swatch_box.h * 0.2,
+                swatch_box.w * 0.6,
+                swatch_box.h * 0.6,
+                CORNER_RADIUS * scale,
+            );
+            canvas.fill_path(&mut label_path, &vg::Paint::color(colors.background));
+
+            let mut text_paint = vg::Paint::color(colors.text);
+            text_paint.set_font_size(swatch_box.h * 0.45);
+            text_paint.set_text_baseline(vg::Baseline::Middle);
+            text_paint.set_text_align(vg::Align::Center);
+            font_id.map(|f| text_paint.set_font(&[f]));
+            let _ = canvas.fill_text(
+                swatch_box.x + swatch_box.w * 0.5,
+                swatch_box.y + swatch_box.h * 0.5,
+                &label,
+                &text_paint,
+            );
+        }
+    }
+}
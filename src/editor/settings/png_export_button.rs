@@ -0,0 +1,117 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::lattice::grid::GridEvent;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS};
+use crate::{GridParams, MAX_PNG_SCALE};
+
+/// Saves the lattice as a PNG image. Scrolling over it picks the image's scale, in pixels per
+/// logical pixel, which doesn't depend on the window's scale.
+pub struct PngExportButton {
+    grid_params: Arc<GridParams>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl PngExportButton {
+    pub fn new<LGridParams>(cx: &mut Context, grid_params: LGridParams) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            grid_params: grid_params.get(cx),
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+/// PNG scale after scrolling some lines, one step per scroll
+fn scrolled_png_scale(png_scale: u8, lines: f32) -> u8 {
+    (png_scale as f32 + lines.signum()).clamp(1.0, MAX_PNG_SCALE as f32) as u8
+}
+
+impl View for PngExportButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("png-export-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            // The grid renders itself, so it's sent the request wherever it is
+            WindowEvent::PressDown { mouse: _ } => cx.emit_custom(
+                Event::new(GridEvent::ExportPng)
+                    .target(Entity::root())
+                    .propagate(Propagation::Subtree),
+            ),
+            WindowEvent::MouseScroll(_, scroll_y) if scroll_y != 0.0 => {
+                let png_scale = scrolled_png_scale(self.grid_params.png_scale(), scroll_y);
+                self.grid_params
+                    .png_scale
+                    .store(png_scale, Ordering::Relaxed);
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(
+            &container_path,
+            &vg::Paint::color(
+                if intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory)) {
+                    colors.highlight
+                } else {
+                    colors.base
+                },
+            ),
+        );
+
+        let mut text_paint = vg::Paint::color(colors.text);
+        text_paint.set_font_size(bounds.h * 0.5);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + bounds.w * 0.5,
+            bounds.y + bounds.h * 0.5,
+            &format!("Export PNG {}x", self.grid_params.png_scale()),
+            &text_paint,
+        );
+    }
+}
+
+#[cfg(test)]
+mod scrolled_png_scale_tests {
+    use crate::editor::settings::png_export_button::scrolled_png_scale;
+    use crate::MAX_PNG_SCALE;
+
+    #[test]
+    fn test_steps_once_per_scroll() {
+        assert_eq!(scrolled_png_scale(2, 1.0), 3);
+        assert_eq!(scrolled_png_scale(2, 0.25), 3);
+        assert_eq!(scrolled_png_scale(2, -3.0), 1);
+    }
+
+    #[test]
+    fn test_stays_in_range() {
+        assert_eq!(scrolled_png_scale(MAX_PNG_SCALE, 1.0), MAX_PNG_SCALE);
+        assert_eq!(scrolled_png_scale(1, -1.0), 1);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::MidiLatticeParams;
+
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::{GuiContextEvent, RawParamEvent};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::editor::color::*;
+use crate::editor::lattice::grid::GridEvent;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS};
+use crate::{CustomColors, GridParams};
+
+// How long the button waits for the confirming second click
+const CONFIRM_DURATION: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ResetScope {
+    /// Grid position, size, zoom, colors (including custom colors) and highlights
+    Display,
+    /// Everything, including tuning
+    All,
+}
+
+/// Resets parameters to their defaults. Needs to be clicked twice, to avoid accidents.
+pub struct ResetButton {
+    params: Arc<MidiLatticeParams>,
+    scope: ResetScope,
+    label: &'static str,
+
+    /// Time until which a second click performs the reset
+    confirm_until: Option<Instant>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl ResetButton {
+    pub fn new<LParams>(
+        cx: &mut Context,
+        params: LParams,
+        scope: ResetScope,
+        label: &'static str,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<MidiLatticeParams>>,
+    {
+        Self {
+            params: params.get(cx),
+            scope,
+            label,
+            confirm_until: None,
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+
+    fn confirming(&self) -> bool {
+        self.confirm_until
+            .map_or(false, |confirm_until| Instant::now() < confirm_until)
+    }
+
+    fn reset(&self, cx: &mut EventContext) {
+        match self.scope {
+            ResetScope::Display => reset_params(cx, self.params.grid_params.as_ref()),
+            ResetScope::All => reset_params(cx, self.params.as_ref()),
+        }
+
+        // Grid size and zoom aren't parameters, but they should still go back to their defaults
+        let default_grid_params = GridParams::default();
+        self.params
+            .grid_params
+            .set_node_size(default_grid_params.node_size());
+        for (size, default_size) in [
+            (&self.params.grid_params.width, &default_grid_params.width),
+            (&self.params.grid_params.height, &default_grid_params.height),
+        ] {
+            size.store(default_size.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        cx.emit(GuiContextEvent::Resize);
+
+        // Custom colors aren't parameters either
+        *self.params.custom_colors.write().unwrap() = CustomColors::default();
+
+        cx.emit_custom(
+            Event::new(GridEvent::ClearHighlights)
+                .target(Entity::root())
+                .propagate(Propagation::Subtree),
+        );
+    }
+}
+
+/// Sets every parameter in a group to the default it was constructed with, one gesture each
+fn reset_params(cx: &mut EventContext, params: &impl Params) {
+    for (_id, param_ptr, _group) in params.param_map() {
+        let default_value = unsafe { param_ptr.default_normalized_value() };
+        cx.emit(RawParamEvent::BeginSetParameter(param_ptr));
+        cx.emit(RawParamEvent::SetParameterNormalized(
+            param_ptr,
+            default_value,
+        ));
+        cx.emit(RawParamEvent::EndSetParameter(param_ptr));
+    }
+}
+
+impl View for ResetButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("reset-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => {
+                if self.confirming() {
+                    self.confirm_until = None;
+                    self.reset(cx);
+                } else {
+                    self.confirm_until = Some(Instant::now() + CONFIRM_DURATION);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.params.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let confirming = self.confirming();
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(
+            &container_path,
+            &vg::Paint::color(if confirming {
+                colors.text
+            } else if intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory)) {
+                colors.highlight
+            } else {
+                colors.base
+            }),
+        );
+
+        let mut text_paint = vg::Paint::color(if confirming {
+            colors.background
+        } else {
+            colors.text
+        });
+        text_paint.set_font_size(bounds.h * 0.5);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + bounds.w * 0.5,
+            bounds.y + bounds.h * 0.5,
+            if confirming {
+                "Click again to confirm"
+            } else {
+                self.label
+            },
+            &text_paint,
+        );
+    }
+}
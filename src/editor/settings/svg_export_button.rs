@@ -0,0 +1,85 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::lattice::grid::GridEvent;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS};
+use crate::GridParams;
+
+/// Saves the lattice as an SVG image, with the nodes' shapes, colors and text
+pub struct SvgExportButton {
+    grid_params: Arc<GridParams>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl SvgExportButton {
+    pub fn new<LGridParams>(cx: &mut Context, grid_params: LGridParams) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            grid_params: grid_params.get(cx),
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for SvgExportButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("svg-export-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            // The grid renders itself, so it's sent the request wherever it is
+            WindowEvent::PressDown { mouse: _ } => cx.emit_custom(
+                Event::new(GridEvent::ExportSvg)
+                    .target(Entity::root())
+                    .propagate(Propagation::Subtree),
+            ),
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(
+            &container_path,
+            &vg::Paint::color(
+                if intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory)) {
+                    colors.highlight
+                } else {
+                    colors.base
+                },
+            ),
+        );
+
+        let mut text_paint = vg::Paint::color(colors.text);
+        text_paint.set_font_size(bounds.h * 0.5);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + bounds.w * 0.5,
+            bounds.y + bounds.h * 0.5,
+            "Export SVG",
+            &text_paint,
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{GridParams, TuningParams};
+
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use nih_plug_vizia::widgets::ParamEvent;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, FontInfo, CORNER_RADIUS, PADDING};
+
+/// Amounts in cents by which the nudge buttons change the parameter, from left to right
+const NUDGE_STEPS: [f32; 4] = [-1.0, -0.1, 0.1, 1.0];
+const NUDGE_LABELS: [&str; 4] = ["-1", "-.1", "+.1", "+1"];
+
+// Auto-repeat timing for held nudge buttons. The interval shrinks with each repeat.
+const REPEAT_DELAY: Duration = Duration::from_millis(400);
+const REPEAT_INTERVAL_START: Duration = Duration::from_millis(150);
+const REPEAT_INTERVAL_MIN: Duration = Duration::from_millis(20);
+const REPEAT_ACCELERATION: f32 = 0.85;
+
+// How long the row flashes after the parameter is changed by something other than this control
+const FLASH_DURATION: Duration = Duration::from_millis(600);
+
+pub enum ControlEvent {
+    Tick,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ControlButton {
+    Nudge(usize),
+    Just,
+}
+
+/// A nudge button that is being held down
+struct HeldButton {
+    nudge_idx: usize,
+    repeats: u32,
+    next_repeat: Instant,
+}
+
+/// A row displaying the value of one tuning parameter, with buttons to nudge it.
+/// Double clicking the label resets the parameter to its default.
+pub struct TuningParamControl {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+    param: fn(&TuningParams) -> &FloatParam,
+    label: &'static str,
+
+    /// Value set by the "just" button. No button is shown if this is `None`.
+    just_value: Option<f32>,
+
+    held_button: Option<HeldButton>,
+
+    /// Used to detect parameter changes made elsewhere, e.g. by the tuning learn button
+    last_value: f32,
+    flash_start: Option<Instant>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl TuningParamControl {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+        label: &'static str,
+        param: fn(&TuningParams) -> &FloatParam,
+        just_value: Option<f32>,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        let tuning_params = tuning_params.get(cx);
+        let last_value = param(&tuning_params).value();
+        Self {
+            tuning_params,
+            grid_params: grid_params.get(cx),
+            param,
+            label,
+            just_value,
+            held_button: None,
+            last_value,
+            flash_start: None,
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+
+    fn param(&self) -> &FloatParam {
+        (self.param)(&self.tuning_params)
+    }
+
+    /// Returns the bounds of each button, from right to left
+    fn button_bounds(&self, bounds: BoundingBox, scale: f32) -> Vec<(ControlButton, BoundingBox)> {
+        let button_width = bounds.h * 1.2;
+        let spacing = PADDING * scale * 0.5;
+
+        let mut buttons: Vec<ControlButton> = Vec::with_capacity(NUDGE_STEPS.len() + 1);
+        if self.just_value.is_some() {
+            buttons.push(ControlButton::Just);
+        }
+        for nudge_idx in (0..NUDGE_STEPS.len()).rev() {
+            buttons.push(ControlButton::Nudge(nudge_idx));
+        }
+
+        buttons
+            .into_iter()
+            .enumerate()
+            .map(|(idx, button)| {
+                let x =
+                    bounds.x + bounds.w - (idx as f32 + 1.0) * (button_width + spacing) + spacing;
+                (
+                    button,
+                    BoundingBox {
+                        x,
+                        y: bounds.y,
+                        w: button_width,
+                        h: bounds.h,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn button_at(&self, bounds: BoundingBox, scale: f32, pos: (f32, f32)) -> Option<ControlButton> {
+        self.button_bounds(bounds, scale)
+            .into_iter()
+            .find(|(_, button_bounds)| intersects_box(*button_bounds, pos))
+            .map(|(button, _)| button)
+    }
+
+    fn set_value(&mut self, cx: &mut EventContext, value: f32) {
+        let param = (self.param)(&self.tuning_params);
+        cx.emit(ParamEvent::SetParameter(param, value).upcast());
+        // Changes made here shouldn't flash
+        self.last_value = param.preview_plain(param.preview_normalized(value));
+    }
+
+    /// Sets the parameter in a single gesture
+    fn set_value_gesture(&mut self, cx: &mut EventContext, value: f32) {
+        cx.emit(ParamEvent::BeginSetParameter(self.param()).upcast());
+        self.set_value(cx, value);
+        cx.emit(ParamEvent::EndSetParameter(self.param()).upcast());
+    }
+
+    /// Marks the parameter as being adjusted while a nudge button is held, so tuning learn leaves
+    /// it alone
+    fn set_adjusting(&self, adjusting: bool) {
+        if let Some(tuning_idx) = self.tuning_params.tuning_idx(self.param()) {
+            self.tuning_params.set_adjusting(tuning_idx, adjusting);
+        }
+    }
+
+    fn nudge(&mut self, cx: &mut EventContext, nudge_idx: usize) {
+        let value = self.param().value() + NUDGE_STEPS[nudge_idx];
+        self.set_value(cx, value);
+    }
+}
+
+impl View for TuningParamControl {
+    fn element(&self) -> Option<&'static str> {
+        Some("tuning-param-control")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|control_event, _meta| match *control_event {
+            ControlEvent::Tick => {
+                let now = Instant::now();
+                if let Some(held) = self.held_button.as_mut() {
+                    if now >= held.next_repeat {
+                        let interval = REPEAT_INTERVAL_START
+                            .mul_f32(REPEAT_ACCELERATION.powi(held.repeats as i32))
+                            .max(REPEAT_INTERVAL_MIN);
+                        held.repeats += 1;
+                        held.next_repeat = now + interval;
+                        let nudge_idx = held.nudge_idx;
+                        self.nudge(cx, nudge_idx);
+                    }
+                }
+
+                let value = self.param().value();
+                if value != self.last_value {
+                    self.last_value = value;
+                    self.flash_start = Some(now);
+                }
+            }
+        });
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::MouseDown(MouseButton::Left) => {
+                let pos = (cx.mouse().cursorx, cx.mouse().cursory);
+                match self.button_at(cx.bounds(), cx.scale_factor(), pos) {
+                    Some(ControlButton::Nudge(nudge_idx)) => {
+                        cx.capture();
+                        self.set_adjusting(true);
+                        cx.emit(ParamEvent::BeginSetParameter(self.param()).upcast());
+                        self.nudge(cx, nudge_idx);
+                        self.held_button = Some(HeldButton {
+                            nudge_idx,
+                            repeats: 0,
+                            next_repeat: Instant::now() + REPEAT_DELAY,
+                        });
+                    }
+                    Some(ControlButton::Just) => {
+                        if let Some(just_value) = self.just_value {
+                            self.set_value_gesture(cx, just_value);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            WindowEvent::MouseUp(MouseButton::Left) => {
+                if self.held_button.take().is_some() {
+                    cx.emit(ParamEvent::EndSetParameter(self.param()).upcast());
+                    self.set_adjusting(false);
+                    cx.release();
+                }
+            }
+            WindowEvent::MouseDoubleClick(MouseButton::Left) => {
+                let pos = (cx.mouse().cursorx, cx.mouse().cursory);
+                if self
+                    .button_at(cx.bounds(), cx.scale_factor(), pos)
+                    .is_none()
+                {
+                    let default_value = self.param().default_plain_value();
+                    self.set_value_gesture(cx, default_value);
+                }
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = cx.bounds();
+        let (font_id, mono_font_id) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let mouse = (cx.mouse().cursorx, cx.mouse().cursory);
+
+        let buttons = self.button_bounds(bounds, scale);
+        let buttons_left_x = buttons
+            .last()
+            .map_or(bounds.x + bounds.w, |(_, button_bounds)| button_bounds.x);
+
+        // Label and value, flashing after external changes
+        let mut label_path = vg::Path::new();
+        label_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            buttons_left_x - bounds.x - PADDING * scale * 0.5,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(&label_path, &vg::Paint::color(colors.base));
+        if let Some(flash_start) = self.flash_start {
+            let flash_progress = flash_start.elapsed().as_secs_f32() / FLASH_DURATION.as_secs_f32();
+            if flash_progress < 1.0 {
+                let mut flash_color = colors.text;
+                flash_color.set_alphaf(0.5 * (1.0 - flash_progress));
+                canvas.fill_path(&label_path, &vg::Paint::color(flash_color));
+            }
+        }
+
+        let mut text_paint = vg::Paint::color(colors.text);
+        text_paint.set_font_size(bounds.h * 0.5);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        text_paint.set_text_align(vg::Align::Left);
+        let _ = canvas.fill_text(
+            bounds.x + PADDING * scale,
+            bounds.y + bounds.h * 0.5,
+            self.label,
+            &text_paint,
+        );
+
+        mono_font_id.map(|f| text_paint.set_font(&[f]));
+        text_paint.set_text_align(vg::Align::Right);
+        let _ = canvas.fill_text(
+            buttons_left_x - PADDING * scale * 1.5,
+            bounds.y + bounds.h * 0.5,
+            self.param().to_string(),
+            &text_paint,
+        );
+
+        // Buttons
+        font_id.map(|f| text_paint.set_font(&[f]));
+        text_paint.set_text_align(vg::Align::Center);
+        for (button, button_bounds) in buttons {
+            let pressed = match (button, &self.held_button) {
+                (ControlButton::Nudge(nudge_idx), Some(held)) => nudge_idx == held.nudge_idx,
+                _ => false,
+            };
+            let mut button_path = vg::Path::new();
+            button_path.rounded_rect(
+                button_bounds.x,
+                button_bounds.y,
+                button_bounds.w,
+                button_bounds.h,
+                CORNER_RADIUS * scale,
+            );
+            canvas.fill_path(
+                &button_path,
+                &vg::Paint::color(if pressed {
+                    colors.text
+                } else if intersects_box(button_bounds, mouse) {
+                    colors.highlight
+                } else {
+                    colors.base
+                }),
+            );
+
+            let mut button_text_paint = text_paint.clone();
+            if pressed {
+                button_text_paint.set_color(colors.background);
+            }
+            let _ = canvas.fill_text(
+                button_bounds.x + button_bounds.w * 0.5,
+                button_bounds.y + button_bounds.h * 0.5,
+                match button {
+                    ControlButton::Nudge(nudge_idx) => NUDGE_LABELS[nudge_idx],
+                    ControlButton::Just => "J",
+                },
+                &button_text_paint,
+            );
+        }
+    }
+}
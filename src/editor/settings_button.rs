@@ -0,0 +1,90 @@
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+
+use crate::editor::color::*;
+use crate::editor::settings::SettingsEvent;
+use crate::editor::{intersects_box, make_icon_stroke_paint, snap_bounds};
+use crate::GridParams;
+use std::sync::Arc;
+
+use super::PADDING;
+
+/// Opens and closes the settings overlay
+pub struct SettingsButton {
+    open: bool,
+    grid_params: Arc<GridParams>,
+}
+
+impl SettingsButton {
+    pub fn new<LGridParams>(cx: &mut Context, grid_params: LGridParams) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            open: false,
+            grid_params: grid_params.get(cx),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for SettingsButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("settings-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => {
+                cx.emit(SettingsEvent::Toggle);
+            }
+            _ => {}
+        });
+        event.map(|settings_event, _meta| match *settings_event {
+            SettingsEvent::Toggle => self.open = !self.open,
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let highlighted: bool =
+            self.open || intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            crate::editor::CORNER_RADIUS * scale,
+        );
+        container_path.close();
+
+        let paint = vg::Paint::color(if self.open {
+            colors.text
+        } else if highlighted {
+            colors.highlight
+        } else {
+            colors.base
+        });
+        canvas.fill_path(&mut container_path, &paint);
+
+        let icon_line_width: f32 = PADDING * scale;
+        let icon_padding: f32 = PADDING * 1.5 * scale + icon_line_width * 0.5;
+
+        // Draw three horizontal lines
+        let mut icon_path = vg::Path::new();
+        for line_idx in 0..3 {
+            let line_y =
+                bounds.y + icon_padding + (bounds.h - icon_padding * 2.0) * line_idx as f32 * 0.5;
+            icon_path.move_to(bounds.x + icon_padding, line_y);
+            icon_path.line_to(bounds.x + bounds.w - icon_padding, line_y);
+        }
+
+        let icon_paint = make_icon_stroke_paint(colors.background, scale);
+
+        canvas.stroke_path(&mut icon_path, &icon_paint);
+    }
+}
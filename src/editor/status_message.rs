@@ -0,0 +1,85 @@
+use nih_plug_vizia::vizia::prelude::*;
+use nih_plug_vizia::vizia::vg;
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{snap_bounds, FontInfo, CORNER_RADIUS, PADDING};
+use crate::GridParams;
+
+pub enum StatusEvent {
+    /// Show a message in the status line
+    Show(String),
+    /// Hide the status line
+    Clear,
+}
+
+/// A line of text telling the user that something went wrong. Disappears when clicked.
+pub struct StatusMessage {
+    message: String,
+    grid_params: Arc<GridParams>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl StatusMessage {
+    pub fn new<LGridParams>(
+        cx: &mut Context,
+        message: String,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            message,
+            grid_params: grid_params.get(cx),
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+}
+
+impl View for StatusMessage {
+    fn element(&self) -> Option<&'static str> {
+        Some("status-message")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => {
+                cx.emit(StatusEvent::Clear);
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(&container_path, &vg::Paint::color(colors.base));
+
+        let mut text_paint = vg::Paint::color(colors.text);
+        text_paint.set_font_size(bounds.h * 0.4);
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Left);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + PADDING * scale,
+            bounds.y + bounds.h * 0.5,
+            &self.message,
+            &text_paint,
+        );
+    }
+}
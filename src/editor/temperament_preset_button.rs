@@ -0,0 +1,124 @@
+use nih_plug::prelude::*;
+use nih_plug_vizia::vizia::view::View;
+use nih_plug_vizia::vizia::{prelude::*, vg};
+use nih_plug_vizia::widgets::ParamEvent;
+
+use crate::tuning::*;
+use crate::{GridParams, TuningParams, MAX_TUNING_OFFSET};
+use std::sync::{Arc, Mutex};
+
+use crate::editor::color::*;
+use crate::editor::{intersects_box, snap_bounds, FontInfo, CORNER_RADIUS};
+
+/// Tunes primes 3, 5 and 7 to a historical temperament, and sets a tuning tolerance to match.
+/// Each click moves on to the next temperament in [`TemperamentPreset::ALL`].
+pub struct TemperamentPresetButton {
+    tuning_params: Arc<TuningParams>,
+    grid_params: Arc<GridParams>,
+
+    /// Index into [`TemperamentPreset::ALL`] of the last applied temperament, if any
+    preset_idx: Option<usize>,
+
+    // Need interior mutability to allow mutation from draw()
+    font_info: Mutex<FontInfo>,
+}
+
+impl TemperamentPresetButton {
+    pub fn new<LParams, LGridParams>(
+        cx: &mut Context,
+        tuning_params: LParams,
+        grid_params: LGridParams,
+    ) -> Handle<Self>
+    where
+        LParams: Lens<Target = Arc<TuningParams>>,
+        LGridParams: Lens<Target = Arc<GridParams>>,
+    {
+        Self {
+            tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
+            preset_idx: None,
+            font_info: Mutex::new(FontInfo::default()),
+        }
+        .build(cx, |_| {})
+    }
+
+    fn apply_preset(&self, cx: &mut EventContext, preset: TemperamentPreset) {
+        let (three, five, seven) = preset.apply();
+        for (param, tuning, just) in [
+            (&self.tuning_params.three, three, THREE_JUST_F32),
+            (&self.tuning_params.five, five, FIVE_JUST_F32),
+            (&self.tuning_params.seven, seven, SEVEN_JUST_F32),
+        ] {
+            // Keep within the parameter's range, in case a temperament is too far from just
+            let tuning = tuning.clamp(just - MAX_TUNING_OFFSET, just + MAX_TUNING_OFFSET);
+            cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+            cx.emit(ParamEvent::SetParameter(param, tuning).upcast());
+            cx.emit(ParamEvent::EndSetParameter(param).upcast());
+        }
+
+        let tolerance = &self.tuning_params.tolerance;
+        cx.emit(ParamEvent::BeginSetParameter(tolerance).upcast());
+        cx.emit(ParamEvent::SetParameter(tolerance, preset.tolerance()).upcast());
+        cx.emit(ParamEvent::EndSetParameter(tolerance).upcast());
+    }
+}
+
+impl View for TemperamentPresetButton {
+    fn element(&self) -> Option<&'static str> {
+        Some("temperament-preset-button")
+    }
+
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|window_event, _meta| match *window_event {
+            WindowEvent::PressDown { mouse: _ } => {
+                let preset_idx = self
+                    .preset_idx
+                    .map_or(0, |idx| (idx + 1) % TemperamentPreset::ALL.len());
+                self.preset_idx = Some(preset_idx);
+                self.apply_preset(cx, TemperamentPreset::ALL[preset_idx]);
+            }
+            _ => {}
+        });
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
+        let scale: f32 = cx.scale_factor() as f32;
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let (font_id, _) = self.font_info.lock().unwrap().load_and_get(canvas);
+        let highlighted: bool = intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+
+        let mut container_path = vg::Path::new();
+        container_path.rounded_rect(
+            bounds.x,
+            bounds.y,
+            bounds.w,
+            bounds.h,
+            CORNER_RADIUS * scale,
+        );
+        canvas.fill_path(
+            &container_path,
+            &vg::Paint::color(if highlighted {
+                colors.highlight
+            } else {
+                colors.base
+            }),
+        );
+
+        let label = self
+            .preset_idx
+            .map_or("MT", |idx| TemperamentPreset::ALL[idx].name());
+        let mut text_paint = vg::Paint::color(colors.text);
+        // Longer names need smaller text to fit
+        text_paint.set_font_size(bounds.h * if label.len() > 3 { 0.32 } else { 0.4 });
+        text_paint.set_text_baseline(vg::Baseline::Middle);
+        text_paint.set_text_align(vg::Align::Center);
+        font_id.map(|f| text_paint.set_font(&[f]));
+        let _ = canvas.fill_text(
+            bounds.x + bounds.w * 0.5,
+            bounds.y + bounds.h * 0.5,
+            label,
+            &text_paint,
+        );
+    }
+}
@@ -4,21 +4,51 @@ use nih_plug_vizia::vizia::{prelude::*, vg};
 use nih_plug_vizia::widgets::ParamEvent;
 use triple_buffer::Output;
 
+use crate::mts_esp::MtsClient;
 use crate::tuning::*;
-use crate::{TuningParams, Voices};
+use crate::{GridParams, TuningParams, Voices};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::time::Instant;
 
 use crate::editor::color::*;
-use crate::editor::{intersects_box, make_icon_stroke_paint};
+use crate::editor::{
+    intersects_box, make_icon_stroke_paint, run_ticks, snap_bounds, TICK_INTERVAL,
+};
 
 use super::PADDING;
 
 pub struct TuningLearnButton {
-    learn_active: bool,
+    /// Shared with the tick thread, which only ticks while learn is active
+    learn_active: Arc<AtomicBool>,
+    /// The tick thread stops once this is dropped along with the view
+    _alive: Arc<()>,
     tuning_params: Arc<TuningParams>,
+    /// For the root note, which the C offset tunes
+    grid_params: Arc<GridParams>,
     voices_output: Arc<Mutex<Output<Voices>>>,
+    mts_client: Arc<MtsClient>,
+    /// For ending gestures when the editor is closed, when events can no longer be sent
+    gui_context: Arc<dyn GuiContext>,
+
+    // Learn state for C, 3, 5, 7, and 11, in that order
+    learned_params: [LearnedParam; 5],
+
+    /// When the learn session last ticked, for smoothing
+    last_tick: Option<Instant>,
+}
+
+/// Tracks what the learn button has done to one parameter during the current learn session
+#[derive(Clone, Copy, Default)]
+struct LearnedParam {
+    /// Whether a gesture has been started, to be ended when learn is deactivated
+    gesture_active: bool,
+
+    /// The value detected on the previous tick
+    last_detected: Option<f32>,
+
+    /// The value eased toward the detected value, when smoothing
+    smoothed: Option<f32>,
 }
 
 pub enum TickEvent {
@@ -26,25 +56,41 @@ pub enum TickEvent {
 }
 
 impl TuningLearnButton {
-    pub fn new<LParams, LVoices>(
+    pub fn new<LParams, LGrid, LVoices, LMts>(
         cx: &mut Context,
         tuning_params: LParams,
+        grid_params: LGrid,
         voices_output: LVoices,
+        mts_client: LMts,
+        gui_context: Arc<dyn GuiContext>,
     ) -> Handle<Self>
     where
         LParams: Lens<Target = Arc<TuningParams>>,
+        LGrid: Lens<Target = Arc<GridParams>>,
         LVoices: Lens<Target = Arc<Mutex<Output<Voices>>>>,
+        LMts: Lens<Target = Arc<MtsClient>>,
     {
+        let learn_active = Arc::new(AtomicBool::new(false));
+        let alive = Arc::new(());
+        let (thread_learn_active, thread_alive) = (learn_active.clone(), Arc::downgrade(&alive));
         Self {
             tuning_params: tuning_params.get(cx),
+            grid_params: grid_params.get(cx),
             voices_output: voices_output.get(cx),
-            learn_active: false,
+            mts_client: mts_client.get(cx),
+            gui_context,
+            learn_active,
+            _alive: alive,
+            learned_params: [LearnedParam::default(); 5],
+            last_tick: None,
         }
-        .build(cx, |cx| {
-            // Emit an event ~60 times per second to update tuning
-            cx.spawn(move |cx_proxy| loop {
-                let _ = cx_proxy.emit(TickEvent::Tick);
-                thread::sleep(Duration::from_millis(16));
+        .build(cx, move |cx| {
+            // Update tuning while learn is active, until the editor is closed
+            cx.spawn(move |cx_proxy| {
+                run_ticks(thread_alive, TICK_INTERVAL, || {
+                    !thread_learn_active.load(Ordering::Relaxed)
+                        || cx_proxy.emit(TickEvent::Tick).is_ok()
+                })
             });
         })
     }
@@ -58,24 +104,30 @@ impl View for TuningLearnButton {
     fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
         event.map(|tick_event: &TickEvent, _meta| match *tick_event {
             TickEvent::Tick => {
-                if self.learn_active {
+                if self.learn_active.load(Ordering::Relaxed) {
                     self.learn_tuning(cx);
                 }
             }
         });
         event.map(|window_event, _meta| match *window_event {
             WindowEvent::PressDown { mouse: _ } => {
-                self.learn_active = !self.learn_active;
+                let learn_active = !self.learn_active.load(Ordering::Relaxed);
+                self.learn_active.store(learn_active, Ordering::Relaxed);
+                if !learn_active {
+                    self.end_learn_session(cx);
+                }
             }
             _ => {}
         });
     }
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let colors = theme_colors(self.grid_params.theme.value());
         let scale: f32 = cx.scale_factor() as f32;
-        let bounds = cx.bounds();
+        let bounds = snap_bounds(cx.bounds(), scale);
+        let learn_active = self.learn_active.load(Ordering::Relaxed);
         let highlighted: bool =
-            self.learn_active || intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
+            learn_active || intersects_box(bounds, (cx.mouse().cursorx, cx.mouse().cursory));
 
         let mut container_path = vg::Path::new();
         container_path.rounded_rect(
@@ -87,12 +139,12 @@ impl View for TuningLearnButton {
         );
         container_path.close();
 
-        let paint = vg::Paint::color(if self.learn_active {
-            TEXT_COLOR
+        let paint = vg::Paint::color(if learn_active {
+            colors.text
         } else if highlighted {
-            HIGHLIGHT_COLOR
+            colors.highlight
         } else {
-            BASE_COLOR
+            colors.base
         });
         canvas.fill_path(&mut container_path, &paint);
 
@@ -112,22 +164,134 @@ impl View for TuningLearnButton {
         );
         icon_path.close();
 
-        let icon_paint = make_icon_stroke_paint(BACKGROUND_COLOR, scale);
+        let icon_paint = make_icon_stroke_paint(colors.background, scale);
 
         canvas.stroke_path(&mut icon_path, &icon_paint);
     }
 }
 
+impl Drop for TuningLearnButton {
+    /// Closing the editor during a learn session would otherwise leave its gestures open in the
+    /// host
+    fn drop(&mut self) {
+        let param_setter = ParamSetter::new(self.gui_context.as_ref());
+        for param_idx in 0..self.learned_params.len() {
+            if self.learned_params[param_idx].gesture_active {
+                param_setter.end_set_parameter(self.tuning_param(param_idx));
+            }
+        }
+    }
+}
+
+// Changes smaller than this aren't written to parameters, to avoid flooding the host with
+// automation from rounding noise
+const LEARN_EPSILON_CENTS: f32 = 0.01;
+
+// Smoothing writes a little on every tick, so it needs bigger changes to write
+const SMOOTHED_LEARN_EPSILON_CENTS: f32 = 0.05;
+
 // How close an interval needs to be to its just interval to be autodetected
 const LEARN_RANGE: PitchClassDistance = PitchClassDistance::from_cents(40);
 
-const DEFAULT_C: PitchClass = PitchClass::from_microcents(0);
+// The eleventh harmonic needs a narrower range, so that 7/5 (~583 cents) isn't mistaken for it
+const ELEVEN_LEARN_RANGE: PitchClassDistance = PitchClassDistance::from_cents(15);
+
 const TUNE_C_TOLERANCE: PitchClassDistance =
     PitchClassDistance::from_microcents(50 * CENTS_TO_MICROCENTS);
 
 impl TuningLearnButton {
-    /// Attempts to tune C; and primes 3, 5, and 7; based on the sounding pitch classes
-    fn learn_tuning(&self, cx: &mut EventContext) {
+    fn tuning_param(&self, param_idx: usize) -> &FloatParam {
+        match param_idx {
+            0 => &self.tuning_params.c_offset,
+            1 => &self.tuning_params.three,
+            2 => &self.tuning_params.five,
+            3 => &self.tuning_params.seven,
+            _ => &self.tuning_params.eleven,
+        }
+    }
+
+    /// Writes a detected value to a parameter, unless nothing new was detected. With smoothing,
+    /// the parameter is eased toward the detected value over several ticks instead.
+    ///
+    /// Values are only written when the detection changes or is still being eased toward, not on
+    /// every tick. This way, adjusting a parameter by hand while learn is active isn't undone
+    /// until different notes are played. Parameters being dragged in the tuning controls aren't
+    /// written at all, and learn ends its own gesture on them so the two don't overlap.
+    ///
+    /// All writes in a learn session share one gesture per parameter. Locked tunings are never
+    /// written.
+    fn write_learned_value(
+        &mut self,
+        cx: &mut EventContext,
+        param_idx: usize,
+        detected: f32,
+        elapsed_secs: f32,
+    ) {
+        if self.tuning_params.learn_locked(param_idx) {
+            return;
+        }
+        if self.tuning_params.adjusting(param_idx) {
+            // Easing restarts from the dragged value afterwards
+            self.end_learned_param(cx, param_idx);
+            return;
+        }
+        let time_constant = self.tuning_params.learn_smoothing.value();
+        let epsilon = if time_constant > 0.0 {
+            SMOOTHED_LEARN_EPSILON_CENTS
+        } else {
+            LEARN_EPSILON_CENTS
+        };
+        let param_value = self.tuning_param(param_idx).value();
+        let learned_param = self.learned_params[param_idx];
+        self.learned_params[param_idx].last_detected = Some(detected);
+        let settled = learned_param
+            .smoothed
+            .map_or(true, |smoothed| (smoothed - detected).abs() <= epsilon);
+        if settled
+            && learned_param
+                .last_detected
+                .map_or(false, |last| (last - detected).abs() <= epsilon)
+        {
+            return;
+        }
+
+        let value = smooth_toward(
+            learned_param.smoothed.unwrap_or(param_value),
+            detected,
+            elapsed_secs,
+            time_constant,
+        );
+        self.learned_params[param_idx].smoothed = Some(value);
+        if (param_value - value).abs() <= epsilon {
+            return;
+        }
+
+        if !learned_param.gesture_active {
+            self.learned_params[param_idx].gesture_active = true;
+            cx.emit(ParamEvent::BeginSetParameter(self.tuning_param(param_idx)).upcast());
+        }
+        cx.emit(ParamEvent::SetParameter(self.tuning_param(param_idx), value).upcast());
+    }
+
+    /// Ends the gesture learn started on a parameter, if any, and forgets what was learned for it
+    fn end_learned_param(&mut self, cx: &mut EventContext, param_idx: usize) {
+        if self.learned_params[param_idx].gesture_active {
+            cx.emit(ParamEvent::EndSetParameter(self.tuning_param(param_idx)).upcast());
+        }
+        self.learned_params[param_idx] = LearnedParam::default();
+    }
+
+    /// Ends any gestures started during the learn session
+    fn end_learn_session(&mut self, cx: &mut EventContext) {
+        for param_idx in 0..self.learned_params.len() {
+            self.end_learned_param(cx, param_idx);
+        }
+        self.last_tick = None;
+    }
+
+    /// Attempts to tune C; and primes 3, 5, 7, and 11; based on the sounding pitch classes. With no
+    /// notes sounding, learns from an MTS-ESP master's tuning table instead, if there is one.
+    fn learn_tuning(&mut self, cx: &mut EventContext) {
         let mut voices_output = self.voices_output.lock().unwrap();
 
         let mut pitch_classes: Vec<PitchClass> = voices_output
@@ -136,110 +300,182 @@ impl TuningLearnButton {
             .map(|voice| voice.get_pitch_class())
             .collect();
         std::mem::drop(voices_output);
+        if pitch_classes.is_empty() {
+            let c_frequency = self.tuning_params.c_frequency.value();
+            if let Some(table_pitch_classes) = self.mts_client.table_pitch_classes(c_frequency) {
+                pitch_classes = table_pitch_classes;
+            }
+        }
         pitch_classes.sort_unstable();
         pitch_classes.dedup();
 
-        self.learn_c_tuning(cx, &pitch_classes);
-        self.learn_intervals_tuning(cx, &pitch_classes);
+        let now = Instant::now();
+        let elapsed_secs = self
+            .last_tick
+            .map_or(0.0, |last_tick| (now - last_tick).as_secs_f32());
+        self.last_tick = Some(now);
+
+        self.learn_c_tuning(cx, &pitch_classes, elapsed_secs);
+        self.learn_intervals_tuning(cx, &pitch_classes, elapsed_secs);
     }
 
-    /// Tunes C to the best approximation present in the given list of pitch classes.
-    /// Only pitch classes within 50 cents of C in MIDI (~262 Hz) are considered
-    fn learn_c_tuning(&self, cx: &mut EventContext, sorted_pitch_classes: &Vec<PitchClass>) {
-        // Tune C
+    /// Tunes the root note (C by default) to the best approximation present in the given list of
+    /// pitch classes. Only pitch classes within 50 cents of the root in 12-TET are considered
+    fn learn_c_tuning(
+        &mut self,
+        cx: &mut EventContext,
+        sorted_pitch_classes: &Vec<PitchClass>,
+        elapsed_secs: f32,
+    ) {
+        let root = root_pitch_class(self.grid_params.root_note.value().fifths());
+
+        // Tune the root
         let mut best_c: Option<PitchClass> = None;
         for pitch_class in sorted_pitch_classes {
-            if pitch_class.distance_to(DEFAULT_C) <= TUNE_C_TOLERANCE {
+            if pitch_class.distance_to(root) <= TUNE_C_TOLERANCE {
                 best_c = match best_c {
                     None => Some(*pitch_class),
-                    Some(c) => Some(
-                        if c.distance_to(DEFAULT_C) < pitch_class.distance_to(DEFAULT_C) {
-                            c
-                        } else {
-                            *pitch_class
-                        },
-                    ),
+                    Some(c) => Some(if c.distance_to(root) < pitch_class.distance_to(root) {
+                        c
+                    } else {
+                        *pitch_class
+                    }),
                 };
             }
         }
-        best_c.map(|new_c| {
-            let c_cents: f32 = new_c.to_cents_f32();
+        if let Some(new_c) = best_c {
+            let c_cents: f32 = (new_c - root).to_cents_f32();
             let zero_centered_c_cents: f32 = if c_cents > 600.0 {
                 c_cents - 1200.0
             } else {
                 c_cents
             };
-            cx.emit(ParamEvent::BeginSetParameter(&self.tuning_params.c_offset).upcast());
-            cx.emit(
-                ParamEvent::SetParameter(&self.tuning_params.c_offset, zero_centered_c_cents)
-                    .upcast(),
-            );
-            cx.emit(ParamEvent::EndSetParameter(&self.tuning_params.c_offset).upcast());
-        });
+            self.write_learned_value(cx, 0, zero_centered_c_cents, elapsed_secs);
+        }
     }
 
-    /// Tunes primes 3, 5, and 7 to the best approximation among the current sounding pitch classes.
-    /// Only considers approximations within [`LEARN_RANGE`] cents of the true interval.
+    /// Tunes primes 3, 5, 7, and 11 to the mean of their approximations among the current sounding
+    /// pitch classes. Only considers approximations within [`LEARN_RANGE`] cents of the true
+    /// interval.
     fn learn_intervals_tuning(
-        &self,
+        &mut self,
         cx: &mut EventContext,
         sorted_pitch_classes: &Vec<PitchClass>,
+        elapsed_secs: f32,
     ) {
-        // Tune intervals
-        let mut best_three: Option<PitchClass> = None;
-        let mut best_five: Option<PitchClass> = None;
-        let mut best_seven: Option<PitchClass> = None;
-
-        let update_best_tuning =
-            |best: &mut Option<PitchClass>, interval: PitchClass, target: PitchClass| {
-                let diff = interval.distance_to(target);
-                if diff <= LEARN_RANGE {
-                    match best {
-                        Some(best_tuning) => {
-                            if diff < best_tuning.distance_to(target) {
-                                *best = Some(interval);
-                            }
-                        }
-                        None => {
-                            *best = Some(interval);
-                        }
-                    }
-                }
-            };
-
-        let mut i = sorted_pitch_classes.iter();
-        while let Some(pc_a) = i.next() {
-            let mut j = i.clone();
-            while let Some(pc_b) = j.next() {
-                // Test A - B as well as B - A.
-                // For example, a tuning for the perfect fourth implies a one for the perfect fifth.
-                // This is true because this plugin assumes perfectly tuned octaves.
-                let interval: PitchClass = *pc_a - *pc_b;
-                let flipped_interval: PitchClass = -interval;
-
-                //nih_log!("{} {}", interval, flipped_interval);
-                update_best_tuning(&mut best_three, interval, THREE_JUST);
-                update_best_tuning(&mut best_five, interval, FIVE_JUST);
-                update_best_tuning(&mut best_seven, interval, SEVEN_JUST);
-                update_best_tuning(&mut best_three, flipped_interval, THREE_JUST);
-                update_best_tuning(&mut best_five, flipped_interval, FIVE_JUST);
-                update_best_tuning(&mut best_seven, flipped_interval, SEVEN_JUST);
+        for (param_idx, target, range) in [
+            (1, THREE_JUST, LEARN_RANGE),
+            (2, FIVE_JUST, LEARN_RANGE),
+            (3, SEVEN_JUST, LEARN_RANGE),
+            (4, ELEVEN_JUST, ELEVEN_LEARN_RANGE),
+        ] {
+            if let Some(tuning) = mean_interval(sorted_pitch_classes, target, range) {
+                self.write_learned_value(cx, param_idx, tuning, elapsed_secs);
             }
         }
+    }
+}
 
-        let mut update_tuning_param =
-            |tuning_param: &FloatParam, opt_tuning: Option<PitchClass>| match opt_tuning {
-                Some(tuning) => {
-                    // nih_dbg!(tuning);
-                    cx.emit(ParamEvent::BeginSetParameter(tuning_param).upcast());
-                    cx.emit(ParamEvent::SetParameter(tuning_param, tuning.to_cents_f32()).upcast());
-                    cx.emit(ParamEvent::EndSetParameter(tuning_param).upcast());
-                }
-                None => (),
+/// Returns the mean in cents of the intervals between pairs of pitch classes that are within
+/// `range` of `target`, if any are.
+///
+/// Both A - B and B - A are tested, since a tuning for the perfect fourth implies one for the
+/// perfect fifth when octaves are pure. Each pair counts once, by whichever is closer.
+fn mean_interval(
+    sorted_pitch_classes: &[PitchClass],
+    target: PitchClass,
+    range: PitchClassDistance,
+) -> Option<f32> {
+    let mut offsets_sum: f32 = 0.0;
+    let mut count: u32 = 0;
+    for (i, pc_a) in sorted_pitch_classes.iter().enumerate() {
+        for pc_b in &sorted_pitch_classes[i + 1..] {
+            let interval: PitchClass = *pc_a - *pc_b;
+            let flipped_interval: PitchClass = -interval;
+            let closest = if interval.distance_to(target) <= flipped_interval.distance_to(target) {
+                interval
+            } else {
+                flipped_interval
             };
+            if closest.distance_to(target) <= range {
+                // Offsets are centered on zero, so intervals on either side of the target average
+                // to it even where pitch classes wrap around
+                let offset_cents = (closest - target).to_cents_f32();
+                offsets_sum += if offset_cents > 600.0 {
+                    offset_cents - 1200.0
+                } else {
+                    offset_cents
+                };
+                count += 1;
+            }
+        }
+    }
+    (count > 0).then(|| target.to_cents_f32() + offsets_sum / count as f32)
+}
+
+/// Eases `current` toward `target` by an exponential decay with the given time constant, over
+/// `elapsed_secs`. Without a time constant, jumps straight to `target`.
+fn smooth_toward(current: f32, target: f32, elapsed_secs: f32, time_constant_secs: f32) -> f32 {
+    if time_constant_secs <= 0.0 {
+        return target;
+    }
+    current + (target - current) * (1.0 - (-elapsed_secs / time_constant_secs).exp())
+}
+
+#[cfg(test)]
+mod smooth_toward_tests {
+    use crate::editor::tuning_learn_button::smooth_toward;
+
+    #[test]
+    fn jumps_without_smoothing() {
+        assert_eq!(smooth_toward(700.0, 702.0, 0.016, 0.0), 702.0);
+    }
+
+    #[test]
+    fn eases_by_time_constant() {
+        // After one time constant, about 63% of the way there
+        let smoothed = smooth_toward(700.0, 710.0, 0.5, 0.5);
+        assert!((smoothed - 706.321).abs() < 0.001);
+        // No time has passed on a session's first tick
+        assert_eq!(smooth_toward(700.0, 710.0, 0.0, 0.5), 700.0);
+    }
+}
+
+#[cfg(test)]
+mod mean_interval_tests {
+    use crate::editor::tuning_learn_button::{mean_interval, LEARN_RANGE};
+    use crate::tuning::{PitchClass, FIVE_JUST, THREE_JUST};
+
+    fn sorted_pitch_classes(cents: &[f32]) -> Vec<PitchClass> {
+        let mut pitch_classes: Vec<PitchClass> = cents
+            .iter()
+            .map(|c| PitchClass::from_cents_f32(*c))
+            .collect();
+        pitch_classes.sort_unstable();
+        pitch_classes
+    }
+
+    #[test]
+    fn averages_slightly_different_fifths() {
+        // Fifths of 701, 702 and 703 cents stacked on C
+        let pitch_classes = sorted_pitch_classes(&[0.0, 701.0, 203.0, 906.0]);
+        let fifth = mean_interval(&pitch_classes, THREE_JUST, LEARN_RANGE).unwrap();
+        assert!((fifth - 702.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn counts_fourths_as_fifths_once() {
+        // G below C is a fourth of 499 cents, the same pair as a fifth of 701 cents
+        let pitch_classes = sorted_pitch_classes(&[0.0, 701.0]);
+        let fifth = mean_interval(&pitch_classes, THREE_JUST, LEARN_RANGE).unwrap();
+        assert!((fifth - 701.0).abs() < 0.001);
+    }
 
-        update_tuning_param(&self.tuning_params.three, best_three);
-        update_tuning_param(&self.tuning_params.five, best_five);
-        update_tuning_param(&self.tuning_params.seven, best_seven);
+    #[test]
+    fn ignores_intervals_out_of_range() {
+        let pitch_classes = sorted_pitch_classes(&[0.0, 386.0, 450.0]);
+        let third = mean_interval(&pitch_classes, FIVE_JUST, LEARN_RANGE).unwrap();
+        assert!((third - 386.0).abs() < 0.001);
+        assert_eq!(mean_interval(&pitch_classes, THREE_JUST, LEARN_RANGE), None);
     }
 }
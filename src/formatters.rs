@@ -0,0 +1,348 @@
+//! Host-facing text for grid offset parameters, describing them as moves on the lattice, for
+//! per-prime tuning tolerances, and for the C frequency.
+
+use std::sync::Arc;
+
+/// Interval that one step along a lattice axis represents
+pub struct AxisInterval {
+    pub singular: &'static str,
+    pub plural: &'static str,
+}
+
+// Intervals for each axis of the grid
+pub const X_AXIS_INTERVAL: AxisInterval = AxisInterval {
+    singular: "major third",
+    plural: "major thirds",
+};
+pub const Y_AXIS_INTERVAL: AxisInterval = AxisInterval {
+    singular: "fifth",
+    plural: "fifths",
+};
+
+// Labels for axes that are navigated one layer at a time
+pub const Z_AXIS_LABEL: &str = "7-limit layer";
+pub const W_AXIS_LABEL: &str = "11-limit layer";
+
+impl AxisInterval {
+    fn count(&self, count: u32) -> String {
+        format!(
+            "{} {}",
+            count,
+            if count == 1 {
+                self.singular
+            } else {
+                self.plural
+            }
+        )
+    }
+}
+
+/// Describes an X or Y offset, e.g. "2 fifths up" or "between 0 and 1 major third down (0.4)".
+/// Fractional offsets end with their exact distance, so that the text parses back to the same
+/// offset.
+pub fn grid_offset_to_string(axis: &AxisInterval, offset: f32) -> String {
+    let direction = if offset < 0.0 { "down" } else { "up" };
+    let magnitude = offset.abs();
+    if magnitude == 0.0 {
+        String::from("centered")
+    } else if magnitude.fract() == 0.0 {
+        format!("{} {}", axis.count(magnitude as u32), direction)
+    } else {
+        format!(
+            "between {} and {} {} ({})",
+            magnitude.floor() as u32,
+            axis.count(magnitude.ceil() as u32),
+            direction,
+            magnitude
+        )
+    }
+}
+
+/// Parses an X or Y offset. Accepts a signed number, optionally followed by the axis interval,
+/// e.g. "+2 fifths" or "-1.5". A direction of "up" or "down" can follow an unsigned number, e.g.
+/// "3 major thirds down". Also reads back [`grid_offset_to_string`]'s text.
+pub fn string_to_grid_offset(axis: &AxisInterval, string: &str) -> Option<f32> {
+    let string = string.trim().to_lowercase();
+    if string == "centered" {
+        return Some(0.0);
+    }
+    if let Some(between) = string.strip_prefix("between ") {
+        // Only the exact distance at the end matters
+        let (description, magnitude) = between.strip_suffix(')')?.rsplit_once('(')?;
+        let magnitude: f32 = magnitude.trim().parse().ok()?;
+        return match description.trim_end().rsplit_once(' ')?.1 {
+            "up" => Some(magnitude),
+            "down" => Some(-magnitude),
+            _ => None,
+        };
+    }
+
+    let signed = string.starts_with(['+', '-']);
+    let (number, rest) = split_number(&string)?;
+    let mut rest = rest.trim();
+    // Check the plural first, since it starts with the singular
+    for interval_name in [axis.plural, axis.singular] {
+        if let Some(stripped) = rest.strip_prefix(interval_name) {
+            rest = stripped.trim();
+            break;
+        }
+    }
+    match rest {
+        "" => Some(number),
+        // A sign and a direction could contradict each other, like "-2 up"
+        "up" if !signed => Some(number),
+        "down" if !signed => Some(-number),
+        _ => None,
+    }
+}
+
+/// Describes a Z or W offset, e.g. "7-limit layer +1"
+pub fn grid_layer_to_string(label: &str, layer: i32) -> String {
+    format!("{} {:+}", label, layer)
+}
+
+/// Parses a Z or W offset, with or without the axis label
+pub fn string_to_grid_layer(label: &str, string: &str) -> Option<i32> {
+    let string = string.trim().to_lowercase();
+    let string = string.strip_prefix(label).unwrap_or(&string).trim();
+    string.strip_prefix('+').unwrap_or(string).parse().ok()
+}
+
+// Text for per-prime tolerances of zero, which fall back to the global tolerance
+const GLOBAL_TOLERANCE_TEXT: &str = "Global";
+
+/// Describes a per-prime tolerance in cents, or "Global" if it's zero
+pub fn prime_tolerance_to_string(tolerance: f32) -> String {
+    if tolerance <= 0.0 {
+        String::from(GLOBAL_TOLERANCE_TEXT)
+    } else {
+        format!("{:.3}", tolerance)
+    }
+}
+
+/// Parses a per-prime tolerance in cents. "Global" is zero.
+pub fn string_to_prime_tolerance(string: &str) -> Option<f32> {
+    let string = string.trim();
+    if string.eq_ignore_ascii_case(GLOBAL_TOLERANCE_TEXT) {
+        return Some(0.0);
+    }
+    string.parse().ok()
+}
+
+// A4 is this many cents above middle C, in 12-TET
+const A4_CENTS: f32 = 900.0;
+
+/// Describes the frequency of middle C in Hz, along with the frequency of A4 a 12-TET major
+/// sixth above it
+pub fn c_frequency_to_string(c_frequency: f32) -> String {
+    format!(
+        "{:.3} (A4 = {:.1})",
+        c_frequency,
+        c_frequency * (A4_CENTS / 1200.0).exp2()
+    )
+}
+
+/// Parses the frequency of middle C in Hz. Ensembles tuning to A can enter A4's frequency
+/// instead, like "A4 = 442" or "A = 442".
+pub fn string_to_c_frequency(string: &str) -> Option<f32> {
+    let string = string.trim();
+    match string.split_once('=') {
+        Some((note, a4_frequency))
+            if ["a", "a4"].contains(&note.trim().to_ascii_lowercase().as_str()) =>
+        {
+            let a4_frequency: f32 = a4_frequency.trim().parse().ok()?;
+            Some(a4_frequency * (-A4_CENTS / 1200.0).exp2())
+        }
+        // Also reads back this parameter's own text, ignoring the A4 frequency after C's
+        _ => split_number(string).map(|(c_frequency, _)| c_frequency),
+    }
+}
+
+/// Splits a string into a leading signed number and the rest of the string
+fn split_number(string: &str) -> Option<(f32, &str)> {
+    let number_len = string
+        .char_indices()
+        .find(|(idx, c)| !(c.is_ascii_digit() || *c == '.' || (*idx == 0 && "+-".contains(*c))))
+        .map_or(string.len(), |(idx, _)| idx);
+    let (number, rest) = string.split_at(number_len);
+    let number = number.strip_prefix('+').unwrap_or(number);
+    Some((number.parse().ok()?, rest))
+}
+
+pub fn v2s_prime_tolerance() -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(prime_tolerance_to_string)
+}
+
+pub fn s2v_prime_tolerance() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(string_to_prime_tolerance)
+}
+
+pub fn v2s_c_frequency() -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(c_frequency_to_string)
+}
+
+pub fn s2v_c_frequency() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(string_to_c_frequency)
+}
+
+pub fn v2s_grid_offset(axis: &'static AxisInterval) -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(move |offset| grid_offset_to_string(axis, offset))
+}
+
+pub fn s2v_grid_offset(
+    axis: &'static AxisInterval,
+) -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(move |string| string_to_grid_offset(axis, string))
+}
+
+pub fn v2s_grid_layer(label: &'static str) -> Arc<dyn Fn(i32) -> String + Send + Sync> {
+    Arc::new(move |layer| grid_layer_to_string(label, layer))
+}
+
+pub fn s2v_grid_layer(label: &'static str) -> Arc<dyn Fn(&str) -> Option<i32> + Send + Sync> {
+    Arc::new(move |string| string_to_grid_layer(label, string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_offset_to_string() {
+        assert_eq!(grid_offset_to_string(&Y_AXIS_INTERVAL, 2.0), "2 fifths up");
+        assert_eq!(
+            grid_offset_to_string(&X_AXIS_INTERVAL, -3.0),
+            "3 major thirds down"
+        );
+        assert_eq!(
+            grid_offset_to_string(&X_AXIS_INTERVAL, 1.0),
+            "1 major third up"
+        );
+        assert_eq!(grid_offset_to_string(&Y_AXIS_INTERVAL, 0.0), "centered");
+        assert_eq!(
+            grid_offset_to_string(&Y_AXIS_INTERVAL, 2.5),
+            "between 2 and 3 fifths up (2.5)"
+        );
+        assert_eq!(
+            grid_offset_to_string(&X_AXIS_INTERVAL, -0.4),
+            "between 0 and 1 major third down (0.4)"
+        );
+    }
+
+    #[test]
+    fn test_string_to_grid_offset() {
+        assert_eq!(
+            string_to_grid_offset(&Y_AXIS_INTERVAL, "+2 fifths"),
+            Some(2.0)
+        );
+        assert_eq!(
+            string_to_grid_offset(&Y_AXIS_INTERVAL, "1 fifth up"),
+            Some(1.0)
+        );
+        assert_eq!(
+            string_to_grid_offset(&X_AXIS_INTERVAL, "3 Major Thirds down"),
+            Some(-3.0)
+        );
+        assert_eq!(
+            string_to_grid_offset(&X_AXIS_INTERVAL, " -1.5 "),
+            Some(-1.5)
+        );
+        assert_eq!(
+            string_to_grid_offset(&X_AXIS_INTERVAL, "centered"),
+            Some(0.0)
+        );
+        assert_eq!(
+            string_to_grid_offset(&Y_AXIS_INTERVAL, "between 2 and 3 fifths up (2.5)"),
+            Some(2.5)
+        );
+        assert_eq!(
+            string_to_grid_offset(&X_AXIS_INTERVAL, "between 0 and 1 major third down (0.4)"),
+            Some(-0.4)
+        );
+        assert_eq!(string_to_grid_offset(&Y_AXIS_INTERVAL, "-2 down"), None);
+        assert_eq!(
+            string_to_grid_offset(&Y_AXIS_INTERVAL, "+2 fifths up"),
+            None
+        );
+        assert_eq!(
+            string_to_grid_offset(&Y_AXIS_INTERVAL, "between 2 and 3 fifths up"),
+            None
+        );
+        assert_eq!(string_to_grid_offset(&X_AXIS_INTERVAL, "2 fifths"), None);
+        assert_eq!(string_to_grid_offset(&Y_AXIS_INTERVAL, "fifths"), None);
+        assert_eq!(string_to_grid_offset(&Y_AXIS_INTERVAL, ""), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for offset in [
+            -20.0,
+            -3.0,
+            -1.0,
+            0.0,
+            1.0,
+            7.0,
+            20.0,
+            -19.999,
+            -2.5,
+            -0.4,
+            0.001,
+            0.1,
+            1.0 / 3.0,
+            6.75,
+        ] {
+            for axis in [&X_AXIS_INTERVAL, &Y_AXIS_INTERVAL] {
+                assert_eq!(
+                    string_to_grid_offset(axis, &grid_offset_to_string(axis, offset)),
+                    Some(offset)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_prime_tolerance() {
+        assert_eq!(prime_tolerance_to_string(0.0), "Global");
+        assert_eq!(prime_tolerance_to_string(2.5), "2.500");
+        assert_eq!(string_to_prime_tolerance("global"), Some(0.0));
+        assert_eq!(string_to_prime_tolerance(" 2.5 "), Some(2.5));
+        assert_eq!(string_to_prime_tolerance("loose"), None);
+    }
+
+    #[test]
+    fn test_c_frequency() {
+        let close = |a: Option<f32>, b: f32| (a.unwrap() - b).abs() < 0.001;
+        assert_eq!(c_frequency_to_string(261.6256), "261.626 (A4 = 440.0)");
+        assert!(close(string_to_c_frequency("261.626"), 261.626));
+        assert!(close(
+            string_to_c_frequency("261.626 (A4 = 440.0)"),
+            261.626
+        ));
+        assert!(close(string_to_c_frequency("A4 = 442"), 262.8148));
+        assert!(close(string_to_c_frequency("a=415"), 246.7605));
+        assert_eq!(string_to_c_frequency("B4 = 442"), None);
+        assert_eq!(string_to_c_frequency("A4 = high"), None);
+    }
+
+    #[test]
+    fn test_grid_layer() {
+        assert_eq!(grid_layer_to_string(Z_AXIS_LABEL, 1), "7-limit layer +1");
+        assert_eq!(grid_layer_to_string(Z_AXIS_LABEL, -2), "7-limit layer -2");
+        assert_eq!(grid_layer_to_string(W_AXIS_LABEL, 0), "11-limit layer +0");
+        assert_eq!(
+            string_to_grid_layer(Z_AXIS_LABEL, "7-limit layer +1"),
+            Some(1)
+        );
+        assert_eq!(
+            string_to_grid_layer(W_AXIS_LABEL, "11-limit layer -1"),
+            Some(-1)
+        );
+        assert_eq!(string_to_grid_layer(Z_AXIS_LABEL, "-2"), Some(-2));
+        assert_eq!(string_to_grid_layer(Z_AXIS_LABEL, "+3"), Some(3));
+        assert_eq!(string_to_grid_layer(Z_AXIS_LABEL, "layer"), None);
+        assert_eq!(
+            string_to_grid_layer(Z_AXIS_LABEL, "11-limit layer +1"),
+            None
+        );
+    }
+}
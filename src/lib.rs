@@ -1,29 +1,61 @@
+use crate::command::{command_queue, handle_command, AudioCommand, CommandSender};
+use crate::editor::lattice::grid::{MAX_NODE_SIZE, MIN_NODE_SIZE, NODE_SIZE};
+use crate::formatters::*;
 use crate::midi::{MidiVoice, VoiceKey};
+use crate::mts_esp::MtsClient;
 use heapless::FnvIndexMap;
-use midi::update_midi_voices;
+use midi::{
+    release_latched_voices, remove_disabled_channel_voices, sort_by_timing, update_midi_voices,
+    update_pitch_bends, ChannelConfig, ChannelStates, VoiceOptions, VoicesPublisher, ALL_CHANNELS,
+    DEFAULT_PITCH_BEND_RANGE,
+};
 use nih_plug::prelude::*;
 use nih_plug_vizia::ViziaState;
+use serde::{Deserialize, Serialize};
 use tuning::*;
 
-use std::sync::atomic::AtomicU8;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Instant;
 
-use triple_buffer::{Input, Output, TripleBuffer};
+use rtrb::Consumer;
+use triple_buffer::{Output, TripleBuffer};
 
 mod assets;
+mod command;
 mod editor;
+mod formatters;
 mod midi;
+mod mts_esp;
 mod tuning;
 
-type Voices = FnvIndexMap<VoiceKey, MidiVoice, 256>;
+/// Most voices that are tracked at once. Beyond this, the oldest voices are evicted.
+const MAX_VOICES: usize = 256;
+
+type Voices = FnvIndexMap<VoiceKey, MidiVoice, MAX_VOICES>;
+
+/// Most events sorted at once. Blocks with more events are sorted in chunks.
+const MAX_BLOCK_EVENTS: usize = 512;
 
 struct MidiLattice {
     params: Arc<MidiLatticeParams>,
 
     voices: Voices,
-    voices_input: Input<Voices>,
+    channel_states: ChannelStates,
+    /// Voice options as of the last `process()` call, to notice when the parameters change
+    voice_options: VoiceOptions,
+    voices_publisher: VoicesPublisher,
+    /// Events from the current block, to be sorted by timing before they're applied
+    event_buffer: heapless::Vec<NoteEvent<()>, MAX_BLOCK_EVENTS>,
     voices_output: Arc<Mutex<Output<Voices>>>,
+
+    // Commands from the editor, drained at the start of each `process()` call
+    command_consumer: Consumer<AudioCommand>,
+    command_sender: Arc<Mutex<CommandSender>>,
+
+    // Tunes incoming notes when an MTS-ESP master is present. Shared with the editor, to learn
+    // the master's tuning.
+    mts_client: Arc<MtsClient>,
 }
 
 #[derive(Params)]
@@ -37,6 +69,18 @@ pub struct MidiLatticeParams {
 
     #[nested(group = "grid")]
     pub grid_params: Arc<GridParams>,
+
+    /// Channels whose notes are shown, one bit per channel. See [`midi::ChannelMask`].
+    #[persist = "enabled-channels"]
+    pub enabled_channels: Arc<AtomicU16>,
+
+    /// Whether each channel's notes are colored, outlined or hidden
+    #[persist = "channel-roles"]
+    pub channel_roles: Arc<RwLock<ChannelConfig>>,
+
+    /// Colors picked in the settings overlay, which replace the color palette's
+    #[persist = "custom-colors"]
+    pub custom_colors: Arc<RwLock<CustomColors>>,
 }
 
 #[derive(Params)]
@@ -49,6 +93,14 @@ pub struct GridParams {
     #[persist = "grid-height"]
     pub height: Arc<AtomicU8>,
 
+    /// Size of grid nodes, in tenths of a logical pixel. Read it with [`GridParams::node_size()`].
+    #[persist = "node-size"]
+    pub node_size: Arc<AtomicU32>,
+
+    /// Pixels per logical pixel of exported PNG images. Read it with [`GridParams::png_scale()`].
+    #[persist = "png-scale"]
+    pub png_scale: Arc<AtomicU8>,
+
     // X offset of the grid from the origin, C
     #[id = "grid-x"]
     pub x: FloatParam,
@@ -61,6 +113,10 @@ pub struct GridParams {
     #[id = "grid-z"]
     pub z: IntParam,
 
+    // W offset of the grid from the origin, C. Each step is a factor of 11.
+    #[id = "grid-w"]
+    pub w: IntParam,
+
     // How many seconds a note remains highlighted after release
     #[id = "highlight-time"]
     pub highlight_time: FloatParam,
@@ -69,6 +125,87 @@ pub struct GridParams {
     #[id = "display-z-axis"]
     pub show_z_axis: EnumParam<ShowZAxis>,
 
+    // Whether to show the W axis (representing the prime factor 11)
+    #[id = "display-w-axis"]
+    pub show_w_axis: EnumParam<ShowWAxis>,
+
+    // Note at the origin of the lattice, which the C offset tunes
+    #[id = "root-note"]
+    pub root_note: EnumParam<RootNote>,
+
+    // Whether note names show the octaves of sounding notes, in Helmholtz notation
+    #[id = "note-naming"]
+    pub note_naming: EnumParam<NoteNaming>,
+
+    // Whether accidentals are written in ASCII or with HEJI-style symbols
+    #[id = "accidentals"]
+    pub accidentals: EnumParam<Accidentals>,
+
+    // What nodes show: their note name and cents, or other ways of writing their pitch class
+    #[id = "node-label"]
+    pub node_label: EnumParam<NodeLabel>,
+
+    // Whether the second row of node text shows fractional cents, frequency, or deviation from
+    // 12-TET
+    #[id = "tuning-detail"]
+    pub tuning_detail: EnumParam<TuningDetail>,
+
+    // Octave that nodes' frequencies are shown in. Octave 4 starts at middle C.
+    #[id = "frequency-octave"]
+    pub frequency_octave: IntParam,
+
+    // Whether released notes stay on the lattice until they're struck again or cleared
+    #[id = "latch"]
+    pub latch: BoolParam,
+
+    // Semitones of pitch bend at either end of the pitch bend wheel, for each channel
+    #[id = "pitch-bend-range"]
+    pub pitch_bend_range: FloatParam,
+
+    // Equal division of the octave that incoming notes are steps of, counting from middle C.
+    // 12 for notes that aren't retuned.
+    #[id = "input-edo"]
+    pub input_edo: IntParam,
+
+    // Whether voices held only by the sustain pedal are drawn dimmer than held ones
+    #[id = "dim-sustained"]
+    pub dim_sustained: BoolParam,
+
+    // Whether quieter notes are drawn fainter and thinner
+    #[id = "velocity-affects-color"]
+    pub velocity_affects_color: BoolParam,
+
+    // Whether lines connect neighboring nodes, by fifths and thirds
+    #[id = "show-edges"]
+    pub show_edges: BoolParam,
+
+    // Whether lines connect neighboring nodes with sounding notes, showing a chord's intervals.
+    // Includes mini nodes a step apart by 7.
+    #[id = "show-intervals"]
+    pub show_intervals: BoolParam,
+
+    // Whether the sounding notes are named as a harmonic series chord, like "4:5:6:7 on C", or as
+    // a ratio if there are two
+    #[id = "show-chord"]
+    pub show_chord: BoolParam,
+
+    // Which sounding notes are listed with their distance in cents from the closest node
+    #[id = "voice-deviations"]
+    pub voice_deviations: EnumParam<VoiceDeviations>,
+
+    // Whether the interval between the last two notes struck is shown, for ear training
+    #[id = "show-last-interval"]
+    pub show_last_interval: BoolParam,
+
+    // Whether the editor is drawn light on dark or dark on light. The window's background only
+    // changes when the editor is reopened.
+    #[id = "theme"]
+    pub theme: EnumParam<Theme>,
+
+    // Colors of the channels with fixed colors
+    #[id = "color-palette"]
+    pub color_palette: EnumParam<ColorPalette>,
+
     // The pitch with the "darkest" color, on channels colored by pitch
     #[id = "darkest-pitch"]
     pub darkest_pitch: FloatParam,
@@ -85,6 +222,138 @@ pub enum ShowZAxis {
     No,
 }
 
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
+pub enum ShowWAxis {
+    Yes,
+    Auto,
+    No,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
+pub enum NodeLabel {
+    #[name = "Note Name and Cents"]
+    NoteNameAndCents,
+    // Just intonation ratio instead of cents, for nodes tuned close enough to just
+    Ratio,
+    // Counts of each prime instead of cents
+    Monzo,
+    #[name = "Cents Only"]
+    CentsOnly,
+    // No text at all, for small windows and large grids
+    #[name = "Colors Only"]
+    ColorsOnly,
+}
+
+// Spelled as in the most common key signatures
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
+pub enum RootNote {
+    C,
+    #[name = "Db"]
+    DFlat,
+    D,
+    #[name = "Eb"]
+    EFlat,
+    E,
+    F,
+    #[name = "F#"]
+    FSharp,
+    G,
+    #[name = "Ab"]
+    AFlat,
+    A,
+    #[name = "Bb"]
+    BFlat,
+    B,
+}
+
+impl RootNote {
+    /// Number of fifths from C to the root
+    pub fn fifths(&self) -> i32 {
+        match self {
+            RootNote::C => 0,
+            RootNote::DFlat => -5,
+            RootNote::D => 2,
+            RootNote::EFlat => -3,
+            RootNote::E => 4,
+            RootNote::F => -1,
+            RootNote::FSharp => 6,
+            RootNote::G => 1,
+            RootNote::AFlat => -4,
+            RootNote::A => 3,
+            RootNote::BFlat => -2,
+            RootNote::B => 5,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
+pub enum NoteNaming {
+    // Uppercase letters without octaves
+    Letter,
+    // Letters cased and marked by octave, for nodes with sounding notes
+    Helmholtz,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
+pub enum Accidentals {
+    // "#", "b", and "+" or "-" for syntonic commas
+    #[name = "ASCII"]
+    Ascii,
+    // "♯", "♭", arrows for syntonic commas and symbols for septimal commas
+    #[name = "HEJI"]
+    Heji,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
+pub enum TuningDetail {
+    Cents,
+    Hertz,
+    // Cents above or below the closest 12-TET semitone
+    #[name = "12-TET Deviation"]
+    Deviation,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum, Clone, Copy)]
+pub enum VoiceDeviations {
+    Off,
+    // Only notes that don't match any node on the lattice
+    Unmatched,
+    // Matching notes too, which are within the tuning tolerance of a node
+    All,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum, Clone, Copy)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Enum, Clone, Copy)]
+pub enum ColorPalette {
+    Standard,
+    // Told apart by lightness as well as hue, for any color vision deficiency. Saved by its index,
+    // so it stays second, and palettes added later go after it.
+    #[name = "Colorblind Friendly"]
+    ColorblindFriendly,
+    // For one kind of color vision deficiency each
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+    // Only bright colors, that stand out from the background
+    #[name = "High Contrast"]
+    HighContrast,
+}
+
+/// Colors that replace the color palette's, packed as 0xRRGGBB. `None` keeps the palette's color.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct CustomColors {
+    /// Colors of channels 1 to 9
+    pub channels: [Option<u32>; 9],
+    /// Ends of the pitch gradient for channels 10 to 16
+    pub darkest: Option<u32>,
+    pub brightest: Option<u32>,
+}
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Enum)]
 pub enum NoteColorScheme {
     Channel,
@@ -93,11 +362,16 @@ pub enum NoteColorScheme {
 
 const MAX_GRID_OFFSET: f32 = 20.0;
 
+/// Largest scale of exported PNG images. Large grids get too big for graphics cards above this.
+pub const MAX_PNG_SCALE: u8 = 4;
+
 impl Default for GridParams {
     fn default() -> Self {
         Self {
-            width: Arc::new(AtomicU8::new(7)),
+            width: Arc::new(AtomicU8::new(editor::MIN_GRID_WIDTH)),
             height: Arc::new(AtomicU8::new(7)),
+            node_size: Arc::new(AtomicU32::new((NODE_SIZE * 10.0) as u32)),
+            png_scale: Arc::new(AtomicU8::new(2)),
             x: FloatParam::new(
                 "Grid X",
                 0.0,
@@ -105,7 +379,9 @@ impl Default for GridParams {
                     min: -MAX_GRID_OFFSET,
                     max: MAX_GRID_OFFSET,
                 },
-            ),
+            )
+            .with_value_to_string(v2s_grid_offset(&X_AXIS_INTERVAL))
+            .with_string_to_value(s2v_grid_offset(&X_AXIS_INTERVAL)),
             y: FloatParam::new(
                 "Grid Y",
                 0.0,
@@ -113,7 +389,9 @@ impl Default for GridParams {
                     min: -MAX_GRID_OFFSET,
                     max: MAX_GRID_OFFSET,
                 },
-            ),
+            )
+            .with_value_to_string(v2s_grid_offset(&Y_AXIS_INTERVAL))
+            .with_string_to_value(s2v_grid_offset(&Y_AXIS_INTERVAL)),
             z: IntParam::new(
                 "Grid Z",
                 0,
@@ -121,7 +399,19 @@ impl Default for GridParams {
                     min: -MAX_GRID_OFFSET as i32,
                     max: MAX_GRID_OFFSET as i32,
                 },
-            ),
+            )
+            .with_value_to_string(v2s_grid_layer(Z_AXIS_LABEL))
+            .with_string_to_value(s2v_grid_layer(Z_AXIS_LABEL)),
+            w: IntParam::new(
+                "Grid W",
+                0,
+                IntRange::Linear {
+                    min: -MAX_GRID_OFFSET as i32,
+                    max: MAX_GRID_OFFSET as i32,
+                },
+            )
+            .with_value_to_string(v2s_grid_layer(W_AXIS_LABEL))
+            .with_string_to_value(s2v_grid_layer(W_AXIS_LABEL)),
             highlight_time: FloatParam::new(
                 "Note Highlight (sec)",
                 1.0,
@@ -132,6 +422,37 @@ impl Default for GridParams {
                 },
             ),
             show_z_axis: EnumParam::new("Show Z Axis", ShowZAxis::Auto),
+            show_w_axis: EnumParam::new("Show W Axis", ShowWAxis::Auto),
+            root_note: EnumParam::new("Root Note", RootNote::C),
+            note_naming: EnumParam::new("Note Naming", NoteNaming::Letter),
+            accidentals: EnumParam::new("Accidentals", Accidentals::Ascii),
+            node_label: EnumParam::new("Node Label", NodeLabel::NoteNameAndCents),
+            tuning_detail: EnumParam::new("Tuning Detail", TuningDetail::Cents),
+            frequency_octave: IntParam::new(
+                "Frequency Octave",
+                4,
+                IntRange::Linear { min: 0, max: 8 },
+            ),
+            latch: BoolParam::new("Latch Notes", false),
+            pitch_bend_range: FloatParam::new(
+                "Pitch Bend Range (semitones)",
+                DEFAULT_PITCH_BEND_RANGE,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 96.0,
+                },
+            )
+            .with_step_size(1.0),
+            input_edo: IntParam::new("Input EDO", 12, IntRange::Linear { min: 5, max: 72 }),
+            dim_sustained: BoolParam::new("Dim Sustained Notes", false),
+            velocity_affects_color: BoolParam::new("Velocity Affects Color", true),
+            show_edges: BoolParam::new("Show Edges", false),
+            show_intervals: BoolParam::new("Show Intervals", false),
+            show_chord: BoolParam::new("Show Chord", false),
+            voice_deviations: EnumParam::new("Voice Deviations", VoiceDeviations::Off),
+            show_last_interval: BoolParam::new("Show Last Interval", false),
+            theme: EnumParam::new("Theme", Theme::Dark),
+            color_palette: EnumParam::new("Color Palette", ColorPalette::Standard),
             darkest_pitch: FloatParam::new(
                 "Darkest pitch",
                 30.0,
@@ -155,6 +476,7 @@ impl Default for GridParams {
 /// Tuning information for each prime harmonic, in cents
 #[derive(Params)]
 pub struct TuningParams {
+    // Offset of the root note from 12-TET. The root is C unless GridParams chooses another.
     #[id = "tuning-c-offset"]
     c_offset: FloatParam,
 
@@ -167,13 +489,58 @@ pub struct TuningParams {
     #[id = "tuning-seven"]
     seven: FloatParam,
 
+    #[id = "tuning-eleven"]
+    eleven: FloatParam,
+
     #[id = "tuning-tolerance"]
     tolerance: FloatParam,
+
+    // Tolerances for nodes with factors of 3, 5 or 7. Zero falls back to the global tolerance.
+    #[id = "tuning-tolerance-three"]
+    tolerance_three: FloatParam,
+
+    #[id = "tuning-tolerance-five"]
+    tolerance_five: FloatParam,
+
+    #[id = "tuning-tolerance-seven"]
+    tolerance_seven: FloatParam,
+
+    // Size of the octave. Only affects how pitches are displayed; pitch classes still repeat
+    // every 1200 cents.
+    #[id = "tuning-octave"]
+    octave: FloatParam,
+
+    // Frequency of C without the C tuning offset, used to show nodes' frequencies
+    #[id = "tuning-c-frequency"]
+    c_frequency: FloatParam,
+
+    // Time constant that tuning learn eases parameters toward detected tunings with. At zero,
+    // detected tunings are set at once.
+    #[id = "tuning-learn-smoothing"]
+    learn_smoothing: FloatParam,
+
+    // Tunings that tuning learn leaves alone, one bit each for C, 3, 5, 7 and 11
+    #[persist = "learn-locks"]
+    learn_locks: Arc<AtomicU8>,
+
+    // Tunings being dragged in the editor's tuning controls, with the same bits as learn_locks.
+    // Not saved.
+    adjusting: AtomicU8,
 }
 
 // Range for the tuning parameter for each prime harmonic
 const MAX_TUNING_OFFSET: f32 = 40.0;
 
+// The eleventh harmonic is almost a quarter tone from 12-TET, so its range is wider to include
+// 12-TET's tritone
+const MAX_ELEVEN_OFFSET: f32 = 60.0;
+
+// Range for stretching or compressing the octave
+const MAX_OCTAVE_STRETCH: f32 = 30.0;
+
+// Largest tuning tolerance, just under half a step of 12-TET
+const MAX_TOLERANCE: f32 = 49.999;
+
 impl Default for TuningParams {
     fn default() -> Self {
         Self {
@@ -209,19 +576,176 @@ impl Default for TuningParams {
                     max: SEVEN_JUST_F32 + MAX_TUNING_OFFSET,
                 },
             ),
+            eleven: FloatParam::new(
+                "Eleventh Harmonic (cents)",
+                ELEVEN_JUST_F32,
+                FloatRange::Linear {
+                    min: ELEVEN_JUST_F32 - MAX_ELEVEN_OFFSET,
+                    max: ELEVEN_JUST_F32 + MAX_ELEVEN_OFFSET,
+                },
+            ),
             tolerance: FloatParam::new(
                 "Tuning Tolerance (cents)",
                 0.5,
                 FloatRange::Skewed {
                     min: 0.001,
-                    max: 49.999,
+                    max: MAX_TOLERANCE,
                     factor: FloatRange::skew_factor(-2.5),
                 },
             ),
+            tolerance_three: prime_tolerance_param("Fifth Tolerance (cents)"),
+            tolerance_five: prime_tolerance_param("Major Third Tolerance (cents)"),
+            tolerance_seven: prime_tolerance_param("Harmonic Seventh Tolerance (cents)"),
+            octave: FloatParam::new(
+                "Octave (cents)",
+                1200.0,
+                FloatRange::Linear {
+                    min: 1200.0 - MAX_OCTAVE_STRETCH,
+                    max: 1200.0 + MAX_OCTAVE_STRETCH,
+                },
+            ),
+            c_frequency: FloatParam::new(
+                "C Frequency (Hz)",
+                261.626,
+                FloatRange::Linear {
+                    min: 200.0,
+                    max: 330.0,
+                },
+            )
+            .with_value_to_string(v2s_c_frequency())
+            .with_string_to_value(s2v_c_frequency()),
+            learn_smoothing: FloatParam::new(
+                "Learn Smoothing (sec)",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            ),
+            learn_locks: Arc::new(AtomicU8::new(0)),
+            adjusting: AtomicU8::new(0),
         }
     }
 }
 
+impl GridParams {
+    /// Size of grid nodes in logical pixels, kept in range even if a saved size isn't
+    pub fn node_size(&self) -> f32 {
+        (self.node_size.load(Ordering::Relaxed) as f32 / 10.0).clamp(MIN_NODE_SIZE, MAX_NODE_SIZE)
+    }
+
+    pub fn set_node_size(&self, node_size: f32) {
+        self.node_size
+            .store((node_size * 10.0).round() as u32, Ordering::Relaxed);
+    }
+
+    pub fn png_scale(&self) -> u8 {
+        self.png_scale
+            .load(Ordering::Relaxed)
+            .clamp(1, MAX_PNG_SCALE)
+    }
+}
+
+impl TuningParams {
+    /// Whether tuning learn leaves a tuning alone. Tunings are numbered 0 to 4, for C and primes
+    /// 3, 5, 7 and 11.
+    pub fn learn_locked(&self, tuning_idx: usize) -> bool {
+        self.learn_locks.load(Ordering::Relaxed) & (1 << tuning_idx) != 0
+    }
+
+    pub fn toggle_learn_lock(&self, tuning_idx: usize) {
+        self.learn_locks
+            .fetch_xor(1 << tuning_idx, Ordering::Relaxed);
+    }
+
+    /// Numbers a tuning parameter the same way as [`Self::learn_locked`], or returns `None` for
+    /// other parameters
+    pub fn tuning_idx(&self, param: &FloatParam) -> Option<usize> {
+        [
+            &self.c_offset,
+            &self.three,
+            &self.five,
+            &self.seven,
+            &self.eleven,
+        ]
+        .iter()
+        .position(|tuning| std::ptr::eq(*tuning, param))
+    }
+
+    /// Whether a tuning has a gesture open in the editor's tuning controls
+    pub fn adjusting(&self, tuning_idx: usize) -> bool {
+        self.adjusting.load(Ordering::Relaxed) & (1 << tuning_idx) != 0
+    }
+
+    pub fn set_adjusting(&self, tuning_idx: usize, adjusting: bool) {
+        if adjusting {
+            self.adjusting.fetch_or(1 << tuning_idx, Ordering::Relaxed);
+        } else {
+            self.adjusting
+                .fetch_and(!(1 << tuning_idx), Ordering::Relaxed);
+        }
+    }
+
+    /// Reads the current tunings as pitch classes
+    pub fn snapshot(&self) -> TuningSnapshot {
+        TuningSnapshot {
+            c_offset: PitchClass::from_cents_f32(self.c_offset.value()),
+            three: PitchClass::from_cents_f32(self.three.value()),
+            five: PitchClass::from_cents_f32(self.five.value()),
+            seven: PitchClass::from_cents_f32(self.seven.value()),
+            eleven: PitchClass::from_cents_f32(self.eleven.value()),
+        }
+    }
+
+    /// Returns the parameters for primes 3, 5 and 7, each with its current tuning rounded to
+    /// the nearest step of an equal division of the octave within the parameter's range
+    pub fn edo_snapped_tunings(&self, edo: u32) -> [(&FloatParam, f32); 3] {
+        [
+            (&self.three, THREE_JUST_F32),
+            (&self.five, FIVE_JUST_F32),
+            (&self.seven, SEVEN_JUST_F32),
+        ]
+        .map(|(param, just)| {
+            let tuning = edo_step_in_range(
+                edo,
+                param.value(),
+                just - MAX_TUNING_OFFSET,
+                just + MAX_TUNING_OFFSET,
+            );
+            (param, tuning)
+        })
+    }
+
+    /// Reads the current tuning tolerances
+    pub fn tolerances(&self) -> Tolerances {
+        let prime_tolerance = |param: &FloatParam| {
+            (param.value() > 0.0).then(|| PitchClassDistance::from_cents_f32(param.value()))
+        };
+        Tolerances {
+            global: PitchClassDistance::from_cents_f32(self.tolerance.value()),
+            three: prime_tolerance(&self.tolerance_three),
+            five: prime_tolerance(&self.tolerance_five),
+            seven: prime_tolerance(&self.tolerance_seven),
+        }
+    }
+}
+
+/// Tolerance for one prime, which is off at zero
+fn prime_tolerance_param(name: &str) -> FloatParam {
+    FloatParam::new(
+        name,
+        0.0,
+        FloatRange::Skewed {
+            min: 0.0,
+            max: MAX_TOLERANCE,
+            factor: FloatRange::skew_factor(-2.5),
+        },
+    )
+    .with_value_to_string(v2s_prime_tolerance())
+    .with_string_to_value(s2v_prime_tolerance())
+}
+
 impl MidiLatticeParams {
     fn new(grid_params: Arc<GridParams>) -> Self {
         nih_log!("created default params");
@@ -229,6 +753,9 @@ impl MidiLatticeParams {
             editor_state: editor::vizia_state(grid_params.clone()),
             grid_params: grid_params,
             tuning_params: Arc::new(TuningParams::default()),
+            enabled_channels: Arc::new(AtomicU16::new(ALL_CHANNELS)),
+            channel_roles: Arc::new(RwLock::new(ChannelConfig::default())),
+            custom_colors: Arc::new(RwLock::new(CustomColors::default())),
         }
     }
 }
@@ -237,15 +764,78 @@ impl Default for MidiLattice {
     fn default() -> Self {
         nih_log!("default");
         let (input, output) = TripleBuffer::default().split();
+        let (command_sender, command_consumer) = command_queue();
         Self {
             params: Arc::new(MidiLatticeParams::new(Arc::default())),
             voices: FnvIndexMap::new(),
-            voices_input: input,
+            channel_states: ChannelStates::default(),
+            voice_options: VoiceOptions::default(),
+            voices_publisher: VoicesPublisher::new(input),
+            event_buffer: heapless::Vec::new(),
             voices_output: Arc::new(Mutex::new(output)),
+            command_consumer,
+            command_sender: Arc::new(Mutex::new(command_sender)),
+            mts_client: Arc::new(MtsClient::new()),
         }
     }
 }
 
+impl MidiLattice {
+    /// Forgets all sounding voices and channel controllers, and lets the editor know
+    fn clear_voices(&mut self) {
+        self.voices.clear();
+        self.channel_states = ChannelStates::default();
+        self.voices_publisher.mark_changed(true);
+        self.voices_publisher.publish(&self.voices);
+    }
+
+    /// Sorts the buffered events by timing, applies them to the voices and passes them through,
+    /// then clears the buffer
+    fn apply_buffered_events(&mut self, context: &mut impl ProcessContext<Self>) {
+        sort_by_timing(&mut self.event_buffer);
+        for i in 0..self.event_buffer.len() {
+            let event = self.event_buffer[i];
+            let changed = update_midi_voices(
+                &mut self.voices,
+                &mut self.channel_states,
+                self.voice_options,
+                event,
+            );
+            self.voices_publisher.mark_changed(changed);
+
+            // An MTS-ESP master's tuning applies to new notes, as if they were tuned by the host
+            if let NoteEvent::NoteOn {
+                timing,
+                voice_id,
+                channel,
+                note,
+                velocity: _,
+            } = event
+            {
+                let c_frequency = self.params.tuning_params.c_frequency.value();
+                if let Some(tuning) = self.mts_client.note_tuning(note, channel, c_frequency) {
+                    // The note on already marked the voices as changed
+                    update_midi_voices(
+                        &mut self.voices,
+                        &mut self.channel_states,
+                        self.voice_options,
+                        NoteEvent::PolyTuning {
+                            timing,
+                            voice_id,
+                            channel,
+                            note,
+                            tuning,
+                        },
+                    );
+                }
+            }
+
+            context.send_event(event);
+        }
+        self.event_buffer.clear();
+    }
+}
+
 impl Plugin for MidiLattice {
     const NAME: &'static str = "MIDI Lattice";
     const VENDOR: &'static str = "Yan Han";
@@ -295,24 +885,59 @@ impl Plugin for MidiLattice {
     ) -> ProcessStatus {
         let _start_time = Instant::now();
 
-        let mut event_counter = 0;
-
-        while let Some(event) = context.next_event() {
-            update_midi_voices(&mut self.voices, event);
-
-            //nih_log!("event: {}", DisplayNoteEvent(event));
-            context.send_event(event);
-
-            event_counter += 1;
+        let voice_options = VoiceOptions {
+            enabled_channels: self.params.enabled_channels.load(Ordering::Relaxed),
+            latch: self.params.grid_params.latch.value(),
+            pitch_bend_range: self.params.grid_params.pitch_bend_range.value(),
+            input_edo: self.params.grid_params.input_edo.value() as u32,
+        };
+        if voice_options != self.voice_options {
+            if voice_options.enabled_channels != self.voice_options.enabled_channels {
+                self.voices_publisher
+                    .mark_changed(remove_disabled_channel_voices(
+                        &mut self.voices,
+                        voice_options.enabled_channels,
+                    ));
+            }
+            if !voice_options.latch {
+                self.voices_publisher
+                    .mark_changed(release_latched_voices(&mut self.voices));
+            }
+            if voice_options.pitch_bend_range != self.voice_options.pitch_bend_range {
+                update_pitch_bends(
+                    &mut self.voices,
+                    &self.channel_states,
+                    voice_options.pitch_bend_range,
+                );
+                self.voices_publisher.mark_changed(!self.voices.is_empty());
+            }
+            self.voice_options = voice_options;
         }
 
-        if event_counter > 0 {
-            self.voices_input.write(self.voices.clone());
+        while let Ok(command) = self.command_consumer.pop() {
+            handle_command(
+                &mut self.voices,
+                &mut self.channel_states,
+                self.voice_options,
+                command,
+                |event| context.send_event(event),
+            );
+            self.voices_publisher.mark_changed(true);
+        }
 
-            for _v in self.voices.values() {
-                //nih_log!("--- voice: {}", v);
+        // Events are applied in the order of their timing, even if the host sends them out of
+        // order, so a note off and note on of the same key in one block don't get swapped
+        self.event_buffer.clear();
+        while let Some(event) = context.next_event() {
+            if let Err(event) = self.event_buffer.push(event) {
+                self.apply_buffered_events(context);
+                let _ = self.event_buffer.push(event);
             }
         }
+        self.apply_buffered_events(context);
+
+        // One copy for the editor per block, however many events there were
+        self.voices_publisher.publish(&self.voices);
 
         ProcessStatus::Normal
     }
@@ -323,16 +948,23 @@ impl Plugin for MidiLattice {
         _buffer_config: &BufferConfig,
         _context: &mut impl InitContext<Self>,
     ) -> bool {
-        // Resize buffers and perform other potentially expensive initialization operations here.
-        // The `reset()` function is always called right after this function. You can remove this
-        // function if you do not need it.
+        // The host may re-activate the plugin without sending note-offs for held notes
+        self.clear_voices();
         true
     }
 
+    fn reset(&mut self) {
+        // Notes held when the transport stops may never get their note-offs. The grid fades out
+        // their highlights as if they were released.
+        self.clear_voices();
+    }
+
     fn editor(&mut self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         editor::create(editor::Data::new(
             self.params.clone(),
             self.voices_output.clone(),
+            self.command_sender.clone(),
+            self.mts_client.clone(),
         ))
     }
 }
@@ -344,7 +976,7 @@ impl ClapPlugin for MidiLattice {
     const CLAP_MANUAL_URL: Option<&'static str> = Some(Self::URL);
     const CLAP_SUPPORT_URL: Option<&'static str> = None;
     const CLAP_POLY_MODULATION_CONFIG: Option<PolyModulationConfig> = Some(PolyModulationConfig {
-        max_voice_capacity: 256,
+        max_voice_capacity: MAX_VOICES as u32,
         supports_overlapping_voices: true,
     });
 
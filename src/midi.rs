@@ -1,14 +1,15 @@
 use core::hash::{Hash, Hasher};
 use hash32;
-use hash32_derive::Hash32;
 use nih_plug::midi::NoteEvent;
 use nih_plug::{nih_error, nih_log};
+use serde::{Deserialize, Serialize};
 
 use std::fmt;
 use std::fmt::Display;
+use triple_buffer::Input;
 
-use crate::tuning::PitchClass;
-use crate::Voices;
+use crate::tuning::{edo_note_offset, PitchClass};
+use crate::{Voices, MAX_VOICES};
 
 #[derive(Debug, PartialEq, Clone, Copy, PartialOrd)]
 pub struct MidiVoice {
@@ -17,6 +18,20 @@ pub struct MidiVoice {
     note: u8,
     pitch: f32,
     pitch_class: PitchClass,
+    /// Offset from the note in semitones, from polyphonic tuning events
+    tuning: f32,
+    /// Offset from the note in semitones, from the channel's pitch bend
+    bend: f32,
+    /// Note on velocity, from 0 to 1
+    velocity: f32,
+    /// Polyphonic or channel aftertouch, from 0 to 1. Reset when the note is released.
+    pressure: f32,
+    /// Released while the channel's sustain pedal was down
+    sustained: bool,
+    /// Released while latching, so kept until struck again or cleared
+    latched: bool,
+    /// Increases with each note on, so older voices have lower values
+    note_on_order: u64,
 }
 
 impl Hash for MidiVoice {
@@ -50,6 +65,13 @@ impl MidiVoice {
             note,
             pitch,
             pitch_class,
+            tuning: 0.0,
+            bend: 0.0,
+            velocity: 1.0,
+            pressure: 0.0,
+            sustained: false,
+            latched: false,
+            note_on_order: 0,
         }
     }
 
@@ -64,9 +86,20 @@ impl MidiVoice {
     }
 
     fn set_tuning(&mut self, tuning_offset: f32) {
-        self.pitch = self.note as f32 + tuning_offset;
-        self.pitch_class = PitchClass::from_midi_note(self.note)
-            + PitchClass::from_midi_note_offset_f32(tuning_offset);
+        self.tuning = tuning_offset;
+        self.update_pitch();
+    }
+
+    fn set_bend(&mut self, bend: f32) {
+        self.bend = bend;
+        self.update_pitch();
+    }
+
+    fn update_pitch(&mut self) {
+        let offset = self.tuning + self.bend;
+        self.pitch = self.note as f32 + offset;
+        self.pitch_class =
+            PitchClass::from_midi_note(self.note) + PitchClass::from_midi_note_offset_f32(offset);
     }
 
     pub fn get_pitch(&self) -> f32 {
@@ -80,26 +113,71 @@ impl MidiVoice {
     pub fn get_channel(&self) -> u8 {
         self.channel
     }
+
+    pub fn get_note(&self) -> u8 {
+        self.note
+    }
+
+    /// Higher for voices struck later. Only comparable between voices sounding at the same time.
+    pub fn get_note_on_order(&self) -> u64 {
+        self.note_on_order
+    }
+
+    pub fn get_velocity(&self) -> f32 {
+        self.velocity
+    }
+
+    pub fn get_pressure(&self) -> f32 {
+        self.pressure
+    }
+
+    pub fn is_sustained(&self) -> bool {
+        self.sustained
+    }
+
+    pub fn is_latched(&self) -> bool {
+        self.latched
+    }
+
+    /// Whether the note's key was released, but the voice is kept
+    fn is_released(&self) -> bool {
+        self.sustained || self.latched
+    }
 }
 
 impl Display for MidiVoice {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "{{ note {}, ch {}, pitch {}, pitch class {} }}",
-            self.note, self.channel, self.pitch, self.pitch_class
+            "{{ note {}, ch {}, pitch {}, pitch class {}, sustained {} }}",
+            self.note, self.channel, self.pitch, self.pitch_class, self.sustained
         )
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Hash, Copy, Clone, Hash32)]
+#[derive(PartialEq, Eq, Debug, Hash, Copy, Clone)]
 pub struct VoiceKey {
+    /// The host's ID for the voice, if it sends one. Lets voices of the same key overlap.
+    pub voice_id: Option<i32>,
     /// The note's channel, in `0..16`.
     pub channel: u8,
     /// The note's MIDI key number, in `0..128`.
     pub note: u8,
 }
 
+// `hash32` has no implementation for `Option`, so this can't be derived
+impl hash32::Hash for VoiceKey {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: hash32::Hasher,
+    {
+        hash32::Hash::hash(&(self.voice_id.is_some() as u8), state);
+        hash32::Hash::hash(&self.voice_id.unwrap_or(0), state);
+        hash32::Hash::hash(&self.channel, state);
+        hash32::Hash::hash(&self.note, state);
+    }
+}
+
 pub struct DisplayNoteEvent(pub NoteEvent<()>);
 
 impl Display for DisplayNoteEvent {
@@ -138,6 +216,26 @@ impl Display for DisplayNoteEvent {
                 "{{ tune: note {}, ch {}, id {:?}, tun {:.9} }}",
                 note, channel, voice_id, tuning
             ),
+            DisplayNoteEvent(NoteEvent::Choke {
+                timing: _,
+                voice_id,
+                channel,
+                note,
+            }) => write!(
+                f,
+                "{{ choke: note {}, ch {}, id {:?} }}",
+                note, channel, voice_id
+            ),
+            DisplayNoteEvent(NoteEvent::VoiceTerminated {
+                timing: _,
+                voice_id,
+                channel,
+                note,
+            }) => write!(
+                f,
+                "{{ terminated: note {}, ch {}, id {:?} }}",
+                note, channel, voice_id
+            ),
             DisplayNoteEvent(note_event) => {
                 write!(f, "other event: {:?}", note_event)
             }
@@ -145,19 +243,192 @@ impl Display for DisplayNoteEvent {
     }
 }
 
-pub fn update_midi_voices(voices: &mut Voices, event: NoteEvent<()>) {
+/// MIDI CC number of the sustain pedal
+const SUSTAIN_PEDAL_CC: u8 = 64;
+
+/// MIDI CC number of the all notes off message, sent by hosts when stopping or panicking
+const ALL_NOTES_OFF_CC: u8 = 123;
+
+/// State of controllers that apply to a whole channel, along with the count of note ons that
+/// orders voices
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelStates {
+    /// Whether the sustain pedal is down, for each channel
+    pub sustain_pedals: [bool; 16],
+    /// Pitch bend for each channel, from 0 to 1. 0.5 is no bend.
+    pub pitch_bends: [f32; 16],
+    /// Channel aftertouch for each channel, from 0 to 1
+    pub channel_pressures: [f32; 16],
+    /// Order given to the next voice struck. Only ever increases, so every voice gets its own.
+    next_note_on_order: u64,
+}
+
+impl Default for ChannelStates {
+    fn default() -> Self {
+        ChannelStates {
+            sustain_pedals: [false; 16],
+            pitch_bends: [0.5; 16],
+            channel_pressures: [0.0; 16],
+            next_note_on_order: 0,
+        }
+    }
+}
+
+impl ChannelStates {
+    /// Returns a channel's pitch bend in semitones
+    fn bend(&self, channel: u8, pitch_bend_range: f32) -> f32 {
+        (self.pitch_bends[channel as usize] - 0.5) * 2.0 * pitch_bend_range
+    }
+}
+
+/// One bit per channel, set for channels whose notes are shown. Bit 0 is channel 0.
+pub type ChannelMask = u16;
+
+pub const ALL_CHANNELS: ChannelMask = ChannelMask::MAX;
+
+pub fn channel_enabled(enabled_channels: ChannelMask, channel: u8) -> bool {
+    enabled_channels & (1 << channel) != 0
+}
+
+/// How notes on a channel are shown
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ChannelRole {
+    /// Colored by channel, or by pitch on channels past the fixed colors
+    Color,
+    /// Only outlined, with no fill color
+    Outline,
+    /// Not shown at all
+    Hide,
+}
+
+/// The role of each channel. Unlike channels left out of a [`ChannelMask`], hidden channels
+/// still have voices, but aren't drawn.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct ChannelConfig {
+    roles: [ChannelRole; 16],
+}
+
+impl Default for ChannelConfig {
+    /// Channels 1 to 14 are colored, 15 is outlined and 16 is hidden
+    fn default() -> Self {
+        let mut roles = [ChannelRole::Color; 16];
+        roles[14] = ChannelRole::Outline;
+        roles[15] = ChannelRole::Hide;
+        ChannelConfig { roles }
+    }
+}
+
+impl ChannelConfig {
+    pub fn role(&self, channel: u8) -> ChannelRole {
+        self.roles[channel as usize]
+    }
+
+    /// Moves a channel on to the next role, from colored to outlined to hidden and back
+    pub fn cycle_role(&mut self, channel: u8) {
+        let role = &mut self.roles[channel as usize];
+        *role = match role {
+            ChannelRole::Color => ChannelRole::Outline,
+            ChannelRole::Outline => ChannelRole::Hide,
+            ChannelRole::Hide => ChannelRole::Color,
+        };
+    }
+}
+
+/// Pitch bend range used by MPE controllers
+pub const DEFAULT_PITCH_BEND_RANGE: f32 = 48.0;
+
+/// Settings from the plugin's parameters that affect how note events change the voices
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct VoiceOptions {
+    /// Notes on other channels are ignored
+    pub enabled_channels: ChannelMask,
+    /// Released notes are kept until struck again
+    pub latch: bool,
+    /// Semitones of pitch bend at either end of the pitch bend wheel
+    pub pitch_bend_range: f32,
+    /// Notes without tuning from the host are steps of this many equal divisions of the octave
+    pub input_edo: u32,
+}
+
+impl Default for VoiceOptions {
+    fn default() -> Self {
+        VoiceOptions {
+            enabled_channels: ALL_CHANNELS,
+            latch: false,
+            pitch_bend_range: DEFAULT_PITCH_BEND_RANGE,
+            input_edo: 12,
+        }
+    }
+}
+
+/// Applies a note event to the sounding voices. While a channel's sustain pedal is down, voices
+/// released on it are kept and marked as sustained, until the pedal is lifted. While latching,
+/// released voices are kept until the same note is struck again. Pitch bend moves every voice on
+/// its channel, so each note can be bent on its own with MPE.
+///
+/// Returns whether any voices changed, so unchanged voices don't need to be sent to the editor.
+pub fn update_midi_voices(
+    voices: &mut Voices,
+    channels: &mut ChannelStates,
+    options: VoiceOptions,
+    event: NoteEvent<()>,
+) -> bool {
     match event {
         NoteEvent::NoteOn {
             timing: _,
-            voice_id,
+            voice_id: _,
+            channel,
+            note: _,
+            velocity: _,
+        } if !channel_enabled(options.enabled_channels, channel) => false,
+        // Striking a latched note again turns it off
+        NoteEvent::NoteOn {
+            timing: _,
+            voice_id: _,
             channel,
             note,
             velocity: _,
+        } if voices
+            .iter()
+            .any(|(key, voice)| key.channel == channel && key.note == note && voice.latched) =>
+        {
+            remove_voices_where(voices, |key, voice| {
+                key.channel == channel && key.note == note && voice.latched
+            })
+        }
+        NoteEvent::NoteOn {
+            timing: _,
+            voice_id,
+            channel,
+            note,
+            velocity,
         } => {
-            match voices.insert(
-                VoiceKey { note, channel },
-                MidiVoice::from_midi_data(voice_id, channel, note),
-            ) {
+            let key = VoiceKey {
+                voice_id,
+                channel,
+                note,
+            };
+            let mut voice = MidiVoice {
+                velocity,
+                // Channel aftertouch held from before the note was struck applies to it too
+                pressure: channels.channel_pressures[channel as usize],
+                note_on_order: channels.next_note_on_order,
+                ..MidiVoice::from_midi_data(voice_id, channel, note)
+            };
+            channels.next_note_on_order += 1;
+            // Polyphonic tuning from the host replaces this
+            voice.set_tuning(edo_note_offset(note, options.input_edo));
+            voice.set_bend(channels.bend(channel, options.pitch_bend_range));
+            let inserted = match voices.insert(key, voice) {
+                // Full - make room by evicting an old voice, so the newest notes always show
+                Err((key, voice)) => {
+                    evict_oldest_voice(voices);
+                    voices.insert(key, voice)
+                }
+                result => result,
+            };
+            match inserted {
+                Ok(Some(old_voice)) if old_voice.sustained => {}
                 Ok(Some(_)) => {
                     nih_error!(
                         "!!! Received note on for existing voice: {}",
@@ -169,42 +440,1037 @@ pub fn update_midi_voices(voices: &mut Voices, event: NoteEvent<()>) {
                 }
                 _ => {}
             }
+            true
         }
         NoteEvent::NoteOff {
             timing: _,
-            voice_id: _,
+            voice_id,
             channel,
             note,
             velocity: _,
-        } => match voices.remove(&VoiceKey { note, channel }) {
-            None => {
+        } => {
+            let key = find_voice(voices, voice_id, channel, note);
+            if options.latch || channels.sustain_pedals[channel as usize] {
+                match key.and_then(|key| voices.get_mut(&key)) {
+                    None => {
+                        nih_log!(
+                            "!!! Received off for nonexisting voice: {}",
+                            DisplayNoteEvent(event)
+                        );
+                        false
+                    }
+                    Some(voice) => {
+                        if options.latch {
+                            voice.latched = true;
+                        } else {
+                            voice.sustained = true;
+                        }
+                        voice.pressure = 0.0;
+                        true
+                    }
+                }
+            } else if key.and_then(|key| voices.remove(&key)).is_none() {
                 nih_log!(
                     "!!! Received off for nonexisting voice: {}",
                     DisplayNoteEvent(event)
                 );
+                false
+            } else {
+                true
+            }
+        }
+        NoteEvent::MidiCC {
+            timing: _,
+            channel,
+            cc: SUSTAIN_PEDAL_CC,
+            value,
+        } => {
+            let pedal_down = value >= 0.5;
+            channels.sustain_pedals[channel as usize] = pedal_down;
+            !pedal_down && release_sustained_voices(voices, channel)
+        }
+        NoteEvent::MidiCC {
+            timing: _,
+            channel,
+            cc: ALL_NOTES_OFF_CC,
+            value: _,
+        } => remove_channel_voices(voices, channel),
+        NoteEvent::MidiPitchBend {
+            timing: _,
+            channel,
+            value,
+        } => {
+            channels.pitch_bends[channel as usize] = value;
+            let bend = channels.bend(channel, options.pitch_bend_range);
+            let mut changed = false;
+            for (_, voice) in voices.iter_mut().filter(|(key, _)| key.channel == channel) {
+                voice.set_bend(bend);
+                changed = true;
+            }
+            changed
+        }
+        NoteEvent::PolyPressure {
+            timing: _,
+            voice_id,
+            channel,
+            note,
+            pressure,
+        } => match find_voice(voices, voice_id, channel, note).and_then(|key| voices.get_mut(&key))
+        {
+            // Controllers keep sending pressure for a moment after note offs, so this is expected
+            // and ignored without logging from the audio thread
+            None => false,
+            // Released voices' keys can't be pressed
+            Some(voice) if voice.is_released() => false,
+            Some(voice) => {
+                voice.pressure = pressure;
+                true
             }
-            _ => {}
         },
+        NoteEvent::MidiChannelPressure {
+            timing: _,
+            channel,
+            pressure,
+        } => {
+            channels.channel_pressures[channel as usize] = pressure;
+            let mut changed = false;
+            for (_, voice) in voices
+                .iter_mut()
+                .filter(|(key, voice)| key.channel == channel && !voice.is_released())
+            {
+                voice.pressure = pressure;
+                changed = true;
+            }
+            changed
+        }
         NoteEvent::PolyTuning {
             timing: _,
-            voice_id: _,
+            voice_id,
             channel,
             note,
             tuning,
         } => {
-            let cur_voice: Option<&mut MidiVoice> = voices.get_mut(&VoiceKey { channel, note });
+            let cur_voice: Option<&mut MidiVoice> =
+                find_voice(voices, voice_id, channel, note).and_then(|key| voices.get_mut(&key));
             match cur_voice {
                 None => {
                     nih_log!(
                         "!!! Received tuning for nonexistent voice: {}",
                         DisplayNoteEvent(event)
                     );
+                    false
                 }
                 Some(voice) => {
                     voice.set_tuning(tuning);
+                    true
                 }
             }
         }
-        _ => {}
+        // Hosts can end voices without a note off
+        NoteEvent::Choke {
+            timing: _,
+            voice_id,
+            channel,
+            note,
+        }
+        | NoteEvent::VoiceTerminated {
+            timing: _,
+            voice_id,
+            channel,
+            note,
+        } => match find_voice(voices, voice_id, channel, note) {
+            None => {
+                nih_log!(
+                    "!!! Received end for nonexisting voice: {}",
+                    DisplayNoteEvent(event)
+                );
+                false
+            }
+            Some(key) => voices.remove(&key).is_some(),
+        },
+        _ => false,
+    }
+}
+
+/// Stably sorts events by their timing within the block. Events from hosts are almost always
+/// sorted already, so this is an insertion sort, which doesn't allocate on the audio thread.
+pub fn sort_by_timing(events: &mut [NoteEvent<()>]) {
+    for i in 1..events.len() {
+        let mut j = i;
+        while j > 0 && events[j - 1].timing() > events[j].timing() {
+            events.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// Sends voices to the editor, at most once per `process()` call and only if they changed
+pub struct VoicesPublisher {
+    input: Input<Voices>,
+    changed: bool,
+}
+
+impl VoicesPublisher {
+    pub fn new(input: Input<Voices>) -> Self {
+        VoicesPublisher {
+            input,
+            changed: false,
+        }
+    }
+
+    pub fn mark_changed(&mut self, changed: bool) {
+        self.changed |= changed;
+    }
+
+    /// Copies the voices into the triple buffer if they changed since the last publish, and
+    /// returns whether they did
+    pub fn publish(&mut self, voices: &Voices) -> bool {
+        if !self.changed {
+            return false;
+        }
+        self.input.input_buffer().clone_from(voices);
+        self.input.publish();
+        self.changed = false;
+        true
+    }
+}
+
+/// Finds the voice an event applies to. Looks the voice up by the host's voice ID if there is
+/// one, and otherwise falls back to the oldest voice on the channel and key.
+fn find_voice(voices: &Voices, voice_id: Option<i32>, channel: u8, note: u8) -> Option<VoiceKey> {
+    voice_id
+        .and_then(|voice_id| voices.keys().find(|key| key.voice_id == Some(voice_id)))
+        .or_else(|| {
+            voices
+                .iter()
+                .filter(|(key, _)| key.channel == channel && key.note == note)
+                .min_by_key(|(_, voice)| voice.note_on_order)
+                .map(|(key, _)| key)
+        })
+        .copied()
+}
+
+/// Removes the voice that was turned on first. Voices whose keys were released go before held
+/// ones, so a held drone outlasts a run of sustained notes struck after it.
+fn evict_oldest_voice(voices: &mut Voices) {
+    let oldest: Option<VoiceKey> = voices
+        .iter()
+        .min_by_key(|(_, voice)| (!voice.is_released(), voice.note_on_order))
+        .map(|(key, _)| *key);
+    if let Some(key) = oldest {
+        voices.remove(&key);
+    }
+}
+
+/// Recalculates the pitch bend of every voice, for when the pitch bend range changes
+pub fn update_pitch_bends(voices: &mut Voices, channels: &ChannelStates, pitch_bend_range: f32) {
+    for (key, voice) in voices.iter_mut() {
+        voice.set_bend(channels.bend(key.channel, pitch_bend_range));
+    }
+}
+
+/// Removes voices on channels that aren't in `enabled_channels`
+pub fn remove_disabled_channel_voices(voices: &mut Voices, enabled_channels: ChannelMask) -> bool {
+    remove_voices_where(voices, |key, _| {
+        !channel_enabled(enabled_channels, key.channel)
+    })
+}
+
+/// Removes all voices on a channel, whether held, sustained or latched
+fn remove_channel_voices(voices: &mut Voices, channel: u8) -> bool {
+    remove_voices_where(voices, |key, _| key.channel == channel)
+}
+
+/// Removes all latched voices
+pub fn release_latched_voices(voices: &mut Voices) -> bool {
+    remove_voices_where(voices, |_, voice| voice.latched)
+}
+
+/// Removes all sustained voices on a channel
+fn release_sustained_voices(voices: &mut Voices, channel: u8) -> bool {
+    remove_voices_where(voices, |key, voice| {
+        key.channel == channel && voice.sustained
+    })
+}
+
+/// Removes the voices matching `remove`, and returns whether there were any
+fn remove_voices_where(
+    voices: &mut Voices,
+    remove: impl Fn(&VoiceKey, &MidiVoice) -> bool,
+) -> bool {
+    let removed: heapless::Vec<VoiceKey, MAX_VOICES> = voices
+        .iter()
+        .filter(|(key, voice)| remove(key, voice))
+        .map(|(key, _)| *key)
+        .collect();
+    for key in &removed {
+        voices.remove(key);
+    }
+    !removed.is_empty()
+}
+
+#[cfg(test)]
+mod sort_by_timing_tests {
+    use nih_plug::midi::NoteEvent;
+
+    use crate::midi::{sort_by_timing, update_midi_voices, ChannelStates, VoiceOptions};
+    use crate::Voices;
+
+    fn note_on(timing: u32, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn note_off(timing: u32, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    #[test]
+    fn keeps_order_of_equal_timings() {
+        let mut events = [
+            note_on(5, 62),
+            note_off(3, 60),
+            note_on(3, 60),
+            note_on(0, 64),
+        ];
+        sort_by_timing(&mut events);
+        assert_eq!(
+            events,
+            [
+                note_on(0, 64),
+                note_off(3, 60),
+                note_on(3, 60),
+                note_on(5, 62)
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_out_of_order_events_in_timing_order() {
+        // A note struck and released in one block, with the release arriving first
+        let mut events = [note_off(20, 60), note_on(10, 60), note_on(30, 64)];
+        sort_by_timing(&mut events);
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        for event in events {
+            update_midi_voices(&mut voices, &mut channels, VoiceOptions::default(), event);
+        }
+        assert_eq!(voices.len(), 1);
+        assert_eq!(voices.values().next().map(|v| v.get_pitch()), Some(64.0));
+    }
+}
+
+#[cfg(test)]
+mod voices_publisher_tests {
+    use nih_plug::midi::NoteEvent;
+    use triple_buffer::TripleBuffer;
+
+    use crate::midi::{update_midi_voices, ChannelStates, VoiceOptions, VoicesPublisher};
+    use crate::Voices;
+
+    #[test]
+    fn publishes_once_per_block() {
+        let (input, mut output) = TripleBuffer::<Voices>::default().split();
+        let mut publisher = VoicesPublisher::new(input);
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        for note in 0..100 {
+            let changed = update_midi_voices(
+                &mut voices,
+                &mut channels,
+                VoiceOptions::default(),
+                NoteEvent::NoteOn {
+                    timing: 0,
+                    voice_id: None,
+                    channel: 0,
+                    note,
+                    velocity: 1.0,
+                },
+            );
+            publisher.mark_changed(changed);
+        }
+        assert!(publisher.publish(&voices));
+        assert!(!publisher.publish(&voices));
+        assert_eq!(output.read().len(), 100);
+    }
+
+    #[test]
+    fn doesnt_publish_unchanged_voices() {
+        let (input, _output) = TripleBuffer::<Voices>::default().split();
+        let mut publisher = VoicesPublisher::new(input);
+        let mut voices = Voices::new();
+        let changed = update_midi_voices(
+            &mut voices,
+            &mut ChannelStates::default(),
+            VoiceOptions::default(),
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            },
+        );
+        publisher.mark_changed(changed);
+        assert!(!publisher.publish(&voices));
+    }
+}
+
+#[cfg(test)]
+mod channel_config_tests {
+    use crate::midi::{ChannelConfig, ChannelRole};
+
+    #[test]
+    fn default_roles() {
+        let config = ChannelConfig::default();
+        assert_eq!(config.role(0), ChannelRole::Color);
+        assert_eq!(config.role(13), ChannelRole::Color);
+        assert_eq!(config.role(14), ChannelRole::Outline);
+        assert_eq!(config.role(15), ChannelRole::Hide);
+    }
+
+    #[test]
+    fn cycles_through_roles() {
+        let mut config = ChannelConfig::default();
+        config.cycle_role(3);
+        assert_eq!(config.role(3), ChannelRole::Outline);
+        config.cycle_role(3);
+        assert_eq!(config.role(3), ChannelRole::Hide);
+        config.cycle_role(3);
+        assert_eq!(config.role(3), ChannelRole::Color);
+        assert_eq!(config.role(4), ChannelRole::Color);
+    }
+}
+
+#[cfg(test)]
+mod update_midi_voices_tests {
+    use nih_plug::midi::NoteEvent;
+
+    use crate::midi::{
+        release_latched_voices, remove_disabled_channel_voices, update_midi_voices,
+        update_pitch_bends, ChannelStates, VoiceKey, VoiceOptions,
+    };
+    use crate::tuning::PitchClass;
+    use crate::{Voices, MAX_VOICES};
+
+    fn note_on(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    fn note_off(channel: u8, note: u8) -> NoteEvent<()> {
+        NoteEvent::NoteOff {
+            timing: 0,
+            voice_id: None,
+            channel,
+            note,
+            velocity: 0.0,
+        }
+    }
+
+    fn pedal(channel: u8, down: bool) -> NoteEvent<()> {
+        NoteEvent::MidiCC {
+            timing: 0,
+            channel,
+            cc: 64,
+            value: if down { 1.0 } else { 0.0 },
+        }
+    }
+
+    fn apply(events: &[NoteEvent<()>]) -> Voices {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        for event in events {
+            update_midi_voices(&mut voices, &mut channels, VoiceOptions::default(), *event);
+        }
+        voices
+    }
+
+    fn sustained(voices: &Voices, channel: u8, note: u8) -> Option<bool> {
+        voices
+            .get(&VoiceKey {
+                voice_id: None,
+                channel,
+                note,
+            })
+            .map(|voice| voice.is_sustained())
+    }
+
+    #[test]
+    fn release_without_pedal_removes_voice() {
+        let voices = apply(&[note_on(0, 60), note_off(0, 60)]);
+        assert!(voices.is_empty());
+    }
+
+    #[test]
+    fn pedal_keeps_released_voices() {
+        let voices = apply(&[
+            pedal(0, true),
+            note_on(0, 60),
+            note_on(0, 64),
+            note_off(0, 60),
+        ]);
+        assert_eq!(sustained(&voices, 0, 60), Some(true));
+        assert_eq!(sustained(&voices, 0, 64), Some(false));
+    }
+
+    #[test]
+    fn lifting_pedal_removes_only_sustained_voices_on_its_channel() {
+        let voices = apply(&[
+            pedal(0, true),
+            pedal(1, true),
+            note_on(0, 60),
+            note_on(0, 64),
+            note_on(1, 67),
+            note_off(0, 60),
+            note_off(1, 67),
+            pedal(0, false),
+        ]);
+        assert_eq!(sustained(&voices, 0, 60), None);
+        assert_eq!(sustained(&voices, 0, 64), Some(false));
+        assert_eq!(sustained(&voices, 1, 67), Some(true));
+    }
+
+    #[test]
+    fn full_voice_map_evicts_oldest_voices() {
+        let key = |idx: usize| VoiceKey {
+            voice_id: None,
+            channel: (idx / 128) as u8,
+            note: (idx % 128) as u8,
+        };
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        for idx in 0..300 {
+            let key = key(idx);
+            update_midi_voices(
+                &mut voices,
+                &mut channels,
+                VoiceOptions::default(),
+                note_on(key.channel, key.note),
+            );
+        }
+        assert_eq!(voices.len(), MAX_VOICES);
+        for idx in 0..300 {
+            assert_eq!(voices.contains_key(&key(idx)), idx >= 300 - MAX_VOICES);
+        }
+    }
+
+    #[test]
+    fn full_voice_map_evicts_released_voices_first() {
+        let key = |idx: usize| VoiceKey {
+            voice_id: None,
+            channel: (idx / 128) as u8,
+            note: (idx % 128) as u8,
+        };
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        let mut update = |voices: &mut Voices, event| {
+            update_midi_voices(voices, &mut channels, VoiceOptions::default(), event);
+        };
+        // A held drone, then a sustained run that fills the map
+        update(&mut voices, note_on(key(0).channel, key(0).note));
+        update(&mut voices, pedal(1, true));
+        update(&mut voices, pedal(2, true));
+        for idx in 128..128 + MAX_VOICES {
+            let key = key(idx);
+            update(&mut voices, note_on(key.channel, key.note));
+            update(&mut voices, note_off(key.channel, key.note));
+        }
+        assert_eq!(voices.len(), MAX_VOICES);
+        assert!(voices.contains_key(&key(0)));
+        assert!(!voices.contains_key(&key(128)));
+        assert!(voices.contains_key(&key(127 + MAX_VOICES)));
+    }
+
+    #[test]
+    fn note_on_order_keeps_increasing_after_releases() {
+        let voices = apply(&[
+            note_on(0, 60),
+            note_on(0, 64),
+            note_off(0, 60),
+            note_off(0, 64),
+            note_on(0, 67),
+        ]);
+        assert_eq!(voices.values().next().unwrap().get_note_on_order(), 2);
+    }
+
+    #[test]
+    fn stores_velocity() {
+        let voices = apply(&[NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: None,
+            channel: 0,
+            note: 60,
+            velocity: 0.25,
+        }]);
+        assert_eq!(
+            voices
+                .get(&VoiceKey {
+                    voice_id: None,
+                    channel: 0,
+                    note: 60
+                })
+                .map(|voice| voice.get_velocity()),
+            Some(0.25)
+        );
+    }
+
+    fn pressure(voices: &Voices, channel: u8, note: u8) -> Option<f32> {
+        voices
+            .get(&VoiceKey {
+                voice_id: None,
+                channel,
+                note,
+            })
+            .map(|voice| voice.get_pressure())
+    }
+
+    #[test]
+    fn poly_pressure_applies_to_one_voice() {
+        let voices = apply(&[
+            note_on(0, 60),
+            note_on(0, 64),
+            NoteEvent::PolyPressure {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                pressure: 0.5,
+            },
+        ]);
+        assert_eq!(pressure(&voices, 0, 60), Some(0.5));
+        assert_eq!(pressure(&voices, 0, 64), Some(0.0));
+    }
+
+    #[test]
+    fn channel_pressure_applies_to_its_channel() {
+        let voices = apply(&[
+            note_on(0, 60),
+            note_on(0, 64),
+            note_on(1, 67),
+            NoteEvent::MidiChannelPressure {
+                timing: 0,
+                channel: 0,
+                pressure: 0.75,
+            },
+        ]);
+        assert_eq!(pressure(&voices, 0, 60), Some(0.75));
+        assert_eq!(pressure(&voices, 0, 64), Some(0.75));
+        assert_eq!(pressure(&voices, 1, 67), Some(0.0));
+    }
+
+    #[test]
+    fn channel_pressure_applies_to_later_notes() {
+        let voices = apply(&[
+            NoteEvent::MidiChannelPressure {
+                timing: 0,
+                channel: 0,
+                pressure: 0.5,
+            },
+            note_on(0, 60),
+            note_on(1, 64),
+        ]);
+        assert_eq!(pressure(&voices, 0, 60), Some(0.5));
+        assert_eq!(pressure(&voices, 1, 64), Some(0.0));
+    }
+
+    #[test]
+    fn ignores_poly_pressure_for_missing_voice() {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        let changed = update_midi_voices(
+            &mut voices,
+            &mut channels,
+            VoiceOptions::default(),
+            NoteEvent::PolyPressure {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                pressure: 0.5,
+            },
+        );
+        assert!(!changed);
+        assert!(voices.is_empty());
+    }
+
+    #[test]
+    fn releasing_sustained_note_resets_pressure() {
+        let voices = apply(&[
+            pedal(0, true),
+            note_on(0, 60),
+            NoteEvent::MidiChannelPressure {
+                timing: 0,
+                channel: 0,
+                pressure: 1.0,
+            },
+            note_off(0, 60),
+            NoteEvent::MidiChannelPressure {
+                timing: 0,
+                channel: 0,
+                pressure: 1.0,
+            },
+        ]);
+        assert_eq!(pressure(&voices, 0, 60), Some(0.0));
+    }
+
+    #[test]
+    fn ignores_notes_on_disabled_channels() {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        let options = VoiceOptions {
+            // Everything but channel 9
+            enabled_channels: !(1 << 9),
+            ..VoiceOptions::default()
+        };
+        for channel in [0, 9] {
+            update_midi_voices(&mut voices, &mut channels, options, note_on(channel, 60));
+        }
+        assert!(voices.contains_key(&VoiceKey {
+            voice_id: None,
+            channel: 0,
+            note: 60
+        }));
+        assert!(!voices.contains_key(&VoiceKey {
+            voice_id: None,
+            channel: 9,
+            note: 60
+        }));
+    }
+
+    #[test]
+    fn disabling_channel_removes_its_voices() {
+        let mut voices = apply(&[note_on(0, 60), note_on(9, 36), note_on(9, 38)]);
+        remove_disabled_channel_voices(&mut voices, !(1 << 9));
+        assert_eq!(voices.len(), 1);
+        assert_eq!(sustained(&voices, 0, 60), Some(false));
+    }
+
+    #[test]
+    fn all_notes_off_clears_its_channel() {
+        let voices = apply(&[
+            pedal(0, true),
+            note_on(0, 60),
+            note_on(0, 64),
+            note_off(0, 64),
+            note_on(1, 67),
+            NoteEvent::MidiCC {
+                timing: 0,
+                channel: 0,
+                cc: 123,
+                value: 0.0,
+            },
+        ]);
+        assert_eq!(sustained(&voices, 0, 60), None);
+        assert_eq!(sustained(&voices, 0, 64), None);
+        assert_eq!(sustained(&voices, 1, 67), Some(false));
+    }
+
+    fn pitch_bend(channel: u8, value: f32) -> NoteEvent<()> {
+        NoteEvent::MidiPitchBend {
+            timing: 0,
+            channel,
+            value,
+        }
+    }
+
+    fn pitch(voices: &Voices, channel: u8, note: u8) -> Option<f32> {
+        voices
+            .get(&VoiceKey {
+                voice_id: None,
+                channel,
+                note,
+            })
+            .map(|voice| voice.get_pitch())
+    }
+
+    #[test]
+    fn pitch_bend_moves_voices_on_its_channel() {
+        let voices = apply(&[note_on(0, 60), note_on(1, 60), pitch_bend(0, 0.75)]);
+        assert_eq!(pitch(&voices, 0, 60), Some(84.0));
+        assert_eq!(pitch(&voices, 1, 60), Some(60.0));
+        assert_eq!(
+            voices
+                .get(&VoiceKey {
+                    voice_id: None,
+                    channel: 0,
+                    note: 60
+                })
+                .map(|voice| voice.get_pitch_class()),
+            Some(PitchClass::from_midi_note(84))
+        );
+    }
+
+    #[test]
+    fn pitch_bend_applies_to_later_notes_and_adds_to_tuning() {
+        let voices = apply(&[
+            pitch_bend(0, 0.25),
+            note_on(0, 60),
+            NoteEvent::PolyTuning {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+                tuning: 0.5,
+            },
+        ]);
+        // A quarter of the way down from the center bends by half the range
+        assert_eq!(pitch(&voices, 0, 60), Some(60.0 - 24.0 + 0.5));
+    }
+
+    #[test]
+    fn changing_pitch_bend_range_updates_voices() {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        for event in [pitch_bend(0, 1.0), note_on(0, 60)] {
+            update_midi_voices(&mut voices, &mut channels, VoiceOptions::default(), event);
+        }
+        assert_eq!(pitch(&voices, 0, 60), Some(108.0));
+        update_pitch_bends(&mut voices, &channels, 2.0);
+        assert_eq!(pitch(&voices, 0, 60), Some(62.0));
+    }
+
+    #[test]
+    fn half_bend_moves_by_half_the_range() {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        let options = VoiceOptions {
+            pitch_bend_range: 2.0,
+            ..VoiceOptions::default()
+        };
+        for event in [note_on(0, 60), pitch_bend(0, 0.75)] {
+            update_midi_voices(&mut voices, &mut channels, options, event);
+        }
+        assert_eq!(pitch(&voices, 0, 60), Some(61.0));
+    }
+
+    #[test]
+    fn input_edo_tunes_notes_until_host_tunes_them() {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        let options = VoiceOptions {
+            input_edo: 24,
+            ..VoiceOptions::default()
+        };
+        for event in [
+            note_on(0, 60),
+            note_on(0, 62),
+            note_on(0, 54),
+            NoteEvent::PolyTuning {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 54,
+                tuning: 0.0,
+            },
+        ] {
+            update_midi_voices(&mut voices, &mut channels, options, event);
+        }
+        assert_eq!(pitch(&voices, 0, 60), Some(60.0));
+        // Two steps of 24-EDO make a semitone
+        assert_eq!(pitch(&voices, 0, 62), Some(61.0));
+        assert_eq!(pitch(&voices, 0, 54), Some(54.0));
+    }
+
+    fn apply_latched(events: &[NoteEvent<()>]) -> Voices {
+        let mut voices = Voices::new();
+        let mut channels = ChannelStates::default();
+        let options = VoiceOptions {
+            latch: true,
+            ..VoiceOptions::default()
+        };
+        for event in events {
+            update_midi_voices(&mut voices, &mut channels, options, *event);
+        }
+        voices
+    }
+
+    fn latched(voices: &Voices, channel: u8, note: u8) -> Option<bool> {
+        voices
+            .get(&VoiceKey {
+                voice_id: None,
+                channel,
+                note,
+            })
+            .map(|voice| voice.is_latched())
+    }
+
+    #[test]
+    fn latch_keeps_released_voices() {
+        let voices = apply_latched(&[
+            note_on(0, 60),
+            note_on(0, 64),
+            note_off(0, 60),
+            note_off(0, 64),
+            note_on(0, 67),
+        ]);
+        assert_eq!(latched(&voices, 0, 60), Some(true));
+        assert_eq!(latched(&voices, 0, 64), Some(true));
+        assert_eq!(latched(&voices, 0, 67), Some(false));
+    }
+
+    #[test]
+    fn striking_latched_note_turns_it_off() {
+        let voices = apply_latched(&[
+            note_on(0, 60),
+            note_off(0, 60),
+            note_on(0, 60),
+            note_off(0, 60),
+        ]);
+        assert!(voices.is_empty());
+    }
+
+    #[test]
+    fn clearing_latched_voices_keeps_held_ones() {
+        let mut voices = apply_latched(&[note_on(0, 60), note_off(0, 60), note_on(0, 64)]);
+        release_latched_voices(&mut voices);
+        assert_eq!(latched(&voices, 0, 60), None);
+        assert_eq!(latched(&voices, 0, 64), Some(false));
+    }
+
+    #[test]
+    fn restriking_sustained_note_holds_it_again() {
+        let voices = apply(&[
+            pedal(0, true),
+            note_on(0, 60),
+            note_off(0, 60),
+            note_on(0, 60),
+            pedal(0, false),
+        ]);
+        assert_eq!(sustained(&voices, 0, 60), Some(false));
+    }
+
+    fn voice_key(voice_id: i32) -> VoiceKey {
+        VoiceKey {
+            voice_id: Some(voice_id),
+            channel: 0,
+            note: 60,
+        }
+    }
+
+    fn note_on_with_id(voice_id: i32) -> NoteEvent<()> {
+        NoteEvent::NoteOn {
+            timing: 0,
+            voice_id: Some(voice_id),
+            channel: 0,
+            note: 60,
+            velocity: 1.0,
+        }
+    }
+
+    #[test]
+    fn overlapping_voices_with_ids_are_kept_apart() {
+        let voices = apply(&[
+            note_on_with_id(1),
+            note_on_with_id(2),
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: Some(1),
+                channel: 0,
+                note: 60,
+                velocity: 0.0,
+            },
+        ]);
+        assert_eq!(voices.keys().collect::<Vec<_>>(), vec![&voice_key(2)]);
+    }
+
+    #[test]
+    fn poly_tuning_applies_to_voice_with_id() {
+        let voices = apply(&[
+            note_on_with_id(1),
+            note_on_with_id(2),
+            NoteEvent::PolyTuning {
+                timing: 0,
+                voice_id: Some(2),
+                channel: 0,
+                note: 60,
+                tuning: 0.5,
+            },
+        ]);
+        assert_eq!(voices.get(&voice_key(1)).map(|v| v.get_pitch()), Some(60.0));
+        assert_eq!(voices.get(&voice_key(2)).map(|v| v.get_pitch()), Some(60.5));
+    }
+
+    #[test]
+    fn events_without_ids_apply_to_oldest_voice() {
+        let voices = apply(&[note_on_with_id(1), note_on_with_id(2), note_off(0, 60)]);
+        assert_eq!(voices.keys().collect::<Vec<_>>(), vec![&voice_key(2)]);
+    }
+
+    #[test]
+    fn voice_terminated_removes_voice() {
+        let voices = apply(&[
+            note_on_with_id(1),
+            note_on_with_id(2),
+            NoteEvent::VoiceTerminated {
+                timing: 0,
+                voice_id: Some(2),
+                channel: 0,
+                note: 60,
+            },
+        ]);
+        assert_eq!(voices.keys().collect::<Vec<_>>(), vec![&voice_key(1)]);
+    }
+
+    #[test]
+    fn choke_removes_only_its_voice() {
+        let voices = apply(&[
+            note_on_with_id(1),
+            note_on_with_id(2),
+            NoteEvent::Choke {
+                timing: 0,
+                voice_id: Some(1),
+                channel: 0,
+                note: 60,
+            },
+        ]);
+        assert_eq!(voices.keys().collect::<Vec<_>>(), vec![&voice_key(2)]);
+    }
+
+    #[test]
+    fn choke_without_id_removes_oldest_voice() {
+        let voices = apply(&[
+            note_on_with_id(1),
+            note_on_with_id(2),
+            NoteEvent::Choke {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+            },
+        ]);
+        assert_eq!(voices.keys().collect::<Vec<_>>(), vec![&voice_key(2)]);
+    }
+
+    #[test]
+    fn sustained_voice_is_choked() {
+        let voices = apply(&[
+            pedal(0, true),
+            note_on(0, 60),
+            note_off(0, 60),
+            NoteEvent::Choke {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: 60,
+            },
+        ]);
+        assert!(voices.is_empty());
     }
 }
@@ -0,0 +1,125 @@
+//! Client for MTS-ESP, which lets a master plugin set the tuning of every note. See
+//! https://github.com/ODDSound/MTS-ESP
+//!
+//! The client library is only linked with the `mts-esp` feature. Without it, there's never a
+//! master, and notes keep their own tuning.
+
+use crate::tuning::PitchClass;
+#[cfg(feature = "mts-esp")]
+use std::ffi::c_char;
+
+// Notes whose pitch classes are read from the master's tuning table, for learning the tuning of
+// C, and primes 5, 3, 7 and 11: C4, E4, G4, Bb4 and F#4
+const TABLE_NOTES: [u8; 5] = [60, 64, 67, 70, 66];
+
+#[cfg(feature = "mts-esp")]
+mod ffi {
+    use std::ffi::{c_char, c_double, c_void};
+
+    // Built from libMTSClient.cpp in the MTS-ESP SDK
+    #[link(name = "MTSClient")]
+    extern "C" {
+        pub fn MTS_RegisterClient() -> *mut c_void;
+        pub fn MTS_DeregisterClient(client: *mut c_void);
+        pub fn MTS_HasMaster(client: *mut c_void) -> bool;
+        pub fn MTS_NoteToFrequency(
+            client: *mut c_void,
+            midinote: c_char,
+            midichannel: c_char,
+        ) -> c_double;
+    }
+}
+
+/// A registered MTS-ESP client. Deregisters when dropped.
+pub struct MtsClient {
+    #[cfg(feature = "mts-esp")]
+    client: *mut std::ffi::c_void,
+}
+
+// The client library may be queried from any thread
+unsafe impl Send for MtsClient {}
+unsafe impl Sync for MtsClient {}
+
+impl MtsClient {
+    pub fn new() -> Self {
+        MtsClient {
+            #[cfg(feature = "mts-esp")]
+            client: unsafe { ffi::MTS_RegisterClient() },
+        }
+    }
+
+    #[cfg(feature = "mts-esp")]
+    pub fn has_master(&self) -> bool {
+        !self.client.is_null() && unsafe { ffi::MTS_HasMaster(self.client) }
+    }
+
+    #[cfg(not(feature = "mts-esp"))]
+    pub fn has_master(&self) -> bool {
+        false
+    }
+
+    /// Returns the master's frequency for a note on a channel, if there is a master
+    #[cfg(feature = "mts-esp")]
+    fn note_frequency(&self, note: u8, channel: u8) -> Option<f64> {
+        self.has_master().then(|| unsafe {
+            ffi::MTS_NoteToFrequency(self.client, note as c_char, channel as c_char)
+        })
+    }
+
+    #[cfg(not(feature = "mts-esp"))]
+    fn note_frequency(&self, _note: u8, _channel: u8) -> Option<f64> {
+        None
+    }
+
+    /// Returns the offset in semitones that the master tunes a note by, relative to C at
+    /// `c_frequency`. `None` if there's no master.
+    pub fn note_tuning(&self, note: u8, channel: u8, c_frequency: f32) -> Option<f32> {
+        self.note_frequency(note, channel)
+            .map(|frequency| frequency_to_pitch(frequency, c_frequency) - note as f32)
+    }
+
+    /// Returns the pitch classes of C, E, G, Bb and F# in the master's tuning table, if there is a
+    /// master
+    pub fn table_pitch_classes(&self, c_frequency: f32) -> Option<Vec<PitchClass>> {
+        TABLE_NOTES
+            .iter()
+            .map(|note| {
+                self.note_tuning(*note, 0, c_frequency).map(|tuning| {
+                    PitchClass::from_midi_note(*note)
+                        + PitchClass::from_midi_note_offset_f32(tuning)
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "mts-esp")]
+impl Drop for MtsClient {
+    fn drop(&mut self) {
+        if !self.client.is_null() {
+            unsafe { ffi::MTS_DeregisterClient(self.client) };
+        }
+    }
+}
+
+/// Converts a frequency to a pitch in MIDI note numbers, where C4 (60) is at `c_frequency`
+fn frequency_to_pitch(frequency: f64, c_frequency: f32) -> f32 {
+    (60.0 + 12.0 * (frequency / c_frequency as f64).log2()) as f32
+}
+
+#[cfg(test)]
+mod frequency_to_pitch_tests {
+    use crate::mts_esp::frequency_to_pitch;
+
+    #[test]
+    fn c_is_middle_c() {
+        assert_eq!(frequency_to_pitch(261.5, 261.5), 60.0);
+        assert_eq!(frequency_to_pitch(523.0, 261.5), 72.0);
+        assert_eq!(frequency_to_pitch(130.75, 261.5), 48.0);
+    }
+
+    #[test]
+    fn uses_reference_frequency() {
+        assert!((frequency_to_pitch(440.0, 261.6256) - 69.0).abs() < 0.0001);
+    }
+}
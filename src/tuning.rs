@@ -5,10 +5,13 @@ use std::{
     ops::{Add, Neg, Sub},
 };
 
+pub mod scala;
+
 // Just tunings for primes 3, 5, and 7
 pub const THREE_JUST_F32: f32 = 701.955001;
 pub const FIVE_JUST_F32: f32 = 386.313714;
 pub const SEVEN_JUST_F32: f32 = 968.825906;
+pub const ELEVEN_JUST_F32: f32 = 551.317942;
 
 // 12TET approximations for primes 3, 5, and 7
 pub const THREE_12TET_F32: f32 = 700.0;
@@ -18,6 +21,7 @@ pub const SEVEN_12TET_F32: f32 = 1000.0;
 pub const THREE_JUST: PitchClass = PitchClass::from_microcents(701_955_001);
 pub const FIVE_JUST: PitchClass = PitchClass::from_microcents(386_313_714);
 pub const SEVEN_JUST: PitchClass = PitchClass::from_microcents(968_825_906);
+pub const ELEVEN_JUST: PitchClass = PitchClass::from_microcents(551_317_942);
 
 pub const CENTS_TO_MICROCENTS: u32 = 1_000_000;
 const MIDI_NOTE_TO_CENTS: u32 = 100;
@@ -33,39 +37,17 @@ pub struct PitchClass(u32);
 
 impl Display for PitchClass {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:.6}", self.to_cents_f32())
+        // Formatted from the integer, since f32 cents can't hold all six decimal places
+        write!(
+            f,
+            "{}.{:06}",
+            self.0 / CENTS_TO_MICROCENTS,
+            self.0 % CENTS_TO_MICROCENTS
+        )
     }
 }
 
 impl PitchClass {
-    pub const fn to_microcents(&self) -> u32 {
-        self.0
-    }
-
-    pub const fn round(&self, decimal_digits: u32) -> PitchClass {
-        if decimal_digits > 6 {
-            return *self;
-        }
-        let raised: u32 = 10u32.pow(6 - decimal_digits);
-        PitchClass::from_microcents(if self.0 % raised >= raised / 2 {
-            self.0 - self.0 % raised + raised
-        } else {
-            self.0 - self.0 % raised
-        })
-    }
-
-    pub const fn get_decimal_digit_num(&self, num: u32) -> u8 {
-        if num > 5 {
-            return 0;
-        }
-        let raised: u32 = 10u32.pow(6 - num);
-        ((self.to_microcents() % raised) / (raised / 10)) as u8
-    }
-
-    pub const fn trunc_cents(&self) -> u32 {
-        self.0 / CENTS_TO_MICROCENTS
-    }
-
     /// Creates a pitch class from a number of microcents.
     ///
     /// # Examples
@@ -85,6 +67,8 @@ impl PitchClass {
         PitchClassDistance(std::cmp::min((self - other).0, (other - self).0))
     }
 
+    /// Returns the pitch class of a MIDI note in 12-TET. Notes tuned otherwise, e.g. by the host,
+    /// an MTS-ESP master or an input EDO, need the tuning's offset added.
     pub fn from_midi_note(note: u8) -> Self {
         PitchClass(u32::from(note % 12) * MIDI_NOTE_TO_CENTS * CENTS_TO_MICROCENTS)
     }
@@ -105,6 +89,75 @@ impl PitchClass {
     pub fn to_cents_f32(self) -> f32 {
         self.0 as f32 / CENTS_TO_MICROCENTS_F32
     }
+
+    /// Name of the closest 12-TET semitone, spelled as in the most common key signatures
+    pub fn twelve_tet_name(self) -> &'static str {
+        const NAMES: [&str; 12] = [
+            "C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+        ];
+        NAMES[self.closest_semitone()]
+    }
+
+    /// Like [`PitchClass::twelve_tet_name`], with HEJI's sharp and flat symbols
+    pub fn twelve_tet_heji_name(self) -> &'static str {
+        const NAMES: [&str; 12] = [
+            "C", "C♯", "D", "E♭", "E", "F", "F♯", "G", "A♭", "A", "B♭", "B",
+        ];
+        NAMES[self.closest_semitone()]
+    }
+
+    /// Semitones above C of the closest 12-TET semitone, from 0 to 11
+    fn closest_semitone(self) -> usize {
+        let semitone = MIDI_NOTE_TO_CENTS * CENTS_TO_MICROCENTS;
+        ((self.0 + semitone / 2) / semitone) as usize % 12
+    }
+
+    /// Returns how many cents this pitch class is above the closest 12-TET semitone, from -50 to
+    /// 50. Pitch classes just below C, like 1190 cents, are below C rather than above B.
+    pub fn deviation_from_12tet(self) -> f32 {
+        let semitone = MIDI_NOTE_TO_CENTS * CENTS_TO_MICROCENTS;
+        let above = (self.0 % semitone) as i64;
+        let deviation = if above * 2 > semitone as i64 {
+            above - semitone as i64
+        } else {
+            above
+        };
+        deviation as f32 / CENTS_TO_MICROCENTS_F32
+    }
+
+    /// Returns how far around the octave this pitch class is, from 0 (inclusive) to 1 (exclusive)
+    pub fn to_octave_fraction(self) -> f32 {
+        self.0 as f32 / OCTAVE_MICROCENTS as f32
+    }
+
+    /// Like [`Self::to_cents_f32`], with enough precision for fractions of a cent
+    pub fn to_cents_f64(self) -> f64 {
+        self.0 as f64 / CENTS_TO_MICROCENTS as f64
+    }
+
+    /// Returns the frequency of this pitch class in the octave above `c_frequency`, in Hz
+    pub fn to_frequency(self, c_frequency: f32) -> f32 {
+        c_frequency * (self.to_cents_f64() / 1200.0).exp2() as f32
+    }
+
+    /// Returns the frequency of this pitch class in an octave numbered as in MIDI, where octave 4
+    /// starts at `c_frequency`, in Hz. Each octave away from octave 4 is `octave_cents` wide, so
+    /// a stretched octave moves other octaves without changing the pitch class itself.
+    pub fn to_frequency_in_octave(self, c_frequency: f32, octave_cents: f32, octave: i32) -> f32 {
+        self.to_frequency(c_frequency) * ((octave - 4) as f32 * octave_cents / 1200.0).exp2()
+    }
+
+    /// Returns the closest MIDI note to this pitch class, in the octave starting at
+    /// `octave_start_note`, along with the tuning offset in semitones that reaches it exactly.
+    pub fn to_midi_note_and_tuning(self, octave_start_note: u8) -> (u8, f32) {
+        let semitones: f32 = self.to_cents_f32() / MIDI_NOTE_TO_CENTS_F32;
+        let rounded_semitones: f32 = semitones.round();
+        (
+            octave_start_note + rounded_semitones as u8,
+            semitones - rounded_semitones,
+        )
+    }
+
     /*
         pub fn with_midi_tuning_offset(self, offset: f32) -> Self {
             nih_dbg!(offset);
@@ -180,6 +233,10 @@ impl PitchClassDistance {
     pub fn from_cents_f32(cents: f32) -> PitchClassDistance {
         Self::from_microcents((cents.rem_euclid(1200.0) * CENTS_TO_MICROCENTS_F32).round() as u32)
     }
+
+    pub fn to_cents_f32(self) -> f32 {
+        self.0 as f32 / CENTS_TO_MICROCENTS_F32
+    }
     /*
     pub fn scale(&self, factor: u32) -> PitchClassDistance {
         PitchClassDistance(self.0 * factor)
@@ -192,43 +249,372 @@ impl Display for PitchClassDistance {
     }
 }
 
-/// Represents an abstract pitch class as its number of prime factors of 3, 5 and 7
-/// C = (0, 0, 0)
+/// Tunings of C and of primes 3, 5, 7, and 11, read together at one point in time
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct TuningSnapshot {
+    pub c_offset: PitchClass,
+    pub three: PitchClass,
+    pub five: PitchClass,
+    pub seven: PitchClass,
+    pub eleven: PitchClass,
+}
+
+// Positions on the chain of fifths that primes 7 and 11 are notated as, relative to C.
+// 7/4 is a minor seventh (two fourths) lowered by a septimal comma, and 11/8 is a perfect fourth
+// raised by an undecimal quarter tone.
+const SEVEN_FIFTHS: i32 = -2;
+const ELEVEN_FIFTHS: i32 = -1;
+
+/// Returns whether a prime is tuned the same as the interval it's notated as on the chain of
+/// fifths, in which case its commas are redundant. For example, in 12-TET the harmonic seventh is
+/// two perfect fourths, and the eleventh harmonic is an augmented fourth (six fifths).
+fn prime_collapses_to_fifths(
+    prime_tuning: PitchClass,
+    three_tuning: PitchClass,
+    fifths: &[i32],
+    tuning_tolerance: PitchClassDistance,
+) -> bool {
+    fifths
+        .iter()
+        .any(|fifths| three_tuning.multiply(*fifths).distance_to(prime_tuning) <= tuning_tolerance)
+}
+
+/// Returns the 12-TET pitch class of a root note `root_fifths` fifths above C
+pub fn root_pitch_class(root_fifths: i32) -> PitchClass {
+    PitchClass::from_midi_note((root_fifths * 7).rem_euclid(12) as u8)
+}
+
+/// Returns the step of an equal division of the octave closest to an interval, in cents
+pub fn edo_approximation(edo: u32, cents: f32) -> f32 {
+    (edo as f32 * cents / 1200.0).round() * 1200.0 / edo as f32
+}
+
+/// Returns how many semitones to add to a MIDI note, so that each note is a step of an equal
+/// division of the octave away from its neighbors. Middle C (60) stays where it is.
+pub fn edo_note_offset(note: u8, edo: u32) -> f32 {
+    let steps = note as f32 - 60.0;
+    steps * 12.0 / edo as f32 - steps
+}
+
+/// Returns the step of an equal division of the octave closest to `cents`, out of the steps
+/// between `min` and `max`. If there are none, returns `cents` clamped between them.
+pub fn edo_step_in_range(edo: u32, cents: f32, min: f32, max: f32) -> f32 {
+    let step = 1200.0 / edo as f32;
+    let (lowest, highest) = ((min / step).ceil() * step, (max / step).floor() * step);
+    if lowest > highest {
+        cents.clamp(min, max)
+    } else {
+        edo_approximation(edo, cents).clamp(lowest, highest)
+    }
+}
+
+/// Returns half a step of an equal division of the octave, in cents. This is the largest
+/// tuning tolerance that still tells its steps apart.
+pub fn edo_tolerance(edo: u32) -> f32 {
+    600.0 / edo as f32
+}
+
+/// Tuning tolerances for matching notes to nodes. Primes 3, 5 and 7 can have their own, and
+/// otherwise use the global tolerance.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Tolerances {
+    pub global: PitchClassDistance,
+    pub three: Option<PitchClassDistance>,
+    pub five: Option<PitchClassDistance>,
+    pub seven: Option<PitchClassDistance>,
+}
+
+impl Tolerances {
+    /// All primes use the same tolerance
+    pub fn uniform(tolerance: PitchClassDistance) -> Self {
+        Tolerances {
+            global: tolerance,
+            three: None,
+            five: None,
+            seven: None,
+        }
+    }
+
+    /// Tolerance for a node: the loosest tolerance among the primes that it has factors of.
+    /// Nodes without factors of 3, 5 or 7, like C, use the global tolerance.
+    pub fn for_primes(&self, primes: &PrimeCountVector) -> PitchClassDistance {
+        [
+            (primes.threes, self.three),
+            (primes.fives, self.five),
+            (primes.sevens, self.seven),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count != 0)
+        .map(|(_, tolerance)| tolerance.unwrap_or(self.global))
+        .max()
+        .unwrap_or(self.global)
+    }
+}
+
+// The syntonic comma, 81/80, that meantone fifths are narrowed by fractions of
+const SYNTONIC_COMMA_F32: f32 = 21.506290;
+
+/// Historical temperaments, for tuning primes 3, 5 and 7 at once
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TemperamentPreset {
+    QuarterCommaMeantone,
+    ThirdCommaMeantone,
+    SixthCommaMeantone,
+    Pythagorean,
+    JustIntonation,
+}
+
+impl TemperamentPreset {
+    pub const ALL: [TemperamentPreset; 5] = [
+        TemperamentPreset::QuarterCommaMeantone,
+        TemperamentPreset::ThirdCommaMeantone,
+        TemperamentPreset::SixthCommaMeantone,
+        TemperamentPreset::Pythagorean,
+        TemperamentPreset::JustIntonation,
+    ];
+
+    /// Short name, to fit on a button
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemperamentPreset::QuarterCommaMeantone => "1/4",
+            TemperamentPreset::ThirdCommaMeantone => "1/3",
+            TemperamentPreset::SixthCommaMeantone => "1/6",
+            TemperamentPreset::Pythagorean => "Pyth",
+            TemperamentPreset::JustIntonation => "JI",
+        }
+    }
+
+    /// Returns the tunings of the fifth, major third and harmonic seventh, in cents.
+    ///
+    /// Temperaments built from fifths tune the third as four fifths, and the seventh as whichever
+    /// of the augmented sixth (ten fifths) and the minor seventh (two fourths) is closer to just.
+    pub fn apply(&self) -> (f32, f32, f32) {
+        let commas = match self {
+            TemperamentPreset::QuarterCommaMeantone => 0.25,
+            TemperamentPreset::ThirdCommaMeantone => 1.0 / 3.0,
+            TemperamentPreset::SixthCommaMeantone => 1.0 / 6.0,
+            TemperamentPreset::Pythagorean => 0.0,
+            TemperamentPreset::JustIntonation => {
+                return (THREE_JUST_F32, FIVE_JUST_F32, SEVEN_JUST_F32)
+            }
+        };
+        let fifth = THREE_JUST_F32 - SYNTONIC_COMMA_F32 * commas;
+        let fifths = |count: i32| (fifth * count as f32).rem_euclid(1200.0);
+        let seven = [fifths(10), fifths(SEVEN_FIFTHS)]
+            .into_iter()
+            .min_by(|a, b| {
+                (a - SEVEN_JUST_F32)
+                    .abs()
+                    .total_cmp(&(b - SEVEN_JUST_F32).abs())
+            })
+            .unwrap();
+        (fifth, fifths(4), seven)
+    }
+
+    /// Tuning tolerance in cents. Meantones get more room, for their small differences from
+    /// just intervals that players may not match exactly.
+    pub fn tolerance(&self) -> f32 {
+        match self {
+            TemperamentPreset::Pythagorean | TemperamentPreset::JustIntonation => 2.0,
+            _ => 5.0,
+        }
+    }
+}
+
+impl TuningSnapshot {
+    /// Moves the lattice's origin from C to a root `root_fifths` fifths above it, in 12-TET. The
+    /// C offset then tunes the root instead.
+    pub fn with_root(self, root_fifths: i32) -> Self {
+        TuningSnapshot {
+            c_offset: self.c_offset + root_pitch_class(root_fifths),
+            ..self
+        }
+    }
+
+    /// Pitch class of a lattice node, including the C offset
+    pub fn pitch_class(&self, primes: &PrimeCountVector) -> PitchClass {
+        primes.pitch_class(self.three, self.five, self.seven, self.eleven) + self.c_offset
+    }
+
+    /// Whether the harmonic seventh is equal to the meantone minor seventh
+    pub fn seven_collapses(&self, tuning_tolerance: PitchClassDistance) -> bool {
+        prime_collapses_to_fifths(self.seven, self.three, &[SEVEN_FIFTHS], tuning_tolerance)
+    }
+
+    /// Whether the eleventh harmonic is equal to the perfect or augmented fourth
+    pub fn eleven_collapses(&self, tuning_tolerance: PitchClassDistance) -> bool {
+        prime_collapses_to_fifths(
+            self.eleven,
+            self.three,
+            &[ELEVEN_FIFTHS, ELEVEN_FIFTHS + 7],
+            tuning_tolerance,
+        )
+    }
+
+    /// Whether a lattice node is tuned as in just intonation, relative to C
+    pub fn is_just(&self, primes: &PrimeCountVector, tuning_tolerance: PitchClassDistance) -> bool {
+        primes
+            .pitch_class(self.three, self.five, self.seven, self.eleven)
+            .distance_to(primes.pitch_class(THREE_JUST, FIVE_JUST, SEVEN_JUST, ELEVEN_JUST))
+            <= tuning_tolerance
+    }
+}
+
+/// Represents an abstract pitch class as its number of prime factors of 3, 5, 7 and 11
+/// C = (0, 0, 0, 0)
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Hash)]
 pub struct PrimeCountVector {
     pub threes: i32,
     pub fives: i32,
     pub sevens: i32,
+    pub elevens: i32,
 }
 
 impl PrimeCountVector {
-    pub fn new(threes: i32, fives: i32, sevens: i32) -> PrimeCountVector {
+    pub fn new(threes: i32, fives: i32, sevens: i32, elevens: i32) -> PrimeCountVector {
         PrimeCountVector {
             threes,
             fives,
             sevens,
+            elevens,
         }
     }
 
-    // Cents value of a pitch class, given tunings for 3, 5 and 7
+    // Cents value of a pitch class, given tunings for 3, 5, 7 and 11
     pub fn pitch_class(
         &self,
         three_tuning: PitchClass,
         five_tuning: PitchClass,
         seven_tuning: PitchClass,
+        eleven_tuning: PitchClass,
     ) -> PitchClass {
         three_tuning.multiply(self.threes)
             + five_tuning.multiply(self.fives)
             + seven_tuning.multiply(self.sevens)
+            + eleven_tuning.multiply(self.elevens)
+    }
+
+    /// Returns the vector with no elevens, and at most `search_radius` factors of 3, 5 and 7 in
+    /// total, whose pitch class is closest to a pitch class relative to C, along with how far it
+    /// is. Of equally close vectors, the one with the fewest factors is returned.
+    pub fn nearest(
+        pitch_class: PitchClass,
+        three_tuning: PitchClass,
+        five_tuning: PitchClass,
+        seven_tuning: PitchClass,
+        search_radius: i32,
+    ) -> (PrimeCountVector, PitchClassDistance) {
+        let complexity = |primes: &PrimeCountVector| {
+            primes.threes.abs() + primes.fives.abs() + primes.sevens.abs()
+        };
+        let mut nearest = PrimeCountVector::new(0, 0, 0, 0);
+        let mut nearest_distance = pitch_class.distance_to(PitchClass::from_microcents(0));
+        for threes in -search_radius..=search_radius {
+            let fives_radius = search_radius - threes.abs();
+            for fives in -fives_radius..=fives_radius {
+                let sevens_radius = fives_radius - fives.abs();
+                for sevens in -sevens_radius..=sevens_radius {
+                    let candidate = PrimeCountVector::new(threes, fives, sevens, 0);
+                    let distance = candidate
+                        .pitch_class(
+                            three_tuning,
+                            five_tuning,
+                            seven_tuning,
+                            PitchClass::from_microcents(0),
+                        )
+                        .distance_to(pitch_class);
+                    if (distance, complexity(&candidate)) < (nearest_distance, complexity(&nearest))
+                    {
+                        nearest = candidate;
+                        nearest_distance = distance;
+                    }
+                }
+            }
+        }
+        (nearest, nearest_distance)
     }
 
-    pub fn note_name_info(&self) -> NoteNameInfo {
+    /// Frequency ratio of the pitch class in just intonation, reduced to be within an octave above
+    /// 1/1. Returns `None` if the numerator or denominator doesn't fit in a u64.
+    pub fn ratio(&self) -> Option<(u64, u64)> {
+        let mut numerator: u64 = 1;
+        let mut denominator: u64 = 1;
+        for (prime, count) in [
+            (3, self.threes),
+            (5, self.fives),
+            (7, self.sevens),
+            (11, self.elevens),
+        ] {
+            let power = u64::checked_pow(prime, count.unsigned_abs())?;
+            if count > 0 {
+                numerator = numerator.checked_mul(power)?;
+            } else {
+                denominator = denominator.checked_mul(power)?;
+            }
+        }
+
+        // Odd primes never cancel out, so only powers of two are left to add
+        while numerator >= denominator.checked_mul(2)? {
+            denominator *= 2;
+        }
+        while numerator < denominator {
+            numerator = numerator.checked_mul(2)?;
+        }
+        Some((numerator, denominator))
+    }
+
+    /// Prime factorization of the pitch class, without factors of 2, like "3^2 · 5^-1".
+    /// C is "1".
+    pub fn factorization_str(&self) -> String {
+        let factors: Vec<String> = [
+            (3, self.threes),
+            (5, self.fives),
+            (7, self.sevens),
+            (11, self.elevens),
+        ]
+        .iter()
+        .filter(|(_, count)| *count != 0)
+        .map(|(prime, count)| match count {
+            1 => prime.to_string(),
+            _ => format!("{}^{}", prime, count),
+        })
+        .collect();
+        if factors.is_empty() {
+            "1".to_string()
+        } else {
+            factors.join(" · ")
+        }
+    }
+
+    /// Counts of 3 and 5 in angle brackets, like "[2 -1⟩". Counts of 7 and 11 follow if the
+    /// pitch class has any.
+    pub fn monzo_str(&self) -> String {
+        let counts = if self.elevens != 0 {
+            vec![self.threes, self.fives, self.sevens, self.elevens]
+        } else if self.sevens != 0 {
+            vec![self.threes, self.fives, self.sevens]
+        } else {
+            vec![self.threes, self.fives]
+        };
+        let counts: Vec<String> = counts.iter().map(|count| count.to_string()).collect();
+        format!("[{}⟩", counts.join(" "))
+    }
+
+    /// Note name of the pitch class, on a lattice whose origin is `root_fifths` fifths above C
+    pub fn note_name_info(&self, root_fifths: i32) -> NoteNameInfo {
         static NOTE_NAMES: [char; 7] = ['F', 'C', 'G', 'D', 'A', 'E', 'B'];
-        let letter_names_idx = 1 + self.threes + self.fives * 4 - self.sevens * 2;
+        let letter_names_idx = 1
+            + root_fifths
+            + self.threes
+            + self.fives * 4
+            + self.sevens * SEVEN_FIFTHS
+            + self.elevens * ELEVEN_FIFTHS;
         NoteNameInfo {
             letter_name: NOTE_NAMES[letter_names_idx.rem_euclid(7) as usize],
             sharps_or_flats: letter_names_idx.div_euclid(7),
             syntonic_commas: -self.fives,
             septimal_commas: -self.sevens,
+            undecimal_commas: self.elevens,
         }
     }
 }
@@ -247,6 +633,9 @@ pub struct NoteNameInfo {
 
     /// Number of septimal commas (64/63) added or subtracted
     pub septimal_commas: i32,
+
+    /// Number of undecimal quarter tones (33/32) added or subtracted
+    pub undecimal_commas: i32,
 }
 
 impl NoteNameInfo {
@@ -259,6 +648,48 @@ impl NoteNameInfo {
         comma_str(self.syntonic_commas, '+', '-')
     }
 
+    /// Returns a string for displaying the number of septimal commas
+    /// 1 comma -> >
+    /// -1 comma -> <
+    /// -3 commas -> <3
+    pub fn septimal_comma_str(&self) -> String {
+        comma_str(self.septimal_commas, '>', '<')
+    }
+
+    /// Returns a string for displaying the number of undecimal quarter tones
+    /// 1 quarter tone -> ^
+    /// -1 quarter tone -> v
+    pub fn undecimal_comma_str(&self) -> String {
+        comma_str(self.undecimal_commas, '^', 'v')
+    }
+
+    /// Returns the letter name in Helmholtz notation, for a note sounding at `pitch` in MIDI note
+    /// numbers. Octaves from C3 (48) are lowercase, with a prime for each octave from middle C up
+    /// ("c'"). Octaves below C2 (36) add commas instead ("C,").
+    pub fn helmholtz_letter_name(&self, pitch: f32) -> String {
+        let letter_semitones = match self.letter_name {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            _ => 11,
+        };
+        // Spelled with its own octave, so B# below middle C is in octave 3
+        let octave =
+            ((pitch - (letter_semitones + self.sharps_or_flats) as f32) / 12.0).round() as i32 - 1;
+        if octave < 3 {
+            format!("{}{}", self.letter_name, ",".repeat((2 - octave) as usize))
+        } else {
+            format!(
+                "{}{}",
+                self.letter_name.to_ascii_lowercase(),
+                "'".repeat((octave - 3) as usize)
+            )
+        }
+    }
+
     /// Returns a string for displaying the number of sharps/flats
     /// 1 sharp -> #
     /// 2 sharps -> ##
@@ -267,6 +698,246 @@ impl NoteNameInfo {
     pub fn sharps_or_flats_str(&self) -> String {
         comma_str(self.sharps_or_flats, '#', 'b')
     }
+
+    /// Returns the sharps or flats with Unicode accidentals
+    /// 1 sharp -> ♯
+    /// 2 sharps -> × (the bundled fonts don't have 𝄪)
+    /// 3 sharps -> ♯3
+    /// 2 flats -> ♭♭
+    pub fn heji_sharps_or_flats_str(&self) -> String {
+        match self.sharps_or_flats {
+            2 => String::from("×"),
+            count => comma_str(count, '♯', '♭'),
+        }
+    }
+
+    /// Returns the syntonic commas as arrows, like HEJI's arrowed accidentals
+    /// 1 comma -> ↑
+    /// -2 commas -> ↓↓
+    pub fn heji_syntonic_comma_str(&self) -> String {
+        comma_str(self.syntonic_commas, '↑', '↓')
+    }
+
+    /// Returns the septimal commas, with glyphs shaped like HEJI's septimal comma symbols
+    /// 1 comma -> ᒪ
+    /// -1 comma -> ᒣ
+    pub fn heji_septimal_comma_str(&self) -> String {
+        comma_str(self.septimal_commas, 'ᒪ', 'ᒣ')
+    }
+
+    /// Returns the full note name with HEJI-style accidentals, e.g. "B♭ᒣ" for 7/4 above C
+    pub fn to_heji_string(&self) -> String {
+        format!(
+            "{}{}{}{}{}",
+            self.letter_name,
+            self.heji_sharps_or_flats_str(),
+            self.heji_syntonic_comma_str(),
+            self.heji_septimal_comma_str(),
+            self.undecimal_comma_str()
+        )
+    }
+}
+
+impl Display for NoteNameInfo {
+    /// The full note name with ASCII accidentals, e.g. "Bb<" for 7/4 above C
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}{}",
+            self.letter_name,
+            self.sharps_or_flats_str(),
+            self.syntonic_comma_str(),
+            self.septimal_comma_str(),
+            self.undecimal_comma_str()
+        )
+    }
+}
+
+// Odd harmonics up to 16 that the lattice's primes can make, with their prime counts. 13 isn't
+// tuned, so 13 is left out.
+const CHORD_ODD_HARMONICS: [(u32, (i32, i32, i32, i32)); 7] = [
+    (1, (0, 0, 0, 0)),
+    (3, (1, 0, 0, 0)),
+    (5, (0, 1, 0, 0)),
+    (7, (0, 0, 1, 0)),
+    (9, (2, 0, 0, 0)),
+    (11, (0, 0, 0, 1)),
+    (15, (1, 1, 0, 0)),
+];
+
+// Names of common chords, by their harmonics in close position and whether they're utonal. Chords
+// are read from their simplest fundamental, so 6:7:9 is read as 9:12:14.
+const CHORD_NAMES: [(&[u32], bool, &str); 12] = [
+    (&[2, 3], false, "perfect fifth"),
+    (&[4, 5], false, "major third"),
+    (&[5, 6], false, "minor third"),
+    (&[4, 7], false, "harmonic seventh"),
+    (&[6, 7], false, "subminor third"),
+    (&[4, 5, 6], false, "major"),
+    (&[4, 5, 6], true, "minor"),
+    (&[5, 6, 7], false, "diminished"),
+    (&[9, 12, 14], false, "subminor"),
+    (&[4, 5, 6, 7], false, "otonal tetrad"),
+    (&[4, 5, 6, 7], true, "utonal tetrad"),
+    (&[8, 10, 12, 15], false, "major seventh"),
+];
+
+/// A chord read as harmonics of one fundamental (otonal), or as subharmonics of one pitch
+/// (utonal)
+#[derive(Debug, PartialEq, Clone)]
+pub struct ChordInterpretation {
+    /// Harmonic numbers in close position, ascending, like [4, 5, 6, 7]
+    pub harmonics: Vec<u32>,
+    pub utonal: bool,
+    /// The fundamental, or for utonal chords, the pitch that every note is a subharmonic of
+    pub fundamental: PitchClass,
+}
+
+impl ChordInterpretation {
+    /// Like the chord's `Display` string, with HEJI's sharp and flat symbols in the fundamental
+    pub fn to_heji_string(&self) -> String {
+        self.describe(self.fundamental.twelve_tet_heji_name())
+    }
+
+    /// Name of the chord, if it's a common one
+    pub fn name(&self) -> Option<&'static str> {
+        CHORD_NAMES
+            .iter()
+            .find(|(harmonics, utonal, _)| *harmonics == self.harmonics && *utonal == self.utonal)
+            .map(|(_, _, name)| *name)
+    }
+
+    fn describe(&self, fundamental_name: &str) -> String {
+        let harmonics: Vec<String> = self.harmonics.iter().map(|h| h.to_string()).collect();
+        // Dyads are shown as the ratio between their notes, since either can be the lower one
+        let description = if self.harmonics.len() == 2 {
+            format!("{}/{}", self.harmonics[1], self.harmonics[0])
+        } else if self.utonal {
+            format!("1/({}) on {}", harmonics.join(":"), fundamental_name)
+        } else {
+            format!("{} on {}", harmonics.join(":"), fundamental_name)
+        };
+        match self.name() {
+            Some(name) => format!("{} ({})", description, name),
+            None => description,
+        }
+    }
+}
+
+impl Display for ChordInterpretation {
+    /// "4:5:6:7 on C (otonal tetrad)", "1/(4:5:6) on E (minor)" for utonal chords, or
+    /// "3/2 (perfect fifth)" for dyads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe(self.fundamental.twelve_tet_name()))
+    }
+}
+
+/// Finds the simplest reading of two or more sorted pitch classes as harmonics (otonal) or
+/// subharmonics (utonal) of one pitch, using odd harmonics up to 15 as tuned by `tuning`. Readings
+/// with smaller harmonics are simpler, and otonal readings win ties. Returns `None` if no reading
+/// matches every pitch class within `tolerance`.
+///
+/// Only tries fundamentals that put the first pitch class on a harmonic, so it's cheap enough to
+/// run on every frame.
+pub fn detect_chord(
+    sorted_pitch_classes: &[PitchClass],
+    tuning: &TuningSnapshot,
+    tolerance: PitchClassDistance,
+) -> Option<ChordInterpretation> {
+    if sorted_pitch_classes.len() < 2 {
+        return None;
+    }
+    let harmonic_pitch_classes: Vec<(u32, PitchClass)> = CHORD_ODD_HARMONICS
+        .iter()
+        .map(|(harmonic, (threes, fives, sevens, elevens))| {
+            let primes = PrimeCountVector::new(*threes, *fives, *sevens, *elevens);
+            let pitch_class =
+                primes.pitch_class(tuning.three, tuning.five, tuning.seven, tuning.eleven);
+            (*harmonic, pitch_class)
+        })
+        .collect();
+
+    let mut best: Option<((u32, u32), ChordInterpretation)> = None;
+    for utonal in [false, true] {
+        // Utonal chords go down from the fundamental instead of up
+        let note_on = |fundamental: PitchClass, harmonic_pitch_class: PitchClass| {
+            if utonal {
+                fundamental - harmonic_pitch_class
+            } else {
+                fundamental + harmonic_pitch_class
+            }
+        };
+        for (_, first_harmonic_pitch_class) in &harmonic_pitch_classes {
+            let fundamental = if utonal {
+                sorted_pitch_classes[0] + *first_harmonic_pitch_class
+            } else {
+                sorted_pitch_classes[0] - *first_harmonic_pitch_class
+            };
+            let odd_harmonics: Option<Vec<u32>> = sorted_pitch_classes
+                .iter()
+                .map(|pitch_class| {
+                    harmonic_pitch_classes
+                        .iter()
+                        .map(|(harmonic, harmonic_pitch_class)| {
+                            let note = note_on(fundamental, *harmonic_pitch_class);
+                            (*harmonic, note.distance_to(*pitch_class))
+                        })
+                        .filter(|(_, distance)| *distance <= tolerance)
+                        .min_by_key(|(_, distance)| *distance)
+                        .map(|(harmonic, _)| harmonic)
+                })
+                .collect();
+            let mut odd_harmonics = match odd_harmonics {
+                Some(odd_harmonics) => odd_harmonics,
+                None => continue,
+            };
+            odd_harmonics.sort_unstable();
+            if odd_harmonics.windows(2).any(|pair| pair[0] == pair[1]) {
+                continue;
+            }
+
+            let score = (
+                *odd_harmonics.last().unwrap(),
+                odd_harmonics.iter().sum::<u32>(),
+            );
+            if best
+                .as_ref()
+                .map_or(true, |(best_score, _)| score < *best_score)
+            {
+                best = Some((
+                    score,
+                    ChordInterpretation {
+                        harmonics: close_position(&odd_harmonics),
+                        utonal,
+                        fundamental,
+                    },
+                ));
+            }
+        }
+    }
+    best.map(|(_, chord)| chord)
+}
+
+/// Moves odd harmonics by octaves into the octave starting at the lowest power of two that fits
+/// them all, e.g. 1, 3, 5 and 7 become 4, 5, 6 and 7
+fn close_position(odd_harmonics: &[u32]) -> Vec<u32> {
+    let max_harmonic = odd_harmonics.iter().copied().max().unwrap_or(1);
+    let mut octave_start = 1;
+    while octave_start * 2 <= max_harmonic {
+        octave_start *= 2;
+    }
+    let mut harmonics: Vec<u32> = odd_harmonics
+        .iter()
+        .map(|harmonic| {
+            let mut harmonic = *harmonic;
+            while harmonic < octave_start {
+                harmonic *= 2;
+            }
+            harmonic
+        })
+        .collect();
+    harmonics.sort_unstable();
+    harmonics
 }
 
 /// Generic way to make a string representing the number of a comma added or subtracted
@@ -355,4 +1026,647 @@ mod tests {
             PitchClass::from_microcents(1_000_000_000)
         );
     }
+
+    #[test]
+    fn test_eleven_note_name() {
+        // 11/8 is an F raised by a quarter tone
+        let info = PrimeCountVector::new(0, 0, 0, 1).note_name_info(0);
+        assert_eq!(info.letter_name, 'F');
+        assert_eq!(info.sharps_or_flats, 0);
+        assert_eq!(info.undecimal_comma_str(), "^");
+
+        // 11/6 = 11/8 divided by 3/2, is a B flat raised by a quarter tone
+        let info = PrimeCountVector::new(-1, 0, 0, 1).note_name_info(0);
+        assert_eq!(info.letter_name, 'B');
+        assert_eq!(info.sharps_or_flats, -1);
+    }
+
+    fn just_tuning() -> TuningSnapshot {
+        TuningSnapshot {
+            c_offset: PitchClass::from_cents_f32(0.0),
+            three: THREE_JUST,
+            five: FIVE_JUST,
+            seven: SEVEN_JUST,
+            eleven: ELEVEN_JUST,
+        }
+    }
+
+    fn assert_nearest(
+        pitch_class: PitchClass,
+        (three, five, seven): (PitchClass, PitchClass, PitchClass),
+        search_radius: i32,
+        (threes, fives, sevens): (i32, i32, i32),
+        distance: PitchClassDistance,
+    ) {
+        let (nearest, nearest_distance) =
+            PrimeCountVector::nearest(pitch_class, three, five, seven, search_radius);
+        assert_eq!(
+            (
+                nearest.threes,
+                nearest.fives,
+                nearest.sevens,
+                nearest.elevens
+            ),
+            (threes, fives, sevens, 0)
+        );
+        assert_eq!(nearest_distance, distance);
+    }
+
+    #[test]
+    fn test_nearest() {
+        let just = (THREE_JUST, FIVE_JUST, SEVEN_JUST);
+        let just_pitch_class = |threes, fives, sevens| {
+            PrimeCountVector::new(threes, fives, sevens, 0).pitch_class(
+                THREE_JUST,
+                FIVE_JUST,
+                SEVEN_JUST,
+                ELEVEN_JUST,
+            )
+        };
+
+        // 5/4 and 7/4
+        assert_nearest(
+            just_pitch_class(0, 1, 0),
+            just,
+            4,
+            (0, 1, 0),
+            PitchClassDistance::from_cents(0),
+        );
+        assert_nearest(
+            just_pitch_class(0, 0, 1),
+            just,
+            4,
+            (0, 0, 1),
+            PitchClassDistance::from_cents(0),
+        );
+
+        // 9/8 is farther than the search radius, so it's taken as 8/7
+        assert_nearest(
+            just_pitch_class(2, 0, 0),
+            just,
+            1,
+            (0, 0, -1),
+            just_pitch_class(2, 0, 0).distance_to(just_pitch_class(0, 0, -1)),
+        );
+
+        // Off the lattice
+        assert_nearest(
+            PitchClass::from_cents_f32(10.0),
+            just,
+            1,
+            (0, 0, 0),
+            PitchClassDistance::from_cents(10),
+        );
+
+        // In 12-TET, four fifths make a major third too
+        assert_nearest(
+            PitchClass::from_cents_f32(400.0),
+            (
+                PitchClass::from_cents_f32(700.0),
+                PitchClass::from_cents_f32(400.0),
+                PitchClass::from_cents_f32(1000.0),
+            ),
+            4,
+            (0, 1, 0),
+            PitchClassDistance::from_cents(0),
+        );
+    }
+
+    fn pitch_classes(cents: &[f32]) -> Vec<PitchClass> {
+        let mut pitch_classes: Vec<PitchClass> = cents
+            .iter()
+            .map(|c| PitchClass::from_cents_f32(*c))
+            .collect();
+        pitch_classes.sort_unstable();
+        pitch_classes
+    }
+
+    #[test]
+    fn test_detect_chord() {
+        let tolerance = PitchClassDistance::from_cents(3);
+        // Just dominant seventh on C
+        let chord = detect_chord(
+            &pitch_classes(&[0.0, 386.3, 702.0, 968.8]),
+            &just_tuning(),
+            tolerance,
+        )
+        .unwrap();
+        assert_eq!(chord.to_string(), "4:5:6:7 on C (otonal tetrad)");
+        // Without its root, it's still read from C
+        let chord = detect_chord(
+            &pitch_classes(&[386.3, 702.0, 968.8]),
+            &just_tuning(),
+            tolerance,
+        );
+        assert_eq!(chord.unwrap().to_string(), "5:6:7 on C (diminished)");
+        // A just minor triad on A is utonal, rather than 10:12:15 on F
+        let chord = detect_chord(
+            &pitch_classes(&[884.4, 0.0, 386.3]),
+            &just_tuning(),
+            tolerance,
+        );
+        assert_eq!(chord.unwrap().to_string(), "1/(4:5:6) on E (minor)");
+        // Fundamentals are named with HEJI's symbols too
+        let chord = detect_chord(
+            &pitch_classes(&[996.1, 182.4, 498.0, 764.9]),
+            &just_tuning(),
+            tolerance,
+        );
+        assert_eq!(
+            chord.unwrap().to_heji_string(),
+            "4:5:6:7 on B♭ (otonal tetrad)"
+        );
+        // Add nine
+        let chord = detect_chord(
+            &pitch_classes(&[0.0, 386.3, 702.0, 203.9]),
+            &just_tuning(),
+            tolerance,
+        );
+        assert_eq!(chord.unwrap().harmonics, vec![8, 9, 10, 12]);
+    }
+
+    #[test]
+    fn test_detect_chord_uses_tuning() {
+        let tolerance = PitchClassDistance::from_cents(3);
+        let twelve_tet_triad = pitch_classes(&[0.0, 400.0, 700.0]);
+        assert_eq!(
+            detect_chord(&twelve_tet_triad, &just_tuning(), tolerance),
+            None
+        );
+        let twelve_tet = TuningSnapshot {
+            three: PitchClass::from_cents_f32(700.0),
+            five: PitchClass::from_cents_f32(400.0),
+            ..just_tuning()
+        };
+        let chord = detect_chord(&twelve_tet_triad, &twelve_tet, tolerance);
+        assert_eq!(chord.unwrap().to_string(), "4:5:6 on C (major)");
+        // One note isn't a chord
+        assert_eq!(
+            detect_chord(&pitch_classes(&[0.0]), &just_tuning(), tolerance),
+            None
+        );
+    }
+
+    #[test]
+    fn test_detect_dyad() {
+        let tolerance = PitchClassDistance::from_cents(3);
+        let dyad = |cents: &[f32]| {
+            detect_chord(&pitch_classes(cents), &just_tuning(), tolerance)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(dyad(&[0.0, 702.0]), "3/2 (perfect fifth)");
+        // Pitch classes don't know which note is lower, so a fourth reads as a fifth
+        assert_eq!(dyad(&[0.0, 498.0]), "3/2 (perfect fifth)");
+        assert_eq!(dyad(&[0.0, 386.3]), "5/4 (major third)");
+        assert_eq!(dyad(&[386.3, 702.0]), "6/5 (minor third)");
+        assert_eq!(dyad(&[0.0, 968.8]), "7/4 (harmonic seventh)");
+        assert_eq!(dyad(&[0.0, 203.9]), "9/8");
+    }
+
+    #[test]
+    fn test_chord_names() {
+        let tolerance = PitchClassDistance::from_cents(3);
+        let chord = |cents: &[f32]| {
+            detect_chord(&pitch_classes(cents), &just_tuning(), tolerance)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(chord(&[0.0, 386.3, 702.0]), "4:5:6 on C (major)");
+        assert_eq!(chord(&[386.3, 702.0, 968.8]), "5:6:7 on C (diminished)");
+        assert_eq!(chord(&[702.0, 968.8, 203.9]), "9:12:14 on C (subminor)");
+        assert_eq!(
+            chord(&[0.0, 386.3, 702.0, 1088.3]),
+            "8:10:12:15 on C (major seventh)"
+        );
+        assert_eq!(chord(&[0.0, 386.3, 203.9]), "8:9:10 on C");
+    }
+
+    #[test]
+    fn test_to_heji_string() {
+        assert_eq!(
+            PrimeCountVector::new(0, 0, 0, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "C"
+        );
+        // 5/4 is a comma below the pythagorean E, and 7/4 a septimal comma below Bb
+        assert_eq!(
+            PrimeCountVector::new(0, 1, 0, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "E↓"
+        );
+        assert_eq!(
+            PrimeCountVector::new(0, 0, 1, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "B♭ᒣ"
+        );
+        assert_eq!(
+            PrimeCountVector::new(0, -1, -1, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "B♭↑ᒪ"
+        );
+        // 25/16 is G# two commas down, and 11/8 keeps its quarter tone
+        assert_eq!(
+            PrimeCountVector::new(0, 2, 0, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "G♯↓↓"
+        );
+        assert_eq!(
+            PrimeCountVector::new(0, 0, 0, 1)
+                .note_name_info(0)
+                .to_heji_string(),
+            "F^"
+        );
+        // Double sharps and flats
+        assert_eq!(
+            PrimeCountVector::new(16, 0, 0, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "D×"
+        );
+        assert_eq!(
+            PrimeCountVector::new(-9, 0, 0, 0)
+                .note_name_info(0)
+                .to_heji_string(),
+            "B♭♭"
+        );
+    }
+
+    #[test]
+    fn test_note_name_display() {
+        let name = |primes: PrimeCountVector| primes.note_name_info(0).to_string();
+        assert_eq!(name(PrimeCountVector::new(0, 0, 0, 0)), "C");
+        assert_eq!(name(PrimeCountVector::new(0, 1, 0, 0)), "E-");
+        assert_eq!(name(PrimeCountVector::new(0, 0, 1, 0)), "Bb<");
+        assert_eq!(name(PrimeCountVector::new(0, 2, 0, 0)), "G#--");
+        assert_eq!(name(PrimeCountVector::new(0, 0, 0, 1)), "F^");
+    }
+
+    #[test]
+    fn test_note_name_from_root() {
+        // On a lattice rooted on D, a fifth up is A and a major third up is F#
+        let info = PrimeCountVector::new(1, 0, 0, 0).note_name_info(2);
+        assert_eq!((info.letter_name, info.sharps_or_flats), ('A', 0));
+        let info = PrimeCountVector::new(0, 1, 0, 0).note_name_info(2);
+        assert_eq!((info.letter_name, info.sharps_or_flats), ('F', 1));
+        assert_eq!(info.syntonic_comma_str(), "-");
+        // The harmonic seventh is a flat seventh lowered by a septimal comma
+        let info = PrimeCountVector::new(0, 0, 1, 0).note_name_info(2);
+        assert_eq!((info.letter_name, info.sharps_or_flats), ('C', 0));
+        assert_eq!(info.septimal_comma_str(), "<");
+        let info = PrimeCountVector::new(0, 0, -2, 0).note_name_info(0);
+        assert_eq!(info.septimal_comma_str(), ">>");
+        // Rooted on Eb, a fifth down is Ab
+        let info = PrimeCountVector::new(-1, 0, 0, 0).note_name_info(-3);
+        assert_eq!((info.letter_name, info.sharps_or_flats), ('A', -1));
+    }
+
+    #[test]
+    fn test_with_root() {
+        let tuning = TuningSnapshot {
+            c_offset: PitchClass::from_cents_f32(-10.0),
+            three: THREE_JUST,
+            five: FIVE_JUST,
+            seven: SEVEN_JUST,
+            eleven: ELEVEN_JUST,
+        };
+        let origin = PrimeCountVector::new(0, 0, 0, 0);
+        assert_eq!(tuning.with_root(0), tuning);
+        // D is two fifths up, and the C offset now tunes D
+        assert_eq!(
+            tuning.with_root(2).pitch_class(&origin),
+            PitchClass::from_cents_f32(190.0)
+        );
+        assert_eq!(
+            tuning.with_root(-1).pitch_class(&origin),
+            PitchClass::from_cents_f32(490.0)
+        );
+    }
+
+    #[test]
+    fn test_helmholtz_letter_name() {
+        let c = PrimeCountVector::new(0, 0, 0, 0).note_name_info(0);
+        assert_eq!(c.helmholtz_letter_name(60.0), "c'");
+        assert_eq!(c.helmholtz_letter_name(72.0), "c''");
+        assert_eq!(c.helmholtz_letter_name(48.0), "c");
+        assert_eq!(c.helmholtz_letter_name(36.0), "C");
+        assert_eq!(c.helmholtz_letter_name(24.0), "C,");
+        // Slightly flat of middle C is still middle C
+        assert_eq!(c.helmholtz_letter_name(59.8), "c'");
+
+        // B below middle C, and B# sounding at middle C, are both in the octave below it
+        let b = PrimeCountVector::new(1, 1, 0, 0).note_name_info(0);
+        assert_eq!(b.helmholtz_letter_name(59.0), "b");
+        let b_sharp = PrimeCountVector::new(12, 0, 0, 0).note_name_info(0);
+        assert_eq!(b_sharp.letter_name, 'B');
+        assert_eq!(b_sharp.helmholtz_letter_name(60.0), "b");
+    }
+
+    #[test]
+    fn test_factorization_str() {
+        assert_eq!(PrimeCountVector::new(0, 0, 0, 0).factorization_str(), "1");
+        assert_eq!(
+            PrimeCountVector::new(2, -1, 0, 0).factorization_str(),
+            "3^2 · 5^-1"
+        );
+        assert_eq!(
+            PrimeCountVector::new(0, 1, 1, -1).factorization_str(),
+            "5 · 7 · 11^-1"
+        );
+    }
+
+    #[test]
+    fn test_monzo_str() {
+        assert_eq!(PrimeCountVector::new(0, 0, 0, 0).monzo_str(), "[0 0⟩");
+        assert_eq!(PrimeCountVector::new(2, -1, 0, 0).monzo_str(), "[2 -1⟩");
+        assert_eq!(PrimeCountVector::new(0, 0, 1, 0).monzo_str(), "[0 0 1⟩");
+        assert_eq!(PrimeCountVector::new(1, 0, 0, -1).monzo_str(), "[1 0 0 -1⟩");
+    }
+
+    #[test]
+    fn test_is_just() {
+        let tolerance = PitchClassDistance::from_cents(1);
+        let tuning = TuningSnapshot {
+            c_offset: PitchClass::from_cents_f32(50.0),
+            three: PitchClass::from_cents_f32(700.0),
+            five: FIVE_JUST,
+            seven: SEVEN_JUST,
+            eleven: ELEVEN_JUST,
+        };
+        assert!(tuning.is_just(&PrimeCountVector::new(0, 0, 0, 0), tolerance));
+        assert!(tuning.is_just(&PrimeCountVector::new(0, -1, 1, 0), tolerance));
+        // 12-TET fifths are about 2 cents flat, so two are about 4 cents flat
+        assert!(!tuning.is_just(&PrimeCountVector::new(2, 0, 0, 0), tolerance));
+        assert!(tuning.is_just(
+            &PrimeCountVector::new(2, 0, 0, 0),
+            PitchClassDistance::from_cents(4)
+        ));
+    }
+
+    #[test]
+    fn test_eleven_pitch_class() {
+        let primes = PrimeCountVector::new(1, 0, 0, -1);
+        assert_eq!(
+            primes.pitch_class(THREE_JUST, FIVE_JUST, SEVEN_JUST, ELEVEN_JUST),
+            THREE_JUST - ELEVEN_JUST
+        );
+    }
+
+    #[test]
+    fn test_prime_collapses() {
+        let tolerance = PitchClassDistance::from_cents(1);
+        let tuning = |three: f32, seven: f32, eleven: f32| TuningSnapshot {
+            c_offset: PitchClass::from_microcents(0),
+            three: PitchClass::from_cents_f32(three),
+            five: FIVE_JUST,
+            seven: PitchClass::from_cents_f32(seven),
+            eleven: PitchClass::from_cents_f32(eleven),
+        };
+
+        // 12-TET: 7 is a minor seventh, 11 is an augmented fourth
+        assert!(tuning(700.0, 1000.0, 600.0).seven_collapses(tolerance));
+        assert!(tuning(700.0, 1000.0, 600.0).eleven_collapses(tolerance));
+        // 11 as a perfect fourth
+        assert!(tuning(700.0, 1000.0, 500.0).eleven_collapses(tolerance));
+        // Just intonation
+        assert!(!tuning(THREE_JUST_F32, SEVEN_JUST_F32, ELEVEN_JUST_F32).seven_collapses(tolerance));
+        assert!(
+            !tuning(THREE_JUST_F32, SEVEN_JUST_F32, ELEVEN_JUST_F32).eleven_collapses(tolerance)
+        );
+    }
+
+    #[test]
+    fn test_deviation_from_12tet() {
+        assert_eq!(PitchClass::from_midi_note(7).deviation_from_12tet(), 0.0);
+        assert!((THREE_JUST.deviation_from_12tet() - 1.955).abs() < 0.001);
+        assert!((FIVE_JUST.deviation_from_12tet() + 13.6863).abs() < 0.001);
+        assert_eq!(
+            PitchClass::from_cents_f32(1190.0).deviation_from_12tet(),
+            -10.0
+        );
+        assert_eq!(
+            PitchClass::from_cents_f32(10.0).deviation_from_12tet(),
+            10.0
+        );
+        assert_eq!(
+            PitchClass::from_cents_f32(650.0).deviation_from_12tet(),
+            50.0
+        );
+    }
+
+    #[test]
+    fn test_twelve_tet_name() {
+        assert_eq!(PitchClass::from_cents_f32(1190.0).twelve_tet_name(), "C");
+        assert_eq!(FIVE_JUST.twelve_tet_name(), "E");
+        assert_eq!(PitchClass::from_cents_f32(310.0).twelve_tet_name(), "Eb");
+        assert_eq!(
+            PitchClass::from_cents_f32(310.0).twelve_tet_heji_name(),
+            "E♭"
+        );
+        assert_eq!(PitchClass::from_midi_note(6).twelve_tet_heji_name(), "F♯");
+    }
+
+    #[test]
+    fn test_to_octave_fraction() {
+        assert_eq!(PitchClass::from_microcents(0).to_octave_fraction(), 0.0);
+        assert_eq!(PitchClass::from_cents_f32(600.0).to_octave_fraction(), 0.5);
+        assert_eq!(
+            PitchClass::from_cents_f32(-300.0).to_octave_fraction(),
+            0.75
+        );
+    }
+
+    #[test]
+    fn test_to_frequency() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        assert!(close(
+            PitchClass::from_microcents(0).to_frequency(261.626),
+            261.626
+        ));
+        assert!(close(
+            PitchClass::from_cents_f32(900.0).to_frequency(261.626),
+            440.0
+        ));
+        assert!(close(THREE_JUST.to_frequency(200.0), 300.0));
+    }
+
+    #[test]
+    fn test_to_frequency_in_octave() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01;
+        let a = PitchClass::from_cents_f32(900.0);
+        assert!(close(a.to_frequency_in_octave(261.626, 1200.0, 4), 440.0));
+        assert!(close(a.to_frequency_in_octave(261.626, 1200.0, 5), 880.0));
+        assert!(close(a.to_frequency_in_octave(261.626, 1200.0, 2), 110.0));
+        // Each octave is stretched, not just the one above C
+        assert!(close(
+            PitchClass::from_microcents(0).to_frequency_in_octave(100.0, 1230.0, 6),
+            100.0 * (2460.0f32 / 1200.0).exp2()
+        ));
+        // The stretch only moves whole octaves, not the pitch class within its octave
+        assert!(close(
+            PitchClass::from_cents_f32(600.0).to_frequency_in_octave(100.0, 1220.0, 4),
+            100.0 * (600.0f32 / 1200.0).exp2()
+        ));
+        assert!(close(
+            PitchClass::from_cents_f32(600.0).to_frequency_in_octave(100.0, 1220.0, 5),
+            100.0 * (1820.0f32 / 1200.0).exp2()
+        ));
+    }
+
+    #[test]
+    fn test_to_cents_f64() {
+        assert!((THREE_JUST.to_cents_f64() - 701.955001).abs() < 1e-9);
+        assert_eq!(PitchClass::from_cents_f32(600.0).to_cents_f64(), 600.0);
+    }
+
+    #[test]
+    fn test_to_midi_note_and_tuning() {
+        // Just major third is slightly flat of E
+        let (note, tuning) = FIVE_JUST.to_midi_note_and_tuning(60);
+        assert_eq!(note, 64);
+        assert!((tuning - -0.13686286).abs() < 0.00001);
+
+        // Rounds up to the next octave's C
+        let (note, tuning) = PitchClass::from_cents_f32(1190.0).to_midi_note_and_tuning(60);
+        assert_eq!(note, 72);
+        assert!((tuning - -0.1).abs() < 0.00001);
+    }
+
+    #[test]
+    fn test_ratio() {
+        assert_eq!(PrimeCountVector::new(0, 0, 0, 0).ratio(), Some((1, 1)));
+        assert_eq!(PrimeCountVector::new(1, 0, 0, 0).ratio(), Some((3, 2)));
+        assert_eq!(PrimeCountVector::new(-1, 0, 0, 0).ratio(), Some((4, 3)));
+        assert_eq!(PrimeCountVector::new(0, 1, 0, 0).ratio(), Some((5, 4)));
+        assert_eq!(PrimeCountVector::new(2, 0, 0, 0).ratio(), Some((9, 8)));
+        assert_eq!(PrimeCountVector::new(1, 1, 0, 0).ratio(), Some((15, 8)));
+        // Syntonic comma below the octave
+        assert_eq!(PrimeCountVector::new(-4, 1, 0, 0).ratio(), Some((160, 81)));
+        assert_eq!(PrimeCountVector::new(0, 0, 1, 0).ratio(), Some((7, 4)));
+        assert_eq!(PrimeCountVector::new(0, 0, -1, 1).ratio(), Some((11, 7)));
+        assert_eq!(PrimeCountVector::new(41, 0, 0, 0).ratio(), None);
+    }
+
+    #[test]
+    fn test_edo_step_in_range() {
+        // Detected tunings round to the nearest step
+        assert_eq!(edo_step_in_range(12, 701.3, 660.0, 740.0), 700.0);
+        assert!((edo_step_in_range(31, 697.5, 660.0, 740.0) - 696.7742).abs() < 0.001);
+        assert_eq!(edo_step_in_range(5, 700.0, 660.0, 740.0), 720.0);
+        // The nearest step is above the range, so the highest step within it is used
+        assert_eq!(edo_step_in_range(12, 755.0, 660.0, 740.0), 700.0);
+        // No steps within the range
+        assert_eq!(edo_step_in_range(5, 600.0, 660.0, 700.0), 660.0);
+    }
+
+    #[test]
+    fn test_edo_approximation() {
+        assert_eq!(edo_approximation(12, THREE_JUST_F32), 700.0);
+        assert_eq!(edo_approximation(12, FIVE_JUST_F32), 400.0);
+        assert_eq!(edo_approximation(12, SEVEN_JUST_F32), 1000.0);
+        assert!((edo_approximation(19, THREE_JUST_F32) - 694.7368).abs() < 0.001);
+        assert!((edo_approximation(31, SEVEN_JUST_F32) - 967.7419).abs() < 0.001);
+        assert!((edo_approximation(53, FIVE_JUST_F32) - 384.9057).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_tolerances_for_primes() {
+        let cents = PitchClassDistance::from_cents;
+        let tolerances = Tolerances {
+            global: cents(1),
+            three: Some(cents(2)),
+            five: None,
+            seven: Some(cents(10)),
+        };
+        assert_eq!(
+            tolerances.for_primes(&PrimeCountVector::new(0, 0, 0, 0)),
+            cents(1)
+        );
+        assert_eq!(
+            tolerances.for_primes(&PrimeCountVector::new(2, 0, 0, 0)),
+            cents(2)
+        );
+        // 5 falls back to the global tolerance
+        assert_eq!(
+            tolerances.for_primes(&PrimeCountVector::new(0, 1, 0, 0)),
+            cents(1)
+        );
+        assert_eq!(
+            tolerances.for_primes(&PrimeCountVector::new(1, 1, 0, 0)),
+            cents(2)
+        );
+        // The loosest tolerance wins
+        assert_eq!(
+            tolerances.for_primes(&PrimeCountVector::new(1, 0, -1, 0)),
+            cents(10)
+        );
+        // 11 has no tolerance of its own
+        assert_eq!(
+            tolerances.for_primes(&PrimeCountVector::new(0, 0, 0, 1)),
+            cents(1)
+        );
+
+        // Without per-prime tolerances, everything uses the global one, as before they existed
+        let uniform = Tolerances::uniform(cents(3));
+        assert_eq!(
+            uniform.for_primes(&PrimeCountVector::new(1, -1, 1, 1)),
+            cents(3)
+        );
+    }
+
+    #[test]
+    fn test_temperament_presets() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.001;
+
+        let (three, five, seven) = TemperamentPreset::QuarterCommaMeantone.apply();
+        assert!(close(three, 696.578));
+        // Quarter-comma meantone has just major thirds
+        assert!(close(five, FIVE_JUST_F32));
+        assert!(close(seven, 965.784));
+
+        let (three, five, _) = TemperamentPreset::ThirdCommaMeantone.apply();
+        assert!(close(three, 694.786));
+        assert!(close(five, 379.145));
+
+        let (three, five, seven) = TemperamentPreset::Pythagorean.apply();
+        assert!(close(three, THREE_JUST_F32));
+        assert!(close(five, 407.820));
+        // The minor seventh, 16/9, is closer than the augmented sixth
+        assert!(close(seven, 996.09));
+
+        assert_eq!(
+            TemperamentPreset::JustIntonation.apply(),
+            (THREE_JUST_F32, FIVE_JUST_F32, SEVEN_JUST_F32)
+        );
+    }
+
+    #[test]
+    fn test_edo_tolerance() {
+        assert_eq!(edo_tolerance(12), 50.0);
+        assert!((edo_tolerance(31) - 19.3548).abs() < 0.001);
+        // Neighboring steps are a whole step apart, so they're out of each other's tolerance
+        let step = PitchClass::from_cents_f32(1200.0 / 53.0);
+        assert!(
+            step.distance_to(PitchClass::from_microcents(0))
+                > PitchClassDistance::from_cents_f32(edo_tolerance(53))
+        );
+    }
+
+    #[test]
+    fn test_edo_note_offset() {
+        assert_eq!(edo_note_offset(67, 12), 0.0);
+        assert_eq!(edo_note_offset(60, 31), 0.0);
+        // 18 steps of 31-EDO is its fifth, and 10 is its major third
+        let fifth = PitchClass::from_midi_note(78)
+            + PitchClass::from_midi_note_offset_f32(edo_note_offset(78, 31));
+        assert!((fifth.to_cents_f32() - 696.7742).abs() < 0.001);
+        let third = PitchClass::from_midi_note(50)
+            + PitchClass::from_midi_note_offset_f32(edo_note_offset(50, 31));
+        assert!((third.to_cents_f32() - (1200.0 - 387.0968)).abs() < 0.001);
+    }
 }
@@ -0,0 +1,350 @@
+//! Parsing for Scala (.scl) scale files. See https://www.huygens-fokker.org/scala/scl_format.html
+
+use std::fmt::{self, Display};
+
+use crate::tuning::{
+    PitchClass, PitchClassDistance, TuningSnapshot, CENTS_TO_MICROCENTS, ELEVEN_JUST, FIVE_JUST,
+    OCTAVE_MICROCENTS, SEVEN_JUST, THREE_JUST,
+};
+
+#[derive(PartialEq, Debug)]
+pub enum ScalaError {
+    /// The file ended before the description or note count
+    MissingHeader,
+    /// The note count line isn't a non-negative integer
+    InvalidNoteCount(String),
+    /// A pitch line isn't a valid cents value or ratio. Line numbers start at 1.
+    InvalidPitch { line: usize, text: String },
+    /// The number of pitch lines doesn't match the note count
+    WrongNoteCount { expected: usize, found: usize },
+}
+
+impl Display for ScalaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalaError::MissingHeader => write!(f, "missing description or note count"),
+            ScalaError::InvalidNoteCount(text) => write!(f, "invalid note count \"{}\"", text),
+            ScalaError::InvalidPitch { line, text } => {
+                write!(f, "invalid pitch \"{}\" on line {}", text, line)
+            }
+            ScalaError::WrongNoteCount { expected, found } => {
+                write!(f, "expected {} notes, found {}", expected, found)
+            }
+        }
+    }
+}
+
+/// Parses the contents of a .scl file into the pitch classes of its notes, in file order.
+/// The implicit 1/1 at the start of the scale isn't included.
+pub fn parse_scl(contents: &str) -> Result<Vec<PitchClass>, ScalaError> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('!'));
+
+    // The description may be empty, but must be present
+    lines.next().ok_or(ScalaError::MissingHeader)?;
+    let (_, count_line) = lines.next().ok_or(ScalaError::MissingHeader)?;
+    let count_text = first_word(count_line);
+    let expected: usize = count_text
+        .parse()
+        .map_err(|_| ScalaError::InvalidNoteCount(String::from(count_text)))?;
+
+    let mut pitch_classes: Vec<PitchClass> = Vec::with_capacity(expected);
+    for (line_idx, line) in lines {
+        let text = first_word(line);
+        if text.is_empty() {
+            continue;
+        }
+        let cents = parse_pitch(text).ok_or_else(|| ScalaError::InvalidPitch {
+            line: line_idx + 1,
+            text: String::from(text),
+        })?;
+        pitch_classes.push(pitch_class_from_cents(cents));
+    }
+
+    if pitch_classes.len() != expected {
+        return Err(ScalaError::WrongNoteCount {
+            expected,
+            found: pitch_classes.len(),
+        });
+    }
+    Ok(pitch_classes)
+}
+
+/// Anything after the first whitespace on a line is a comment
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+/// Parses a pitch in cents. Values with a period are in cents, and anything else is a ratio
+/// like "3/2", or an integer like "2" meaning "2/1".
+fn parse_pitch(text: &str) -> Option<f64> {
+    if text.contains('.') {
+        return text.parse().ok();
+    }
+    let (numerator, denominator) = text.split_once('/').unwrap_or((text, "1"));
+    let numerator: u64 = numerator.parse().ok()?;
+    let denominator: u64 = denominator.parse().ok()?;
+    if numerator == 0 || denominator == 0 {
+        return None;
+    }
+    Some((numerator as f64 / denominator as f64).log2() * 1200.0)
+}
+
+/// Like [`PitchClass::from_cents_f32()`], but precise enough to keep all six decimal places
+fn pitch_class_from_cents(cents: f64) -> PitchClass {
+    let microcents = (cents.rem_euclid(1200.0) * CENTS_TO_MICROCENTS as f64).round() as u32;
+    PitchClass::from_microcents(microcents % OCTAVE_MICROCENTS)
+}
+
+/// Writes a Scala file with the tunings of primes 3, 5 and 7 as its notes, followed by the octave.
+/// Cents are written like [`PitchClass`]'s `Display`, so importing the file gives back the same
+/// tunings.
+pub fn to_scl(tuning: &TuningSnapshot) -> String {
+    let mut contents = String::from("! Exported from MIDI Lattice\n!\n");
+    contents.push_str("Tunings of primes 3, 5 and 7\n");
+    contents.push_str(" 4\n!\n");
+    for (pitch_class, name) in [
+        (tuning.three, "fifth"),
+        (tuning.five, "major third"),
+        (tuning.seven, "harmonic seventh"),
+    ] {
+        contents.push_str(&format!(" {} {}\n", pitch_class, name));
+    }
+    contents.push_str(" 2/1\n");
+    contents
+}
+
+// How close an interval in the scale needs to be to a just interval to be used for its tuning
+const SCALE_TUNING_RANGE: PitchClassDistance = PitchClassDistance::from_cents(40);
+
+// The eleventh harmonic needs a narrower range, so that 7/5 (~583 cents) isn't used for it
+const ELEVEN_SCALE_TUNING_RANGE: PitchClassDistance = PitchClassDistance::from_cents(15);
+
+/// Tunings for primes 3, 5, 7 and 11 found in a scale
+#[derive(PartialEq, Debug)]
+pub struct ScaleTunings {
+    pub three: Option<PitchClass>,
+    pub five: Option<PitchClass>,
+    pub seven: Option<PitchClass>,
+    pub eleven: Option<PitchClass>,
+}
+
+/// Finds the intervals between notes of a scale that best approximate the just fifth, major
+/// third, harmonic seventh and eleventh harmonic, if there are any close enough
+pub fn scale_tunings(scale: &[PitchClass]) -> ScaleTunings {
+    let notes: Vec<PitchClass> = std::iter::once(PitchClass::from_microcents(0))
+        .chain(scale.iter().cloned())
+        .collect();
+
+    let best_interval = |target: PitchClass, range: PitchClassDistance| -> Option<PitchClass> {
+        let mut best: Option<PitchClass> = None;
+        for a in &notes {
+            for b in &notes {
+                let interval = *a - *b;
+                let distance = interval.distance_to(target);
+                if distance <= range
+                    && best.map_or(true, |best| distance < best.distance_to(target))
+                {
+                    best = Some(interval);
+                }
+            }
+        }
+        best
+    };
+
+    ScaleTunings {
+        three: best_interval(THREE_JUST, SCALE_TUNING_RANGE),
+        five: best_interval(FIVE_JUST, SCALE_TUNING_RANGE),
+        seven: best_interval(SEVEN_JUST, SCALE_TUNING_RANGE),
+        eleven: best_interval(ELEVEN_JUST, ELEVEN_SCALE_TUNING_RANGE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWELVE_TET: &str = "! 12tet.scl
+!
+12-tone equal temperament
+ 12
+!
+ 100.0
+ 200.
+ 300.0
+ 400.0
+ 500.0
+ 600.0
+ 700.0
+ 800.0
+ 900.0
+ 1000.0
+ 1100.0
+ 2/1
+";
+
+    const JUST_PENTATONIC: &str = "Just pentatonic
+5
+9/8 major second
+5/4
+3/2
+5/3
+2
+";
+
+    const PYTHAGOREAN: &str = "! pyth_7.scl
+!
+Pythagorean diatonic scale
+ 7
+!
+ 9/8
+ 81/64
+ 4/3
+ 3/2
+ 27/16
+ 243/128
+ 2/1
+";
+
+    const PARTCH_11_LIMIT_HEXAD: &str = "! hexad.scl
+!
+11-limit otonal hexad on 1/1
+ 5
+!
+ 5/4
+ 11/8
+ 3/2
+ 7/4
+ 2/1
+";
+
+    fn cents(pitch_classes: &[PitchClass]) -> Vec<f32> {
+        pitch_classes
+            .iter()
+            .map(|pitch_class| (pitch_class.to_cents_f32() * 1000.0).round() / 1000.0)
+            .collect()
+    }
+
+    #[test]
+    fn parses_cents() {
+        let scale = parse_scl(TWELVE_TET).unwrap();
+        assert_eq!(scale.len(), 12);
+        assert_eq!(scale[0], PitchClass::from_cents_f32(100.0));
+        assert_eq!(scale[1], PitchClass::from_cents_f32(200.0));
+        assert_eq!(scale[11], PitchClass::from_cents_f32(0.0));
+    }
+
+    #[test]
+    fn parses_ratios() {
+        let scale = parse_scl(JUST_PENTATONIC).unwrap();
+        assert_eq!(cents(&scale), vec![203.91, 386.314, 701.955, 884.359, 0.0]);
+    }
+
+    #[test]
+    fn parses_pythagorean_ratios() {
+        let scale = parse_scl(PYTHAGOREAN).unwrap();
+        assert_eq!(
+            cents(&scale),
+            vec![203.91, 407.82, 498.045, 701.955, 905.865, 1109.775, 0.0]
+        );
+
+        // With no 5 or 7 in the scale, the ditone (81/64) and the minor seventh (16/9, a whole
+        // tone below the octave) are the closest intervals
+        let tunings = scale_tunings(&scale);
+        assert_eq!(tunings.three, Some(scale[3]));
+        assert_eq!(tunings.five, Some(scale[1]));
+        assert_eq!(tunings.seven, Some(-scale[0]));
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert_eq!(parse_scl(""), Err(ScalaError::MissingHeader));
+        assert_eq!(
+            parse_scl("! only a comment\n"),
+            Err(ScalaError::MissingHeader)
+        );
+        assert_eq!(
+            parse_scl("desc\nfive\n"),
+            Err(ScalaError::InvalidNoteCount(String::from("five")))
+        );
+        assert_eq!(
+            parse_scl("desc\n2\n3/2\nthree\n"),
+            Err(ScalaError::InvalidPitch {
+                line: 4,
+                text: String::from("three")
+            })
+        );
+        assert_eq!(
+            parse_scl("desc\n1\n3/0\n"),
+            Err(ScalaError::InvalidPitch {
+                line: 3,
+                text: String::from("3/0")
+            })
+        );
+        assert_eq!(
+            parse_scl("desc\n3\n3/2\n2/1\n"),
+            Err(ScalaError::WrongNoteCount {
+                expected: 3,
+                found: 2
+            })
+        );
+    }
+
+    #[test]
+    fn exports_tuning() {
+        let tuning = TuningSnapshot {
+            c_offset: PitchClass::from_microcents(0),
+            three: PitchClass::from_microcents(696_578_428),
+            five: FIVE_JUST,
+            seven: PitchClass::from_microcents(1_000_000_000),
+            eleven: PitchClass::from_microcents(551_317_942),
+        };
+        let contents = to_scl(&tuning);
+        assert!(contents.contains(" 696.578428 fifth\n"));
+        assert!(contents.contains(" 386.313714 major third\n"));
+        assert!(contents.contains(" 1000.000000 harmonic seventh\n"));
+
+        let scale = parse_scl(&contents).unwrap();
+        assert_eq!(
+            scale,
+            vec![
+                tuning.three,
+                tuning.five,
+                tuning.seven,
+                PitchClass::from_microcents(0)
+            ]
+        );
+        assert_eq!(
+            scale_tunings(&scale),
+            ScaleTunings {
+                three: Some(tuning.three),
+                five: Some(tuning.five),
+                seven: Some(tuning.seven),
+                eleven: None,
+            }
+        );
+    }
+
+    #[test]
+    fn finds_scale_tunings() {
+        let tunings = scale_tunings(&parse_scl(TWELVE_TET).unwrap());
+        assert_eq!(tunings.three, Some(PitchClass::from_cents_f32(700.0)));
+        assert_eq!(tunings.five, Some(PitchClass::from_cents_f32(400.0)));
+        assert_eq!(tunings.seven, Some(PitchClass::from_cents_f32(1000.0)));
+        // The augmented fourth is too far from 11/8
+        assert_eq!(tunings.eleven, None);
+
+        // A just major triad has no approximation of the harmonic seventh
+        let tunings = scale_tunings(&parse_scl("Major triad\n3\n5/4\n3/2\n2/1\n").unwrap());
+        let tolerance = PitchClassDistance::from_cents_f32(0.001);
+        assert!(tunings.three.unwrap().distance_to(THREE_JUST) < tolerance);
+        assert!(tunings.five.unwrap().distance_to(FIVE_JUST) < tolerance);
+        assert_eq!(tunings.seven, None);
+
+        let tunings = scale_tunings(&parse_scl(PARTCH_11_LIMIT_HEXAD).unwrap());
+        assert!(tunings.seven.unwrap().distance_to(SEVEN_JUST) < tolerance);
+        assert!(tunings.eleven.unwrap().distance_to(ELEVEN_JUST) < tolerance);
+    }
+}